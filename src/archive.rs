@@ -0,0 +1,271 @@
+//! A single file page archive, so saved pages can be viewed offline. The format is a subset of
+//! MHTML: a `multipart/related` MIME message where the first part is the page and the rest of the
+//! parts are its sub-resources. Parts are written without any transfer encoding.
+//!
+//! Only the stylesheets linked with `<link rel="stylesheet">` are bundled for now, since they are
+//! the only sub-resources the renderer uses. A stylesheet is left out if it doesn't match the
+//! `integrity` attribute of its link, or if the server forbids guessing its type with `nosniff` and
+//! it isn't sent as `text/css`.
+
+use crate::html::{parse_html, Element, Tag};
+use crate::integrity;
+use crate::requests::{get_site, resolve_url, try_get_site};
+use std::collections::HashMap;
+
+/// A single fetched file inside of an archive
+#[derive(Debug)]
+pub struct Resource {
+    pub url: String,
+    pub mime_type: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct Archive {
+    /// The page the archive was made from. Its url is the base url for the links on the page.
+    pub page: Resource,
+    pub resources: Vec<Resource>,
+}
+
+/// Returns the href of the element if it links a stylesheet
+fn style_sheet_link(element: &Element) -> Option<String> {
+    let rel = element.get_attribute("rel")?;
+    if element.element_type == Tag::Link && rel.split_whitespace().any(|x| x == "stylesheet") {
+        element.get_attribute("href")
+    } else {
+        None
+    }
+}
+
+/// Collects the hrefs of the stylesheet links with their `integrity` attributes
+fn collect_style_sheet_links(element: &Element, output: &mut Vec<(String, Option<String>)>) {
+    if let Some(href) = style_sheet_link(element) {
+        output.push((href, element.get_attribute("integrity")));
+    }
+    for child in &element.children {
+        collect_style_sheet_links(child, output);
+    }
+}
+
+/// Turns the stylesheet links into `<style>` elements with the bundled stylesheet as the content
+fn inline_style_sheets(element: &mut Element, base: &str, style_sheets: &HashMap<String, &str>) {
+    if let Some(href) = style_sheet_link(element) {
+        if let Some(css) = style_sheets.get(&resolve_url(base, &href)) {
+            element.element_type = Tag::Style;
+            element.children = vec![Element::new_with_text(Tag::PlainText, css)];
+        }
+    }
+    for child in &mut element.children {
+        inline_style_sheets(child, base, style_sheets);
+    }
+}
+
+/// Decodes the `quoted-printable` transfer encoding, which MHTML files saved by other browsers
+/// tend to use.
+fn decode_quoted_printable(body: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut input = body.as_bytes().iter().peekable();
+    while let Some(&byte) = input.next() {
+        if byte != b'=' {
+            bytes.push(byte);
+            continue;
+        }
+        // A `=` at the end of a line is a soft line break
+        match input.peek() {
+            Some(b'\r') => {
+                input.next();
+                input.next_if_eq(&&b'\n');
+            }
+            Some(b'\n') => {
+                input.next();
+            }
+            _ => {
+                let hex: Vec<u8> = input.by_ref().take(2).copied().collect();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|x| u8::from_str_radix(x, 16).ok())
+                {
+                    Some(v) => bytes.push(v),
+                    None => {
+                        bytes.push(b'=');
+                        bytes.extend(hex);
+                    }
+                }
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Splits a MIME part into its headers and body. The header names are lowercased.
+fn split_headers(part: &str) -> (HashMap<String, String>, &str) {
+    let (head, body) = part
+        .split_once("\r\n\r\n")
+        .or(part.split_once("\n\n"))
+        .unwrap_or((part, ""));
+    let mut headers = HashMap::new();
+    for line in head.lines() {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+        }
+    }
+    (headers, body)
+}
+
+/// Returns the value of a parameter in a header, for example `boundary` in
+/// `multipart/related; boundary="abc"`
+fn header_parameter(header: &str, name: &str) -> Option<String> {
+    header.split(';').skip(1).find_map(|x| {
+        let (key, value) = x.split_once('=')?;
+        (key.trim() == name).then(|| value.trim().trim_matches('"').to_owned())
+    })
+}
+
+impl Archive {
+    /// Fetches the page and the resources it uses
+    pub fn fetch(url: &str) -> Self {
+        let response = get_site(url);
+        let page = Resource {
+            url: url.to_owned(),
+            mime_type: response.mime_type.unwrap_or("text/html".to_owned()),
+            body: response.body,
+        };
+
+        let mut links = Vec::new();
+        for element in &parse_html(&page.body) {
+            collect_style_sheet_links(element, &mut links);
+        }
+
+        let mut resources: Vec<Resource> = Vec::new();
+        for (link, integrity) in links {
+            let url = resolve_url(&page.url, &link);
+            if resources.iter().any(|x| x.url == url) {
+                continue;
+            }
+            println!("Archiving {}", url);
+            // A stylesheet that can't be fetched is left out, like the browser leaves it out
+            let response = match try_get_site(&url, None) {
+                Ok(v) => v,
+                Err(error) => {
+                    println!("Couldn't archive the stylesheet {}: {}", url, error);
+                    continue;
+                }
+            };
+            let mime_type = response.mime_type.unwrap_or("text/css".to_owned());
+            if response.nosniff && mime_type != "text/css" {
+                println!(
+                    "Refused the stylesheet {}, since its type {} isn't text/css and the server \
+                     sent nosniff",
+                    url, mime_type
+                );
+                continue;
+            }
+            if let Some(integrity) = integrity {
+                if !integrity::matches(response.body.as_bytes(), &integrity) {
+                    println!(
+                        "Refused the stylesheet {}, since it doesn't match its integrity {}",
+                        url, integrity
+                    );
+                    continue;
+                }
+            }
+            resources.push(Resource {
+                url,
+                mime_type,
+                body: response.body,
+            });
+        }
+
+        Self { page, resources }
+    }
+
+    /// Parses an archive. Returns an error if the text isn't a `multipart/related` message.
+    pub fn from_string(text: &str) -> Result<Self, String> {
+        let (headers, body) = split_headers(text);
+        let content_type = headers
+            .get("content-type")
+            .ok_or("The archive doesn't have a Content-Type header")?;
+        let boundary = header_parameter(content_type, "boundary")
+            .ok_or("The archive doesn't have a boundary")?;
+
+        let mut resources = Vec::new();
+        // The first item is the text before the first boundary and the parts end with `--`
+        for part in body.split(&format!("--{}", boundary)).skip(1) {
+            if part.starts_with("--") {
+                break;
+            }
+            let (headers, body) = split_headers(part.trim_start_matches(['\r', '\n']));
+            // The line break before a boundary belongs to the boundary
+            let body = body
+                .strip_suffix("\r\n")
+                .or(body.strip_suffix('\n'))
+                .unwrap_or(body);
+            let body = match headers.get("content-transfer-encoding").map(|x| x.as_str()) {
+                Some("quoted-printable") => decode_quoted_printable(body),
+                _ => body.to_owned(),
+            };
+            let mime_type = headers
+                .get("content-type")
+                .and_then(|x| x.split(';').next())
+                .unwrap_or("text/plain")
+                .trim()
+                .to_owned();
+            resources.push(Resource {
+                url: headers.get("content-location").cloned().unwrap_or_default(),
+                mime_type,
+                body,
+            });
+        }
+
+        if resources.is_empty() {
+            return Err("The archive is empty".to_owned());
+        }
+        let page = resources.remove(0);
+        Ok(Self { page, resources })
+    }
+
+    /// Writes the archive as a MIME message
+    pub fn serialize(&self) -> String {
+        let parts: Vec<&Resource> = std::iter::once(&self.page).chain(&self.resources).collect();
+
+        // The boundary can't appear inside of any of the parts
+        let mut boundary = "----web-browser-archive".to_owned();
+        while parts.iter().any(|x| x.body.contains(&boundary)) {
+            boundary.push('-');
+        }
+
+        let mut output = String::new();
+        output += "MIME-Version: 1.0\r\n";
+        output += &format!("Snapshot-Content-Location: {}\r\n", self.page.url);
+        output += &format!(
+            "Content-Type: multipart/related; type=\"{}\"; boundary=\"{}\"\r\n\r\n",
+            self.page.mime_type, boundary
+        );
+        for part in parts {
+            output += &format!("--{}\r\n", boundary);
+            output += &format!("Content-Type: {}\r\n", part.mime_type);
+            output += "Content-Transfer-Encoding: binary\r\n";
+            output += &format!("Content-Location: {}\r\n\r\n", part.url);
+            output += &part.body;
+            output += "\r\n";
+        }
+        output += &format!("--{}--\r\n", boundary);
+        output
+    }
+
+    /// Parses the page of the archive. The bundled stylesheets are placed inside of the page, so
+    /// nothing has to be fetched.
+    pub fn into_elements(self) -> Vec<Element> {
+        let style_sheets: HashMap<String, &str> = self
+            .resources
+            .iter()
+            .filter(|x| x.mime_type == "text/css")
+            .map(|x| (x.url.clone(), x.body.as_str()))
+            .collect();
+        let mut elements = parse_html(&self.page.body);
+        for element in &mut elements {
+            inline_style_sheets(element, &self.page.url, &style_sheets);
+        }
+        elements
+    }
+}
@@ -0,0 +1,37 @@
+//! The base64 encoding, which the WebSocket handshake and the hashes of subresource integrity are
+//! written in
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the bytes with padding
+pub fn encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(3) {
+        let byte = |index: usize| chunk.get(index).copied().unwrap_or(0) as u32;
+        let bits = byte(0) << 16 | byte(1) << 8 | byte(2);
+        for index in 0..4 {
+            // A chunk of n bytes makes n + 1 characters, and the rest is padding
+            if index <= chunk.len() {
+                output.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_partial_chunks() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"M"), "TQ==");
+        assert_eq!(encode(b"Ma"), "TWE=");
+        assert_eq!(encode(b"Man"), "TWFu");
+        assert_eq!(encode(b"Many"), "TWFueQ==");
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+    }
+}
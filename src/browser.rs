@@ -0,0 +1,338 @@
+//! The engine without a window. A [Browser] loads pages into a tab, keeps a back and forward
+//! history of its own and gives the title and the text of the page, so other programs can drive
+//! the engine from their own event loop or without a display at all.
+
+use crate::color::Color;
+use crate::css::Image;
+use crate::display_list::DisplayList;
+use crate::font::FontRegistry;
+use crate::html::{Element, Tag};
+use crate::media::{ColorScheme, MediaEnvironment};
+use crate::render_backend::{paint, SoftwareBackend};
+use crate::render_layout::{Layout, Size};
+use crate::requests::{get_bytes, resolve_url, Response};
+use crate::tab::{PageLoad, Tab, VIEW_SOURCE_PREFIX};
+use image::RgbaImage;
+
+/// How the page that is loading was reached. The history only changes once the page has loaded,
+/// so a page that fails to load doesn't leave an entry behind.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Traversal {
+    Navigate,
+    Back,
+    Forward,
+    Reload,
+}
+
+/// Tells how a load ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadEvent {
+    pub url: String,
+    /// Why the page couldn't be loaded. The previous page stays in the browser in that case.
+    pub error: Option<String>,
+}
+
+/// Called with the end of every load, see [Browser::on_load]
+pub type LoadCallback = Box<dyn FnMut(&LoadEvent)>;
+
+/// A single tab that can be driven with code. Loads happen in the background, and they are
+/// finished by [Browser::poll] or [Browser::wait].
+pub struct Browser {
+    tab: Tab,
+    traversal: Traversal,
+    back: Vec<String>,
+    forward: Vec<String>,
+    on_load: Option<LoadCallback>,
+}
+
+impl Default for Browser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Browser {
+    /// Creates a browser that has no page yet
+    pub fn new() -> Self {
+        Self {
+            tab: Tab::new(""),
+            traversal: Traversal::Navigate,
+            back: Vec::new(),
+            forward: Vec::new(),
+            on_load: None,
+        }
+    }
+
+    /// Sets the function that is called every time a load finishes or fails
+    pub fn on_load(&mut self, callback: impl FnMut(&LoadEvent) + 'static) {
+        self.on_load = Some(Box::new(callback));
+    }
+
+    /// Starts loading the url. A load that is still running is abandoned.
+    pub fn navigate(&mut self, url: &str) {
+        self.start(url, Traversal::Navigate);
+    }
+
+    /// Starts loading the previous page. Returns false if there is none.
+    pub fn back(&mut self) -> bool {
+        match self.back.last().cloned() {
+            Some(url) => {
+                self.start(&url, Traversal::Back);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Starts loading the page that was left with [Browser::back]. Returns false if there is
+    /// none.
+    pub fn forward(&mut self) -> bool {
+        match self.forward.last().cloned() {
+            Some(url) => {
+                self.start(&url, Traversal::Forward);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Loads the current page again. Does nothing if no page has been loaded.
+    pub fn reload(&mut self) {
+        if !self.tab.url.is_empty() {
+            let url = self.tab.url.clone();
+            self.start(&url, Traversal::Reload);
+        }
+    }
+
+    fn start(&mut self, url: &str, traversal: Traversal) {
+        self.tab.loading = Some(PageLoad::start(url, None));
+        self.traversal = traversal;
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.tab.loading.is_some()
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    /// Finishes the load if its response has arrived. This doesn't block, so it can be called
+    /// from the event loop of the program. Returns true if a load ended.
+    pub fn poll(&mut self) -> bool {
+        let Some(result) = self.tab.loading.as_ref().and_then(|x| x.poll()) else {
+            return false;
+        };
+        let load = self.tab.loading.take().unwrap();
+        // The callback of [Browser::on_load] gets the error
+        let _ = self.finish(&load.url, result);
+        true
+    }
+
+    /// Blocks until the load that is running has ended. Returns the error if the page couldn't be
+    /// loaded.
+    pub fn wait(&mut self) -> Result<(), String> {
+        let Some(load) = self.tab.loading.take() else {
+            return Ok(());
+        };
+        let result = load.wait();
+        self.finish(&load.url, result)
+    }
+
+    fn finish(&mut self, url: &str, result: Result<Response, String>) -> Result<(), String> {
+        let result = result.and_then(|response| match response.download {
+            true => Err(format!("{} is a download, not a page", url)),
+            false => Ok(response),
+        });
+        let event = match result {
+            Ok(response) => {
+                self.show(url, &response);
+                LoadEvent {
+                    url: url.to_owned(),
+                    error: None,
+                }
+            }
+            Err(error) => LoadEvent {
+                url: url.to_owned(),
+                error: Some(error),
+            },
+        };
+        if let Some(callback) = self.on_load.as_mut() {
+            callback(&event);
+        }
+        match event.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Shows the response in the tab and moves the history to it
+    fn show(&mut self, url: &str, response: &Response) {
+        let previous = std::mem::replace(&mut self.tab.url, url.to_owned());
+        match self.traversal {
+            Traversal::Navigate => {
+                if !previous.is_empty() {
+                    self.back.push(previous);
+                }
+                self.forward.clear();
+            }
+            Traversal::Back => {
+                self.back.pop();
+                self.forward.push(previous);
+            }
+            Traversal::Forward => {
+                self.forward.pop();
+                self.back.push(previous);
+            }
+            Traversal::Reload => (),
+        }
+        if url.starts_with(VIEW_SOURCE_PREFIX) {
+            self.tab.set_source(&response.body, None);
+        } else {
+            self.tab.set_response(response);
+        }
+        self.tab.scroll_y = 0;
+        self.tab.title = self.tab.document.as_ref().and_then(|x| x.title());
+    }
+
+    /// The url of the page that is shown. Empty before the first page has loaded.
+    pub fn url(&self) -> &str {
+        &self.tab.url
+    }
+
+    /// Returns the `<title>` of the page
+    pub fn get_title(&self) -> Option<String> {
+        self.tab.title.clone()
+    }
+
+    /// Returns the text of the page. The words of an html page are separated by single spaces,
+    /// and its scripts and styles are left out. Plain text is returned as is.
+    pub fn get_text(&self) -> String {
+        if let Some(text_document) = self.tab.text_document.as_ref() {
+            return text_document.text();
+        }
+        let mut words = Vec::new();
+        for element in self.tab.document.iter().flat_map(|x| &x.elements) {
+            collect_words(element, &mut words);
+        }
+        words.join(" ")
+    }
+
+    /// The tab the pages are loaded into, for laying out and drawing the page
+    pub fn tab(&self) -> &Tab {
+        &self.tab
+    }
+
+    pub fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tab
+    }
+
+    /// Lays out the page for a viewport of the size and draws it into an image of that size, at
+    /// the scroll of the tab. The page is drawn over white with the default settings and the
+    /// light color scheme, and the images of the page are fetched first. Nothing but the white
+    /// is drawn before the first page has loaded.
+    pub fn render_to_image(&mut self, size: Size, fonts: &FontRegistry) -> RgbaImage {
+        let layout = self.layout(size, fonts);
+        let display_list = layout
+            .as_ref()
+            .map(|x| DisplayList::new(x, fonts))
+            .unwrap_or_default();
+        let background_images = layout
+            .as_ref()
+            .map(|x| x.background_images.as_slice())
+            .unwrap_or_default();
+        let image_pixels: Vec<Option<RgbaImage>> = background_images
+            .iter()
+            .map(|x| match &x.image {
+                Image::Url(url) => self.fetch_image(&resolve_url(&self.tab.url, url)),
+                Image::LinearGradient(_) => None,
+            })
+            .collect();
+        let image_pixels: Vec<Option<&RgbaImage>> =
+            image_pixels.iter().map(|x| x.as_ref()).collect();
+
+        let mut backend = SoftwareBackend::new(size, Color::white(), fonts, self.tab.scroll());
+        backend.set_background_images(background_images, &image_pixels);
+        paint(&display_list, self.tab.scroll_y, &mut backend);
+        backend.image
+    }
+
+    /// Lays out the page like [Browser::render_to_image] draws it
+    fn layout(&mut self, size: Size, fonts: &FontRegistry) -> Option<Layout> {
+        if let Some(text_document) = self.tab.text_document.as_ref() {
+            return Some(text_document.layout(size, 1.0, fonts.default_font()));
+        }
+        let images: Vec<(usize, String)> = self
+            .tab
+            .document
+            .as_ref()?
+            .pending_images()
+            .into_iter()
+            .map(|(id, src)| (id, resolve_url(&self.tab.url, &src)))
+            .collect();
+        for (id, url) in images {
+            let size = self
+                .fetch_image(&url)
+                .map(|x| x.dimensions())
+                .unwrap_or((0, 0));
+            self.tab.document.as_mut()?.set_natural_size(id, size);
+        }
+
+        let tab = &mut self.tab;
+        let document = tab.document.as_mut()?;
+        document.update_styles(MediaEnvironment {
+            width: size.width,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            forced_colors: None,
+            text_zoom: 1.0,
+            page_zoom: 1.0,
+        });
+        let body = document.elements[0]
+            .children
+            .iter()
+            .find(|x| x.element_type == Tag::Body)?;
+        // The paragraphs of a gigantic page are only laid out a screen above and below the
+        // visible part, like in the window
+        let top = -tab.scroll_y - size.height;
+        tab.virtualization.visible = top..top + size.height * 3;
+        Some(Layout::from_body(
+            body,
+            size,
+            fonts,
+            &tab.box_scroll,
+            &mut tab.virtualization,
+        ))
+    }
+
+    /// Fetches the image with the referrer policy of the page
+    fn fetch_image(&self, url: &str) -> Option<RgbaImage> {
+        let policy = self
+            .tab
+            .document
+            .as_ref()
+            .and_then(|x| x.referrer_policy())
+            .unwrap_or_default();
+        let referrer = policy.referrer(&self.tab.url, url);
+        let bytes = get_bytes(url, referrer.as_deref())?;
+        image::load_from_memory(&bytes).ok().map(|x| x.into_rgba8())
+    }
+}
+
+/// Collects the words of the text in the element, leaving out the `<head>`, the scripts and the
+/// styles
+fn collect_words<'a>(element: &'a Element, words: &mut Vec<&'a str>) {
+    match element.element_type {
+        Tag::Head | Tag::Title | Tag::Script | Tag::Style => (),
+        Tag::PlainText => words.extend(element.inner_text.split_whitespace()),
+        _ => {
+            for child in &element.children {
+                collect_words(child, words);
+            }
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! `calc()` expressions. The lengths in an expression are added up into a single [Calc], which is
+//! resolved into pixels like the other units once the size of the containing block is known.
+
+use crate::css::Unit;
+use crate::render_layout::DEFAULT_FONT_SIZE;
+
+/// A length that is a sum of pixels, `em`s, `rem`s and a percentage of the containing block, such
+/// as `calc(100% - 2em + 10px)`
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Calc {
+    pub px: f32,
+    pub em: f32,
+    pub rem: f32,
+    pub percentage: f32,
+}
+
+impl Calc {
+    /// Resolves the length into pixels, see [Unit::to_px]
+    pub fn to_px(self, font_size: f32, containing_length: i32) -> i32 {
+        (self.px
+            + self.em * font_size
+            + self.rem * DEFAULT_FONT_SIZE
+            + self.percentage * containing_length as f32 / 100.0) as i32
+    }
+
+    /// Adds the `rem` part to the pixels, see [Unit::resolve_rem]
+    pub fn resolve_rem(self, root_font_size: f32) -> Self {
+        Self {
+            px: self.px + self.rem * root_font_size,
+            rem: 0.0,
+            ..self
+        }
+    }
+
+    /// Adds the other length to this one, or subtracts it with a negative sign
+    pub fn add(self, other: Self, sign: f32) -> Self {
+        Self {
+            px: self.px + other.px * sign,
+            em: self.em + other.em * sign,
+            rem: self.rem + other.rem * sign,
+            percentage: self.percentage + other.percentage * sign,
+        }
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Self {
+            px: self.px * factor,
+            em: self.em * factor,
+            rem: self.rem * factor,
+            percentage: self.percentage * factor,
+        }
+    }
+}
+
+impl From<Unit> for Calc {
+    fn from(unit: Unit) -> Self {
+        let calc = Self::default();
+        match unit {
+            Unit::Px(v) => Self {
+                px: v as f32,
+                ..calc
+            },
+            Unit::Pt(v) => Self {
+                px: v as f32 * 4.0 / 3.0,
+                ..calc
+            },
+            Unit::Em(v) => Self { em: v, ..calc },
+            Unit::Rem(v) => Self { rem: v, ..calc },
+            Unit::Percentage(v) => Self {
+                percentage: v as f32,
+                ..calc
+            },
+            Unit::Calc(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Length(Calc),
+    Plus,
+    Minus,
+    Times,
+    Divide,
+    Open,
+    Close,
+}
+
+/// A value in the middle of the expression. Lengths can only be multiplied and divided by plain
+/// numbers, so the result always stays a sum of lengths.
+#[derive(Debug, Copy, Clone)]
+enum Operand {
+    Number(f32),
+    Length(Calc),
+}
+
+/// Parses the expression inside of `calc()`. Returns [None] if the expression is invalid or uses
+/// units that aren't supported.
+pub fn parse_calc(expression: &str) -> Option<Unit> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+    };
+    let value = parser.sum()?;
+    if parser.position != tokens.len() {
+        return None;
+    }
+    match value {
+        Operand::Length(v) => Some(Unit::Calc(v)),
+        // A plain zero is a valid length, like outside of calc()
+        Operand::Number(0.0) => Some(Unit::Px(0)),
+        Operand::Number(_) => None,
+    }
+}
+
+fn tokenize(expression: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut iterator = expression.chars().peekable();
+    while let Some(character) = iterator.next() {
+        // A sign right before a number belongs to the number when there is nothing to subtract
+        // it from
+        let is_sign = matches!(character, '-' | '+')
+            && iterator
+                .peek()
+                .is_some_and(|x| x.is_ascii_digit() || *x == '.')
+            && !matches!(
+                tokens.last(),
+                Some(Token::Number(_) | Token::Length(_) | Token::Close)
+            );
+        let token = match character {
+            v if v.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '*' => Token::Times,
+            '/' => Token::Divide,
+            '+' if !is_sign => Token::Plus,
+            '-' if !is_sign => Token::Minus,
+            v if v.is_alphabetic() => {
+                // Nested `calc()` is the same as parentheses
+                let mut name = v.to_string();
+                while let Some(v) = iterator.next_if(|x| x.is_alphabetic()) {
+                    name.push(v);
+                }
+                if name != "calc" || iterator.next() != Some('(') {
+                    return None;
+                }
+                Token::Open
+            }
+            v => {
+                let mut number = v.to_string();
+                while let Some(v) = iterator.next_if(|x| x.is_ascii_digit() || *x == '.') {
+                    number.push(v);
+                }
+                let value = number.parse::<f32>().ok()?;
+                let mut unit = String::new();
+                while let Some(v) = iterator.next_if(|x| x.is_alphabetic() || *x == '%') {
+                    unit.push(v);
+                }
+                let length = match unit.as_str() {
+                    "" => {
+                        tokens.push(Token::Number(value));
+                        continue;
+                    }
+                    "px" => Calc {
+                        px: value,
+                        ..Default::default()
+                    },
+                    "pt" => Calc {
+                        px: value * 4.0 / 3.0,
+                        ..Default::default()
+                    },
+                    "em" => Calc {
+                        em: value,
+                        ..Default::default()
+                    },
+                    "rem" => Calc {
+                        rem: value,
+                        ..Default::default()
+                    },
+                    "%" => Calc {
+                        percentage: value,
+                        ..Default::default()
+                    },
+                    _ => return None,
+                };
+                Token::Length(length)
+            }
+        };
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn next_if(&mut self, token: Token) -> bool {
+        let matches = self.tokens.get(self.position) == Some(&token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    /// Parses terms separated by `+` and `-`
+    fn sum(&mut self) -> Option<Operand> {
+        let mut value = self.product()?;
+        loop {
+            let sign = if self.next_if(Token::Plus) {
+                1.0
+            } else if self.next_if(Token::Minus) {
+                -1.0
+            } else {
+                return Some(value);
+            };
+            value = match (value, self.product()?) {
+                (Operand::Number(a), Operand::Number(b)) => Operand::Number(a + b * sign),
+                (Operand::Length(a), Operand::Length(b)) => Operand::Length(a.add(b, sign)),
+                // Numbers and lengths can't be added together
+                _ => return None,
+            };
+        }
+    }
+
+    /// Parses factors separated by `*` and `/`
+    fn product(&mut self) -> Option<Operand> {
+        let mut value = self.factor()?;
+        loop {
+            if self.next_if(Token::Times) {
+                value = match (value, self.factor()?) {
+                    (Operand::Number(a), Operand::Number(b)) => Operand::Number(a * b),
+                    (Operand::Number(a), Operand::Length(b)) => Operand::Length(b.scale(a)),
+                    (Operand::Length(a), Operand::Number(b)) => Operand::Length(a.scale(b)),
+                    (Operand::Length(_), Operand::Length(_)) => return None,
+                };
+            } else if self.next_if(Token::Divide) {
+                value = match (value, self.factor()?) {
+                    (_, Operand::Number(0.0)) => return None,
+                    (Operand::Number(a), Operand::Number(b)) => Operand::Number(a / b),
+                    (Operand::Length(a), Operand::Number(b)) => Operand::Length(a.scale(1.0 / b)),
+                    (_, Operand::Length(_)) => return None,
+                };
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Option<Operand> {
+        let token = *self.tokens.get(self.position)?;
+        self.position += 1;
+        match token {
+            Token::Number(v) => Some(Operand::Number(v)),
+            Token::Length(v) => Some(Operand::Length(v)),
+            Token::Open => {
+                let value = self.sum()?;
+                self.next_if(Token::Close).then_some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::{parse_inline_css, Rule};
+
+    /// Parses the expression, which has to be a valid length
+    fn calc(expression: &str) -> Calc {
+        match parse_calc(expression) {
+            Some(Unit::Calc(v)) => v,
+            v => panic!("Expected a length from '{}', got {:?}", expression, v),
+        }
+    }
+
+    fn px(px: f32) -> Calc {
+        Calc {
+            px,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn multiplies_before_adding() {
+        assert_eq!(calc("10px + 2 * 5px"), px(20.0));
+        assert_eq!(calc("10px - 4px / 2"), px(8.0));
+        assert_eq!(calc("2 * 3 * 1px - 1px"), px(5.0));
+    }
+
+    #[test]
+    fn evaluates_nested_parentheses() {
+        assert_eq!(calc("(10px + 2px) * (1 + 1)"), px(24.0));
+        assert_eq!(calc("((1px + 1px) * 2) * 2"), px(8.0));
+        // Nested calc() is the same as parentheses
+        assert_eq!(
+            calc("2 * (1em + calc(1% * 2))"),
+            Calc {
+                em: 2.0,
+                percentage: 4.0,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_the_units_apart() {
+        assert_eq!(
+            calc("100% - 2em + 10px"),
+            Calc {
+                px: 10.0,
+                em: -2.0,
+                percentage: 100.0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            calc("1rem + 3pt"),
+            Calc {
+                px: 4.0,
+                rem: 1.0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(calc("-5px + -5px"), px(-10.0));
+    }
+
+    #[test]
+    fn rejects_division_by_zero() {
+        assert!(parse_calc("10px / 0").is_none());
+        assert!(parse_calc("10px / (1 - 1)").is_none());
+        assert!(parse_calc("1 / 0 * 0px").is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "",
+            "10vw",
+            "10px +",
+            "(10px",
+            "10px)",
+            "10px 5px",
+            "10px + 2",
+            "10px * 2px",
+            "2 / 1px",
+            "5",
+            "min(1px, 2px)",
+        ] {
+            assert!(
+                parse_calc(expression).is_none(),
+                "'{}' should be rejected",
+                expression
+            );
+        }
+        // A plain zero is still a length
+        assert!(matches!(parse_calc("0"), Some(Unit::Px(0))));
+    }
+
+    #[test]
+    fn drops_declarations_with_invalid_calc() {
+        let rules = parse_inline_css("width: calc(10vw + 5px); height: calc(2 * 5px)");
+        assert_eq!(rules.len(), 1);
+        assert!(matches!(
+            rules[0],
+            Rule::Height(Some(Unit::Calc(v))) if v == px(10.0)
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+//! The system clipboard. On Linux there is also the primary selection, which holds the text that
+//! was selected last and is pasted with the middle mouse button. Both X11 and Wayland have it.
+
+/// The clipboard of the system. Copying and pasting silently does nothing if the clipboard
+/// couldn't be opened, such as when there is no display server.
+pub struct Clipboard {
+    inner: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        let inner = match arboard::Clipboard::new() {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Couldn't open the clipboard: {}", e);
+                None
+            }
+        };
+        Self { inner }
+    }
+
+    /// Puts the text into the clipboard and the primary selection
+    pub fn copy(&mut self, text: &str) {
+        let Some(clipboard) = self.inner.as_mut() else {
+            return;
+        };
+        if let Err(e) = clipboard.set_text(text) {
+            println!("Couldn't copy to the clipboard: {}", e);
+        }
+        self.select(text);
+    }
+
+    /// Returns the text in the clipboard
+    pub fn paste(&mut self) -> Option<String> {
+        self.inner.as_mut()?.get_text().ok()
+    }
+
+    /// Makes the text the primary selection. Does nothing on the platforms without one.
+    pub fn select(&mut self, text: &str) {
+        #[cfg(target_os = "linux")]
+        {
+            use arboard::{LinuxClipboardKind, SetExtLinux};
+            let Some(clipboard) = self.inner.as_mut() else {
+                return;
+            };
+            let result = clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text);
+            if let Err(e) = result {
+                println!("Couldn't set the primary selection: {}", e);
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = text;
+    }
+
+    /// Returns the text of the primary selection. Returns [None] on the platforms without one.
+    pub fn paste_selection(&mut self) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        {
+            use arboard::{GetExtLinux, LinuxClipboardKind};
+            self.inner
+                .as_mut()?
+                .get()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text()
+                .ok()
+        }
+        #[cfg(not(target_os = "linux"))]
+        None
+    }
+}
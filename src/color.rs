@@ -1,4 +1,6 @@
-#[derive(Debug, Clone, Copy)]
+use crate::css::Color as CssColor;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -25,19 +27,44 @@ impl Color {
         }
     }
 
-    pub fn blue() -> Self {
+    pub fn gray() -> Self {
         Self {
             r: 0.5,
             g: 0.5,
-            b: 1.0,
+            b: 0.5,
             a: 1.0,
         }
     }
 
+    /// Converts a css color into a color that can be rendered. Returns `None` for transparent
+    /// colors, as there is nothing to render.
+    pub fn from_css(color: CssColor) -> Option<Self> {
+        let (r, g, b) = match color {
+            CssColor::Hex(r, g, b) => (r, g, b),
+            CssColor::White => (255, 255, 255),
+            CssColor::Gray => (128, 128, 128),
+            CssColor::Lightgray => (211, 211, 211),
+            CssColor::Black => (0, 0, 0),
+            CssColor::Transparent => return None,
+        };
+        Some(Self {
+            r: r as f32 / 255.0,
+            g: g as f32 / 255.0,
+            b: b as f32 / 255.0,
+            a: 1.0,
+        })
+    }
+
     pub fn as_opengl_color(&self) -> [f32; 4] {
         [self.r, self.g, self.b, self.a]
     }
 
+    /// Returns the color in the `#rrggbb` form. The alpha is left out.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, _] = self.as_8_bit();
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
     pub fn as_8_bit(&self) -> [u8; 4] {
         [
             (self.r * 255.0) as u8,
@@ -1,6 +1,12 @@
 //! A Css parser
 
-use crate::html::Tag;
+use crate::calc::{parse_calc, Calc};
+use crate::document::InteractionState;
+use crate::html::{Element, Tag};
+use crate::media::{MediaEnvironment, MediaQueryList};
+use crate::render_layout::DEFAULT_FONT_SIZE;
+use crate::transform::{parse_transform, Translate};
+use crate::transition::{parse_transitions, Transition};
 use std::{iter::Peekable, str::Chars};
 
 /// These represent the different units that are used in CSS. Their names should be equivalent to
@@ -19,7 +25,73 @@ pub enum Unit {
     Px(i32),
     Pt(i32),
     Em(f32),
+    /// Relative to the font size of the root element. It's turned into pixels when the style is
+    /// computed, see [Unit::resolve_rem].
+    Rem(f32),
     Percentage(i32),
+    /// The result of a `calc()` expression
+    Calc(Calc),
+}
+
+impl Unit {
+    /// Resolves this unit into pixels.
+    ///
+    /// * `font_size` - The font size of the element, used by relative units such as `em`
+    ///
+    /// * `containing_length` - The length that percentages are relative to
+    pub fn to_px(self, font_size: f32, containing_length: i32) -> i32 {
+        match self {
+            Unit::Px(v) => v,
+            Unit::Pt(v) => (v as f32 * 4.0 / 3.0) as i32,
+            Unit::Em(v) => (v * font_size) as i32,
+            // Only lengths outside of the styles, like the ones of media queries, are still in
+            // `rem` here. They are relative to the default font size.
+            Unit::Rem(v) => (v * DEFAULT_FONT_SIZE) as i32,
+            Unit::Percentage(v) => containing_length * v / 100,
+            Unit::Calc(v) => v.to_px(font_size, containing_length),
+        }
+    }
+
+    /// Turns `rem` into pixels, since the font size of the root element isn't known anymore
+    /// when the length is resolved
+    pub fn resolve_rem(self, root_font_size: f32) -> Unit {
+        match self {
+            Unit::Rem(v) => Unit::Calc(Calc {
+                px: v * root_font_size,
+                ..Default::default()
+            }),
+            Unit::Calc(v) => Unit::Calc(v.resolve_rem(root_font_size)),
+            v => v,
+        }
+    }
+
+    /// Multiplies the absolute lengths with the factor, for zooming the page. The relative
+    /// lengths follow the zoomed font sizes and boxes by themselves.
+    pub fn zoom(self, factor: f32) -> Unit {
+        match self {
+            Unit::Px(v) => Unit::Px((v as f32 * factor).round() as i32),
+            Unit::Pt(v) => Unit::Calc(Calc {
+                px: v as f32 * 4.0 / 3.0 * factor,
+                ..Default::default()
+            }),
+            Unit::Rem(v) => Unit::Rem(v * factor),
+            Unit::Calc(v) => Unit::Calc(Calc {
+                px: v.px * factor,
+                rem: v.rem * factor,
+                ..v
+            }),
+            v => v,
+        }
+    }
+
+    /// Checks if the length depends on the size of the containing block
+    pub fn has_percentage(self) -> bool {
+        match self {
+            Unit::Percentage(_) => true,
+            Unit::Calc(v) => v.percentage != 0.0,
+            _ => false,
+        }
+    }
 }
 
 /// This contains all of the different colours that css supports.
@@ -33,45 +105,391 @@ pub enum Color {
     Transparent,
 }
 
+/// Returns the named colors that don't have a variant of their own
+fn named_color(name: &str) -> Option<Color> {
+    match name {
+        "red" => Some(Color::Hex(255, 0, 0)),
+        "green" => Some(Color::Hex(0, 128, 0)),
+        "blue" => Some(Color::Hex(0, 0, 255)),
+        "purple" => Some(Color::Hex(128, 0, 128)),
+        "orange" => Some(Color::Hex(255, 165, 0)),
+        "yellow" => Some(Color::Hex(255, 255, 0)),
+        "navy" => Some(Color::Hex(0, 0, 128)),
+        "maroon" => Some(Color::Hex(128, 0, 0)),
+        "silver" => Some(Color::Hex(192, 192, 192)),
+        _ => None,
+    }
+}
+
 /// This contains all of the different values for border and such. For example
 /// `border: 20px solid`
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum BorderStyle {
     Solid,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DisplayStyle {
     Block,
+    Inline,
+    InlineBlock,
     None,
     Flex,
+    /// A block with a marker in front of it, like `<li>`
+    ListItem,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FlexDirection {
+    Row,
+    Column,
+}
+
+/// The keywords used by `justify-content` and `align-items` to place the items of a flex
+/// container.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Alignment {
+    Start,
+    End,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    Stretch,
+}
+
+/// The lines drawn over the text with `text-decoration`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextDecoration {
+    None,
+    Underline,
+    LineThrough,
+}
+
+/// The value of `line-height`, which sets the height of the lines of text
+#[derive(Debug, Copy, Clone)]
+pub enum LineHeight {
+    /// The height the font itself recommends
+    Normal,
+    /// A multiple of the font size
+    Number(f32),
+    /// Percentages are relative to the font size
+    Length(Unit),
+}
+
+impl LineHeight {
+    /// Resolves the line height into pixels.
+    ///
+    /// * `normal` - The height of a line when the line height is `normal`
+    pub fn to_px(self, font_size: f32, normal: i32) -> i32 {
+        match self {
+            LineHeight::Normal => normal,
+            LineHeight::Number(v) => (v * font_size) as i32,
+            LineHeight::Length(v) => v.to_px(font_size, font_size as i32),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum Direction {
+    Top,
     Right,
+    Bottom,
     Left,
 }
 
-#[derive(Debug)]
+/// The value of `background-repeat`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BackgroundRepeat {
+    Repeat,
+    RepeatX,
+    RepeatY,
+    NoRepeat,
+}
+
+/// The value of `background-size`
+#[derive(Debug, Copy, Clone)]
+pub enum BackgroundSize {
+    /// Scaled to cover the entire box, cutting off what doesn't fit
+    Cover,
+    /// Scaled to fit inside of the box
+    Contain,
+    /// The width and the height of the image. [None] is `auto`, which keeps the aspect ratio of
+    /// the image. Percentages are relative to the size of the box.
+    Size(Option<Unit>, Option<Unit>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FontStyle {
+    Normal,
     Italic,
-    Bold,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FontWeight {
     Normal,
+    Bold,
 }
 
-#[derive(Debug)]
+/// The value of `list-style-type`, which is the marker drawn in front of a list item
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ListStyleType {
+    Disc,
+    Circle,
+    Square,
+    Decimal,
+    /// The triangles of `<summary>`, which point right or down
+    DisclosureClosed,
+    DisclosureOpen,
+    None,
+}
+
+impl ListStyleType {
+    /// Returns the text of the marker of the item. `number` is the position of the item in its
+    /// list.
+    pub fn marker(self, number: i32) -> Option<String> {
+        match self {
+            ListStyleType::Disc => Some("•".to_owned()),
+            ListStyleType::Circle => Some("◦".to_owned()),
+            ListStyleType::Square => Some("▪".to_owned()),
+            ListStyleType::Decimal => Some(format!("{}.", number)),
+            ListStyleType::DisclosureClosed => Some("▸".to_owned()),
+            ListStyleType::DisclosureOpen => Some("▾".to_owned()),
+            ListStyleType::None => None,
+        }
+    }
+}
+
+/// The value of `direction`, which is the order that the text of a paragraph goes in. Words of
+/// the other direction inside of the text are still drawn in their own order.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// The value of `list-style-position`. The markers are always drawn outside of the items, so this
+/// is only parsed to allow it in the `list-style` shorthand.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ListStylePosition {
+    Inside,
+    Outside,
+}
+
+/// The value of `white-space`, which tells how the spaces and the newlines of the text are kept
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum WhiteSpace {
+    /// The spaces and newlines are collapsed, and the lines are wrapped to fit
+    Normal,
+    /// Like [WhiteSpace::Normal], but the lines are never wrapped
     NoWrap,
+    /// The spaces and newlines are kept, and the lines are never wrapped
+    Pre,
+    /// Like [WhiteSpace::Pre], but the lines are wrapped to fit
+    PreWrap,
 }
 
-#[derive(Debug)]
+impl WhiteSpace {
+    /// Checks if the lines can be wrapped at the spaces
+    pub fn wraps(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::PreWrap)
+    }
+
+    /// Checks if the spaces and the newlines are kept as they are
+    pub fn preserves_spaces(self) -> bool {
+        matches!(self, WhiteSpace::Pre | WhiteSpace::PreWrap)
+    }
+}
+
+/// An image that can be used as a background
+#[derive(Debug, Clone)]
+pub enum Image {
+    /// The url of the image as it was written in the css
+    Url(String),
+    LinearGradient(LinearGradient),
+}
+
+/// Where a linear gradient goes towards
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GradientDirection {
+    /// In degrees. `0deg` goes up and `90deg` goes to the right.
+    Angle(f32),
+    /// `to top right` and the other corners. The angle depends on the shape of the box, so that
+    /// the middle color goes through the two other corners.
+    Corner { right: bool, top: bool },
+}
+
+impl GradientDirection {
+    /// Returns the angle of the gradient in degrees in a box of the given size
+    pub fn degrees(self, width: f32, height: f32) -> f32 {
+        match self {
+            Self::Angle(v) => v,
+            Self::Corner { right, top } => {
+                let x = if right { height } else { -height };
+                let y = if top { width } else { -width };
+                x.atan2(y).to_degrees()
+            }
+        }
+    }
+}
+
+/// A color of a gradient and where it is on the gradient line
+#[derive(Debug, Copy, Clone)]
+pub struct ColorStop {
+    pub color: Color,
+    /// [None] spreads the stop evenly between its neighbours
+    pub position: Option<Unit>,
+}
+
+/// The value of `linear-gradient()`
+#[derive(Debug, Clone)]
+pub struct LinearGradient {
+    pub direction: GradientDirection,
+    pub stops: Vec<ColorStop>,
+}
+
+impl LinearGradient {
+    /// Parses the arguments of `linear-gradient()` without the parentheses. Returns [None] if
+    /// they aren't supported.
+    pub fn from_arguments(arguments: &str) -> Option<Self> {
+        let mut arguments = split_arguments(arguments);
+        let direction = match arguments.first().and_then(|x| gradient_direction(x)) {
+            Some(v) => {
+                arguments.remove(0);
+                v
+            }
+            None => GradientDirection::Angle(180.0),
+        };
+
+        let mut stops = Vec::new();
+        for argument in arguments {
+            let parts: Vec<&str> = argument.split_whitespace().collect();
+            let color = match Value::from_string(parts.first()?)? {
+                Value::Color(v) => v,
+                _ => return None,
+            };
+            // A stop with two positions is the same as two stops with the same color
+            let positions = match &parts[1..] {
+                [] => vec![None],
+                v => v
+                    .iter()
+                    .map(|x| length_from_value(&Value::from_string(x)?).map(Some))
+                    .collect::<Option<Vec<_>>>()?,
+            };
+            for position in positions {
+                stops.push(ColorStop { color, position });
+            }
+        }
+        // A gradient needs at least two colors
+        (stops.len() >= 2).then_some(Self { direction, stops })
+    }
+}
+
+/// Splits the arguments of a function at the commas that aren't inside of nested functions
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut output = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, character) in arguments.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                output.push(arguments[start..index].trim());
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    output.push(arguments[start..].trim());
+    output
+}
+
+/// Parses the direction of a gradient, such as `45deg` or `to left`. Returns [None] if the
+/// argument isn't a direction, so it's the first color stop instead.
+fn gradient_direction(argument: &str) -> Option<GradientDirection> {
+    if let Some(sides) = argument.strip_prefix("to ") {
+        let mut x = None;
+        let mut y = None;
+        for side in sides.split_whitespace() {
+            match side {
+                "left" => x = Some(false),
+                "right" => x = Some(true),
+                "top" => y = Some(true),
+                "bottom" => y = Some(false),
+                _ => return None,
+            }
+        }
+        let angle = match (x, y) {
+            (Some(right), Some(top)) => return Some(GradientDirection::Corner { right, top }),
+            (Some(true), None) => 90.0,
+            (Some(false), None) => 270.0,
+            (None, Some(true)) => 0.0,
+            (None, Some(false)) => 180.0,
+            (None, None) => return None,
+        };
+        return Some(GradientDirection::Angle(angle));
+    }
+
+    let units = [
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / std::f32::consts::PI),
+        ("turn", 360.0),
+    ];
+    for (suffix, scale) in units {
+        if let Some(v) = argument.strip_suffix(suffix) {
+            return v
+                .parse::<f32>()
+                .ok()
+                .map(|x| GradientDirection::Angle(x * scale));
+        }
+    }
+    None
+}
+
+/// What happens to the content that doesn't fit into a box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Overflow {
+    Visible,
+    /// The content is clipped to the padding box
+    Hidden,
+    /// The content is clipped, and the box can be scrolled to see the rest of it
+    Scroll,
+    /// Like [Overflow::Scroll]. There are no scrollbars yet, so the two look the same.
+    Auto,
+}
+
+impl Overflow {
+    pub fn is_scrollable(self) -> bool {
+        matches!(self, Overflow::Scroll | Overflow::Auto)
+    }
+}
+
+/// The value of `visibility`. A hidden box still takes its space in the layout, unlike a box with
+/// `display: none`, but nothing of it is drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Visibility {
+    Visible,
+    Hidden,
+}
+
+/// The value of `position`
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Position {
+    Static,
+    /// Moved by `top`, `right`, `bottom` and `left` after the layout, keeping its space
+    Relative,
+    /// Taken out of the flow and placed inside of the nearest positioned ancestor
     Absolute,
+    /// Like [Position::Absolute], but placed inside of the window and not moved by scrolling
+    Fixed,
+}
+
+impl Position {
+    /// Checks if the box is taken out of the normal flow, so it doesn't take any space there
+    pub fn is_out_of_flow(self) -> bool {
+        matches!(self, Position::Absolute | Position::Fixed)
+    }
 }
 
 /// This won't be used in the final product, but it will be used to contain a variable value to
@@ -84,122 +502,894 @@ pub enum Value {
     Color(Color),
     BorderStyle(BorderStyle),
     DisplayStyle(DisplayStyle),
+    FlexDirection(FlexDirection),
+    Alignment(Alignment),
+    TextDecoration(TextDecoration),
     Direction(Direction),
+    TextDirection(TextDirection),
     FontStyle(FontStyle),
     WhiteSpace(WhiteSpace),
     FontWeight(FontWeight),
     Number(i32),
+    /// A number with a fractional part, such as `1.5`
+    Decimal(f32),
     Position(Position),
+    Overflow(Overflow),
+    Visibility(Visibility),
+    BackgroundRepeat(BackgroundRepeat),
+    BackgroundSize(BackgroundSize),
+    ListStyleType(ListStyleType),
+    ListStylePosition(ListStylePosition),
+    /// The content of `url(...)` without the quotes
+    Url(String),
+    LinearGradient(LinearGradient),
+    /// The `/` that separates values in shorthands, such as the position and the size in
+    /// `background`
+    Slash,
     Inherit,
+    Auto,
 }
 
 impl Value {
-    /// Checks for a lot of values and returns one if it matches. Else it panics. This could
-    /// probably be an option in the future, but for now to keep things simple, we'll just write it
-    /// like this.
-    pub fn from_string(css_value: &str) -> Value {
+    /// Checks for a lot of values and returns one if it matches. Returns [None] for unknown
+    /// keywords, such as `display: grid`, and for a `calc()` that can't be parsed, so a page with
+    /// one doesn't crash, and the declaration is dropped instead.
+    pub fn from_string(css_value: &str) -> Option<Value> {
         if css_value.ends_with("px") {
+            // Lengths can be negative, such as the `-10px` of a negative margin
             let without_px_suffix = css_value.strip_suffix("px").unwrap();
-            if without_px_suffix.chars().all(|x| x.is_numeric()) {
-                return Value::Unit(Unit::Px(without_px_suffix.parse().unwrap()));
+            if let Ok(v) = without_px_suffix.parse::<i32>() {
+                return Some(Value::Unit(Unit::Px(v)));
             }
         } else if css_value.ends_with("pt") {
             let without_pt_suffix = css_value.strip_suffix("pt").unwrap();
-            if without_pt_suffix.chars().all(|x| x.is_numeric()) {
-                return Value::Unit(Unit::Pt(without_pt_suffix.parse().unwrap()));
-            }
-        } else if css_value.ends_with("em") {
-            let without_em_suffix = css_value.strip_suffix("em").unwrap();
-            if let Ok(v) = without_em_suffix.parse::<f32>() {
-                return Value::Unit(Unit::Em(v));
+            if let Ok(v) = without_pt_suffix.parse::<i32>() {
+                return Some(Value::Unit(Unit::Pt(v)));
             }
+        } else if let Some(v) = css_value
+            .strip_suffix("rem")
+            .and_then(|x| x.parse::<f32>().ok())
+        {
+            return Some(Value::Unit(Unit::Rem(v)));
+        } else if let Some(v) = css_value
+            .strip_suffix("em")
+            .and_then(|x| x.parse::<f32>().ok())
+        {
+            return Some(Value::Unit(Unit::Em(v)));
         } else if css_value.ends_with("%") {
             let without_percentage = css_value.strip_suffix("%").unwrap();
             if let Ok(v) = without_percentage.parse::<i32>() {
-                return Value::Unit(Unit::Percentage(v));
+                return Some(Value::Unit(Unit::Percentage(v)));
             }
         } else if css_value == "white" {
-            return Value::Color(Color::White);
+            return Some(Value::Color(Color::White));
+        } else if css_value == "black" {
+            return Some(Value::Color(Color::Black));
         } else if css_value == "gray" {
-            return Value::Color(Color::Gray);
+            return Some(Value::Color(Color::Gray));
         } else if css_value == "lightgray" {
-            return Value::Color(Color::Lightgray);
+            return Some(Value::Color(Color::Lightgray));
+        } else if let Some(color) = named_color(css_value) {
+            return Some(Value::Color(color));
         } else if css_value == "solid" {
-            return Value::BorderStyle(BorderStyle::Solid);
+            return Some(Value::BorderStyle(BorderStyle::Solid));
         } else if css_value == "transparent" {
-            return Value::Color(Color::Transparent);
+            return Some(Value::Color(Color::Transparent));
         } else if css_value == "block" {
-            return Value::DisplayStyle(DisplayStyle::Block);
+            return Some(Value::DisplayStyle(DisplayStyle::Block));
+        } else if css_value == "inline" {
+            return Some(Value::DisplayStyle(DisplayStyle::Inline));
+        } else if css_value == "inline-block" {
+            return Some(Value::DisplayStyle(DisplayStyle::InlineBlock));
+        } else if css_value == "list-item" {
+            return Some(Value::DisplayStyle(DisplayStyle::ListItem));
+        } else if css_value == "disc" {
+            return Some(Value::ListStyleType(ListStyleType::Disc));
+        } else if css_value == "circle" {
+            return Some(Value::ListStyleType(ListStyleType::Circle));
+        } else if css_value == "square" {
+            return Some(Value::ListStyleType(ListStyleType::Square));
+        } else if css_value == "decimal" {
+            return Some(Value::ListStyleType(ListStyleType::Decimal));
+        } else if css_value == "disclosure-closed" {
+            return Some(Value::ListStyleType(ListStyleType::DisclosureClosed));
+        } else if css_value == "disclosure-open" {
+            return Some(Value::ListStyleType(ListStyleType::DisclosureOpen));
+        } else if css_value == "inside" {
+            return Some(Value::ListStylePosition(ListStylePosition::Inside));
+        } else if css_value == "outside" {
+            return Some(Value::ListStylePosition(ListStylePosition::Outside));
+        } else if css_value == "underline" {
+            return Some(Value::TextDecoration(TextDecoration::Underline));
+        } else if css_value == "line-through" {
+            return Some(Value::TextDecoration(TextDecoration::LineThrough));
+        } else if css_value == "top" {
+            return Some(Value::Direction(Direction::Top));
         } else if css_value == "right" {
-            return Value::Direction(Direction::Right);
+            return Some(Value::Direction(Direction::Right));
+        } else if css_value == "bottom" {
+            return Some(Value::Direction(Direction::Bottom));
         } else if css_value == "left" {
-            return Value::Direction(Direction::Left);
+            return Some(Value::Direction(Direction::Left));
+        } else if css_value == "repeat" {
+            return Some(Value::BackgroundRepeat(BackgroundRepeat::Repeat));
+        } else if css_value == "repeat-x" {
+            return Some(Value::BackgroundRepeat(BackgroundRepeat::RepeatX));
+        } else if css_value == "repeat-y" {
+            return Some(Value::BackgroundRepeat(BackgroundRepeat::RepeatY));
+        } else if css_value == "no-repeat" {
+            return Some(Value::BackgroundRepeat(BackgroundRepeat::NoRepeat));
+        } else if css_value == "cover" {
+            return Some(Value::BackgroundSize(BackgroundSize::Cover));
+        } else if css_value == "contain" {
+            return Some(Value::BackgroundSize(BackgroundSize::Contain));
+        } else if let Some(url) = css_value
+            .strip_prefix("url(")
+            .and_then(|x| x.strip_suffix(')'))
+        {
+            let url = url.trim().trim_matches(|x| x == '"' || x == '\'');
+            return Some(Value::Url(url.to_owned()));
+        } else if let Some(expression) = css_value
+            .strip_prefix("calc(")
+            .and_then(|x| x.strip_suffix(')'))
+        {
+            return parse_calc(expression).map(Value::Unit);
+        } else if let Some(arguments) = css_value
+            .strip_prefix("linear-gradient(")
+            .and_then(|x| x.strip_suffix(')'))
+        {
+            if let Some(gradient) = LinearGradient::from_arguments(arguments) {
+                return Some(Value::LinearGradient(gradient));
+            }
         } else if css_value == "none" {
-            return Value::DisplayStyle(DisplayStyle::None);
+            return Some(Value::DisplayStyle(DisplayStyle::None));
         } else if css_value == "italic" {
-            return Value::FontStyle(FontStyle::Italic);
+            return Some(Value::FontStyle(FontStyle::Italic));
         } else if css_value == "bold" {
-            return Value::FontStyle(FontStyle::Bold);
+            return Some(Value::FontWeight(FontWeight::Bold));
         } else if css_value == "nowrap" {
-            return Value::WhiteSpace(WhiteSpace::NoWrap);
+            return Some(Value::WhiteSpace(WhiteSpace::NoWrap));
+        } else if css_value == "pre" {
+            return Some(Value::WhiteSpace(WhiteSpace::Pre));
+        } else if css_value == "pre-wrap" {
+            return Some(Value::WhiteSpace(WhiteSpace::PreWrap));
+        } else if css_value == "ltr" {
+            return Some(Value::TextDirection(TextDirection::LeftToRight));
+        } else if css_value == "rtl" {
+            return Some(Value::TextDirection(TextDirection::RightToLeft));
         } else if css_value == "normal" {
-            return Value::FontWeight(FontWeight::Normal);
+            return Some(Value::FontWeight(FontWeight::Normal));
         } else if css_value == "flex" {
-            return Value::DisplayStyle(DisplayStyle::Flex);
+            return Some(Value::DisplayStyle(DisplayStyle::Flex));
+        } else if css_value == "row" {
+            return Some(Value::FlexDirection(FlexDirection::Row));
+        } else if css_value == "column" {
+            return Some(Value::FlexDirection(FlexDirection::Column));
+        } else if css_value == "flex-start" || css_value == "start" {
+            return Some(Value::Alignment(Alignment::Start));
+        } else if css_value == "flex-end" || css_value == "end" {
+            return Some(Value::Alignment(Alignment::End));
+        } else if css_value == "center" {
+            return Some(Value::Alignment(Alignment::Center));
+        } else if css_value == "space-between" {
+            return Some(Value::Alignment(Alignment::SpaceBetween));
+        } else if css_value == "space-around" {
+            return Some(Value::Alignment(Alignment::SpaceAround));
+        } else if css_value == "stretch" {
+            return Some(Value::Alignment(Alignment::Stretch));
         } else if css_value == "inherit" {
-            return Value::Inherit;
+            return Some(Value::Inherit);
+        } else if css_value == "static" {
+            return Some(Value::Position(Position::Static));
+        } else if css_value == "relative" {
+            return Some(Value::Position(Position::Relative));
         } else if css_value == "absolute" {
-            return Value::Position(Position::Absolute);
+            return Some(Value::Position(Position::Absolute));
+        } else if css_value == "fixed" {
+            return Some(Value::Position(Position::Fixed));
+        } else if css_value == "auto" {
+            return Some(Value::Auto);
+        } else if css_value == "visible" {
+            return Some(Value::Overflow(Overflow::Visible));
+        } else if css_value == "hidden" || css_value == "clip" {
+            return Some(Value::Overflow(Overflow::Hidden));
+        } else if css_value == "scroll" {
+            return Some(Value::Overflow(Overflow::Scroll));
+        } else if css_value == "collapse" {
+            // Only differs from `hidden` for table rows and columns, which aren't supported
+            return Some(Value::Visibility(Visibility::Hidden));
         } else if css_value.starts_with('#') {
-            println!("Hex colors aren't implemented yet!");
-            return Value::Color(Color::Black);
-        } else if css_value.chars().all(|x| x.is_numeric()) {
-            return Value::Number(css_value.parse::<i32>().unwrap());
+            return collect_hex_color(&mut css_value.chars().peekable()).map(Value::Color);
+        } else if let Ok(v) = css_value.parse::<i32>() {
+            return Some(Value::Number(v));
+        } else if let Ok(v) = css_value.parse::<f32>() {
+            return Some(Value::Decimal(v));
         }
 
-        panic!("Couldn't convert '{}' into a css value", css_value);
+        println!("Couldn't convert '{}' into a css value", css_value);
+        None
     }
 }
 
+/// The font families that aren't the names of fonts, but ask for a kind of a font
+pub const GENERIC_FONT_FAMILIES: [&str; 5] =
+    ["serif", "sans-serif", "monospace", "cursive", "fantasy"];
+
 /// Represents a single rule in a ruleset block
 ///
 /// ## Example
 ///
-/// `Rule::Width(Unit::Px(50))` would represent the following CSS code.
+/// `Rule::Width(Some(Unit::Px(50)))` would represent the following CSS code.
 ///
 /// ```css
 /// width: 50px;
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub enum Rule {
-    Width(Unit),
+    /// The size of the content box. [None] is `auto`.
+    Width(Option<Unit>),
+    Height(Option<Unit>),
+    MinWidth(Unit),
+    /// [None] is `none`, so there is no limit
+    MaxWidth(Option<Unit>),
+    MinHeight(Unit),
+    MaxHeight(Option<Unit>),
+    Position(Position),
+    /// The offsets of positioned boxes. [None] is `auto`.
+    Top(Option<Unit>),
+    Right(Option<Unit>),
+    Bottom(Option<Unit>),
+    Left(Option<Unit>),
+    MarginTop(Unit),
+    MarginRight(Unit),
+    MarginBottom(Unit),
     MarginLeft(Unit),
+    PaddingTop(Unit),
+    PaddingRight(Unit),
+    PaddingBottom(Unit),
+    PaddingLeft(Unit),
+    /// Sets the width of all four sides
+    BorderWidth(Unit),
+    BorderTopWidth(Unit),
+    BorderRightWidth(Unit),
+    BorderBottomWidth(Unit),
+    BorderLeftWidth(Unit),
+    BorderStyle(BorderStyle),
+    BorderColor(Color),
     BackgroundColor(Color),
+    /// [None] is `none`
+    BackgroundImage(Option<Image>),
+    BackgroundRepeat(BackgroundRepeat),
+    BackgroundSize(BackgroundSize),
+    /// The horizontal and the vertical position of the image. A percentage lines up the same
+    /// point of the image and the box, so `100%` puts the image against the right or bottom edge.
+    BackgroundPosition(Unit, Unit),
+    Color(Color),
     FontSize(Unit),
+    FontWeight(FontWeight),
+    FontStyle(FontStyle),
+    /// The names of the fonts in the order of preference, such as `["Consolas", "monospace"]`
+    FontFamily(Vec<String>),
+    LineHeight(LineHeight),
+    TextDecoration(TextDecoration),
+    Display(DisplayStyle),
+    Overflow(Overflow),
+    FlexDirection(FlexDirection),
+    JustifyContent(Alignment),
+    AlignItems(Alignment),
+    FlexGrow(i32),
+    ListStyleType(ListStyleType),
+    WhiteSpace(WhiteSpace),
+    /// Inherited, like in other browsers
+    Direction(TextDirection),
+    Visibility(Visibility),
+    /// From 0.0 to 1.0
+    Opacity(f32),
+    /// The properties that are animated when they change, see [crate::transition]
+    Transition(Vec<Transition>),
+    /// Only translations are supported, see [crate::transform]
+    Transform(Translate),
+}
+
+/// Turns a value into a length. A plain `0` is a valid length in css even without a unit.
+fn length_from_value(value: &Value) -> Option<Unit> {
+    match value {
+        Value::Unit(unit) => Some(*unit),
+        Value::Number(0) => Some(Unit::Px(0)),
+        _ => None,
+    }
+}
+
+/// Turns the values of `background-position` into the horizontal and the vertical position. The
+/// keywords are turned into percentages.
+fn background_position_from_values(values: &[Value]) -> Option<(Unit, Unit)> {
+    // Keywords that only make sense on one of the axes are swapped into the right order
+    let is_vertical = |x: &Value| matches!(x, Value::Direction(Direction::Top | Direction::Bottom));
+    let is_horizontal =
+        |x: &Value| matches!(x, Value::Direction(Direction::Left | Direction::Right));
+    let position = |x: &Value| match x {
+        Value::Direction(Direction::Left | Direction::Top) => Some(Unit::Percentage(0)),
+        Value::Direction(Direction::Right | Direction::Bottom) => Some(Unit::Percentage(100)),
+        Value::Alignment(Alignment::Center) => Some(Unit::Percentage(50)),
+        v => length_from_value(v),
+    };
+    match values {
+        [v] if is_vertical(v) => Some((Unit::Percentage(50), position(v)?)),
+        [v] => Some((position(v)?, Unit::Percentage(50))),
+        [a, b] if is_vertical(a) || is_horizontal(b) => Some((position(b)?, position(a)?)),
+        [a, b] => Some((position(a)?, position(b)?)),
+        _ => None,
+    }
+}
+
+/// Turns the values of `background-size` into a size
+fn background_size_from_values(values: &[Value]) -> Option<BackgroundSize> {
+    let length = |x: &Value| match x {
+        Value::Auto => Some(None),
+        v => length_from_value(v).map(Some),
+    };
+    match values {
+        [Value::BackgroundSize(v)] => Some(*v),
+        [width] => Some(BackgroundSize::Size(length(width)?, None)),
+        [width, height] => Some(BackgroundSize::Size(length(width)?, length(height)?)),
+        _ => None,
+    }
+}
+
+/// Expands the 1 to 4 values of an edge shorthand, such as `margin` or `padding`, into the top,
+/// right, bottom and left values.
+fn expand_edges(values: &[Unit]) -> Option<[Unit; 4]> {
+    match values {
+        [all] => Some([*all, *all, *all, *all]),
+        [vertical, horizontal] => Some([*vertical, *horizontal, *vertical, *horizontal]),
+        [top, horizontal, bottom] => Some([*top, *horizontal, *bottom, *horizontal]),
+        [top, right, bottom, left] => Some([*top, *right, *bottom, *left]),
+        _ => None,
+    }
 }
 
 impl Rule {
+    /// Turns the `rem` lengths of the rule into pixels, see [Unit::resolve_rem]
+    pub fn resolve_rem(&self, root_font_size: f32) -> Rule {
+        let resolve = |x: &Unit| x.resolve_rem(root_font_size);
+        let resolve_option = |x: &Option<Unit>| x.map(|x| x.resolve_rem(root_font_size));
+        match self {
+            Rule::Width(v) => Rule::Width(resolve_option(v)),
+            Rule::Height(v) => Rule::Height(resolve_option(v)),
+            Rule::MinWidth(v) => Rule::MinWidth(resolve(v)),
+            Rule::MaxWidth(v) => Rule::MaxWidth(resolve_option(v)),
+            Rule::MinHeight(v) => Rule::MinHeight(resolve(v)),
+            Rule::MaxHeight(v) => Rule::MaxHeight(resolve_option(v)),
+            Rule::Top(v) => Rule::Top(resolve_option(v)),
+            Rule::Right(v) => Rule::Right(resolve_option(v)),
+            Rule::Bottom(v) => Rule::Bottom(resolve_option(v)),
+            Rule::Left(v) => Rule::Left(resolve_option(v)),
+            Rule::MarginTop(v) => Rule::MarginTop(resolve(v)),
+            Rule::MarginRight(v) => Rule::MarginRight(resolve(v)),
+            Rule::MarginBottom(v) => Rule::MarginBottom(resolve(v)),
+            Rule::MarginLeft(v) => Rule::MarginLeft(resolve(v)),
+            Rule::PaddingTop(v) => Rule::PaddingTop(resolve(v)),
+            Rule::PaddingRight(v) => Rule::PaddingRight(resolve(v)),
+            Rule::PaddingBottom(v) => Rule::PaddingBottom(resolve(v)),
+            Rule::PaddingLeft(v) => Rule::PaddingLeft(resolve(v)),
+            Rule::BorderWidth(v) => Rule::BorderWidth(resolve(v)),
+            Rule::BorderTopWidth(v) => Rule::BorderTopWidth(resolve(v)),
+            Rule::BorderRightWidth(v) => Rule::BorderRightWidth(resolve(v)),
+            Rule::BorderBottomWidth(v) => Rule::BorderBottomWidth(resolve(v)),
+            Rule::BorderLeftWidth(v) => Rule::BorderLeftWidth(resolve(v)),
+            Rule::BackgroundPosition(x, y) => Rule::BackgroundPosition(resolve(x), resolve(y)),
+            Rule::BackgroundSize(BackgroundSize::Size(width, height)) => Rule::BackgroundSize(
+                BackgroundSize::Size(resolve_option(width), resolve_option(height)),
+            ),
+            Rule::FontSize(v) => Rule::FontSize(resolve(v)),
+            Rule::LineHeight(LineHeight::Length(v)) => {
+                Rule::LineHeight(LineHeight::Length(resolve(v)))
+            }
+            Rule::Transform(v) => Rule::Transform(v.resolve_rem(root_font_size)),
+            v => v.clone(),
+        }
+    }
+
+    /// Checks if the property is supported at all. The values of unsupported properties are
+    /// skipped without parsing them, so stylesheets with unknown values don't crash the browser.
+    pub fn is_supported(identifier: &str) -> bool {
+        matches!(
+            identifier,
+            "width"
+                | "height"
+                | "min-width"
+                | "max-width"
+                | "min-height"
+                | "max-height"
+                | "position"
+                | "top"
+                | "right"
+                | "bottom"
+                | "left"
+                | "margin"
+                | "margin-top"
+                | "margin-right"
+                | "margin-bottom"
+                | "margin-left"
+                | "padding"
+                | "padding-top"
+                | "padding-right"
+                | "padding-bottom"
+                | "padding-left"
+                | "border"
+                | "border-top"
+                | "border-right"
+                | "border-bottom"
+                | "border-left"
+                | "border-width"
+                | "border-top-width"
+                | "border-right-width"
+                | "border-bottom-width"
+                | "border-left-width"
+                | "border-style"
+                | "border-color"
+                | "background"
+                | "background-color"
+                | "background-image"
+                | "background-repeat"
+                | "background-size"
+                | "background-position"
+                | "color"
+                | "display"
+                | "overflow"
+                | "flex"
+                | "flex-direction"
+                | "justify-content"
+                | "align-items"
+                | "flex-grow"
+                | "font-size"
+                | "font-weight"
+                | "font-style"
+                | "font-family"
+                | "line-height"
+                | "text-decoration"
+                | "list-style"
+                | "list-style-type"
+                | "white-space"
+                | "direction"
+                | "visibility"
+                | "opacity"
+                | "transition"
+                | "transform"
+        )
+    }
+
+    /// Checks if the rule only changes a color
+    pub fn is_color(&self) -> bool {
+        matches!(
+            self,
+            Rule::Color(_) | Rule::BackgroundColor(_) | Rule::BorderColor(_)
+        )
+    }
+
+    /// Creates the rules of a single declaration. Unlike [Rule::new] this also handles shorthand
+    /// properties, which set multiple rules at once.
+    ///
+    /// ## Example
+    ///
+    /// `margin: 10px 20px;` would return the four rules `MarginTop(Px(10))`,
+    /// `MarginRight(Px(20))`, `MarginBottom(Px(10))` and `MarginLeft(Px(20))`.
+    pub fn from_declaration(identifier: &str, value: Vec<Value>) -> Vec<Self> {
+        match identifier {
+            "margin" | "padding" => {
+                let units: Vec<Unit> = value.iter().filter_map(length_from_value).collect();
+                if units.len() != value.len() {
+                    println!("Unsupported value in '{}': {:?}", identifier, value);
+                    return Vec::new();
+                }
+                let Some([top, right, bottom, left]) = expand_edges(&units) else {
+                    println!("Expected 1 to 4 values for '{}'", identifier);
+                    return Vec::new();
+                };
+                if identifier == "margin" {
+                    vec![
+                        Self::MarginTop(top),
+                        Self::MarginRight(right),
+                        Self::MarginBottom(bottom),
+                        Self::MarginLeft(left),
+                    ]
+                } else {
+                    vec![
+                        Self::PaddingTop(top),
+                        Self::PaddingRight(right),
+                        Self::PaddingBottom(bottom),
+                        Self::PaddingLeft(left),
+                    ]
+                }
+            }
+
+            // The style and the color are shared by all of the sides, so the shorthands of the
+            // single sides set them for the whole box
+            "border" | "border-top" | "border-right" | "border-bottom" | "border-left" => {
+                let mut rules = Vec::new();
+                for value in &value {
+                    match value {
+                        Value::Unit(_) | Value::Number(0) => {
+                            let width = length_from_value(value).unwrap();
+                            rules.push(match identifier {
+                                "border-top" => Self::BorderTopWidth(width),
+                                "border-right" => Self::BorderRightWidth(width),
+                                "border-bottom" => Self::BorderBottomWidth(width),
+                                "border-left" => Self::BorderLeftWidth(width),
+                                _ => Self::BorderWidth(width),
+                            })
+                        }
+                        Value::BorderStyle(style) => rules.push(Self::BorderStyle(*style)),
+                        Value::Color(color) => rules.push(Self::BorderColor(*color)),
+                        v => println!("Unsupported border value: {:?}", v),
+                    }
+                }
+                rules
+            }
+
+            // The parts of the shorthand that are left out are reset to their initial values
+            "background" => {
+                let mut color = Color::Transparent;
+                let mut image = None;
+                let mut repeat = BackgroundRepeat::Repeat;
+                let mut position = Vec::new();
+                let mut size = Vec::new();
+                let mut after_slash = false;
+                for value in value {
+                    match value {
+                        Value::Color(v) => color = v,
+                        Value::Url(v) => image = Some(Image::Url(v)),
+                        Value::LinearGradient(v) => image = Some(Image::LinearGradient(v)),
+                        Value::DisplayStyle(DisplayStyle::None) => image = None,
+                        Value::BackgroundRepeat(v) => repeat = v,
+                        Value::Slash => after_slash = true,
+                        v if after_slash => size.push(v),
+                        v => position.push(v),
+                    }
+                }
+                let position = match position.as_slice() {
+                    [] => Some((Unit::Percentage(0), Unit::Percentage(0))),
+                    v => background_position_from_values(v),
+                };
+                let size = match size.as_slice() {
+                    [] => Some(BackgroundSize::Size(None, None)),
+                    v => background_size_from_values(v),
+                };
+                let (Some((x, y)), Some(size)) = (position, size) else {
+                    println!("Unsupported background value");
+                    return Vec::new();
+                };
+                vec![
+                    Self::BackgroundColor(color),
+                    Self::BackgroundImage(image),
+                    Self::BackgroundRepeat(repeat),
+                    Self::BackgroundPosition(x, y),
+                    Self::BackgroundSize(size),
+                ]
+            }
+
+            // Only the grow factor of the shorthand is supported for now
+            "flex" => match value.first() {
+                Some(Value::Number(v)) => vec![Self::FlexGrow(*v)],
+                v => {
+                    println!("Unsupported flex value: {:?}", v);
+                    Vec::new()
+                }
+            },
+
+            // Only the type of the marker is supported. A missing type is reset to `disc`.
+            "list-style" => {
+                let mut list_style_type = ListStyleType::Disc;
+                for value in &value {
+                    match value {
+                        Value::ListStyleType(v) => list_style_type = *v,
+                        Value::DisplayStyle(DisplayStyle::None) => {
+                            list_style_type = ListStyleType::None
+                        }
+                        Value::ListStylePosition(_) => (),
+                        v => println!("Unsupported list style value: {:?}", v),
+                    }
+                }
+                vec![Self::ListStyleType(list_style_type)]
+            }
+
+            _ => Self::new(identifier, value).into_iter().collect(),
+        }
+    }
+
     pub fn new(identifier: &str, value: Vec<Value>) -> Option<Self> {
+        // An empty declaration such as `color: ;` is dropped
+        let Some(first) = value.first() else {
+            println!("Missing value for '{}'", identifier);
+            return None;
+        };
         match identifier {
-            "width" => match value.first().unwrap() {
-                Value::Unit(unit) => Some(Self::Width(*unit)),
-                _ => panic!("Expected unit"),
+            "width" | "height" | "min-width" | "max-width" | "min-height" | "max-height"
+            | "top" | "right" | "bottom" | "left" => {
+                // `auto` and `none` both mean that the size isn't limited
+                let unit = match first {
+                    Value::Auto | Value::DisplayStyle(DisplayStyle::None) => None,
+                    v => match length_from_value(v) {
+                        Some(v) => Some(v),
+                        None => {
+                            println!("Unsupported value for '{}': {:?}", identifier, v);
+                            return None;
+                        }
+                    },
+                };
+                match identifier {
+                    "width" => Some(Self::Width(unit)),
+                    "height" => Some(Self::Height(unit)),
+                    "min-width" => Some(Self::MinWidth(unit.unwrap_or(Unit::Px(0)))),
+                    "max-width" => Some(Self::MaxWidth(unit)),
+                    "min-height" => Some(Self::MinHeight(unit.unwrap_or(Unit::Px(0)))),
+                    "max-height" => Some(Self::MaxHeight(unit)),
+                    "top" => Some(Self::Top(unit)),
+                    "right" => Some(Self::Right(unit)),
+                    "bottom" => Some(Self::Bottom(unit)),
+                    _ => Some(Self::Left(unit)),
+                }
+            }
+
+            "position" => match first {
+                Value::Position(v) => Some(Self::Position(*v)),
+                v => {
+                    println!("Unsupported position: {:?}", v);
+                    None
+                }
+            },
+
+            "margin-top"
+            | "margin-right"
+            | "margin-bottom"
+            | "margin-left"
+            | "padding-top"
+            | "padding-right"
+            | "padding-bottom"
+            | "padding-left"
+            | "border-width"
+            | "border-top-width"
+            | "border-right-width"
+            | "border-bottom-width"
+            | "border-left-width" => {
+                let Some(unit) = length_from_value(first) else {
+                    println!("Unsupported value for '{}': {:?}", identifier, value);
+                    return None;
+                };
+                match identifier {
+                    "margin-top" => Some(Self::MarginTop(unit)),
+                    "margin-right" => Some(Self::MarginRight(unit)),
+                    "margin-bottom" => Some(Self::MarginBottom(unit)),
+                    "margin-left" => Some(Self::MarginLeft(unit)),
+                    "padding-top" => Some(Self::PaddingTop(unit)),
+                    "padding-right" => Some(Self::PaddingRight(unit)),
+                    "padding-bottom" => Some(Self::PaddingBottom(unit)),
+                    "padding-left" => Some(Self::PaddingLeft(unit)),
+                    "border-top-width" => Some(Self::BorderTopWidth(unit)),
+                    "border-right-width" => Some(Self::BorderRightWidth(unit)),
+                    "border-bottom-width" => Some(Self::BorderBottomWidth(unit)),
+                    "border-left-width" => Some(Self::BorderLeftWidth(unit)),
+                    _ => Some(Self::BorderWidth(unit)),
+                }
+            }
+
+            "border-style" => match first {
+                Value::BorderStyle(style) => Some(Self::BorderStyle(*style)),
+                v => {
+                    println!("Unsupported border style: {:?}", v);
+                    None
+                }
             },
 
-            "margin-left" => match value.first().unwrap() {
-                Value::Unit(unit) => Some(Self::MarginLeft(*unit)),
-                _ => panic!("Expected unit"),
+            "border-color" => match first {
+                Value::Color(color) => Some(Self::BorderColor(*color)),
+                v => {
+                    println!("Unsupported border color: {:?}", v);
+                    None
+                }
             },
 
-            "background-color" => match value.first().unwrap() {
+            "background-color" => match first {
                 Value::Color(color) => Some(Self::BackgroundColor(*color)),
-                _ => panic!("Expected a color"),
+                v => {
+                    println!("Unsupported background color: {:?}", v);
+                    None
+                }
             },
 
-            "font-size" => match value.first().unwrap() {
-                Value::Unit(v) => Some(Self::FontSize(*v)),
-                v => panic!("Expected unit. Got '{:?}'", v),
+            "background-image" => match first {
+                Value::Url(url) => Some(Self::BackgroundImage(Some(Image::Url(url.clone())))),
+                Value::LinearGradient(v) => Some(Self::BackgroundImage(Some(
+                    Image::LinearGradient(v.clone()),
+                ))),
+                Value::DisplayStyle(DisplayStyle::None) => Some(Self::BackgroundImage(None)),
+                v => {
+                    println!("Unsupported background image: {:?}", v);
+                    None
+                }
+            },
+
+            "background-repeat" => match first {
+                Value::BackgroundRepeat(v) => Some(Self::BackgroundRepeat(*v)),
+                v => {
+                    println!("Unsupported background repeat: {:?}", v);
+                    None
+                }
+            },
+
+            "background-size" => match background_size_from_values(&value) {
+                Some(v) => Some(Self::BackgroundSize(v)),
+                None => {
+                    println!("Unsupported background size: {:?}", value);
+                    None
+                }
+            },
+
+            "background-position" => match background_position_from_values(&value) {
+                Some((x, y)) => Some(Self::BackgroundPosition(x, y)),
+                None => {
+                    println!("Unsupported background position: {:?}", value);
+                    None
+                }
+            },
+
+            "color" => match first {
+                Value::Color(color) => Some(Self::Color(*color)),
+                v => {
+                    println!("Unsupported color: {:?}", v);
+                    None
+                }
+            },
+
+            "display" => match first {
+                Value::DisplayStyle(v) => Some(Self::Display(*v)),
+                v => {
+                    println!("Unsupported display style: {:?}", v);
+                    None
+                }
+            },
+
+            "flex-direction" => match first {
+                Value::FlexDirection(v) => Some(Self::FlexDirection(*v)),
+                v => {
+                    println!("Unsupported flex direction: {:?}", v);
+                    None
+                }
+            },
+
+            "justify-content" | "align-items" => match first {
+                Value::Alignment(v) if identifier == "justify-content" => {
+                    Some(Self::JustifyContent(*v))
+                }
+                Value::Alignment(v) => Some(Self::AlignItems(*v)),
+                v => {
+                    println!("Unsupported value for '{}': {:?}", identifier, v);
+                    None
+                }
+            },
+
+            "flex-grow" => match first {
+                Value::Number(v) => Some(Self::FlexGrow(*v)),
+                // Fractional grow factors aren't supported, since the factors are whole numbers
+                v => {
+                    println!("Unsupported flex grow: {:?}", v);
+                    None
+                }
+            },
+
+            "font-size" => match length_from_value(first) {
+                Some(v) => Some(Self::FontSize(v)),
+                None => {
+                    println!("Unsupported font size: {:?}", first);
+                    None
+                }
+            },
+
+            "font-weight" => match first {
+                Value::FontWeight(v) => Some(Self::FontWeight(*v)),
+                // There is only a normal and a bold font, so the numeric weights are rounded to
+                // the closer one
+                Value::Number(v) => Some(Self::FontWeight(if *v >= 600 {
+                    FontWeight::Bold
+                } else {
+                    FontWeight::Normal
+                })),
+                v => {
+                    println!("Unsupported font weight: {:?}", v);
+                    None
+                }
+            },
+
+            "font-style" => match first {
+                Value::FontStyle(v) => Some(Self::FontStyle(*v)),
+                Value::FontWeight(FontWeight::Normal) => Some(Self::FontStyle(FontStyle::Normal)),
+                v => {
+                    println!("Unsupported font style: {:?}", v);
+                    None
+                }
+            },
+
+            "line-height" => match first {
+                Value::FontWeight(FontWeight::Normal) => Some(Self::LineHeight(LineHeight::Normal)),
+                Value::Number(v) => Some(Self::LineHeight(LineHeight::Number(*v as f32))),
+                Value::Decimal(v) => Some(Self::LineHeight(LineHeight::Number(*v))),
+                Value::Unit(v) => Some(Self::LineHeight(LineHeight::Length(*v))),
+                v => {
+                    println!("Unsupported line height: {:?}", v);
+                    None
+                }
+            },
+
+            "overflow" => match first {
+                Value::Overflow(v) => Some(Self::Overflow(*v)),
+                Value::Auto => Some(Self::Overflow(Overflow::Auto)),
+                v => {
+                    println!("Unsupported overflow: {:?}", v);
+                    None
+                }
+            },
+
+            "text-decoration" => match first {
+                Value::TextDecoration(v) => Some(Self::TextDecoration(*v)),
+                Value::DisplayStyle(DisplayStyle::None) => {
+                    Some(Self::TextDecoration(TextDecoration::None))
+                }
+                v => {
+                    println!("Unsupported text decoration: {:?}", v);
+                    None
+                }
+            },
+
+            // `visible` and `hidden` are parsed as values of `overflow`
+            "visibility" => match first {
+                Value::Overflow(Overflow::Visible) => Some(Self::Visibility(Visibility::Visible)),
+                Value::Overflow(Overflow::Hidden) => Some(Self::Visibility(Visibility::Hidden)),
+                Value::Visibility(v) => Some(Self::Visibility(*v)),
+                v => {
+                    println!("Unsupported visibility: {:?}", v);
+                    None
+                }
+            },
+
+            "opacity" => match first {
+                Value::Number(v) => Some(Self::Opacity((*v as f32).clamp(0.0, 1.0))),
+                Value::Decimal(v) => Some(Self::Opacity(v.clamp(0.0, 1.0))),
+                Value::Unit(Unit::Percentage(v)) => {
+                    Some(Self::Opacity((*v as f32 / 100.0).clamp(0.0, 1.0)))
+                }
+                v => {
+                    println!("Unsupported opacity: {:?}", v);
+                    None
+                }
+            },
+
+            "direction" => match first {
+                Value::TextDirection(v) => Some(Self::Direction(*v)),
+                v => {
+                    println!("Unsupported direction: {:?}", v);
+                    None
+                }
+            },
+
+            "white-space" => match first {
+                Value::WhiteSpace(v) => Some(Self::WhiteSpace(*v)),
+                Value::FontWeight(FontWeight::Normal) => Some(Self::WhiteSpace(WhiteSpace::Normal)),
+                v => {
+                    println!("Unsupported white space: {:?}", v);
+                    None
+                }
+            },
+
+            "list-style-type" => match first {
+                Value::ListStyleType(v) => Some(Self::ListStyleType(*v)),
+                Value::DisplayStyle(DisplayStyle::None) => {
+                    Some(Self::ListStyleType(ListStyleType::None))
+                }
+                v => {
+                    println!("Unsupported list style type: {:?}", v);
+                    None
+                }
             },
 
             _ => {
@@ -225,16 +1415,285 @@ impl Rule {
 #[allow(dead_code)]
 pub enum Selector {
     Element(Tag),
+    /// `*`, which matches every element
+    Universal,
+    PseudoClass(PseudoClass),
+    Attribute(AttributeSelector),
+    /// Selectors that all have to match the same element, such as `a:hover`
+    Compound(Vec<Selector>),
+    /// Selectors separated by whitespace, such as `nav a`. The last one has to match the element,
+    /// and the ones before it have to match its ancestors in the same order.
+    Descendant(Vec<Selector>),
+}
+
+/// Matches elements by their attributes, such as `[href]` or `[type="text"]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSelector {
+    /// The name of the attribute in lowercase, since attribute names aren't case-sensitive
+    pub name: String,
+    /// The value that the attribute has to have. [None] matches any value, like `[href]` does.
+    pub value: Option<String>,
+}
+
+impl AttributeSelector {
+    /// Parses the text between the brackets, such as `type="text"`
+    pub fn from_string(selector: &str) -> Option<Self> {
+        let (name, value) = match selector.split_once('=') {
+            Some((name, value)) => (name, Some(value.trim())),
+            None => (selector, None),
+        };
+        let name = name.trim().to_lowercase();
+        let is_identifier = |x: &str| {
+            !x.is_empty()
+                && x.chars()
+                    .all(|x| x.is_alphanumeric() || x == '-' || x == '_')
+        };
+        if !is_identifier(&name) {
+            return None;
+        }
+
+        // The value can be quoted with either kind of quotes, or be a plain identifier
+        let value = match value {
+            Some(v) => {
+                let quoted = ['"', '\''].into_iter().find_map(|quote| {
+                    v.strip_prefix(quote)
+                        .and_then(|x| x.strip_suffix(quote))
+                        .filter(|x| !x.contains(quote))
+                });
+                match quoted {
+                    Some(v) => Some(v.to_owned()),
+                    None if is_identifier(v) => Some(v.to_owned()),
+                    None => return None,
+                }
+            }
+            None => None,
+        };
+        Some(Self { name, value })
+    }
+
+    pub fn matches(&self, element: &Element) -> bool {
+        let value = element
+            .attributes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&self.name))
+            .map(|(_, value)| value);
+        match (&self.value, value) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(expected), Some(value)) => expected == value,
+        }
+    }
+}
+
+/// Selectors that depend on the state of the element instead of the document
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PseudoClass {
+    /// A link that hasn't been visited
+    Link,
+    Visited,
+    Hover,
+    Active,
+    Focus,
+}
+
+impl PseudoClass {
+    pub fn from_string(name: &str) -> Option<Self> {
+        match name {
+            "link" => Some(Self::Link),
+            "visited" => Some(Self::Visited),
+            "hover" => Some(Self::Hover),
+            "active" => Some(Self::Active),
+            "focus" => Some(Self::Focus),
+            _ => None,
+        }
+    }
+}
+
+impl Selector {
+    /// Checks if the selector only matches visited links
+    pub fn uses_visited(&self) -> bool {
+        match self {
+            Selector::PseudoClass(pseudo_class) => *pseudo_class == PseudoClass::Visited,
+            Selector::Compound(selectors) | Selector::Descendant(selectors) => {
+                selectors.iter().any(|x| x.uses_visited())
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if the selector matches the element
+    ///
+    /// * `ancestors` - The ancestors of the element, with the parent last
+    pub fn matches(
+        &self,
+        element: &Element,
+        ancestors: &[&Element],
+        state: &InteractionState,
+    ) -> bool {
+        match self {
+            Selector::Element(tag) => element.element_type == *tag,
+            Selector::Universal => true,
+            Selector::PseudoClass(pseudo_class) => {
+                let is_link =
+                    element.element_type == Tag::A && element.attributes.contains_key("href");
+                match pseudo_class {
+                    PseudoClass::Link => is_link && !state.visited.contains(&element.id),
+                    PseudoClass::Visited => is_link && state.visited.contains(&element.id),
+                    PseudoClass::Hover => state.hovered.contains(&element.id),
+                    PseudoClass::Active => state.active.contains(&element.id),
+                    PseudoClass::Focus => state.focused == Some(element.id),
+                }
+            }
+            Selector::Attribute(attribute) => attribute.matches(element),
+            Selector::Compound(selectors) => selectors
+                .iter()
+                .all(|x| x.matches(element, ancestors, state)),
+            Selector::Descendant(selectors) => {
+                let Some((subject, selectors)) = selectors.split_last() else {
+                    return false;
+                };
+                if !subject.matches(element, ancestors, state) {
+                    return false;
+                }
+                // Matching the nearest ancestor that fits each selector leaves the most ancestors
+                // for the selectors before it
+                let mut selectors = selectors.iter().rev().peekable();
+                for (index, ancestor) in ancestors.iter().enumerate().rev() {
+                    let Some(selector) = selectors.peek() else {
+                        break;
+                    };
+                    if selector.matches(ancestor, &ancestors[..index], state) {
+                        selectors.next();
+                    }
+                }
+                selectors.peek().is_none()
+            }
+        }
+    }
+
+    /// Parses a single selector, such as `a:hover`, `input[type="text"]` or `nav a`. Returns
+    /// [None] if the selector isn't supported.
+    pub fn from_string(selector: &str) -> Option<Self> {
+        let mut selectors = split_descendants(selector)
+            .into_iter()
+            .map(Self::compound_from_string)
+            .collect::<Option<Vec<_>>>()?;
+        match selectors.len() {
+            0 => None,
+            1 => selectors.pop(),
+            _ => Some(Selector::Descendant(selectors)),
+        }
+    }
+
+    /// Parses the selectors of a single element, such as `a:hover`
+    fn compound_from_string(selector: &str) -> Option<Self> {
+        let mut iterator = selector.chars().peekable();
+
+        // The element type comes first, and it can be left out
+        let mut selectors = Vec::new();
+        let tag = collect_selector_name(&mut iterator).to_lowercase();
+        match tag.as_str() {
+            "" => (),
+            "*" => selectors.push(Selector::Universal),
+            tag => selectors.push(Selector::Element(Tag::from_string(tag).ok()?)),
+        }
+
+        while let Some(character) = iterator.next() {
+            match character {
+                ':' => {
+                    let name = collect_selector_name(&mut iterator).to_lowercase();
+                    selectors.push(Selector::PseudoClass(PseudoClass::from_string(&name)?));
+                }
+                '[' => {
+                    // The value can have `]` and `:` inside of quotes
+                    let mut content = String::new();
+                    let mut quote = None;
+                    loop {
+                        let character = iterator.next()?;
+                        match (character, quote) {
+                            (']', None) => break,
+                            ('"' | '\'', None) => quote = Some(character),
+                            (v, Some(q)) if v == q => quote = None,
+                            _ => (),
+                        }
+                        content.push(character);
+                    }
+                    selectors.push(Selector::Attribute(AttributeSelector::from_string(
+                        &content,
+                    )?));
+                }
+                _ => return None,
+            }
+        }
+
+        match selectors.len() {
+            0 => None,
+            1 => selectors.pop(),
+            _ => Some(Selector::Compound(selectors)),
+        }
+    }
+}
+
+/// Splits the selector at the whitespace between the selectors of the element and its ancestors.
+/// The whitespace inside of the brackets of attribute selectors doesn't split it.
+fn split_descendants(selector: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut in_brackets = false;
+    for (index, character) in selector.char_indices() {
+        match (character, quote) {
+            ('"' | '\'', None) if in_brackets => quote = Some(character),
+            (v, Some(q)) if v == q => quote = None,
+            ('[', None) => in_brackets = true,
+            (']', None) => in_brackets = false,
+            (v, None) if v.is_whitespace() && !in_brackets => {
+                if let Some(start) = start.take() {
+                    parts.push(&selector[start..index]);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(start) = start {
+        parts.push(&selector[start..]);
+    }
+    parts
+}
+
+/// Collects the name of an element type or a pseudo-class. The iterator is left on the `:` or
+/// `[` that starts the next part of the selector.
+fn collect_selector_name(iterator: &mut Peekable<Chars>) -> String {
+    let mut output = String::new();
+    while let Some(v) = iterator.next_if(|x| *x != ':' && *x != '[') {
+        output.push(v);
+    }
+    output
+}
+
+/// Parses a comma separated list of selectors. Unsupported selectors are left out.
+fn parse_selectors(selectors: &str) -> Vec<Selector> {
+    let mut output = Vec::new();
+    for selector in selectors.split(',').map(|x| x.trim()) {
+        match Selector::from_string(selector) {
+            Some(v) => output.push(v),
+            None => println!("Unsupported selector: {}", selector),
+        }
+    }
+    output
 }
 
 /// Represents an entire ruleset block with selectors and rules.
 ///
 /// ## Example
 ///
-/// ```rust
+/// ```ignore
 /// RuleSet {
 ///     selectors: vec![Selector::Element(Tag::P)],
-///     rules: vec![Rule::Width(Unit::Px(50))],
+///     rules: vec![Rule::Width(Some(Unit::Px(50)))],
+///     media: vec![],
 /// }
 /// ```
 ///
@@ -250,6 +1709,41 @@ pub enum Selector {
 pub struct RuleSet {
     pub selectors: Vec<Selector>,
     pub rules: Vec<Rule>,
+    /// The media query lists of the `@media` blocks around this ruleset. All of them have to
+    /// match for the rules to apply.
+    pub media: Vec<MediaQueryList>,
+}
+
+impl RuleSet {
+    pub fn matches_media(&self, environment: &MediaEnvironment) -> bool {
+        self.media.iter().all(|x| x.matches(environment))
+    }
+
+    /// Returns the rules that apply to the element. If the ruleset only matches through
+    /// `:visited`, only the colors are applied, so the browsing history can never change the
+    /// layout of the page.
+    ///
+    /// * `ancestors` - The ancestors of the element, with the parent last
+    pub fn matching_rules(
+        &self,
+        element: &Element,
+        ancestors: &[&Element],
+        state: &InteractionState,
+    ) -> Vec<&Rule> {
+        let matching: Vec<&Selector> = self
+            .selectors
+            .iter()
+            .filter(|x| x.matches(element, ancestors, state))
+            .collect();
+        if matching.is_empty() {
+            return Vec::new();
+        }
+        let colors_only = matching.iter().all(|x| x.uses_visited());
+        self.rules
+            .iter()
+            .filter(|x| !colors_only || x.is_color())
+            .collect()
+    }
 }
 
 pub fn skip_whitespace(iterator: &mut Peekable<Chars>) {
@@ -311,47 +1805,108 @@ pub fn collect_hex(iterator: &mut Peekable<Chars>) -> u8 {
     u8::from_str_radix(&hex, 16).unwrap()
 }
 
-/// Collects a hex number. The iterator must be placed on the '#' character
-pub fn collect_hex_color(iterator: &mut Peekable<Chars>) -> Color {
+/// Collects a function call, such as `url(...)`, including the parentheses. The iterator is left
+/// after the `)` that closes the function, so nested functions are collected as well.
+fn collect_function(iterator: &mut Peekable<Chars>) -> String {
+    let mut output = collect_until_terminator(iterator, &['(']);
+    let mut depth = 0;
+    for character in iterator.by_ref() {
+        output.push(character);
+        match character {
+            '(' => depth += 1,
+            ')' if depth == 1 => break,
+            ')' => depth -= 1,
+            _ => (),
+        }
+    }
+    output
+}
+
+/// Collects a hex number. The iterator must be placed on the '#' character. The alpha of `#rgba`
+/// and `#rrggbbaa` is dropped, since only opaque colors are supported. Returns [None] if there
+/// aren't 3, 4, 6 or 8 digits.
+pub fn collect_hex_color(iterator: &mut Peekable<Chars>) -> Option<Color> {
     assert_eq!(iterator.next(), Some('#'));
 
-    let r = collect_hex(iterator);
-    let g = collect_hex(iterator);
-    let b = collect_hex(iterator);
+    let mut digits = String::new();
+    while let Some(v) = iterator.next_if(|x| x.is_ascii_hexdigit()) {
+        digits.push(v);
+    }
+
+    // The short forms `#abc` and `#abcd` are the same as `#aabbcc` and `#aabbccdd`
+    if matches!(digits.len(), 3 | 4) {
+        digits = digits.chars().flat_map(|x| [x, x]).collect();
+    }
+    if !matches!(digits.len(), 6 | 8) {
+        return None;
+    }
+
+    let mut digits = digits.chars().peekable();
+    let r = collect_hex(&mut digits);
+    let g = collect_hex(&mut digits);
+    let b = collect_hex(&mut digits);
 
-    Color::Hex(r, g, b)
+    Some(Color::Hex(r, g, b))
 }
 
 /// The iterator has to be placed at the first starting character of the CSS value. The iterator
-/// will return in the `;` character's position
-pub fn parse_css_value(iterator: &mut Peekable<Chars>) -> Vec<Value> {
+/// will return in the `;` character's position. Returns [None] if one of the values is invalid,
+/// see [Value::from_string].
+pub fn parse_css_value(iterator: &mut Peekable<Chars>) -> Option<Vec<Value>> {
     let mut output = Vec::new();
     let a = iterator.clone();
     loop {
         match iterator.peek() {
-            Some(v) if v.is_alphabetic() || v.is_numeric() || *v == '.' => loop {
-                let value = collect_until_terminator(iterator, &[';', ' ']);
-                output.push(Value::from_string(&value));
-                match iterator.peek() {
-                    Some(';') => break,
-                    Some(' ') => skip_whitespace(iterator),
-                    _ => panic!("Invalid state"),
+            Some(v) if v.is_alphanumeric() || matches!(v, '.' | '-' | '+') => loop {
+                // Functions can contain the terminators, so they are collected until the closing
+                // `)`
+                let is_function = ["url(", "linear-gradient(", "calc("]
+                    .iter()
+                    .any(|x| iterator.clone().take(x.len()).eq(x.chars()));
+                let value = if is_function {
+                    collect_function(iterator)
+                } else {
+                    collect_until_terminator(iterator, &[';', ' ', '/', '\n', '\t', '\r'])
+                };
+                let Some(value) = Value::from_string(&value) else {
+                    collect_until_terminator(iterator, &[';']);
+                    return None;
+                };
+                output.push(value);
+                skip_whitespace(iterator);
+                if matches!(iterator.peek(), Some(';' | '/')) {
+                    break;
                 }
             },
 
+            Some(v) if v.is_whitespace() => skip_whitespace(iterator),
+
             Some(';') => break,
 
+            Some('/') => {
+                iterator.next();
+                output.push(Value::Slash);
+            }
+
             Some('#') => {
-                let color = collect_hex_color(iterator);
+                let Some(color) = collect_hex_color(iterator) else {
+                    println!("Unsupported hex color in css value {:?}", a);
+                    collect_until_terminator(iterator, &[';']);
+                    return None;
+                };
                 output.push(Value::Color(color));
             }
 
-            Some(v) => panic!("Invalid state '{}'\n{:?}", v, a),
+            Some(v) => {
+                println!("Unexpected '{}' in css value {:?}", v, a);
+                collect_until_terminator(iterator, &[';']);
+                return None;
+            }
 
             None => panic!("Expected more values"),
         }
     }
-    output
+    Some(output)
 }
 
 /// Just a simple function to transform css into a valid block. An inefficient solution, but it
@@ -377,7 +1932,7 @@ pub fn parse_block(iterator: &mut Peekable<Chars>) -> Vec<Rule> {
                 skip_whitespace(iterator);
             }
 
-            Some(v) if v.is_alphabetic() => {
+            Some(v) if v.is_alphabetic() || *v == '-' => {
                 let identifier = get_identifier(iterator);
                 println!("Value Identifier - {}", identifier);
 
@@ -385,12 +1940,46 @@ pub fn parse_block(iterator: &mut Peekable<Chars>) -> Vec<Rule> {
                 assert_eq!(iterator.next(), Some(':'));
                 skip_whitespace(iterator);
 
+                if !Rule::is_supported(&identifier) {
+                    println!("Unknown css identifier: {}", identifier);
+                    collect_until_terminator(iterator, &[';']);
+                    iterator.next();
+                    continue;
+                }
+
+                // The font names aren't css values, so they are parsed on their own
+                if identifier == "font-family" {
+                    let families = collect_until_terminator(iterator, &[';']);
+                    iterator.next();
+                    rules.push(Rule::FontFamily(parse_font_family(&families)));
+                    continue;
+                }
+                // The names of the properties and the times aren't css values either
+                if identifier == "transition" {
+                    let transitions = collect_until_terminator(iterator, &[';']);
+                    iterator.next();
+                    rules.push(Rule::Transition(parse_transitions(&transitions)));
+                    continue;
+                }
+                // Neither are the functions of `transform`
+                if identifier == "transform" {
+                    let transform = collect_until_terminator(iterator, &[';']);
+                    iterator.next();
+                    match parse_transform(&transform) {
+                        Some(v) => rules.push(Rule::Transform(v)),
+                        None => println!("Unsupported transform: {}", transform),
+                    }
+                    continue;
+                }
+
                 let value = parse_css_value(iterator);
                 assert_eq!(iterator.next(), Some(';'));
+                let Some(value) = value else {
+                    println!("Invalid value for the css identifier: {}", identifier);
+                    continue;
+                };
 
-                if let Some(rule) = Rule::new(&identifier, value) {
-                    rules.push(rule);
-                }
+                rules.extend(Rule::from_declaration(&identifier, value));
             }
 
             Some(';') => {
@@ -406,3 +1995,258 @@ pub fn parse_block(iterator: &mut Peekable<Chars>) -> Vec<Rule> {
     }
     rules
 }
+
+/// Parses the comma separated list of `font-family`. The names can be quoted or not, and the
+/// generic families such as `monospace` are turned into lowercase.
+fn parse_font_family(families: &str) -> Vec<String> {
+    families
+        .split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| match x.strip_prefix(['"', '\'']) {
+            Some(v) => v.trim_end_matches(['"', '\'']).to_owned(),
+            None if GENERIC_FONT_FAMILIES.contains(&x.to_lowercase().as_str()) => x.to_lowercase(),
+            None => x.to_owned(),
+        })
+        .collect()
+}
+
+/// Removes the `/* */` comments from css code
+fn remove_comments(css: &str) -> String {
+    let mut output = String::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Skips a block whose opening `{` has already been consumed. The iterator is placed after the
+/// closing `}` character.
+fn skip_block(iterator: &mut Peekable<Chars>) {
+    let mut depth = 1;
+    while depth > 0 {
+        match iterator.next() {
+            Some('{') => depth += 1,
+            Some('}') => depth -= 1,
+            Some(_) => (),
+            None => break,
+        }
+    }
+}
+
+/// Parses an entire stylesheet, such as the content of a `<style>` element
+pub fn parse_css(css: &str) -> Vec<RuleSet> {
+    let css = remove_comments(css);
+    let mut iterator = css.chars().peekable();
+    parse_rule_sets(&mut iterator, &[])
+}
+
+/// Parses rulesets until the stylesheet ends or until the `}` character that closes the enclosing
+/// block. The iterator is placed on that `}` character.
+///
+/// * `media` - The media query lists of the enclosing `@media` blocks
+fn parse_rule_sets(iterator: &mut Peekable<Chars>, media: &[MediaQueryList]) -> Vec<RuleSet> {
+    let mut rule_sets = Vec::new();
+    loop {
+        skip_whitespace(iterator);
+        match iterator.peek() {
+            None | Some('}') => break,
+
+            Some('@') => {
+                iterator.next();
+                let keyword = get_identifier(iterator);
+                let prelude = collect_until_terminator(iterator, &['{', ';']);
+
+                // Statements such as `@import` and `@charset` don't have a block
+                if iterator.next() == Some(';') {
+                    println!("Unsupported at-rule: @{}", keyword);
+                    continue;
+                }
+
+                if keyword == "media" {
+                    let mut media = media.to_vec();
+                    media.push(MediaQueryList::parse(&prelude));
+                    rule_sets.extend(parse_rule_sets(iterator, &media));
+                    iterator.next();
+                } else {
+                    println!("Unsupported at-rule: @{}", keyword);
+                    skip_block(iterator);
+                }
+            }
+
+            Some(_) => {
+                let selectors = collect_until_terminator(iterator, &['{']);
+                iterator.next();
+                let declarations = collect_until_terminator(iterator, &['}']);
+                iterator.next();
+                rule_sets.push(RuleSet {
+                    selectors: parse_selectors(&selectors),
+                    rules: parse_inline_css(&declarations),
+                    media: media.to_vec(),
+                });
+            }
+        }
+    }
+    rule_sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(name: &str, value: &str) -> Option<AttributeSelector> {
+        Some(AttributeSelector {
+            name: name.to_owned(),
+            value: Some(value.to_owned()),
+        })
+    }
+
+    #[test]
+    fn drops_unsupported_declarations() {
+        let rules = parse_inline_css("color: ; font-size: inherit; background-color: inherit");
+        assert!(rules.is_empty(), "{:?}", rules);
+    }
+
+    #[test]
+    fn parses_zero_font_size() {
+        let rules = parse_inline_css("font-size: 0");
+        assert!(
+            matches!(rules[..], [Rule::FontSize(Unit::Px(0))]),
+            "{:?}",
+            rules
+        );
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        let color = |x: &str| {
+            collect_hex_color(&mut x.chars().peekable()).map(|x| match x {
+                Color::Hex(r, g, b) => (r, g, b),
+                v => panic!("Expected a hex color, got {:?}", v),
+            })
+        };
+        assert_eq!(color("#a1b2c3"), Some((0xa1, 0xb2, 0xc3)));
+        // The short forms have every digit twice
+        assert_eq!(color("#abc"), Some((0xaa, 0xbb, 0xcc)));
+        // The alpha is dropped
+        assert_eq!(color("#abcd"), Some((0xaa, 0xbb, 0xcc)));
+        assert_eq!(color("#a1b2c3d4"), Some((0xa1, 0xb2, 0xc3)));
+        assert_eq!(color("#"), None);
+        assert_eq!(color("#ab"), None);
+        assert_eq!(color("#abcde"), None);
+    }
+
+    #[test]
+    fn drops_declarations_with_broken_hex_colors() {
+        let rules = parse_inline_css("color: #; border-color: #abcde; background-color: #abcd");
+        assert!(
+            matches!(
+                rules[..],
+                [Rule::BackgroundColor(Color::Hex(0xaa, 0xbb, 0xcc))]
+            ),
+            "{:?}",
+            rules
+        );
+    }
+
+    #[test]
+    fn parses_attribute_selectors() {
+        assert_eq!(
+            AttributeSelector::from_string("href"),
+            Some(AttributeSelector {
+                name: "href".to_owned(),
+                value: None,
+            })
+        );
+        assert_eq!(
+            AttributeSelector::from_string("type=\"text\""),
+            attribute("type", "text")
+        );
+        assert_eq!(
+            AttributeSelector::from_string("type='text'"),
+            attribute("type", "text")
+        );
+        assert_eq!(
+            AttributeSelector::from_string("type = text"),
+            attribute("type", "text")
+        );
+        // The quotes can have anything inside of them, unlike the plain values
+        assert_eq!(
+            AttributeSelector::from_string("title=\"a b]\""),
+            attribute("title", "a b]")
+        );
+        assert_eq!(AttributeSelector::from_string("title=a b"), None);
+        assert_eq!(AttributeSelector::from_string("type=\"text'"), None);
+        assert_eq!(AttributeSelector::from_string("type='text\""), None);
+        assert_eq!(AttributeSelector::from_string("type=\"te\"xt\""), None);
+        assert_eq!(AttributeSelector::from_string("=text"), None);
+    }
+
+    #[test]
+    fn matches_attribute_names_case_insensitively() {
+        let mut element = Element::new(Tag::A);
+        element
+            .attributes
+            .insert("HREF".to_owned(), "/page".to_owned());
+        let state = InteractionState::default();
+        let matches = |selector: &str| {
+            Selector::from_string(selector)
+                .unwrap()
+                .matches(&element, &[], &state)
+        };
+        assert!(matches("[href]"));
+        assert!(matches("[HrEf]"));
+        assert!(matches("a[href=\"/page\"]"));
+        // The values are case-sensitive
+        assert!(!matches("[href=\"/PAGE\"]"));
+        assert!(!matches("[title]"));
+    }
+
+    #[test]
+    fn matches_visited_links() {
+        let mut link = Element::new(Tag::A);
+        link.id = 1;
+        link.attributes
+            .insert("href".to_owned(), "/page".to_owned());
+        let anchor = Element::new(Tag::A);
+        let mut state = InteractionState::default();
+        let visited = Selector::from_string("a:visited").unwrap();
+        let unvisited = Selector::from_string("a:link").unwrap();
+        assert!(!visited.matches(&link, &[], &state));
+        assert!(unvisited.matches(&link, &[], &state));
+
+        state.visited.insert(link.id);
+        assert!(visited.matches(&link, &[], &state));
+        assert!(!unvisited.matches(&link, &[], &state));
+        // Only the elements that link somewhere are links
+        state.visited.insert(anchor.id);
+        assert!(!visited.matches(&anchor, &[], &state));
+        assert!(!unvisited.matches(&anchor, &[], &state));
+    }
+
+    #[test]
+    fn matches_descendants() {
+        let nav = Element::new(Tag::Nav);
+        let li = Element::new(Tag::Li);
+        let a = Element::new(Tag::A);
+        let state = InteractionState::default();
+        let selector = Selector::from_string("nav a").unwrap();
+        assert!(selector.matches(&a, &[&nav], &state));
+        // The nav doesn't have to be the parent
+        assert!(selector.matches(&a, &[&nav, &li], &state));
+        assert!(!selector.matches(&a, &[&li], &state));
+        assert!(!selector.matches(&a, &[], &state));
+        // The nav itself isn't its own descendant, and the ancestors aren't matched in reverse
+        assert!(!selector.matches(&nav, &[], &state));
+        assert!(!selector.matches(&nav, &[&a], &state));
+        assert!(!Selector::from_string("nav li a")
+            .unwrap()
+            .matches(&a, &[&li, &nav], &state));
+    }
+}
@@ -0,0 +1,338 @@
+//! Compares the text of two documents and builds a page that shows what was added and removed.
+//! The comparison is done word by word with the longest common subsequence of the two texts.
+
+use crate::css::DisplayStyle;
+use crate::html::{parse_html, Element, Tag};
+use crate::requests::Response;
+use crate::style::default_display;
+
+const ADDED_STYLE: &str = "background-color: #c8f0c8";
+const REMOVED_STYLE: &str = "background-color: #f0c8c8";
+
+/// The most cells that the table of the longest common subsequence can have, which is 64 MB. Two
+/// pages that differ in more words are shown as the old words removed and the new words added.
+const MAX_TABLE_SIZE: usize = 16 * 1024 * 1024;
+
+/// A word and the index of the text block it came from
+#[derive(Debug)]
+struct BlockWord {
+    word: String,
+    block: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Change {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// Consecutive words with the same change, which are shown as a single block
+#[derive(Debug)]
+struct Run {
+    change: Change,
+    words: Vec<String>,
+}
+
+/// Collects the words of the visible text. Text inside of the same block element belongs to the
+/// same block.
+fn collect_words(element: &Element, block: &mut usize, output: &mut Vec<BlockWord>) {
+    match element.element_type {
+        Tag::Head | Tag::Title | Tag::Script | Tag::Style => return,
+        Tag::PlainText => {
+            for word in element.inner_text.split_whitespace() {
+                output.push(BlockWord {
+                    word: word.to_owned(),
+                    block: *block,
+                });
+            }
+            return;
+        }
+        _ => (),
+    }
+
+    let is_block = matches!(
+        default_display(element.element_type),
+        DisplayStyle::Block | DisplayStyle::ListItem
+    );
+    if is_block {
+        *block += 1;
+    }
+    for child in &element.children {
+        collect_words(child, block, output);
+    }
+    if is_block {
+        *block += 1;
+    }
+}
+
+/// Extracts the words of a fetched page. Plain text documents use their lines as the blocks.
+fn extract_words(response: &Response) -> Vec<BlockWord> {
+    let mut output = Vec::new();
+    if response.is_plain_text() {
+        for (block, line) in response.body.lines().enumerate() {
+            output.extend(line.split_whitespace().map(|x| BlockWord {
+                word: x.to_owned(),
+                block,
+            }));
+        }
+    } else {
+        let mut block = 0;
+        for element in &parse_html(&response.body) {
+            collect_words(element, &mut block, &mut output);
+        }
+    }
+    output
+}
+
+/// Computes the changes that turn the old words into the new words. Every word of both inputs is
+/// returned once, in the order they should be shown.
+fn diff_words(old: &[BlockWord], new: &[BlockWord]) -> Vec<(Change, usize)> {
+    // The common start and end are skipped, since they are usually most of the page and would
+    // make the table huge for no reason
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a.word == b.word)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a.word == b.word)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    // The indices point to the new words, except for the removed ones
+    let mut changes: Vec<(Change, usize)> = (0..prefix).map(|x| (Change::Unchanged, x)).collect();
+    let suffix_start = new.len() - suffix;
+    let suffix_changes = (suffix_start..new.len()).map(|x| (Change::Unchanged, x));
+
+    if (old_middle.len() + 1).saturating_mul(new_middle.len() + 1) > MAX_TABLE_SIZE {
+        changes.extend((prefix..old.len() - suffix).map(|x| (Change::Removed, x)));
+        changes.extend((prefix..suffix_start).map(|x| (Change::Added, x)));
+        changes.extend(suffix_changes);
+        return changes;
+    }
+
+    // lengths[i][j] is the length of the longest common subsequence of old_middle[i..] and
+    // new_middle[j..]
+    let columns = new_middle.len() + 1;
+    let mut lengths = vec![0u32; (old_middle.len() + 1) * columns];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i * columns + j] = if old_middle[i].word == new_middle[j].word {
+                lengths[(i + 1) * columns + j + 1] + 1
+            } else {
+                lengths[(i + 1) * columns + j].max(lengths[i * columns + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i].word == new_middle[j].word
+        {
+            changes.push((Change::Unchanged, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < old_middle.len()
+            && (j == new_middle.len()
+                || lengths[(i + 1) * columns + j] >= lengths[i * columns + j + 1])
+        {
+            changes.push((Change::Removed, prefix + i));
+            i += 1;
+        } else {
+            changes.push((Change::Added, prefix + j));
+            j += 1;
+        }
+    }
+    changes.extend(suffix_changes);
+    changes
+}
+
+/// Groups the changes into runs. A run ends when the change or the text block changes.
+fn group_into_runs(old: &[BlockWord], new: &[BlockWord], changes: &[(Change, usize)]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut previous = None;
+    for (change, index) in changes {
+        let word = match change {
+            Change::Removed => &old[*index],
+            _ => &new[*index],
+        };
+        if previous != Some((*change, word.block)) {
+            runs.push(Run {
+                change: *change,
+                words: Vec::new(),
+            });
+        }
+        previous = Some((*change, word.block));
+        runs.last_mut().unwrap().words.push(word.word.clone());
+    }
+    runs
+}
+
+fn paragraph(text: &str, style: Option<&str>) -> Element {
+    let mut element = Element::new(Tag::Paragraph);
+    element
+        .children
+        .push(Element::new_with_text(Tag::PlainText, text));
+    if let Some(style) = style {
+        element
+            .attributes
+            .insert("style".to_owned(), style.to_owned());
+    }
+    element
+}
+
+/// Compares the two pages and returns the elements of a page showing the differences. Added
+/// blocks are highlighted in green and removed blocks in red.
+pub fn diff_pages(old_url: &str, old: &Response, new_url: &str, new: &Response) -> Vec<Element> {
+    let old_words = extract_words(old);
+    let new_words = extract_words(new);
+    let changes = diff_words(&old_words, &new_words);
+    let runs = group_into_runs(&old_words, &new_words, &changes);
+
+    let mut body = Element::new(Tag::Body);
+    let mut heading = Element::new(Tag::H(1));
+    heading
+        .children
+        .push(Element::new_with_text(Tag::PlainText, "Diff"));
+    body.children.push(heading);
+    body.children
+        .push(paragraph(&format!("- {}", old_url), Some(REMOVED_STYLE)));
+    body.children
+        .push(paragraph(&format!("+ {}", new_url), Some(ADDED_STYLE)));
+
+    for run in runs {
+        let text = run.words.join(" ");
+        let element = match run.change {
+            Change::Unchanged => paragraph(&text, None),
+            Change::Added => paragraph(&format!("+ {}", text), Some(ADDED_STYLE)),
+            Change::Removed => paragraph(&format!("- {}", text), Some(REMOVED_STYLE)),
+        };
+        body.children.push(element);
+    }
+
+    let mut html = Element::new(Tag::Html);
+    html.children.push(body);
+    vec![html]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Change::*;
+
+    /// Splits the text into words of a single block
+    fn words(text: &str) -> Vec<BlockWord> {
+        text.split_whitespace()
+            .map(|x| BlockWord {
+                word: x.to_owned(),
+                block: 0,
+            })
+            .collect()
+    }
+
+    /// Returns the changes with their words
+    fn diff(old: &str, new: &str) -> Vec<(Change, String)> {
+        let (old, new) = (words(old), words(new));
+        diff_words(&old, &new)
+            .into_iter()
+            .map(|(change, index)| {
+                let word = match change {
+                    Removed => &old[index],
+                    _ => &new[index],
+                };
+                (change, word.word.clone())
+            })
+            .collect()
+    }
+
+    fn changes(changes: &[(Change, &str)]) -> Vec<(Change, String)> {
+        changes
+            .iter()
+            .map(|(change, word)| (*change, word.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn identical_texts_are_unchanged() {
+        assert_eq!(
+            diff("a b c", "a b c"),
+            changes(&[(Unchanged, "a"), (Unchanged, "b"), (Unchanged, "c")])
+        );
+        assert_eq!(diff("", ""), []);
+    }
+
+    #[test]
+    fn finds_inserted_words() {
+        assert_eq!(
+            diff("a d", "a b c d"),
+            changes(&[
+                (Unchanged, "a"),
+                (Added, "b"),
+                (Added, "c"),
+                (Unchanged, "d")
+            ])
+        );
+        assert_eq!(diff("", "a"), changes(&[(Added, "a")]));
+    }
+
+    #[test]
+    fn finds_deleted_words() {
+        assert_eq!(
+            diff("a b c d", "a d"),
+            changes(&[
+                (Unchanged, "a"),
+                (Removed, "b"),
+                (Removed, "c"),
+                (Unchanged, "d")
+            ])
+        );
+        assert_eq!(diff("a", ""), changes(&[(Removed, "a")]));
+    }
+
+    #[test]
+    fn finds_replaced_words_in_the_middle() {
+        assert_eq!(
+            diff("a b x c d", "a b y c d"),
+            changes(&[
+                (Unchanged, "a"),
+                (Unchanged, "b"),
+                (Removed, "x"),
+                (Added, "y"),
+                (Unchanged, "c"),
+                (Unchanged, "d")
+            ])
+        );
+        // The common words between the changes are found as well
+        assert_eq!(
+            diff("a x b y c", "a z b w c"),
+            changes(&[
+                (Unchanged, "a"),
+                (Removed, "x"),
+                (Added, "z"),
+                (Unchanged, "b"),
+                (Removed, "y"),
+                (Added, "w"),
+                (Unchanged, "c")
+            ])
+        );
+    }
+
+    #[test]
+    fn replaces_the_whole_middle_of_pages_that_differ_too_much() {
+        let old: Vec<String> = (0..5000).map(|x| format!("old{}", x % 2)).collect();
+        let new: Vec<String> = (0..5000).map(|x| format!("new{}", x % 2)).collect();
+        let result = diff(
+            &format!("start {} end", old.join(" ")),
+            &format!("start {} end", new.join(" ")),
+        );
+        let mut expected = vec![(Unchanged, "start".to_owned())];
+        expected.extend(old.into_iter().map(|x| (Removed, x)));
+        expected.extend(new.into_iter().map(|x| (Added, x)));
+        expected.push((Unchanged, "end".to_owned()));
+        assert_eq!(result, expected);
+    }
+}
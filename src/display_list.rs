@@ -0,0 +1,226 @@
+//! The flat list of what a page draws, in the order it's painted in. It's made once from the
+//! [Layout] when the page is laid out, so drawing a frame only has to go through the list. The
+//! rects and the text of the list are drawn in batches, and only the background images need draws
+//! of their own.
+
+use crate::color::Color;
+use crate::css::TextDecoration;
+use crate::font::{FontFace, FontMetrics, FontRegistry, ScaledFont, ShapedGlyph};
+use crate::gauge;
+use crate::render_layout::{Layout, Position, Rect, Size};
+
+/// A word of text. It's drawn from the top left corner of [DisplayItem::rect].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    /// The family of the font in the [FontRegistry]
+    pub font_family: String,
+    pub face: FontFace,
+    pub font_size: f32,
+    pub color: Color,
+    /// Every character is drawn in a cell of the same width, see [FontMetrics::get_monospace_width]
+    pub monospace: bool,
+}
+
+impl TextRun {
+    /// Shapes the text with the font of the run, at the size that it's drawn at. Monospace text
+    /// has every character centered in a cell of the same width.
+    pub fn glyphs(&self, font: ScaledFont) -> Vec<ShapedGlyph> {
+        if !self.monospace {
+            return font.shape(&self.text).glyphs;
+        }
+        let cell_width = font.font().get_monospace_width(font.size());
+        self.text
+            .chars()
+            .enumerate()
+            .map(|(index, character)| {
+                let padding = (cell_width - font.glyph_width(character)) / 2;
+                ShapedGlyph {
+                    x: (index as i32 * cell_width + padding) as f32,
+                    ..font.font().glyph(character)
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayItemKind {
+    /// Fills the rect with the color
+    Rect(Color),
+    Text(TextRun),
+    /// The index of the image in [Layout::background_images]. The images are loaded while the page
+    /// is drawn, so the tiles are only known then.
+    BackgroundImage(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayItem {
+    pub kind: DisplayItemKind,
+    /// The part of the page the item covers
+    pub rect: Rect,
+    /// See [crate::style::ComputedStyle::opacity]
+    pub opacity: f32,
+    /// Fixed items stay in place when the page is scrolled
+    pub fixed: bool,
+    /// See [crate::render_layout::Paragraph::clip]
+    pub clip: Option<Rect>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DisplayList {
+    pub items: Vec<DisplayItem>,
+}
+
+impl DisplayList {
+    /// Flattens the layout. The boxes are painted below the text, and the backgrounds of the words
+    /// of a paragraph below all of its text.
+    pub fn new(layout: &Layout, fonts: &FontRegistry<impl FontMetrics>) -> Self {
+        let mut list = Self::default();
+        for background in &layout.backgrounds {
+            list.push(
+                DisplayItemKind::Rect(background.color),
+                background.rect,
+                background.opacity,
+                background.fixed,
+                background.clip,
+            );
+        }
+        for (index, image) in layout.background_images.iter().enumerate() {
+            list.push(
+                DisplayItemKind::BackgroundImage(index),
+                image.rect,
+                image.opacity,
+                image.fixed,
+                image.clip,
+            );
+        }
+        for border in &layout.borders {
+            let Rect { position, size } = border.rect;
+            let widths = border.widths;
+            // Top and bottom lines span the whole width, so the sides only fill the space between
+            // them
+            let side_height = size.height - widths.top - widths.bottom;
+            let lines = [
+                (0, 0, size.width, widths.top),
+                (0, size.height - widths.bottom, size.width, widths.bottom),
+                (0, widths.top, widths.left, side_height),
+                (
+                    size.width - widths.right,
+                    widths.top,
+                    widths.right,
+                    side_height,
+                ),
+            ];
+            for (x, y, width, height) in lines {
+                if width > 0 && height > 0 {
+                    list.push(
+                        DisplayItemKind::Rect(border.color),
+                        Rect::new(position + Position::new(x, y), Size::new(width, height)),
+                        border.opacity,
+                        border.fixed,
+                        border.clip,
+                    );
+                }
+            }
+        }
+        for bar in &layout.bars {
+            list.push(
+                DisplayItemKind::Rect(gauge::track_color()),
+                bar.rect,
+                bar.opacity,
+                bar.fixed,
+                bar.clip,
+            );
+            let filled = (bar.rect.size.width as f32 * bar.gauge.fraction) as i32;
+            if filled > 0 {
+                list.push(
+                    DisplayItemKind::Rect(bar.gauge.color),
+                    Rect::new(bar.rect.position, Size::new(filled, bar.rect.size.height)),
+                    bar.opacity,
+                    bar.fixed,
+                    bar.clip,
+                );
+            }
+        }
+
+        for paragraph in &layout.paragraphs {
+            let (fixed, clip) = (paragraph.fixed, paragraph.clip);
+            for sentence in &paragraph.sentences {
+                let Some(color) = sentence.background_color else {
+                    continue;
+                };
+                for rect in sentence.background_rects() {
+                    list.push(
+                        DisplayItemKind::Rect(color),
+                        rect,
+                        sentence.opacity,
+                        fixed,
+                        clip,
+                    );
+                }
+            }
+            for sentence in &paragraph.sentences {
+                let font = fonts.get_face(&paragraph.font_family, sentence.face);
+                let color = sentence.text_color.unwrap_or(Color::black());
+                let ascent = font.get_ascent(sentence.font_size);
+                let thickness = (sentence.font_size / 16.0).max(1.0) as i32;
+                for word in &sentence.words {
+                    let rect = Rect::new(word.position, Size::new(word.width, word.height));
+                    let text = TextRun {
+                        text: word.word.clone(),
+                        font_family: paragraph.font_family.clone(),
+                        face: sentence.face,
+                        font_size: sentence.font_size,
+                        color,
+                        monospace: paragraph.monospace,
+                    };
+                    list.push(
+                        DisplayItemKind::Text(text),
+                        rect,
+                        sentence.opacity,
+                        fixed,
+                        clip,
+                    );
+
+                    // Monospace text is drawn without decorations
+                    let y = match sentence.text_decoration {
+                        _ if paragraph.monospace => continue,
+                        TextDecoration::None => continue,
+                        TextDecoration::Underline => ascent + thickness,
+                        // Roughly through the middle of the lowercase letters
+                        TextDecoration::LineThrough => ascent - ascent / 3,
+                    };
+                    list.push(
+                        DisplayItemKind::Rect(color),
+                        Rect::new(
+                            word.position + Position::new(0, y),
+                            Size::new(word.width, thickness),
+                        ),
+                        sentence.opacity,
+                        fixed,
+                        clip,
+                    );
+                }
+            }
+        }
+        list
+    }
+
+    fn push(
+        &mut self,
+        kind: DisplayItemKind,
+        rect: Rect,
+        opacity: f32,
+        fixed: bool,
+        clip: Option<Rect>,
+    ) {
+        self.items.push(DisplayItem {
+            kind,
+            rect,
+            opacity,
+            fixed,
+            clip,
+        });
+    }
+}
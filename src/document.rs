@@ -1,23 +1,388 @@
-use crate::css::RuleSet;
-use crate::html::Element;
+use crate::css::{parse_css, RuleSet};
+use crate::html::{Element, Tag};
+use crate::media::MediaEnvironment;
+use crate::referrer::ReferrerPolicy;
+use crate::requests::resolve_url;
+use crate::rule_index::RuleIndex;
+use crate::transition::{self, Animations, PreviousValues};
+use std::collections::HashSet;
+use std::time::Instant;
+
+/// The styles every document starts with. The stylesheets of the page are applied after these.
+const USER_AGENT_STYLE_SHEET: &str = include_str!("user_agent.css");
+
+/// The state of the user's interaction with the elements, which the pseudo-classes such as
+/// `:hover` depend on. The elements are identified by their ids.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InteractionState {
+    /// The element under the mouse and all of its ancestors
+    pub hovered: Vec<usize>,
+    /// The elements that are being pressed with the mouse, the same way as [Self::hovered]
+    pub active: Vec<usize>,
+    pub focused: Option<usize>,
+    /// The links that point to pages in the history
+    pub visited: HashSet<usize>,
+}
 
 #[allow(dead_code)]
 pub struct Document {
     pub elements: Vec<Element>,
     pub css_rules: Vec<RuleSet>,
+    interaction: InteractionState,
+    /// The environment the styles were last computed for
+    media: Option<MediaEnvironment>,
+    /// Set when the styles have to be computed again even if the environment stays the same
+    styles_outdated: bool,
+    animations: Animations,
 }
 
 impl Document {
-    pub fn new(elements: Vec<Element>, css_rules: Vec<RuleSet>) -> Self {
+    /// Creates a document and gives every element an id that is unique inside of the document
+    pub fn new(mut elements: Vec<Element>, css_rules: Vec<RuleSet>) -> Self {
+        let mut next_id = 0;
+        for element in &mut elements {
+            element.assign_ids(&mut next_id);
+        }
         Self {
             elements,
             css_rules,
+            interaction: InteractionState::default(),
+            media: None,
+            styles_outdated: true,
+            animations: Animations::default(),
         }
     }
 
+    /// Returns the content of the `<title>` element
+    pub fn title(&self) -> Option<String> {
+        let title = self.elements.iter().find_map(|x| x.find(Tag::Title))?;
+        Some(title.text_content().trim().to_owned()).filter(|x| !x.is_empty())
+    }
+
+    /// Returns the id of the element a url fragment points to, which is the element with the
+    /// fragment as its `id`, or an `<a>` with it as its `name`
+    pub fn fragment_target(&self, fragment: &str) -> Option<usize> {
+        let by_id = |x: &Element| x.get_attribute("id").as_deref() == Some(fragment);
+        let by_name = |x: &Element| {
+            x.element_type == Tag::A && x.get_attribute("name").as_deref() == Some(fragment)
+        };
+        let target = self
+            .elements
+            .iter()
+            .find_map(|x| x.find_matching(&by_id))
+            .or_else(|| self.elements.iter().find_map(|x| x.find_matching(&by_name)))?;
+        Some(target.id)
+    }
+
+    /// Returns the url from `<link rel="canonical">`, which points to the preferred version of
+    /// pages with the same content
+    ///
+    /// * `base` - The url of the page, which a relative link is resolved against
+    pub fn canonical_url(&self, base: &str) -> Option<String> {
+        let is_canonical = |x: &Element| {
+            x.element_type == Tag::Link
+                && x.get_attribute("rel")
+                    .is_some_and(|x| x.split_whitespace().any(|x| x == "canonical"))
+        };
+        let link = self
+            .elements
+            .iter()
+            .find_map(|x| x.find_matching(&is_canonical))?;
+        Some(resolve_url(base, &link.get_attribute("href")?))
+    }
+
+    /// Returns the url of the icon of the page from `<link rel="icon">`. Sites served over http
+    /// have one at `/favicon.ico` by convention, so that is used when there is no link.
+    ///
+    /// * `base` - The url of the page, which a relative link is resolved against
+    pub fn favicon_url(&self, base: &str) -> Option<String> {
+        let is_icon = |x: &Element| {
+            x.element_type == Tag::Link
+                && x.get_attribute("rel")
+                    .is_some_and(|x| x.split_whitespace().any(|x| x == "icon"))
+        };
+        let link = self
+            .elements
+            .iter()
+            .find_map(|x| x.find_matching(&is_icon))
+            .and_then(|x| x.get_attribute("href"));
+        match link {
+            Some(href) => Some(resolve_url(base, &href)),
+            None if base.starts_with("http://") || base.starts_with("https://") => {
+                Some(resolve_url(base, "/favicon.ico"))
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the referrer policy the page sets with `<meta name="referrer">`
+    pub fn referrer_policy(&self) -> Option<ReferrerPolicy> {
+        let is_referrer = |x: &Element| {
+            x.element_type == Tag::Meta && x.get_attribute("name").as_deref() == Some("referrer")
+        };
+        let meta = self
+            .elements
+            .iter()
+            .find_map(|x| x.find_matching(&is_referrer))?;
+        ReferrerPolicy::from_string(&meta.get_attribute("content")?)
+    }
+
+    /// Returns the closest `<a>` element that contains the element with the id
+    pub fn link_around(&self, id: usize) -> Option<&Element> {
+        self.path_to(id)
+            .into_iter()
+            .rev()
+            .filter_map(|x| self.element(x))
+            .find(|x| x.element_type == Tag::A)
+    }
+
+    pub fn element(&self, id: usize) -> Option<&Element> {
+        self.elements.iter().find_map(|x| x.find_by_id(id))
+    }
+
+    /// Returns the ids and the `src` of the `<img>` elements whose pictures haven't been loaded
+    pub fn pending_images(&self) -> Vec<(usize, String)> {
+        fn collect(element: &Element, output: &mut Vec<(usize, String)>) {
+            if element.element_type == Tag::Img && element.natural_size.is_none() {
+                if let Some(src) = element.get_attribute("src") {
+                    output.push((element.id, src));
+                }
+            }
+            for child in &element.children {
+                collect(child, output);
+            }
+        }
+
+        let mut images = Vec::new();
+        for element in &self.elements {
+            collect(element, &mut images);
+        }
+        images
+    }
+
+    /// Sets the size of the picture of an `<img>`. The page is laid out again on the next update.
+    pub fn set_natural_size(&mut self, id: usize, size: (u32, u32)) {
+        let Some(image) = self.elements.iter_mut().find_map(|x| x.find_by_id_mut(id)) else {
+            return;
+        };
+        image.natural_size = Some(size);
+        self.styles_outdated = true;
+    }
+
+    /// Returns the id of the `<details>` that is opened and closed by clicking the element, if
+    /// the element is inside of a `<summary>`
+    pub fn details_toggled_by(&self, id: usize) -> Option<usize> {
+        let path = self.path_to(id);
+        path.windows(2).rev().find_map(|x| {
+            let details = self.element(x[0])?;
+            let summary = self.element(x[1])?;
+            summary.is_summary_of(details).then_some(details.id)
+        })
+    }
+
+    /// Opens or closes the `<details>` with the id. The page is laid out again on the next
+    /// update.
+    pub fn set_details_open(&mut self, id: usize, open: bool) {
+        let Some(details) = self.elements.iter_mut().find_map(|x| x.find_by_id_mut(id)) else {
+            return;
+        };
+        if open {
+            details.attributes.insert("open".to_owned(), String::new());
+        } else {
+            details.attributes.remove("open");
+        }
+        self.styles_outdated = true;
+    }
+
+    /// Changes the text of a text field. The page is laid out again on the next update.
+    pub fn set_field_value(&mut self, id: usize, value: &str) {
+        let Some(field) = self.elements.iter_mut().find_map(|x| x.find_by_id_mut(id)) else {
+            return;
+        };
+        field
+            .attributes
+            .insert("value".to_owned(), value.to_owned());
+        self.styles_outdated = true;
+    }
+
+    /// Returns the options of the `<datalist>` named by the `list` of the text field that match
+    /// the text in the field. An option that is the same as the text isn't included.
+    pub fn datalist_suggestions(&self, id: usize) -> Vec<String> {
+        let Some(field) = self.element(id).filter(|x| x.is_text_field()) else {
+            return Vec::new();
+        };
+        let Some(list) = field.get_attribute("list") else {
+            return Vec::new();
+        };
+        let is_datalist = |x: &Element| {
+            x.element_type == Tag::Datalist && x.get_attribute("id").as_ref() == Some(&list)
+        };
+        let Some(datalist) = self
+            .elements
+            .iter()
+            .find_map(|x| x.find_matching(&is_datalist))
+        else {
+            return Vec::new();
+        };
+        let mut options = Vec::new();
+        collect_options(datalist, &mut options);
+        let text = field
+            .get_attribute("value")
+            .unwrap_or_default()
+            .to_lowercase();
+        options
+            .into_iter()
+            .filter(|x| {
+                let option = x.to_lowercase();
+                option.contains(&text) && option != text
+            })
+            .collect()
+    }
+
+    /// Returns the ids of the elements from the root element down to the element with the id
+    pub fn path_to(&self, id: usize) -> Vec<usize> {
+        self.elements
+            .iter()
+            .map(|x| x.path_to(id))
+            .find(|x| !x.is_empty())
+            .unwrap_or_default()
+    }
+
+    pub fn interaction(&self) -> &InteractionState {
+        &self.interaction
+    }
+
+    /// Changes the interaction state. The styles are computed again on the next update if the
+    /// state changed.
+    pub fn set_interaction(&mut self, interaction: InteractionState) {
+        if self.interaction != interaction {
+            self.interaction = interaction;
+            self.styles_outdated = true;
+        }
+    }
+
+    /// Marks the links whose href passes the check as visited
+    pub fn set_visited_links(&mut self, is_visited: impl Fn(&str) -> bool) {
+        fn collect(element: &Element, is_visited: &impl Fn(&str) -> bool, output: &mut Vec<usize>) {
+            if element.element_type == Tag::A {
+                if let Some(href) = element.attributes.get("href") {
+                    if is_visited(href) {
+                        output.push(element.id);
+                    }
+                }
+            }
+            for child in &element.children {
+                collect(child, is_visited, output);
+            }
+        }
+
+        let mut visited = Vec::new();
+        for element in &self.elements {
+            collect(element, &is_visited, &mut visited);
+        }
+        let mut interaction = self.interaction.clone();
+        interaction.visited = visited.into_iter().collect();
+        self.set_interaction(interaction);
+    }
+
     pub fn parse_inline_css(&mut self) {
         for element in &mut self.elements {
             element.parse_inline_css();
         }
     }
+
+    /// Parses the `<style>` elements of the document into its css rules. The user agent
+    /// stylesheet goes before every other rule, so it has the lowest priority.
+    pub fn parse_style_sheets(&mut self) {
+        self.css_rules
+            .splice(0..0, parse_css(USER_AGENT_STYLE_SHEET));
+        for element in &self.elements {
+            element.collect_style_sheets(&mut self.css_rules);
+        }
+    }
+
+    pub fn compute_styles(&mut self) {
+        for element in &mut self.elements {
+            element.compute_style(None);
+        }
+        // The forced colors override everything else, so they are applied last
+        if let Some(palette) = self.media.and_then(|x| x.forced_colors) {
+            for element in &mut self.elements {
+                element.force_colors(&palette, palette.text, &self.interaction);
+            }
+        }
+        let text_zoom = self.media.map_or(1.0, |x| x.text_zoom);
+        if text_zoom != 1.0 {
+            for element in &mut self.elements {
+                element.zoom_text(text_zoom);
+            }
+        }
+        let page_zoom = self.media.map_or(1.0, |x| x.page_zoom);
+        if page_zoom != 1.0 {
+            for element in &mut self.elements {
+                element.zoom(page_zoom);
+            }
+        }
+    }
+
+    /// Evaluates the media queries of the stylesheets against the environment and recomputes the
+    /// styles. Nothing is done if neither the environment nor the interaction state has changed
+    /// since the last time. Returns true if the styles were computed again, which means that the
+    /// page has to be laid out again.
+    pub fn update_styles(&mut self, media: MediaEnvironment) -> bool {
+        if self.media == Some(media) && !self.styles_outdated {
+            return false;
+        }
+        self.media = Some(media);
+        self.styles_outdated = false;
+
+        let rule_sets: Vec<&RuleSet> = self
+            .css_rules
+            .iter()
+            .filter(|x| x.matches_media(&media))
+            .collect();
+        let rule_index = RuleIndex::new(rule_sets);
+        for element in &mut self.elements {
+            element.apply_css_rules(&rule_index, &self.interaction);
+        }
+        let mut previous = PreviousValues::new();
+        for element in &self.elements {
+            transition::collect_values(element, &mut previous);
+        }
+        self.compute_styles();
+
+        // Everything changes at once when the user has asked for less motion
+        if media.reduced_motion {
+            self.animations.clear();
+            return true;
+        }
+        let now = Instant::now();
+        for element in &self.elements {
+            self.animations.start(element, &previous, now);
+        }
+        true
+    }
+
+    /// Moves the running transitions to where they are at the moment. This has to be called after
+    /// [Self::update_styles] on every frame. Returns true if any transitions are still running.
+    pub fn animate(&mut self, now: Instant) -> bool {
+        self.animations.apply(&mut self.elements, now)
+    }
+}
+
+/// Collects the values of the `<option>` elements inside of the element. An option without a
+/// `value` uses its text.
+fn collect_options(element: &Element, output: &mut Vec<String>) {
+    for child in &element.children {
+        if child.element_type == Tag::Option {
+            let value = child
+                .get_attribute("value")
+                .unwrap_or_else(|| child.text_content().trim().to_owned());
+            if !value.is_empty() && !output.contains(&value) {
+                output.push(value);
+            }
+        } else {
+            collect_options(child, output);
+        }
+    }
 }
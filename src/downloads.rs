@@ -0,0 +1,506 @@
+//! Downloads the files that can't be shown as pages. A download that gets interrupted keeps its
+//! partial file, and it's resumed with a `Range` request if the server supports it. The `ETag` of
+//! the file is sent in `If-Range`, so a file that has changed on the server is downloaded again
+//! from the start instead of mixing the two versions.
+
+use crate::migration::{self, Migration};
+use std::{
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+pub const DOWNLOADS_PATH: &str = "./downloads.txt";
+pub const DOWNLOADS_DIRECTORY: &str = "./downloads";
+/// The changes to the format of the downloads file, see [crate::migration]
+const MIGRATIONS: [Migration; 1] = [migration::add_version];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DownloadState {
+    InProgress,
+    /// The partial file is kept, so the download can be resumed
+    Interrupted,
+    Finished,
+}
+
+impl DownloadState {
+    fn as_str(self) -> &'static str {
+        match self {
+            // The browser can't be downloading anything when the file is read back, so a download
+            // that was running is saved as interrupted
+            Self::InProgress | Self::Interrupted => "interrupted",
+            Self::Finished => "finished",
+        }
+    }
+}
+
+/// What the download thread tells about its progress
+enum Progress {
+    /// The server answered. `received` is where the body starts in the file, which is 0 if the
+    /// download starts over.
+    Started {
+        etag: Option<String>,
+        resumable: bool,
+        received: u64,
+        total: Option<u64>,
+    },
+    Received(u64),
+    Finished,
+    Failed(String),
+}
+
+/// A download that finished or failed, see [Downloads::poll]
+pub enum DownloadEvent {
+    Finished(usize),
+    Failed(usize, String),
+}
+
+pub struct Download {
+    pub url: String,
+    /// Where the finished file is saved. The partial file is next to it, see [Download::part_path].
+    pub path: PathBuf,
+    /// A strong `ETag` of the file, which is needed to resume the download safely
+    pub etag: Option<String>,
+    /// Set if the server said it accepts byte ranges for the file
+    pub resumable: bool,
+    /// How many bytes are in the partial file
+    pub received: u64,
+    /// The size of the whole file. [None] if the server didn't tell it.
+    pub total: Option<u64>,
+    pub state: DownloadState,
+    progress: Option<Receiver<Progress>>,
+}
+
+impl Download {
+    /// The file that the download is written into until it's finished
+    pub fn part_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".part");
+        PathBuf::from(path)
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Checks if the partial file can be continued instead of downloading the file again
+    pub fn can_resume(&self) -> bool {
+        self.resumable && self.etag.is_some() && self.received > 0
+    }
+
+    /// Starts the thread that fetches the file
+    fn spawn(&mut self, referrer: Option<&str>) {
+        let (sender, receiver) = mpsc::channel();
+        let url = self.url.clone();
+        let part_path = self.part_path();
+        let resume_from = self
+            .can_resume()
+            .then(|| (self.received, self.etag.clone().unwrap()));
+        let referrer = referrer.map(|x| x.to_owned());
+        thread::spawn(move || {
+            let result = fetch(&url, &part_path, resume_from, referrer.as_deref(), &sender);
+            // Nobody is listening anymore if the browser was closed
+            let _ = sender.send(match result {
+                Ok(()) => Progress::Finished,
+                Err(error) => Progress::Failed(error),
+            });
+        });
+        self.state = DownloadState::InProgress;
+        self.progress = Some(receiver);
+    }
+}
+
+/// The downloads manager. The downloads are saved into a file, so interrupted downloads can be
+/// resumed after the browser is restarted.
+#[derive(Default)]
+pub struct Downloads {
+    downloads: Vec<Download>,
+}
+
+impl Downloads {
+    /// Loads the downloads from a file. A missing file gives an empty list.
+    pub fn load(path: &Path) -> Self {
+        let Some(content) = migration::read(path, &MIGRATIONS) else {
+            return Self::default();
+        };
+
+        let mut downloads = Vec::new();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [state, url, file, etag, resumable, total] = fields[..] else {
+                println!("Skipping a broken downloads line: {}", line);
+                continue;
+            };
+            let mut download = Download {
+                url: url.to_owned(),
+                path: PathBuf::from(file),
+                etag: (!etag.is_empty()).then(|| etag.to_owned()),
+                resumable: resumable == "1",
+                received: 0,
+                total: total.parse().ok(),
+                state: match state {
+                    "finished" => DownloadState::Finished,
+                    _ => DownloadState::Interrupted,
+                },
+                progress: None,
+            };
+            // The partial file is the truth about how much has been received, since the browser
+            // may have been closed in the middle of writing it
+            download.received = match download.state {
+                DownloadState::Finished => download.total.unwrap_or(0),
+                _ => fs::metadata(download.part_path()).map_or(0, |x| x.len()),
+            };
+            downloads.push(download);
+        }
+        Self { downloads }
+    }
+
+    /// Writes every download on its own line as `state\turl\tpath\tetag\tresumable\ttotal`
+    pub fn save(&self, path: &Path) {
+        let mut content = String::new();
+        for download in &self.downloads {
+            content += &format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\n",
+                download.state.as_str(),
+                download.url,
+                download.path.display(),
+                download.etag.as_deref().unwrap_or(""),
+                download.resumable as u8,
+                download.total.map(|x| x.to_string()).unwrap_or_default()
+            );
+        }
+        if let Err(error) = migration::write(path, &content, &MIGRATIONS) {
+            println!("Couldn't save the downloads: {}", error);
+        }
+    }
+
+    /// Starts downloading the url and returns the index of the download. An interrupted download
+    /// of the same url is resumed instead of starting a new one.
+    ///
+    /// * `referrer` - The value of the `Referer` header
+    pub fn start(&mut self, url: &str, referrer: Option<&str>) -> usize {
+        let interrupted = self
+            .downloads
+            .iter()
+            .position(|x| x.url == url && x.state == DownloadState::Interrupted);
+        if let Some(index) = interrupted {
+            self.resume(index, referrer);
+            return index;
+        }
+
+        if let Err(error) = fs::create_dir_all(DOWNLOADS_DIRECTORY) {
+            println!("Couldn't create the downloads directory: {}", error);
+        }
+        let mut download = Download {
+            url: url.to_owned(),
+            path: self.unique_path(&file_name_of(url)),
+            etag: None,
+            resumable: false,
+            received: 0,
+            total: None,
+            state: DownloadState::InProgress,
+            progress: None,
+        };
+        download.spawn(referrer);
+        self.downloads.push(download);
+        self.downloads.len() - 1
+    }
+
+    /// Continues an interrupted download. It starts over if the download can't be resumed.
+    pub fn resume(&mut self, index: usize, referrer: Option<&str>) {
+        let Some(download) = self.downloads.get_mut(index) else {
+            return;
+        };
+        if download.state != DownloadState::Interrupted {
+            return;
+        }
+        if !download.can_resume() {
+            download.received = 0;
+        }
+        download.spawn(referrer);
+    }
+
+    /// Reads the progress of the running downloads. Returns the downloads that finished or
+    /// failed since the last call.
+    pub fn poll(&mut self) -> Vec<DownloadEvent> {
+        let mut events = Vec::new();
+        for (index, download) in self.downloads.iter_mut().enumerate() {
+            let Some(receiver) = download.progress.as_ref() else {
+                continue;
+            };
+            loop {
+                let progress = match receiver.try_recv() {
+                    Ok(v) => v,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        Progress::Failed("The download stopped unexpectedly".to_owned())
+                    }
+                };
+                match progress {
+                    Progress::Started {
+                        etag,
+                        resumable,
+                        received,
+                        total,
+                    } => {
+                        download.etag = etag;
+                        download.resumable = resumable;
+                        download.received = received;
+                        download.total = total;
+                    }
+                    Progress::Received(received) => download.received = received,
+                    Progress::Finished => {
+                        download.progress = None;
+                        download.state = match fs::rename(download.part_path(), &download.path) {
+                            Ok(()) => DownloadState::Finished,
+                            Err(error) => {
+                                println!("Couldn't move the finished download: {}", error);
+                                DownloadState::Interrupted
+                            }
+                        };
+                        events.push(DownloadEvent::Finished(index));
+                        break;
+                    }
+                    Progress::Failed(error) => {
+                        download.progress = None;
+                        download.state = DownloadState::Interrupted;
+                        events.push(DownloadEvent::Failed(index, error));
+                        break;
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    /// Checks if any of the downloads is still receiving its file
+    pub fn is_running(&self) -> bool {
+        self.downloads.iter().any(|x| x.progress.is_some())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Download> {
+        self.downloads.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Download> {
+        self.downloads.iter()
+    }
+
+    /// Returns a path in the downloads directory that isn't used by a file or another download.
+    /// A number is added to the name if it's taken, like `file (1).zip`.
+    fn unique_path(&self, file_name: &str) -> PathBuf {
+        let (stem, extension) = match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (file_name, String::new()),
+        };
+        let mut number = 0;
+        loop {
+            let name = match number {
+                0 => file_name.to_owned(),
+                v => format!("{} ({}){}", stem, v, extension),
+            };
+            let path = Path::new(DOWNLOADS_DIRECTORY).join(name);
+            let mut part_path = path.clone().into_os_string();
+            part_path.push(".part");
+            let taken = path.exists()
+                || Path::new(&part_path).exists()
+                || self.downloads.iter().any(|x| x.path == path);
+            if !taken {
+                return path;
+            }
+            number += 1;
+        }
+    }
+}
+
+/// Takes the file name from the last segment of the path of the url
+fn file_name_of(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |x| x.1);
+    let name = match path.split_once('/') {
+        Some((_, path)) => path.rsplit('/').next().unwrap_or(""),
+        None => "",
+    };
+    // The name can't be allowed to go outside of the downloads directory
+    let name = name.replace(['\\', ':'], "_");
+    match name.as_str() {
+        "" | "." | ".." => "download".to_owned(),
+        _ => name,
+    }
+}
+
+/// Fetches the file into the partial file, and sends the progress through the channel
+///
+/// * `resume_from` - The length of the partial file and its `ETag`, if the download is resumed
+fn fetch(
+    url: &str,
+    part_path: &Path,
+    resume_from: Option<(u64, String)>,
+    referrer: Option<&str>,
+    sender: &Sender<Progress>,
+) -> Result<(), String> {
+    let mut request = ureq::get(url.to_owned());
+    if let Some(referrer) = referrer {
+        request = request.header("Referer", referrer);
+    }
+    if let Some((received, etag)) = &resume_from {
+        request = request
+            .header("Range", format!("bytes={}-", received))
+            .header("If-Range", etag);
+    }
+    let mut response = request.call().map_err(|x| x.to_string())?;
+
+    // 206 means that only the rest of the file was sent. Any other answer is the whole file, for
+    // example because the file has changed since the download was interrupted.
+    let partial = response.status().as_u16() == 206;
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.to_owned())
+    };
+    // Weak etags can't be used in `If-Range`
+    let etag = header("etag").filter(|x| !x.starts_with("W/"));
+    let resumable = partial || header("accept-ranges").as_deref() == Some("bytes");
+    let mut received = match (&resume_from, partial) {
+        (Some((received, _)), true) => *received,
+        _ => 0,
+    };
+    let total = response.body().content_length().map(|x| x + received);
+    let _ = sender.send(Progress::Started {
+        etag,
+        resumable,
+        received,
+        total,
+    });
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(partial)
+        .truncate(!partial)
+        .open(part_path)
+        .map_err(|x| x.to_string())?;
+    let mut reader = response.body_mut().as_reader();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let length = reader.read(&mut buffer).map_err(|x| x.to_string())?;
+        if length == 0 {
+            // The connection can close before the whole file is sent. The partial file is kept
+            // so that the download can be resumed.
+            return match total {
+                Some(total) if received != total => Err(format!(
+                    "The connection closed after {} of {} bytes",
+                    received, total
+                )),
+                _ => Ok(()),
+            };
+        }
+        file.write_all(&buffer[..length])
+            .map_err(|x| x.to_string())?;
+        received += length as u64;
+        let _ = sender.send(Progress::Received(received));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("web-browser-{}-{}", std::process::id(), name))
+    }
+
+    fn download(url: &str, path: PathBuf, state: DownloadState) -> Download {
+        Download {
+            url: url.to_owned(),
+            path,
+            etag: None,
+            resumable: false,
+            received: 0,
+            total: None,
+            state,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn saves_and_loads_downloads() {
+        let path = temp_path("downloads.txt");
+        let finished_path = temp_path("finished.zip");
+        let interrupted_path = temp_path("interrupted.zip");
+        let running_path = temp_path("running.zip");
+        let mut finished = download(
+            "https://a.b/f.zip",
+            finished_path.clone(),
+            DownloadState::Finished,
+        );
+        finished.total = Some(100);
+        let mut interrupted = download(
+            "https://a.b/i.zip",
+            interrupted_path.clone(),
+            DownloadState::Interrupted,
+        );
+        interrupted.etag = Some("\"abc\"".to_owned());
+        interrupted.resumable = true;
+        interrupted.total = Some(50);
+        let running = download(
+            "https://a.b/r.zip",
+            running_path.clone(),
+            DownloadState::InProgress,
+        );
+        // The partial file tells how much was received
+        fs::write(interrupted.part_path(), b"12345").unwrap();
+        Downloads {
+            downloads: vec![finished, interrupted, running],
+        }
+        .save(&path);
+        let loaded = Downloads::load(&path);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(temp_path("interrupted.zip.part")).unwrap();
+
+        let [finished, interrupted, running] = &loaded.downloads[..] else {
+            panic!("Expected 3 downloads");
+        };
+        assert_eq!(finished.url, "https://a.b/f.zip");
+        assert_eq!(finished.path, finished_path);
+        assert_eq!(finished.state, DownloadState::Finished);
+        assert_eq!((finished.received, finished.total), (100, Some(100)));
+        assert_eq!(finished.etag, None);
+        assert!(!finished.resumable);
+
+        assert_eq!(interrupted.path, interrupted_path);
+        assert_eq!(interrupted.state, DownloadState::Interrupted);
+        assert_eq!(interrupted.etag.as_deref(), Some("\"abc\""));
+        assert!(interrupted.resumable);
+        assert_eq!((interrupted.received, interrupted.total), (5, Some(50)));
+        assert!(interrupted.can_resume());
+
+        // A download that was running when the browser was closed can be resumed later
+        assert_eq!(running.path, running_path);
+        assert_eq!(running.state, DownloadState::Interrupted);
+        assert_eq!((running.received, running.total), (0, None));
+        assert!(!running.can_resume());
+    }
+
+    #[test]
+    fn skips_broken_lines() {
+        let path = temp_path("broken-downloads.txt");
+        fs::write(
+            &path,
+            "#version 1\nfinished\thttps://a.b/\nfinished\thttps://a.b/f\tf\t\t0\t10\n",
+        )
+        .unwrap();
+        let loaded = Downloads::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.downloads.len(), 1);
+        assert_eq!(loaded.downloads[0].url, "https://a.b/f");
+        assert_eq!(loaded.downloads[0].total, Some(10));
+    }
+}
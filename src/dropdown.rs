@@ -0,0 +1,65 @@
+//! A list of suggestions below a text field. The url bar shows urls from the history in one, and
+//! the text inputs of a page show the options of their `<datalist>`.
+
+/// The items of a dropdown and the one that is selected with the arrow keys
+pub struct Dropdown {
+    pub items: Vec<String>,
+    pub selected: Option<usize>,
+    pub item_height: i32,
+}
+
+impl Dropdown {
+    pub fn new(item_height: i32) -> Self {
+        Self {
+            items: Vec::new(),
+            selected: None,
+            item_height,
+        }
+    }
+
+    /// Replaces the items. Nothing is selected afterwards.
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.selected = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set_items(Vec::new());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Selects the item below the selected one. The selection wraps around to the top.
+    pub fn select_next(&mut self) {
+        let count = self.items.len();
+        if count > 0 {
+            self.selected = Some(self.selected.map_or(0, |x| (x + 1) % count));
+        }
+    }
+
+    /// Selects the item above the selected one. The selection wraps around to the bottom.
+    pub fn select_previous(&mut self) {
+        let count = self.items.len();
+        if count > 0 {
+            self.selected = Some(self.selected.map_or(count - 1, |x| (x + count - 1) % count));
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<&str> {
+        self.items.get(self.selected?).map(|x| x.as_str())
+    }
+
+    /// Returns the index of the item at the y coordinate
+    ///
+    /// * `top` - The y coordinate of the top of the first item
+    pub fn item_at(&self, top: i32, y: i32) -> Option<usize> {
+        let bottom = top + self.items.len() as i32 * self.item_height;
+        if y >= top && y < bottom {
+            Some(((y - top) / self.item_height) as usize)
+        } else {
+            None
+        }
+    }
+}
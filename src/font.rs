@@ -1,85 +1,948 @@
-use crate::bound::Bound;
 use crate::color::Color;
-use ab_glyph::{point, Font as AbFont, FontVec, ScaleFont};
-use image::{Rgba, RgbaImage};
-use std::{fs::File, io::Read, path::Path};
+use crate::system_fonts::SystemFonts;
+use ab_glyph::{point, Font as AbFont, FontRef, GlyphImageFormat, OutlinedGlyph, Point, ScaleFont};
+use image::imageops::FilterType;
+use image::{ImageFormat, Rgba, RgbaImage};
+use memmap2::Mmap;
+use rustybuzz::{Direction, UnicodeBuffer};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{collections::HashMap, fs, fs::File, path::Path};
+
+pub use ab_glyph::GlyphId;
+
+/// The family that is used when none of the families that a page asks for are found
+pub const DEFAULT_FONT_FAMILY: &str = "liberation sans";
+
+/// Families that Liberation Sans can stand in for, because it has the same metrics as them. The
+/// generic `sans-serif` family is also drawn with it.
+const DEFAULT_FONT_ALIASES: [&str; 3] = ["sans-serif", "arial", "helvetica"];
+
+/// The faces of the default font that are built into the browser. They are used when they are
+/// missing from the fonts directory, so the browser works without it.
+#[cfg(feature = "embed-font")]
+const EMBEDDED_FONT_FACES: [(FontFace, &[u8]); 4] = [
+    (
+        FontFace::REGULAR,
+        include_bytes!("../fonts/liberation-sans/LiberationSans-Regular.ttf"),
+    ),
+    (
+        FontFace {
+            bold: true,
+            italic: false,
+        },
+        include_bytes!("../fonts/liberation-sans/LiberationSans-Bold.ttf"),
+    ),
+    (
+        FontFace {
+            bold: false,
+            italic: true,
+        },
+        include_bytes!("../fonts/liberation-sans/LiberationSans-Italic.ttf"),
+    ),
+    (
+        FontFace {
+            bold: true,
+            italic: true,
+        },
+        include_bytes!("../fonts/liberation-sans/LiberationSans-BoldItalic.ttf"),
+    ),
+];
+
+/// The directory of the font that the emoji are drawn with, see [FontRegistry::load]
+const EMOJI_FONT_DIRECTORY: &str = "emoji";
+
+/// The width and the height of a [GlyphAtlas]
+pub const GLYPH_ATLAS_SIZE: u32 = 1024;
+
+/// How many variants of a glyph the [GlyphAtlas] has, each shifted by a fraction of a pixel. The
+/// glyphs are drawn with the variant that is closest to where they are between the pixels, so the
+/// spacing of the text stays even.
+pub const SUBPIXEL_STEPS: u32 = 4;
+
+/// The id of the next font that is loaded, see [Font::id]
+static NEXT_FONT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Which face of a family text is drawn with, from `font-weight` and `font-style`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FontFace {
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl FontFace {
+    pub const REGULAR: Self = Self {
+        bold: false,
+        italic: false,
+    };
+
+    const ALL: [Self; 4] = [
+        Self::REGULAR,
+        Self {
+            bold: true,
+            italic: false,
+        },
+        Self {
+            bold: false,
+            italic: true,
+        },
+        Self {
+            bold: true,
+            italic: true,
+        },
+    ];
+
+    /// The end of the file name of the face, such as `-BoldItalic.ttf`
+    fn file_suffix(self) -> &'static str {
+        match (self.bold, self.italic) {
+            (false, false) => "-Regular.ttf",
+            (true, false) => "-Bold.ttf",
+            (false, true) => "-Italic.ttf",
+            (true, true) => "-BoldItalic.ttf",
+        }
+    }
+}
+
+/// The measurements of a font that the layout needs. The layout only measures the text, so it can
+/// be given any font that implements this, such as [MockFont] in the tests. The layout measures
+/// the paragraphs from several threads at once.
+pub trait FontMetrics: Sync {
+    fn get_glyph_width(&self, character: char, font_size: f32) -> i32;
+
+    fn get_glyph_height(&self, font_size: f32) -> i32;
+
+    /// The distance from the top of a line to the baseline of the text
+    fn get_ascent(&self, font_size: f32) -> i32;
+
+    fn get_word_width(&self, word: &str, font_size: f32) -> i32;
+
+    /// The width of a single character when text is drawn as monospace. The font itself isn't
+    /// monospace, so every character is given a cell as wide as the widest common glyph.
+    fn get_monospace_width(&self, font_size: f32) -> i32 {
+        self.get_glyph_width('M', font_size)
+    }
+}
 
 pub struct Font {
-    font: FontVec,
+    font: FontRef<'static>,
+    /// Shapes the text, so that it's drawn with the kerning and the ligatures of the font
+    face: rustybuzz::Face<'static>,
+    /// Draws the characters that the font doesn't have, like the emoji
+    fallback: Option<Arc<Font>>,
+    /// Tells the fonts apart in a [GlyphAtlas]
+    id: usize,
 }
 
 impl Font {
     pub fn load(path: &Path) -> Result<Self, String> {
+        Self::load_index(path, 0)
+    }
+
+    /// Loads a face of a font collection, such as a `.ttc` file
+    pub fn load_index(path: &Path, index: u32) -> Result<Self, String> {
         let file = match File::open(path) {
             Ok(v) => v,
             Err(e) => return Err(e.to_string()),
         };
 
-        let bytes = file.bytes();
-        let bytes: Vec<u8> = bytes.map(|x| x.unwrap()).collect();
-        println!("{}", bytes.len());
+        // Every font of the system is loaded, so the files are mapped and only the parts of them
+        // that are used are read.
+        // SAFETY: The font files aren't expected to change while the browser is running
+        let bytes = unsafe { Mmap::map(&file) }.map_err(|x| x.to_string())?;
+        // The fonts are kept until the browser is closed, so the data can live as long as it
+        let bytes: &'static [u8] = Box::leak(Box::new(bytes));
+        Self::from_bytes(bytes, index)
+    }
 
-        let font = match FontVec::try_from_vec(bytes) {
+    /// Reads a face of the font data, such as the default font that is built into the browser
+    pub fn from_bytes(bytes: &'static [u8], index: u32) -> Result<Self, String> {
+        let font = match FontRef::try_from_slice_and_index(bytes, index) {
             Ok(v) => v,
             Err(e) => return Err(format!("{}", e)),
         };
+        let Some(face) = rustybuzz::Face::from_slice(bytes, index) else {
+            return Err("Couldn't read the tables of the font".to_string());
+        };
+
+        Ok(Self {
+            font,
+            face,
+            fallback: None,
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Returns the unshaped glyph of the character, from the fallback font if this font doesn't
+    /// have it
+    pub fn glyph(&self, character: char) -> ShapedGlyph {
+        let fallback = self.uses_fallback(character);
+        ShapedGlyph {
+            id: self.font_of_run(fallback).font.glyph_id(character),
+            x: 0.0,
+            y: 0,
+            fallback,
+        }
+    }
 
-        Ok(Self { font })
+    /// The font that the glyph is drawn with
+    pub fn font_of(&self, glyph: &ShapedGlyph) -> &Font {
+        self.font_of_run(glyph.fallback)
     }
 
-    pub fn get_glyph_width(&self, character: char, font_size: f32) -> i32 {
-        let glyph = self.font.glyph_id(character);
-        self.font.as_scaled(font_size).h_advance(glyph) as i32
+    fn font_of_run(&self, fallback: bool) -> &Font {
+        match fallback {
+            true => self.fallback.as_deref().unwrap_or(self),
+            false => self,
+        }
     }
 
-    pub fn get_glyph_height(&self, font_size: f32) -> i32 {
-        self.font.as_scaled(font_size).height() as i32
+    fn uses_fallback(&self, character: char) -> bool {
+        self.fallback.as_ref().is_some_and(|fallback| {
+            self.font.glyph_id(character).0 == 0 && fallback.font.glyph_id(character).0 != 0
+        })
     }
 
-    pub fn get_glyph_bounds(&self, character: char, font_size: f32) -> Bound<i32> {
-        let font_scaled = self.font.as_scaled(font_size);
-        Bound::<i32>::new(
-            self.get_glyph_width(character, font_size),
-            font_scaled.height() as i32,
-        )
+    /// Splits the text into the runs that are shaped with this font and the ones that are shaped
+    /// with the fallback font. The joiners and the variation selectors stay with the character
+    /// before them, so that the sequences of emoji are shaped together.
+    fn runs(&self, text: &str) -> Vec<(Range<usize>, bool)> {
+        let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+        for (index, character) in text.char_indices() {
+            let end = index + character.len_utf8();
+            let joins = matches!(character, '\u{200d}' | '\u{fe0e}' | '\u{fe0f}');
+            match runs.last_mut() {
+                Some((range, _)) if joins => range.end = end,
+                Some((range, fallback)) if *fallback == self.uses_fallback(character) => {
+                    range.end = end
+                }
+                _ => runs.push((index..end, self.uses_fallback(character))),
+            }
+        }
+        runs
     }
 
-    pub fn get_word_width(&self, word: &str, font_size: f32) -> i32 {
-        let mut width = 0;
-        for character in word.chars() {
-            width += self.get_glyph_bounds(character, font_size).width;
+    /// The font at a size. Everything that is measured, shaped or rasterized at a size goes
+    /// through it.
+    pub fn scaled(&self, font_size: f32) -> ScaledFont<'_> {
+        let scaled = self.font.as_scaled(font_size);
+        ScaledFont {
+            font: self,
+            size: font_size,
+            scale: scaled.h_scale_factor(),
+            ascent: scaled.ascent(),
+            height: scaled.height(),
         }
-        width
+    }
+}
+
+impl FontMetrics for Font {
+    fn get_glyph_width(&self, character: char, font_size: f32) -> i32 {
+        self.scaled(font_size).glyph_width(character)
+    }
+
+    fn get_glyph_height(&self, font_size: f32) -> i32 {
+        self.scaled(font_size).height()
+    }
+
+    fn get_ascent(&self, font_size: f32) -> i32 {
+        self.scaled(font_size).ascent()
     }
 
-    pub fn render_string(&self, word: &str, font_size: f32, font_color: Color) -> RgbaImage {
+    fn get_word_width(&self, word: &str, font_size: f32) -> i32 {
+        self.scaled(font_size).shape(word).width
+    }
+}
+
+/// A [Font] at a size, made with [Font::scaled]. The handle works out the scale of the font and
+/// the vertical metrics once, so they aren't looked up from the font tables again for every glyph
+/// that is measured or drawn with it.
+#[derive(Clone, Copy)]
+pub struct ScaledFont<'a> {
+    font: &'a Font,
+    size: f32,
+    /// The pixels in a unit of the font
+    scale: f32,
+    ascent: f32,
+    height: f32,
+}
+
+impl<'a> ScaledFont<'a> {
+    pub fn font(&self) -> &'a Font {
+        self.font
+    }
+
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    /// The distance from the top of a line to the baseline of the text
+    pub fn ascent(&self) -> i32 {
+        self.ascent as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height as i32
+    }
+
+    pub fn glyph_width(&self, character: char) -> i32 {
+        let glyph = self.font.glyph(character);
+        self.of(&glyph).advance(glyph.id)
+    }
+
+    /// The same font at the same size as the one that the glyph is drawn with, see
+    /// [Font::font_of]
+    pub fn of(&self, glyph: &ShapedGlyph) -> ScaledFont<'a> {
+        self.of_run(glyph.fallback)
+    }
+
+    fn of_run(&self, fallback: bool) -> ScaledFont<'a> {
+        match fallback {
+            true => self.font.font_of_run(true).scaled(self.size),
+            false => *self,
+        }
+    }
+
+    fn advance(&self, id: GlyphId) -> i32 {
+        (self.font.font.h_advance_unscaled(id) * self.scale) as i32
+    }
+
+    /// Picks the glyphs of the text and places them with the kerning, the ligatures and the marks
+    /// of the font. The words are already in the order they are shown in, so the text is always
+    /// shaped from left to right. The characters that the font doesn't have are shaped with the
+    /// fallback font.
+    pub fn shape(&self, text: &str) -> ShapedText {
+        let mut glyphs = Vec::new();
+        let mut width = 0.0;
+        for (range, fallback) in self.font.runs(text) {
+            let (run, advance) = self.of_run(fallback).shape_run(&text[range]);
+            glyphs.extend(run.into_iter().map(|glyph| ShapedGlyph {
+                x: glyph.x + width,
+                fallback,
+                ..glyph
+            }));
+            width += advance;
+        }
+        ShapedText {
+            glyphs,
+            width: width.round() as i32,
+        }
+    }
+
+    /// Returns the glyphs of the text with how far they advance the pen
+    fn shape_run(&self, text: &str) -> (Vec<ShapedGlyph>, f32) {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        buffer.set_direction(Direction::LeftToRight);
+        let output = rustybuzz::shape(&self.font.face, &[], buffer);
+
+        // The positions are in the units of the font
+        let mut pen = 0.0;
+        let glyphs = output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .map(|(info, position)| {
+                let glyph = ShapedGlyph {
+                    id: GlyphId(info.glyph_id as u16),
+                    x: (pen + position.x_offset as f32) * self.scale,
+                    y: (-position.y_offset as f32 * self.scale).round() as i32,
+                    fallback: false,
+                };
+                pen += position.x_advance as f32;
+                glyph
+            })
+            .collect();
+        (glyphs, pen * self.scale)
+    }
+
+    /// The outline of the glyph with the left end of its baseline at the position
+    fn outline(&self, id: GlyphId, position: Point) -> Option<OutlinedGlyph> {
+        self.font
+            .font
+            .outline_glyph(id.with_scale_and_position(self.size, position))
+    }
+
+    pub fn render_string(&self, word: &str, font_color: Color) -> RgbaImage {
+        let shaped = self.shape(word);
         let mut output = RgbaImage::new(
             // +2 due to some weird off by one error
-            self.get_word_width(word, font_size) as u32 + 2,
-            self.get_glyph_height(font_size) as u32 + 1,
+            shaped.width as u32 + 2,
+            self.height() as u32 + 1,
         );
 
-        let mut previous_point = point(0.0, self.font.as_scaled(font_size).ascent());
+        for glyph in shaped.glyphs {
+            self.of(&glyph).draw_glyph(
+                &mut output,
+                glyph.id,
+                point(glyph.x, self.ascent + glyph.y as f32),
+                font_color,
+            );
+        }
+        output
+    }
+
+    /// Draws a single glyph onto the image. The position is the left end of the glyph's baseline.
+    fn draw_glyph(
+        &self,
+        output: &mut RgbaImage,
+        glyph: GlyphId,
+        position: Point,
+        font_color: Color,
+    ) {
         let color = font_color.as_8_bit();
-        for character in word.chars() {
-            let glyph = self
-                .font
-                .glyph_id(character)
-                .with_scale_and_position(font_size, previous_point);
-            if let Some(outline) = self.font.outline_glyph(glyph) {
-                let bounding_box = outline.px_bounds();
-                outline.draw(|x, y, c| {
-                    if c > 0.0 {
-                        output.put_pixel(
-                            x + bounding_box.min.x as u32,
-                            (y as i32 + bounding_box.min.y as i32) as u32,
-                            Rgba([color[0], color[1], color[2], (255.0 * c) as u8]),
-                        );
+        self.coverage(glyph, position.x, position.y, |x, y, c| {
+            if c > 0.0
+                && x >= 0
+                && y >= 0
+                && (x as u32) < output.width()
+                && (y as u32) < output.height()
+            {
+                output.put_pixel(
+                    x as u32,
+                    y as u32,
+                    Rgba([color[0], color[1], color[2], (255.0 * c) as u8]),
+                );
+            }
+        });
+    }
+
+    /// Rasterizes the outline of the glyph with the left end of its baseline at x and y, and
+    /// calls `pixel` with the coverage of every pixel that it touches
+    pub fn coverage(&self, glyph: GlyphId, x: f32, y: f32, mut pixel: impl FnMut(i32, i32, f32)) {
+        if let Some(outline) = self.outline(glyph, point(x, y)) {
+            let bounding_box = outline.px_bounds();
+            outline.draw(|x, y, c| {
+                pixel(
+                    x as i32 + bounding_box.min.x as i32,
+                    y as i32 + bounding_box.min.y as i32,
+                    c,
+                )
+            });
+        }
+    }
+
+    /// The colored bitmap of the glyph, like the ones of the emoji, scaled to the font size.
+    /// Returns the pixels with where their top left corner is from the left end of the baseline.
+    pub fn color_image(&self, id: GlyphId) -> Option<(RgbaImage, i32, i32)> {
+        let font = &self.font.font;
+        let image = font.glyph_raster_image2(id, self.size as u16)?;
+        if !matches!(image.format, GlyphImageFormat::Png) {
+            return None;
+        }
+        let pixels = image::load_from_memory_with_format(image.data, ImageFormat::Png)
+            .ok()?
+            .into_rgba8();
+        // The bitmaps come in a few sizes, and the closest one is scaled to the size of the text
+        let pixels_per_em = self.scale * font.units_per_em()?;
+        let scale = pixels_per_em / image.pixels_per_em as f32;
+        let width = (pixels.width() as f32 * scale).round().max(1.0) as u32;
+        let height = (pixels.height() as f32 * scale).round().max(1.0) as u32;
+        let pixels = image::imageops::resize(&pixels, width, height, FilterType::Triangle);
+        // The origin is the bottom left corner of the bitmap, with the y growing upwards
+        let offset_x = (image.origin.x * scale).round() as i32;
+        let offset_y = -((image.origin.y + image.height as f32) * scale).round() as i32;
+        Some((pixels, offset_x, offset_y))
+    }
+}
+
+/// A glyph of text that has been shaped, see [ScaledFont::shape]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    pub id: GlyphId,
+    /// Where the glyph is drawn, from the left end of the baseline of the text. The glyphs are
+    /// placed between the pixels too, see [SUBPIXEL_STEPS].
+    pub x: f32,
+    pub y: i32,
+    /// The glyph is from the fallback font, see [Font::font_of]
+    pub fallback: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    /// How far the text advances the pen
+    pub width: i32,
+}
+
+/// Splits an x into the whole pixel and the subpixel step of the glyph variant that is drawn
+/// there, see [SUBPIXEL_STEPS]
+pub fn subpixel_position(x: f32) -> (i32, u32) {
+    let steps = (x * SUBPIXEL_STEPS as f32).round() as i32;
+    let whole = steps.div_euclid(SUBPIXEL_STEPS as i32);
+    (whole, steps.rem_euclid(SUBPIXEL_STEPS as i32) as u32)
+}
+
+/// Where a glyph is in a [GlyphAtlas]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph {
+    /// The top left corner of the glyph in the atlas
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Where the top left corner of the glyph is drawn, from the left end of its baseline
+    pub offset_x: i32,
+    pub offset_y: i32,
+    /// The glyph has colors of its own, like the emoji, and isn't drawn in the color of the text
+    pub color: bool,
+}
+
+/// Packs the glyphs that text is drawn with into a single texture, so that the text of a whole
+/// word can be drawn from it at once. Every glyph of a font is rasterized only once for each size.
+/// The atlas only keeps track of where the glyphs are, and the texture itself is owned by whoever
+/// draws the text. See [Self::take_added].
+///
+/// The glyphs are packed into rows from the top left corner. The atlas is cleared when it's full,
+/// and the glyphs are added again as they are drawn.
+pub struct GlyphAtlas {
+    /// By the id of the font, the glyph, the bits of the font size and the subpixel step. Glyphs
+    /// without an outline, like the space, are [None].
+    glyphs: HashMap<(usize, GlyphId, u32, u32), Option<AtlasGlyph>>,
+    /// Where the next glyph is placed
+    cursor: (u32, u32),
+    /// The height of the tallest glyph in the current row
+    row_height: u32,
+    /// The glyphs that have been added since [Self::take_added] was called, with their pixels
+    added: Vec<(AtlasGlyph, Vec<u8>)>,
+    /// Counts how many times the atlas has been cleared
+    generation: u32,
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        let mut atlas = Self {
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+            added: Vec::new(),
+            generation: 0,
+        };
+        atlas.clear();
+        atlas
+    }
+}
+
+impl GlyphAtlas {
+    /// A white pixel that is always in the atlas. Rects are filled with it, so that they can be
+    /// drawn together with the text.
+    pub const WHITE: AtlasGlyph = AtlasGlyph {
+        x: 0,
+        y: 0,
+        width: 1,
+        height: 1,
+        offset_x: 0,
+        offset_y: 0,
+        color: false,
+    };
+
+    /// Returns where the shaped glyphs of the font are, rasterizing the ones that aren't in the
+    /// atlas yet. The atlas is cleared if they don't fit, so it only has the glyphs that are asked
+    /// for at the same time.
+    ///
+    /// The glyphs come with their subpixel steps, see [subpixel_position].
+    pub fn glyphs(
+        &mut self,
+        font: ScaledFont,
+        glyphs: &[(ShapedGlyph, u32)],
+    ) -> Vec<Option<AtlasGlyph>> {
+        let generation = self.generation;
+        let atlas_glyphs: Vec<_> = glyphs
+            .iter()
+            .map(|(x, step)| self.glyph(font.of(x), x.id, *step))
+            .collect();
+        if self.generation == generation {
+            return atlas_glyphs;
+        }
+        // The atlas was cleared in the middle, so the first glyphs aren't in it anymore
+        glyphs
+            .iter()
+            .map(|(x, step)| self.glyph(font.of(x), x.id, *step))
+            .collect()
+    }
+
+    fn glyph(&mut self, font: ScaledFont, id: GlyphId, step: u32) -> Option<AtlasGlyph> {
+        let key = (font.font.id, id, font.size.to_bits(), step);
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+        let shift = step as f32 / SUBPIXEL_STEPS as f32;
+        let glyph = match font.outline(id, point(shift, 0.0)) {
+            Some(outline) => self.insert_outline(&outline),
+            None => font
+                .color_image(id)
+                .and_then(|(pixels, offset_x, offset_y)| {
+                    self.insert_image(pixels, offset_x, offset_y)
+                }),
+        };
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    /// Rasterizes the outline of a glyph. The pixels are white and the coverage of the glyph is in
+    /// their alpha, so the text can be drawn in any color.
+    fn insert_outline(&mut self, outline: &OutlinedGlyph) -> Option<AtlasGlyph> {
+        let bounds = outline.px_bounds();
+        let glyph = self.allocate(
+            bounds.width() as u32,
+            bounds.height() as u32,
+            bounds.min.x as i32,
+            bounds.min.y as i32,
+        )?;
+        let mut pixels = [255, 255, 255, 0].repeat((glyph.width * glyph.height) as usize);
+        outline.draw(|x, y, coverage| {
+            if x < glyph.width && y < glyph.height {
+                pixels[((y * glyph.width + x) * 4 + 3) as usize] =
+                    (255.0 * coverage.min(1.0)) as u8;
+            }
+        });
+        self.added.push((glyph, pixels));
+        Some(glyph)
+    }
+
+    /// Adds a glyph that has colors of its own, see [Font::color_image]
+    fn insert_image(
+        &mut self,
+        pixels: RgbaImage,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Option<AtlasGlyph> {
+        let glyph = AtlasGlyph {
+            color: true,
+            ..self.allocate(pixels.width(), pixels.height(), offset_x, offset_y)?
+        };
+        self.added.push((glyph, pixels.into_raw()));
+        Some(glyph)
+    }
+
+    /// Finds room for a glyph of the size. Returns [None] if the glyph is bigger than the whole
+    /// atlas.
+    fn allocate(
+        &mut self,
+        width: u32,
+        height: u32,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> Option<AtlasGlyph> {
+        // The glyphs are a pixel apart, so the edges of their neighbours don't bleed into them
+        let (padded_width, padded_height) = (width + 1, height + 1);
+        if padded_width > GLYPH_ATLAS_SIZE || padded_height > GLYPH_ATLAS_SIZE {
+            return None;
+        }
+        if self.cursor.0 + padded_width > GLYPH_ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height);
+            self.row_height = 0;
+        }
+        if self.cursor.1 + padded_height > GLYPH_ATLAS_SIZE {
+            self.clear();
+        }
+
+        let (x, y) = self.cursor;
+        self.cursor.0 += padded_width;
+        self.row_height = self.row_height.max(padded_height);
+        Some(AtlasGlyph {
+            x,
+            y,
+            width,
+            height,
+            offset_x,
+            offset_y,
+            color: false,
+        })
+    }
+
+    /// Forgets every glyph except [Self::WHITE]. The glyphs that were already returned can't be
+    /// drawn after this.
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+        // The white pixel is followed by the same gap as the glyphs
+        self.cursor = (Self::WHITE.width + 1, 0);
+        self.row_height = Self::WHITE.height + 1;
+        self.added = vec![(Self::WHITE, vec![255; 4])];
+        self.generation += 1;
+    }
+
+    /// Counts how many times the atlas has been cleared. The glyphs from before the last time
+    /// can't be drawn anymore.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Returns the glyphs that have been added since this was last called, with their RGBA
+    /// pixels. They have to be written into the texture of the atlas before they are drawn.
+    pub fn take_added(&mut self) -> Vec<(AtlasGlyph, Vec<u8>)> {
+        std::mem::take(&mut self.added)
+    }
+}
+
+/// The fonts that pages can pick with `font-family`, by their family names. The names are
+/// lowercase, since `font-family` is matched case-insensitively. Every family has a regular face,
+/// and it can have bold and italic faces too.
+pub struct FontRegistry<F = Font> {
+    fonts: HashMap<String, HashMap<FontFace, F>>,
+    /// Other names for the families, such as `arial` for Liberation Sans
+    aliases: HashMap<String, String>,
+    default_family: String,
+}
+
+impl<F: FontMetrics> FontRegistry<F> {
+    /// Creates a registry with only the default font in it
+    pub fn new(default_family: &str, default_font: F) -> Self {
+        let default_family = default_family.to_lowercase();
+        let faces = HashMap::from([(FontFace::REGULAR, default_font)]);
+        Self {
+            fonts: HashMap::from([(default_family.clone(), faces)]),
+            aliases: HashMap::new(),
+            default_family,
+        }
+    }
+
+    /// Adds the regular face of a family
+    pub fn add(&mut self, family: &str, font: F) {
+        self.add_face(family, FontFace::REGULAR, font);
+    }
+
+    /// Adds a face of a family. A family needs a regular face to be used.
+    pub fn add_face(&mut self, family: &str, face: FontFace, font: F) {
+        self.fonts
+            .entry(family.to_lowercase())
+            .or_default()
+            .insert(face, font);
+    }
+
+    /// Makes `alias` another name for a family that is already in the registry
+    pub fn add_alias(&mut self, alias: &str, family: &str) {
+        self.aliases
+            .insert(alias.to_lowercase(), family.to_lowercase());
+    }
+
+    /// Checks if the family has a regular face in the registry
+    pub fn has_family(&self, family: &str) -> bool {
+        self.fonts
+            .get(&family.to_lowercase())
+            .is_some_and(|x| x.contains_key(&FontFace::REGULAR))
+    }
+
+    /// Goes through the families of `font-family` in order, and returns the name of the first one
+    /// that is in the registry. The default family is used if none of them are.
+    pub fn resolve(&self, families: &[String]) -> &str {
+        for family in families {
+            let family = family.to_lowercase();
+            let family = self.aliases.get(&family).unwrap_or(&family);
+            match self.fonts.get_key_value(family) {
+                Some((name, faces)) if faces.contains_key(&FontFace::REGULAR) => return name,
+                _ => (),
+            }
+        }
+        &self.default_family
+    }
+
+    /// Returns the regular face of the family, or the default font if there is no such family.
+    /// The name should come from [Self::resolve].
+    pub fn get(&self, family: &str) -> &F {
+        self.get_face(family, FontFace::REGULAR)
+    }
+
+    /// Returns a face of the family. A missing bold italic face falls back to the bold face, and
+    /// the other missing faces fall back to the regular face.
+    pub fn get_face(&self, family: &str, face: FontFace) -> &F {
+        let faces = self
+            .fonts
+            .get(family)
+            .filter(|x| x.contains_key(&FontFace::REGULAR))
+            .unwrap_or_else(|| &self.fonts[&self.default_family]);
+        let bold = FontFace {
+            italic: false,
+            ..face
+        };
+        faces
+            .get(&face)
+            .or_else(|| faces.get(&bold))
+            .unwrap_or_else(|| &faces[&FontFace::REGULAR])
+    }
+
+    pub fn default_font(&self) -> &F {
+        self.get_face(&self.default_family, FontFace::REGULAR)
+    }
+}
+
+/// Finds the directory of the fonts that come with the browser. It's looked up from the working
+/// directory, next to the executable and in the source tree, so that the browser can be started
+/// from anywhere.
+pub fn bundled_font_directory() -> PathBuf {
+    let executable_directory = std::env::current_exe()
+        .ok()
+        .and_then(|x| x.parent().map(|x| x.join("fonts")));
+    let candidates = [
+        Some(PathBuf::from("./fonts")),
+        executable_directory,
+        Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("fonts")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|x| x.is_dir())
+        .unwrap_or_else(|| PathBuf::from("./fonts"))
+}
+
+impl FontRegistry<Font> {
+    /// Loads every font family in the directory. Each family has a directory of its own, such as
+    /// `liberation-sans`, and the dashes of its name are read as spaces. The faces of the family
+    /// are the files that end in `-Regular.ttf`, `-Bold.ttf`, `-Italic.ttf` and
+    /// `-BoldItalic.ttf`. Only the regular face is required. With the `embed-font` feature, the
+    /// faces of the default font that are missing from the directory are the built in ones.
+    ///
+    /// The font in the `emoji` directory, such as Noto Color Emoji, isn't a family of its own.
+    /// Every font falls back to it for the characters that it doesn't have. Without one, the emoji
+    /// font of the system is used, see [SystemFonts::emoji_font].
+    ///
+    /// The families of the system are added after the bundled ones, and the generic families such
+    /// as `serif` are drawn with the families that the system picks for them. The bundled families
+    /// are used over the ones of the system with the same name.
+    pub fn load(path: &Path, system: &SystemFonts) -> Result<Self, String> {
+        let mut fonts = Vec::new();
+        let mut emoji = None;
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries.flatten().collect(),
+            Err(error) => {
+                println!("Couldn't read the fonts in {}: {}", path.display(), error);
+                Vec::new()
+            }
+        };
+        for entry in entries {
+            let family = entry.file_name().to_string_lossy().replace('-', " ");
+            let Ok(files) = fs::read_dir(entry.path()) else {
+                continue;
+            };
+            let files: Vec<_> = files.flatten().map(|x| x.path()).collect();
+            if family == EMOJI_FONT_DIRECTORY {
+                let file = files
+                    .iter()
+                    .find(|x| x.extension().is_some_and(|x| x == "ttf"));
+                match file.map(|x| Font::load(x)) {
+                    Some(Ok(font)) => emoji = Some(Arc::new(font)),
+                    Some(Err(error)) => println!("Couldn't load the emoji font: {}", error),
+                    None => println!("No font found in {}", entry.path().display()),
+                }
+                continue;
+            }
+            for face in FontFace::ALL {
+                let file = files
+                    .iter()
+                    .find(|x| x.to_string_lossy().ends_with(face.file_suffix()));
+                let Some(file) = file else {
+                    if face == FontFace::REGULAR {
+                        println!("No regular face found for the font family {}", family);
                     }
-                });
+                    continue;
+                };
+                match Font::load(file) {
+                    Ok(font) => fonts.push((family.to_lowercase(), face, font)),
+                    Err(error) => println!("Couldn't load the font {}: {}", file.display(), error),
+                }
             }
-            previous_point.x += self.get_glyph_width(character, font_size) as f32;
         }
-        output
+
+        #[cfg(feature = "embed-font")]
+        for (face, bytes) in EMBEDDED_FONT_FACES {
+            let loaded = fonts
+                .iter()
+                .any(|(family, x, _)| family == DEFAULT_FONT_FAMILY && *x == face);
+            if !loaded {
+                fonts.push((
+                    DEFAULT_FONT_FAMILY.to_string(),
+                    face,
+                    Font::from_bytes(bytes, 0)?,
+                ));
+            }
+        }
+
+        let bundled: Vec<String> = fonts.iter().map(|x| x.0.clone()).collect();
+        for system_font in &system.fonts {
+            if bundled.contains(&system_font.family) {
+                continue;
+            }
+            match Font::load_index(&system_font.path, system_font.index) {
+                Ok(font) => fonts.push((system_font.family.clone(), system_font.face, font)),
+                Err(error) => println!(
+                    "Couldn't load the font {}: {}",
+                    system_font.path.display(),
+                    error
+                ),
+            }
+        }
+
+        // Without a bundled emoji font, the one of the system is used
+        if emoji.is_none() {
+            if let Some(system_font) = system.emoji_font() {
+                match Font::load_index(&system_font.path, system_font.index) {
+                    Ok(font) => emoji = Some(Arc::new(font)),
+                    Err(error) => println!("Couldn't load the emoji font: {}", error),
+                }
+            }
+        }
+
+        for (_, _, font) in &mut fonts {
+            font.fallback = emoji.clone();
+        }
+
+        let default_font = fonts
+            .iter()
+            .position(|(family, face, _)| {
+                family == DEFAULT_FONT_FAMILY && *face == FontFace::REGULAR
+            })
+            .map(|x| fonts.swap_remove(x).2);
+        let Some(default_font) = default_font else {
+            return Err(format!(
+                "The default font {} is missing from {}",
+                DEFAULT_FONT_FAMILY,
+                path.display()
+            ));
+        };
+        let mut registry = Self::new(DEFAULT_FONT_FAMILY, default_font);
+        for (family, face, font) in fonts {
+            registry.add_face(&family, face, font);
+        }
+        for alias in DEFAULT_FONT_ALIASES {
+            registry.add_alias(alias, DEFAULT_FONT_FAMILY);
+        }
+        for (generic, family) in &system.generic_families {
+            if registry.has_family(family) {
+                registry.add_alias(generic, family);
+            }
+        }
+        Ok(registry)
+    }
+}
+
+/// A font for the tests. Every character has the same advance, so the expected positions of the
+/// text are easy to work out by hand.
+#[cfg(test)]
+pub struct MockFont;
+
+#[cfg(test)]
+impl MockFont {
+    /// The advance of every character as a fraction of the font size
+    pub const ADVANCE: f32 = 0.5;
+}
+
+#[cfg(test)]
+impl FontMetrics for MockFont {
+    fn get_glyph_width(&self, _character: char, font_size: f32) -> i32 {
+        (font_size * Self::ADVANCE) as i32
+    }
+
+    fn get_glyph_height(&self, font_size: f32) -> i32 {
+        font_size as i32
+    }
+
+    fn get_ascent(&self, font_size: f32) -> i32 {
+        (font_size * 0.8) as i32
+    }
+
+    fn get_word_width(&self, word: &str, font_size: f32) -> i32 {
+        word.chars().count() as i32 * self.get_glyph_width(' ', font_size)
     }
 }
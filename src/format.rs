@@ -0,0 +1,216 @@
+//! Human readable sizes and dates for the pages the browser generates itself. The format follows
+//! the locale of the user, which is read from the `LC_ALL`, `LC_TIME` and `LANG` environment
+//! variables like other programs do.
+
+/// The order and the separator of the parts of a date
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DateOrder {
+    /// `2026-10-16`, which is also used when the locale isn't known
+    YearMonthDay,
+    /// `16.10.2026`
+    DayMonthYearDots,
+    /// `16/10/2026`
+    DayMonthYearSlashes,
+    /// `10/16/2026`
+    MonthDayYear,
+}
+
+/// Whether the times are written with a 12-hour clock and AM and PM
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HourCycle {
+    H12,
+    H24,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Locale {
+    pub date_order: DateOrder,
+    pub hour_cycle: HourCycle,
+    pub decimal_separator: char,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::from_name("C")
+    }
+}
+
+impl Locale {
+    /// Reads the locale from the environment variables. The first one that is set wins.
+    pub fn from_env() -> Self {
+        let name = ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|x| std::env::var(x).ok())
+            .find(|x| !x.is_empty())
+            .unwrap_or_default();
+        Self::from_name(&name)
+    }
+
+    /// Parses a locale name such as `en_US.UTF-8` or `fi_FI`
+    pub fn from_name(name: &str) -> Self {
+        let name = name.split(['.', '@']).next().unwrap_or("");
+        let (language, region) = name.split_once('_').unwrap_or((name, ""));
+        let date_order = match (language, region) {
+            ("en", "US") => DateOrder::MonthDayYear,
+            ("en" | "fr" | "es" | "it" | "pt", _) => DateOrder::DayMonthYearSlashes,
+            ("de" | "fi" | "ru" | "pl" | "cs" | "da" | "nb" | "nn" | "tr", _) => {
+                DateOrder::DayMonthYearDots
+            }
+            _ => DateOrder::YearMonthDay,
+        };
+        let hour_cycle = match region {
+            "US" | "CA" | "AU" | "NZ" | "IN" | "PH" => HourCycle::H12,
+            _ => HourCycle::H24,
+        };
+        let decimal_separator = match language {
+            "de" | "fi" | "fr" | "es" | "it" | "pt" | "ru" | "pl" | "nl" | "sv" | "cs" | "da"
+            | "nb" | "nn" | "tr" => ',',
+            _ => '.',
+        };
+        Self {
+            date_order,
+            hour_cycle,
+            decimal_separator,
+        }
+    }
+}
+
+/// Writes a size in bytes with the largest unit that keeps the number above one, such as
+/// `1.5 MB`. A kilobyte is 1024 bytes.
+pub fn format_size(bytes: u64, locale: &Locale) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    let number = format!("{:.1}", size).replace('.', &locale.decimal_separator.to_string());
+    format!("{} {}", number, UNITS[unit])
+}
+
+/// Turns days since the unix epoch into the year, the month and the day
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // The years are counted from March, so the leap day is the last day of the year
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Writes the date of a unix timestamp, such as `16.10.2026`
+pub fn format_date(timestamp: u64, locale: &Locale) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86400) as i64);
+    match locale.date_order {
+        DateOrder::YearMonthDay => format!("{}-{:02}-{:02}", year, month, day),
+        DateOrder::DayMonthYearDots => format!("{}.{}.{}", day, month, year),
+        DateOrder::DayMonthYearSlashes => format!("{:02}/{:02}/{}", day, month, year),
+        DateOrder::MonthDayYear => format!("{}/{}/{}", month, day, year),
+    }
+}
+
+/// Writes the time of day of a unix timestamp, such as `14:05` or `2:05 PM`. The time zone of the
+/// computer isn't known, so the times are in UTC.
+pub fn format_time(timestamp: u64, locale: &Locale) -> String {
+    let minutes = timestamp / 60 % 60;
+    let hours = timestamp / 3600 % 24;
+    match locale.hour_cycle {
+        HourCycle::H24 => format!("{:02}:{:02} UTC", hours, minutes),
+        HourCycle::H12 => {
+            let suffix = if hours < 12 { "AM" } else { "PM" };
+            let hours = match hours % 12 {
+                0 => 12,
+                v => v,
+            };
+            format!("{}:{:02} {} UTC", hours, minutes, suffix)
+        }
+    }
+}
+
+/// Writes the date and the time of a unix timestamp
+pub fn format_date_time(timestamp: u64, locale: &Locale) -> String {
+    format!(
+        "{} {}",
+        format_date(timestamp, locale),
+        format_time(timestamp, locale)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-16 14:05 UTC
+    const TIMESTAMP: u64 = 1792159500;
+
+    #[test]
+    fn reads_locale_names() {
+        let locale = |x| {
+            let locale = Locale::from_name(x);
+            (
+                locale.date_order,
+                locale.hour_cycle,
+                locale.decimal_separator,
+            )
+        };
+        assert_eq!(
+            locale("en_US.UTF-8"),
+            (DateOrder::MonthDayYear, HourCycle::H12, '.')
+        );
+        assert_eq!(
+            locale("en_GB.UTF-8"),
+            (DateOrder::DayMonthYearSlashes, HourCycle::H24, '.')
+        );
+        assert_eq!(
+            locale("fi_FI"),
+            (DateOrder::DayMonthYearDots, HourCycle::H24, ',')
+        );
+        assert_eq!(
+            locale("de_DE@euro"),
+            (DateOrder::DayMonthYearDots, HourCycle::H24, ',')
+        );
+        assert_eq!(locale("C"), (DateOrder::YearMonthDay, HourCycle::H24, '.'));
+        assert_eq!(locale(""), (DateOrder::YearMonthDay, HourCycle::H24, '.'));
+        assert_eq!(Locale::default(), Locale::from_name("C"));
+    }
+
+    #[test]
+    fn formats_sizes_with_the_largest_unit() {
+        let locale = Locale::from_name("C");
+        assert_eq!(format_size(0, &locale), "0 B");
+        assert_eq!(format_size(1023, &locale), "1023 B");
+        assert_eq!(format_size(1024, &locale), "1.0 KB");
+        assert_eq!(format_size(1536, &locale), "1.5 KB");
+        assert_eq!(format_size(1024 * 1024, &locale), "1.0 MB");
+        assert_eq!(format_size(1024 * 1024 * 1024, &locale), "1.0 GB");
+        assert_eq!(format_size(1024_u64.pow(4), &locale), "1.0 TB");
+        // There is no unit after terabytes
+        assert_eq!(format_size(1024_u64.pow(5), &locale), "1024.0 TB");
+        assert_eq!(format_size(1536, &Locale::from_name("fi_FI")), "1,5 KB");
+    }
+
+    #[test]
+    fn formats_dates_and_times() {
+        let format = |x| format_date_time(TIMESTAMP, &Locale::from_name(x));
+        assert_eq!(format("C"), "2026-10-16 14:05 UTC");
+        assert_eq!(format("fi_FI"), "16.10.2026 14:05 UTC");
+        assert_eq!(format("en_GB"), "16/10/2026 14:05 UTC");
+        assert_eq!(format("en_US"), "10/16/2026 2:05 PM UTC");
+        assert_eq!(format_date(0, &Locale::default()), "1970-01-01");
+        assert_eq!(format_time(0, &Locale::from_name("en_US")), "12:00 AM UTC");
+    }
+}
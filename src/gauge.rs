@@ -0,0 +1,85 @@
+//! `<progress>` and `<meter>`, which are drawn as bars that are filled as far as their value goes.
+//! The values are read from the attributes every time the page is laid out, so the bars follow
+//! the changes to them.
+
+use crate::color::Color;
+use crate::css::Color as CssColor;
+use crate::html::{Element, Tag};
+
+/// How a bar is filled
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gauge {
+    /// From 0.0 to 1.0
+    pub fraction: f32,
+    pub color: Color,
+}
+
+fn number_attribute(element: &Element, name: &str) -> Option<f32> {
+    element
+        .get_attribute(name)
+        .and_then(|x| x.trim().parse::<f32>().ok())
+        .filter(|x| x.is_finite())
+}
+
+fn hex(r: u8, g: u8, b: u8) -> Color {
+    Color::from_css(CssColor::Hex(r, g, b)).unwrap()
+}
+
+/// The color of the empty part of the bars
+pub fn track_color() -> Color {
+    hex(224, 224, 224)
+}
+
+/// Returns how the bar of the element is filled. Returns [None] for the other elements.
+pub fn gauge_of(element: &Element) -> Option<Gauge> {
+    match element.element_type {
+        Tag::Progress => Some(progress(element)),
+        Tag::Meter => Some(meter(element)),
+        _ => None,
+    }
+}
+
+/// A progress without a value is indeterminate, and its bar is left empty
+fn progress(element: &Element) -> Gauge {
+    let max = number_attribute(element, "max")
+        .filter(|x| *x > 0.0)
+        .unwrap_or(1.0);
+    let value = number_attribute(element, "value").unwrap_or(0.0);
+    Gauge {
+        fraction: (value / max).clamp(0.0, 1.0),
+        color: hex(0, 117, 255),
+    }
+}
+
+/// The color of a meter tells how good the value is. `low` and `high` split the range into three
+/// regions, and the value is green in the same region as `optimum`, yellow next to it and red
+/// two regions away.
+fn meter(element: &Element) -> Gauge {
+    let min = number_attribute(element, "min").unwrap_or(0.0);
+    let max = number_attribute(element, "max").unwrap_or(1.0).max(min);
+    let clamp = |x: f32| x.clamp(min, max);
+    let value = clamp(number_attribute(element, "value").unwrap_or(0.0));
+    let low = clamp(number_attribute(element, "low").unwrap_or(min));
+    let high = clamp(number_attribute(element, "high").unwrap_or(max)).max(low);
+    let optimum = clamp(number_attribute(element, "optimum").unwrap_or((min + max) / 2.0));
+
+    let region = |x: f32| -> u32 {
+        if x < low {
+            0
+        } else if x > high {
+            2
+        } else {
+            1
+        }
+    };
+    let color = match region(value).abs_diff(region(optimum)) {
+        0 => hex(16, 160, 16),
+        1 => hex(224, 176, 0),
+        _ => hex(208, 32, 32),
+    };
+    let fraction = match max - min {
+        0.0 => 0.0,
+        v => (value - min) / v,
+    };
+    Gauge { fraction, color }
+}
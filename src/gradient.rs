@@ -0,0 +1,160 @@
+//! Colors for `linear-gradient()` backgrounds. The colors along the gradient line are baked into a
+//! small ramp texture, which the gradient shader looks up for every pixel.
+
+use crate::color::Color;
+use crate::css::LinearGradient;
+use image::{Rgba, RgbaImage};
+
+/// The number of colors in a ramp. The colors between them are blended by the texture sampler.
+pub const RAMP_WIDTH: u32 = 256;
+
+/// Returns the length of the gradient line in a box of the given size. The line is long enough
+/// that the corners of the box get the first and the last color.
+///
+/// * `angle` - The angle of the gradient in degrees
+pub fn line_length(angle: f32, width: f32, height: f32) -> f32 {
+    let angle = angle.to_radians();
+    (width * angle.sin()).abs() + (height * angle.cos()).abs()
+}
+
+/// Resolves the positions of the color stops into fractions of the gradient line. The stops
+/// without a position are spread evenly between the stops around them. An empty box has no
+/// gradient line to place the stops on, so all of its stops are spread evenly.
+pub fn stop_positions(gradient: &LinearGradient, line_length: f32, font_size: f32) -> Vec<f32> {
+    let mut positions: Vec<Option<f32>> = gradient
+        .stops
+        .iter()
+        .map(|x| {
+            x.position
+                .filter(|_| line_length > 0.0)
+                .map(|x| x.to_px(font_size, line_length as i32) as f32 / line_length)
+        })
+        .collect();
+    let last = positions.len() - 1;
+    positions[0] = positions[0].or(Some(0.0));
+    positions[last] = positions[last].or(Some(1.0));
+
+    // A stop can't go before the stops in front of it
+    let mut previous = f32::MIN;
+    for position in positions.iter_mut().flatten() {
+        *position = position.max(previous);
+        previous = *position;
+    }
+
+    let mut index = 1;
+    while index < last {
+        if positions[index].is_some() {
+            index += 1;
+            continue;
+        }
+        let end = (index..=last).find(|x| positions[*x].is_some()).unwrap();
+        let from = positions[index - 1].unwrap();
+        let to = positions[end].unwrap();
+        let count = (end - index + 1) as f32;
+        for (step, position) in positions[index..end].iter_mut().enumerate() {
+            *position = Some(from + (to - from) * (step + 1) as f32 / count);
+        }
+        index = end;
+    }
+    positions.into_iter().map(|x| x.unwrap()).collect()
+}
+
+/// Makes the ramp texture of the gradient. The first pixel is the start of the gradient line and
+/// the last pixel is the end.
+pub fn ramp(gradient: &LinearGradient, line_length: f32, font_size: f32) -> RgbaImage {
+    let positions = stop_positions(gradient, line_length, font_size);
+    let colors: Vec<Color> = gradient
+        .stops
+        .iter()
+        .map(|x| {
+            Color::from_css(x.color).unwrap_or(Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+            })
+        })
+        .collect();
+
+    RgbaImage::from_fn(RAMP_WIDTH, 1, |x, _| {
+        let t = x as f32 / (RAMP_WIDTH - 1) as f32;
+        let color = match positions.iter().position(|x| *x > t) {
+            Some(0) => colors[0],
+            Some(index) => {
+                let (from, to) = (positions[index - 1], positions[index]);
+                mix(colors[index - 1], colors[index], (t - from) / (to - from))
+            }
+            None => colors[colors.len() - 1],
+        };
+        let [r, g, b, a] = color.as_opengl_color().map(|x| (x * 255.0).round() as u8);
+        Rgba([r, g, b, a])
+    })
+}
+
+/// Blends two colors. The colors are weighted by their alpha, so fading into `transparent`
+/// doesn't turn the color darker.
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let a = from.a + (to.a - from.a) * amount;
+    if a == 0.0 {
+        return to;
+    }
+    let channel = |from_value: f32, to_value: f32| {
+        (from_value * from.a + (to_value * to.a - from_value * from.a) * amount) / a
+    };
+    Color {
+        r: channel(from.r, to.r),
+        g: channel(from.g, to.g),
+        b: channel(from.b, to.b),
+        a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(arguments: &str, line_length: f32) -> Vec<f32> {
+        let gradient = LinearGradient::from_arguments(arguments).unwrap();
+        stop_positions(&gradient, line_length, 10.0)
+    }
+
+    #[test]
+    fn spreads_stops_without_positions_evenly() {
+        assert_eq!(positions("red, blue", 100.0), [0.0, 1.0]);
+        assert_eq!(positions("red, green, blue", 100.0), [0.0, 0.5, 1.0]);
+        assert_eq!(
+            positions("red, green 25%, white, black, blue", 100.0),
+            [0.0, 0.25, 0.5, 0.75, 1.0]
+        );
+    }
+
+    #[test]
+    fn resolves_lengths_on_the_gradient_line() {
+        assert_eq!(positions("red 10px, blue 50%", 200.0), [0.05, 0.5]);
+        assert_eq!(positions("red 2em, blue", 200.0), [0.1, 1.0]);
+    }
+
+    #[test]
+    fn keeps_stops_in_order() {
+        // A stop before the previous one is moved to it
+        assert_eq!(
+            positions("red 60%, green 20%, blue", 100.0),
+            [0.6, 0.6, 1.0]
+        );
+        assert_eq!(
+            positions("red, green 150%, blue 50%", 100.0),
+            [0.0, 1.5, 1.5]
+        );
+    }
+
+    #[test]
+    fn spreads_the_stops_of_empty_boxes() {
+        assert_eq!(line_length(90.0, 0.0, 0.0), 0.0);
+        assert_eq!(positions("red 10px, green 50%, blue", 0.0), [0.0, 0.5, 1.0]);
+        // The ramp still goes from the first color to the last one
+        let gradient = LinearGradient::from_arguments("red 10px, blue 50%").unwrap();
+        let ramp = ramp(&gradient, 0.0, 10.0);
+        assert_eq!(ramp.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(ramp.get_pixel(RAMP_WIDTH - 1, 0), &Rgba([0, 0, 255, 255]));
+    }
+}
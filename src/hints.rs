@@ -0,0 +1,231 @@
+//! The hint mode, which lets the links be followed with the keyboard. Pressing `f` puts a short
+//! label made of letters next to every link on the screen, and typing a label follows its link.
+//! Typing the label in uppercase opens the link in a new tab instead.
+
+use web::render_layout::{Layout, Position};
+
+/// The letters the labels are made of. The home row comes first, since it's the easiest to type.
+const HINT_CHARACTERS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// A label on a link
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub label: String,
+    pub href: String,
+    /// The element the link text is in, which tells if the link has `rel="noreferrer"`
+    pub element_id: Option<usize>,
+    /// The top left corner of the first word of the link on the screen
+    pub position: Position,
+}
+
+/// What typing a character did, see [HintMode::type_character]
+#[derive(Debug, PartialEq)]
+pub enum HintResult {
+    /// The typed characters are the start of some labels
+    Pending,
+    /// No label starts with the typed characters, so the hint mode is over
+    Cancelled,
+    /// The label of the link was typed
+    Follow { index: usize, new_tab: bool },
+}
+
+pub struct HintMode {
+    pub hints: Vec<Hint>,
+    /// The characters of a label that have been typed so far, in lowercase
+    pub typed: String,
+    /// Set if any of the typed characters were uppercase
+    new_tab: bool,
+}
+
+impl HintMode {
+    /// Gives a label to every link that is visible on the screen. A link that is split into many
+    /// sentences only gets a label on its first one.
+    ///
+    /// * `screen_top` - Where the page starts on the screen, below the toolbar
+    /// * `scroll` - How far the page is scrolled, see [web::tab::Tab::scroll]
+    pub fn new(layout: &Layout, scroll: Position, screen_top: i32, screen_height: i32) -> Self {
+        let mut hints: Vec<Hint> = Vec::new();
+        for paragraph in &layout.paragraphs {
+            let scroll = if paragraph.fixed {
+                Position::new(0, 0)
+            } else {
+                scroll
+            };
+            for sentence in &paragraph.sentences {
+                let (Some(href), Some(word)) = (sentence.href.as_ref(), sentence.words.first())
+                else {
+                    continue;
+                };
+                let position = word.position + scroll;
+                let visible = position.y + word.height > screen_top
+                    && position.y < screen_height
+                    && paragraph
+                        .clip
+                        .is_none_or(|x| x.contains(word.position.x, word.position.y));
+                let same_link = hints
+                    .last()
+                    .is_some_and(|x| x.href == *href && x.element_id == sentence.element_id);
+                if visible && !same_link {
+                    hints.push(Hint {
+                        label: String::new(),
+                        href: href.clone(),
+                        element_id: sentence.element_id,
+                        position,
+                    });
+                }
+            }
+        }
+        let labels = hint_labels(hints.len());
+        for (hint, label) in hints.iter_mut().zip(labels) {
+            hint.label = label;
+        }
+        Self {
+            hints,
+            typed: String::new(),
+            new_tab: false,
+        }
+    }
+
+    /// Adds the character to the typed label
+    pub fn type_character(&mut self, character: char) -> HintResult {
+        if character.is_uppercase() {
+            self.new_tab = true;
+        }
+        self.typed.extend(character.to_lowercase());
+        if let Some(index) = self.hints.iter().position(|x| x.label == self.typed) {
+            return HintResult::Follow {
+                index,
+                new_tab: self.new_tab,
+            };
+        }
+        if self.hints.iter().any(|x| x.label.starts_with(&self.typed)) {
+            HintResult::Pending
+        } else {
+            HintResult::Cancelled
+        }
+    }
+
+    /// Removes the last typed character
+    pub fn backspace(&mut self) {
+        self.typed.pop();
+    }
+
+    /// Returns the hints whose labels start with the typed characters
+    pub fn visible_hints(&self) -> impl Iterator<Item = &Hint> {
+        self.hints
+            .iter()
+            .filter(|x| x.label.starts_with(&self.typed))
+    }
+}
+
+/// Makes `count` labels where none is the start of another one, so every label can be typed
+/// without waiting to see if more characters follow. The labels are as short as possible.
+pub fn hint_labels(count: usize) -> Vec<String> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let characters: Vec<char> = HINT_CHARACTERS.chars().collect();
+    let mut labels = vec![String::new()];
+    // A label is replaced with the labels that continue it, until there are enough of them. The
+    // oldest labels are the shortest ones, so they are continued first.
+    let mut next = 0;
+    while next == 0 || labels.len() - next < count {
+        let label = labels[next].clone();
+        next += 1;
+        labels.extend(characters.iter().map(|x| format!("{}{}", label, x)));
+    }
+    let mut labels = labels.split_off(next);
+    labels.truncate(count);
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the labels are unique and that none is the start of another one
+    fn assert_prefix_free(labels: &[String]) {
+        for (index, label) in labels.iter().enumerate() {
+            for (other_index, other) in labels.iter().enumerate() {
+                assert!(
+                    index == other_index || !other.starts_with(label.as_str()),
+                    "{} is the start of {}",
+                    label,
+                    other
+                );
+            }
+        }
+    }
+
+    fn hint_mode(count: usize) -> HintMode {
+        let hints = hint_labels(count)
+            .into_iter()
+            .map(|label| Hint {
+                href: format!("/{}", label),
+                label,
+                element_id: None,
+                position: Position::new(0, 0),
+            })
+            .collect();
+        HintMode {
+            hints,
+            typed: String::new(),
+            new_tab: false,
+        }
+    }
+
+    #[test]
+    fn makes_the_shortest_labels() {
+        assert!(hint_labels(0).is_empty());
+        assert_eq!(hint_labels(3), ["a", "s", "d"]);
+        let labels = hint_labels(26);
+        assert!(labels.iter().all(|x| x.len() == 1));
+        assert_eq!(labels.concat(), HINT_CHARACTERS);
+    }
+
+    #[test]
+    fn no_label_is_the_start_of_another() {
+        // The first letter is continued to make room for the 27th label
+        let labels = hint_labels(27);
+        assert_eq!(labels.len(), 27);
+        assert_eq!(labels[..2], ["s", "d"]);
+        assert_eq!(labels[25..], ["aa", "as"]);
+        assert_prefix_free(&labels);
+
+        let labels = hint_labels(700);
+        assert_eq!(labels.len(), 700);
+        assert!(labels.iter().all(|x| (2..=3).contains(&x.len())));
+        assert_prefix_free(&labels);
+    }
+
+    #[test]
+    fn follows_the_typed_label() {
+        let mut hints = hint_mode(27);
+        assert_eq!(hints.type_character('a'), HintResult::Pending);
+        assert_eq!(hints.visible_hints().count(), 2);
+        assert_eq!(
+            hints.type_character('s'),
+            HintResult::Follow {
+                index: 26,
+                new_tab: false
+            }
+        );
+
+        // An uppercase character opens the link in a new tab
+        let mut hints = hint_mode(27);
+        assert_eq!(hints.type_character('A'), HintResult::Pending);
+        assert_eq!(
+            hints.type_character('a'),
+            HintResult::Follow {
+                index: 25,
+                new_tab: true
+            }
+        );
+
+        let mut hints = hint_mode(27);
+        hints.type_character('a');
+        assert_eq!(hints.type_character('q'), HintResult::Cancelled);
+        hints.backspace();
+        assert_eq!(hints.typed, "a");
+    }
+}
@@ -0,0 +1,285 @@
+//! The browsing history. Every visited page gets a frecency score, which combines how often and
+//! how recently the page was visited. The score is used to rank the url bar suggestions.
+
+use crate::migration::{self, Migration};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const HISTORY_PATH: &str = "./history.txt";
+/// The changes to the format of the history file, see [crate::migration]
+const MIGRATIONS: [Migration; 1] = [migration::add_version];
+
+/// The time it takes for the score of a visit to drop to half, in seconds
+const HALF_LIFE: f64 = 14.0 * 24.0 * 60.0 * 60.0;
+
+/// Returns the current time in seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: Option<String>,
+    pub visit_count: u32,
+    pub last_visit: u64,
+    /// The score at the time of the last visit. Every visit adds 1 to the score and the score
+    /// halves every [HALF_LIFE], so it only has to be updated when the page is visited again.
+    score: f64,
+}
+
+impl HistoryEntry {
+    /// Returns the frecency of the entry at the given time
+    pub fn frecency(&self, now: u64) -> f64 {
+        let elapsed = now.saturating_sub(self.last_visit) as f64;
+        self.score * 0.5_f64.powf(elapsed / HALF_LIFE)
+    }
+
+    /// Checks if every word of the query is found in the title or the url
+    fn matches(&self, words: &[String]) -> bool {
+        let url = self.url.to_lowercase();
+        let title = self.title.as_deref().unwrap_or("").to_lowercase();
+        words
+            .iter()
+            .all(|x| url.contains(x.as_str()) || title.contains(x.as_str()))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Loads the history from a file. A missing or broken file gives an empty history.
+    pub fn load(path: &Path) -> Self {
+        let Some(content) = migration::read(path, &MIGRATIONS) else {
+            return Self::default();
+        };
+
+        // Every line is `url\ttitle\tvisit count\tlast visit\tscore`
+        let mut entries = Vec::new();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [url, title, visit_count, last_visit, score] = fields[..] else {
+                println!("Skipping a broken history line: {}", line);
+                continue;
+            };
+            let (Ok(visit_count), Ok(last_visit), Ok(score)) =
+                (visit_count.parse(), last_visit.parse(), score.parse())
+            else {
+                println!("Skipping a broken history line: {}", line);
+                continue;
+            };
+            entries.push(HistoryEntry {
+                url: url.to_owned(),
+                title: (!title.is_empty()).then(|| title.to_owned()),
+                visit_count,
+                last_visit,
+                score,
+            });
+        }
+        Self { entries }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let mut content = String::new();
+        for entry in &self.entries {
+            // Tabs and line breaks would break the format, so they are turned into spaces
+            let title = entry
+                .title
+                .as_deref()
+                .unwrap_or("")
+                .replace(['\t', '\n'], " ");
+            content += &format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                entry.url, title, entry.visit_count, entry.last_visit, entry.score
+            );
+        }
+        if let Err(error) = migration::write(path, &content, &MIGRATIONS) {
+            println!("Couldn't save the history: {}", error);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    /// Merges the entry of one url into the entry of another, for example when a page turns out to
+    /// be a copy of another page. The visits of both entries are kept.
+    pub fn coalesce(&mut self, from: &str, into: &str, now: u64) {
+        if from == into {
+            return;
+        }
+        let Some(index) = self.entries.iter().position(|x| x.url == from) else {
+            return;
+        };
+        let old = self.entries.remove(index);
+        match self.entries.iter_mut().find(|x| x.url == into) {
+            Some(entry) => {
+                entry.score = entry.frecency(now) + old.frecency(now);
+                entry.visit_count += old.visit_count;
+                entry.last_visit = now;
+                if entry.title.is_none() {
+                    entry.title = old.title;
+                }
+            }
+            None => self.entries.push(HistoryEntry {
+                url: into.to_owned(),
+                score: old.frecency(now),
+                last_visit: now,
+                ..old
+            }),
+        }
+    }
+
+    /// Records a visit to the page. Only the score of the visited page changes.
+    pub fn add_visit(&mut self, url: &str, title: Option<String>, now: u64) {
+        match self.entries.iter_mut().find(|x| x.url == url) {
+            Some(entry) => {
+                entry.score = entry.frecency(now) + 1.0;
+                entry.visit_count += 1;
+                entry.last_visit = now;
+                if title.is_some() {
+                    entry.title = title;
+                }
+            }
+            None => self.entries.push(HistoryEntry {
+                url: url.to_owned(),
+                title,
+                visit_count: 1,
+                last_visit: now,
+                score: 1.0,
+            }),
+        }
+    }
+
+    /// Searches the titles and urls for the words of the query. The results are sorted by their
+    /// frecency, highest first.
+    pub fn search(&self, query: &str, now: u64) -> Vec<&HistoryEntry> {
+        let words: Vec<String> = query.split_whitespace().map(|x| x.to_lowercase()).collect();
+        let mut results: Vec<(&HistoryEntry, f64)> = self
+            .entries
+            .iter()
+            .filter(|x| x.matches(&words))
+            .map(|x| (x, x.frecency(now)))
+            .collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1));
+        results.into_iter().map(|x| x.0).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn urls<'a>(entries: &[&'a HistoryEntry]) -> Vec<&'a str> {
+        entries.iter().map(|x| x.url.as_str()).collect()
+    }
+
+    #[test]
+    fn frecency_halves_every_half_life() {
+        let mut history = History::default();
+        history.add_visit("https://a.b/", None, 0);
+        let entry = history.iter().next().unwrap();
+        assert_eq!(entry.frecency(0), 1.0);
+        assert_eq!(entry.frecency(14 * DAY), 0.5);
+        assert_eq!(entry.frecency(28 * DAY), 0.25);
+
+        // The decayed score is carried over to the next visit
+        history.add_visit("https://a.b/", Some("A".to_owned()), 14 * DAY);
+        let entry = history.iter().next().unwrap();
+        assert_eq!(entry.frecency(14 * DAY), 1.5);
+        assert_eq!(entry.visit_count, 2);
+        assert_eq!(entry.title.as_deref(), Some("A"));
+        // A visit without a title keeps the old one
+        history.add_visit("https://a.b/", None, 14 * DAY);
+        assert_eq!(history.iter().next().unwrap().title.as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn recent_visits_outrank_old_ones() {
+        let mut history = History::default();
+        for _ in 0..3 {
+            history.add_visit("https://old.com/", None, 0);
+        }
+        history.add_visit("https://new.com/", None, 60 * DAY);
+        assert_eq!(
+            urls(&history.search("", 60 * DAY)),
+            ["https://new.com/", "https://old.com/"]
+        );
+        // More visits win when they are about as recent
+        history.add_visit("https://often.com/", None, 59 * DAY);
+        history.add_visit("https://often.com/", None, 59 * DAY);
+        assert_eq!(
+            urls(&history.search("", 60 * DAY)),
+            ["https://often.com/", "https://new.com/", "https://old.com/"]
+        );
+    }
+
+    #[test]
+    fn coalesces_into_the_canonical_url() {
+        let mut history = History::default();
+        history.add_visit("https://a.b/", Some("A".to_owned()), 0);
+        history.add_visit("https://a.b/?ref=1", Some("Copy".to_owned()), 0);
+        history.add_visit("https://a.b/?ref=1", None, 0);
+        history.coalesce("https://a.b/?ref=1", "https://a.b/", 14 * DAY);
+        let entries: Vec<&HistoryEntry> = history.iter().collect();
+        assert_eq!(urls(&entries), ["https://a.b/"]);
+        assert_eq!(entries[0].visit_count, 3);
+        assert_eq!(entries[0].last_visit, 14 * DAY);
+        assert_eq!(entries[0].frecency(14 * DAY), 1.5);
+        assert_eq!(entries[0].title.as_deref(), Some("A"));
+
+        // An url that isn't in the history yet takes over the entry
+        history.coalesce("https://a.b/", "https://c.d/", 14 * DAY);
+        let entries: Vec<&HistoryEntry> = history.iter().collect();
+        assert_eq!(urls(&entries), ["https://c.d/"]);
+        assert_eq!(entries[0].visit_count, 3);
+        assert_eq!(entries[0].title.as_deref(), Some("A"));
+
+        // Coalescing an url into itself or an unknown url does nothing
+        history.coalesce("https://c.d/", "https://c.d/", 14 * DAY);
+        history.coalesce("https://e.f/", "https://c.d/", 14 * DAY);
+        assert_eq!(history.iter().next().unwrap().visit_count, 3);
+    }
+
+    #[test]
+    fn searches_every_word_in_titles_and_urls() {
+        let mut history = History::default();
+        history.add_visit("https://rust-lang.org/", Some("Rust".to_owned()), 0);
+        history.add_visit(
+            "https://docs.rs/",
+            Some("Docs of Rust crates".to_owned()),
+            0,
+        );
+        history.add_visit("https://example.com/", None, 0);
+        history.add_visit("https://example.com/", None, 0);
+
+        assert_eq!(
+            urls(&history.search("RUST", 0)),
+            ["https://rust-lang.org/", "https://docs.rs/"]
+        );
+        assert_eq!(
+            urls(&history.search("rust crates", 0)),
+            ["https://docs.rs/"]
+        );
+        assert_eq!(urls(&history.search("rust example", 0)), Vec::<&str>::new());
+        // Ties keep the order the pages were first visited in
+        assert_eq!(
+            urls(&history.search("https", 0)),
+            [
+                "https://example.com/",
+                "https://rust-lang.org/",
+                "https://docs.rs/"
+            ]
+        );
+    }
+}
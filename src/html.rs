@@ -1,7 +1,12 @@
-use crate::css::{parse_inline_css, Rule};
-use std::{collections::HashMap, iter::Peekable, str::Chars};
-
-#[derive(Debug, PartialEq, Clone, Copy)]
+use crate::color::Color;
+use crate::css::{parse_css, parse_inline_css, DisplayStyle, ListStyleType, Rule, RuleSet};
+use crate::document::InteractionState;
+use crate::media::MediaQueryList;
+use crate::rule_index::{AncestorFilter, RuleIndex};
+use crate::style::{ComputedStyle, ForcedColors};
+use std::{collections::HashMap, ops::Range};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Tag {
     PlainText,
     H(u32),
@@ -16,6 +21,7 @@ pub enum Tag {
     Title,
     Track,
     Audio,
+    Video,
     Small,
     Select,
     Aside,
@@ -37,6 +43,8 @@ pub enum Tag {
     Label,
     Option,
     Tbody,
+    Thead,
+    Tfoot,
     Caption,
     Section,
     Form,
@@ -70,6 +78,14 @@ pub enum Tag {
     Picture,
     Q,
     I,
+    Pre,
+    Code,
+    Blockquote,
+    Details,
+    Summary,
+    Progress,
+    Meter,
+    Datalist,
 }
 
 impl Tag {
@@ -98,6 +114,8 @@ impl Tag {
             "body" => Ok(Tag::Body),
             "th" => Ok(Tag::Th),
             "tbody" => Ok(Tag::Tbody),
+            "thead" => Ok(Tag::Thead),
+            "tfoot" => Ok(Tag::Tfoot),
             "main" => Ok(Tag::Main),
             "html" => Ok(Tag::Html),
             "footer" => Ok(Tag::Footer),
@@ -109,6 +127,7 @@ impl Tag {
             "aside" => Ok(Tag::Aside),
             "figure" => Ok(Tag::Figure),
             "audio" => Ok(Tag::Audio),
+            "video" => Ok(Tag::Video),
             "figcaption" => Ok(Tag::Figcaption),
             "bdi" => Ok(Tag::Bdi),
             "sup" => Ok(Tag::Sup),
@@ -146,6 +165,14 @@ impl Tag {
             "form" => Ok(Tag::Form),
             "script" => Ok(Tag::Script),
             "strong" => Ok(Tag::Strong),
+            "pre" => Ok(Tag::Pre),
+            "code" => Ok(Tag::Code),
+            "blockquote" => Ok(Tag::Blockquote),
+            "details" => Ok(Tag::Details),
+            "summary" => Ok(Tag::Summary),
+            "progress" => Ok(Tag::Progress),
+            "meter" => Ok(Tag::Meter),
+            "datalist" => Ok(Tag::Datalist),
             v => Err(format!("Unknown tag: {}", v)),
         }
     }
@@ -154,34 +181,51 @@ impl Tag {
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Element {
+    /// Identifies the element inside of its document. See [crate::document::Document::new]
+    pub id: usize,
     pub element_type: Tag,
     pub children: Vec<Element>,
     pub inner_text: String,
     pub attributes: HashMap<String, String>,
     pub styles: Vec<Rule>,
     pub inner_styles: Vec<Rule>,
+    pub style: ComputedStyle,
+    /// Where the element is in the html it was parsed from, from the `<` of its start tag to the
+    /// end of its end tag. Empty for elements that didn't come from html.
+    pub source_span: Range<usize>,
+    /// The width and the height of the picture of an `<img>` once it has been loaded. The size is
+    /// zero if the picture couldn't be loaded.
+    pub natural_size: Option<(u32, u32)>,
 }
 
 impl Element {
     pub fn new(tag: Tag) -> Self {
         Self {
+            id: 0,
             element_type: tag,
             children: Vec::new(),
             inner_text: String::new(),
             attributes: HashMap::new(),
             styles: Vec::new(),
             inner_styles: Vec::new(),
+            style: ComputedStyle::default(),
+            source_span: 0..0,
+            natural_size: None,
         }
     }
 
     pub fn new_with_text(tag: Tag, inner_text: &str) -> Self {
         Self {
+            id: 0,
             element_type: tag,
             inner_text: inner_text.to_string(),
             children: Vec::new(),
             attributes: HashMap::new(),
             styles: Vec::new(),
             inner_styles: Vec::new(),
+            style: ComputedStyle::default(),
+            source_span: 0..0,
+            natural_size: None,
         }
     }
 
@@ -194,12 +238,262 @@ impl Element {
         }
     }
 
+    /// Collects the stylesheets of the `<style>` elements inside of this element, including the
+    /// element itself.
+    pub fn collect_style_sheets(&self, output: &mut Vec<RuleSet>) {
+        if self.element_type == Tag::Style {
+            let css: String = self
+                .children
+                .iter()
+                .map(|x| x.inner_text.as_str())
+                .collect();
+            let mut rule_sets = parse_css(&css);
+            if let Some(media) = self.attributes.get("media") {
+                for rule_set in &mut rule_sets {
+                    rule_set.media.insert(0, MediaQueryList::parse(media));
+                }
+            }
+            output.extend(rule_sets);
+        }
+        for child in &self.children {
+            child.collect_style_sheets(output);
+        }
+    }
+
+    /// Gives this element and its children ids starting from `next_id`, in document order
+    pub fn assign_ids(&mut self, next_id: &mut usize) {
+        self.id = *next_id;
+        *next_id += 1;
+        for child in &mut self.children {
+            child.assign_ids(next_id);
+        }
+    }
+
+    /// Returns the element with the id if it is this element or inside of it
+    pub fn find_by_id(&self, id: usize) -> Option<&Element> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|x| x.find_by_id(id))
+    }
+
+    pub fn find_by_id_mut(&mut self, id: usize) -> Option<&mut Element> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|x| x.find_by_id_mut(id))
+    }
+
+    /// Checks if the element is the `<summary>` that opens and closes its `<details>`. Only the
+    /// first summary of the details does that.
+    pub fn is_summary_of(&self, details: &Element) -> bool {
+        details.element_type == Tag::Details
+            && details
+                .children
+                .iter()
+                .find(|x| x.element_type == Tag::Summary)
+                .is_some_and(|x| x.id == self.id)
+    }
+
+    /// Checks if the element can get the focus, which links and form controls can
+    pub fn is_focusable(&self) -> bool {
+        match self.element_type {
+            Tag::A => self.attributes.contains_key("href"),
+            Tag::Button | Tag::Input | Tag::Select => true,
+            _ => false,
+        }
+    }
+
+    /// Checks if the element is an `<input>` that text is typed into
+    pub fn is_text_field(&self) -> bool {
+        self.element_type == Tag::Input
+            && matches!(
+                self.get_attribute("type")
+                    .map(|x| x.to_lowercase())
+                    .as_deref(),
+                None | Some("text" | "search" | "email" | "url" | "tel")
+            )
+    }
+
+    /// Returns the ids of the elements from this element down to the element with the id. Returns
+    /// an empty list if the element isn't inside of this one.
+    pub fn path_to(&self, id: usize) -> Vec<usize> {
+        if self.id == id {
+            return vec![id];
+        }
+        for child in &self.children {
+            let mut path = child.path_to(id);
+            if !path.is_empty() {
+                path.insert(0, self.id);
+                return path;
+            }
+        }
+        Vec::new()
+    }
+
+    /// Sets the rules from the stylesheets that apply to this element and its children. The rules
+    /// are applied in the order of the rulesets.
+    pub fn apply_css_rules(&mut self, rule_index: &RuleIndex, state: &InteractionState) {
+        // The selectors look at the ancestors, which can't be borrowed while the children are
+        // changed, so the rules of every element are found before any of them are set
+        let mut styles = Vec::new();
+        self.find_css_rules(
+            rule_index,
+            state,
+            &mut Vec::new(),
+            &AncestorFilter::default(),
+            &mut styles,
+        );
+        self.set_css_rules(&mut styles.into_iter());
+    }
+
+    /// Finds the rules that apply to this element and its children, in the order of
+    /// [Element::set_css_rules]
+    ///
+    /// * `ancestors` - The ancestors of the element, with the parent last
+    fn find_css_rules<'a>(
+        &'a self,
+        rule_index: &RuleIndex,
+        state: &InteractionState,
+        ancestors: &mut Vec<&'a Element>,
+        filter: &AncestorFilter,
+        output: &mut Vec<Vec<Rule>>,
+    ) {
+        output.push(
+            rule_index
+                .candidates(self, filter)
+                .flat_map(|x| x.matching_rules(self, ancestors, state))
+                .cloned()
+                .collect(),
+        );
+        let filter = filter.with_parent(self);
+        ancestors.push(self);
+        for child in &self.children {
+            child.find_css_rules(rule_index, state, ancestors, &filter, output);
+        }
+        ancestors.pop();
+    }
+
+    /// Sets the rules of this element and its children from the ones found by
+    /// [Element::find_css_rules]
+    fn set_css_rules(&mut self, styles: &mut impl Iterator<Item = Vec<Rule>>) {
+        self.styles = styles.next().unwrap_or_default();
+        for child in &mut self.children {
+            child.set_css_rules(styles);
+        }
+    }
+
+    /// Computes the style of this element and its children. The inline styles are applied last so
+    /// they override the styles from the stylesheets.
+    pub fn compute_style(&mut self, parent: Option<&ComputedStyle>) {
+        self.style = ComputedStyle::from_rules(
+            self.element_type,
+            parent,
+            self.styles.iter().chain(self.inner_styles.iter()),
+        );
+        for child in &mut self.children {
+            child.compute_style(Some(&self.style));
+        }
+        if self.element_type == Tag::Details {
+            self.apply_details_state();
+        }
+    }
+
+    /// A closed `<details>` only shows its summary, and the marker of the summary points down
+    /// when the details are open
+    fn apply_details_state(&mut self) {
+        let open = self.attributes.contains_key("open");
+        let summary = self
+            .children
+            .iter()
+            .position(|x| x.element_type == Tag::Summary);
+        for (index, child) in self.children.iter_mut().enumerate() {
+            if Some(index) == summary {
+                if open && child.style.list_style_type == ListStyleType::DisclosureClosed {
+                    child.style.list_style_type = ListStyleType::DisclosureOpen;
+                }
+            } else if !open {
+                child.style.display = DisplayStyle::None;
+            }
+        }
+    }
+
+    /// Replaces the colors of the computed styles of this element and its children with the
+    /// palette. Links and the focused element get colors of their own, so they stay distinct.
+    ///
+    /// * `color` - The text color passed down from the parent element
+    pub fn force_colors(&mut self, palette: &ForcedColors, color: Color, state: &InteractionState) {
+        let color = if state.focused == Some(self.id) {
+            palette.focus
+        } else if self.element_type == Tag::A && self.attributes.contains_key("href") {
+            if state.visited.contains(&self.id) {
+                palette.visited
+            } else {
+                palette.link
+            }
+        } else {
+            color
+        };
+        self.style.color = color;
+        self.style.border_color = color;
+        if self.style.background_color.is_some() {
+            self.style.background_color = Some(palette.background);
+        }
+        for child in &mut self.children {
+            child.force_colors(palette, color, state);
+        }
+    }
+
+    /// Multiplies the font sizes of the computed styles of this element and its children. The
+    /// other lengths stay as they are, except the ones that are relative to the font size.
+    pub fn zoom_text(&mut self, factor: f32) {
+        self.style.font_size *= factor;
+        for child in &mut self.children {
+            child.zoom_text(factor);
+        }
+    }
+
+    /// Zooms the styles of the element and its children, see [ComputedStyle::zoom]
+    pub fn zoom(&mut self, factor: f32) {
+        self.style.zoom(factor);
+        for child in &mut self.children {
+            child.zoom(factor);
+        }
+    }
+
+    /// Returns the first element with the tag, searching this element and its children depth first
+    pub fn find(&self, tag: Tag) -> Option<&Element> {
+        if self.element_type == tag {
+            return Some(self);
+        }
+        self.children.iter().find_map(|x| x.find(tag))
+    }
+
+    /// Returns the first element that passes the check, searching depth first like [Self::find]
+    pub fn find_matching(&self, predicate: &impl Fn(&Element) -> bool) -> Option<&Element> {
+        if predicate(self) {
+            return Some(self);
+        }
+        self.children
+            .iter()
+            .find_map(|x| x.find_matching(predicate))
+    }
+
+    /// Returns the text of the element and all of its children
+    pub fn text_content(&self) -> String {
+        if self.element_type == Tag::PlainText {
+            return self.inner_text.clone();
+        }
+        let texts: Vec<String> = self.children.iter().map(|x| x.text_content()).collect();
+        texts.join(" ")
+    }
+
     pub fn get_attribute(&self, key: &str) -> Option<String> {
         self.attributes.get(key).cloned()
     }
 }
 
-pub fn get_identifier(iter: &mut Peekable<Chars>) -> String {
+pub fn get_identifier(iter: &mut Source) -> String {
     let mut string = String::new();
     while let Some(character) = iter.peek() {
         if character.is_alphabetic()
@@ -217,7 +511,7 @@ pub fn get_identifier(iter: &mut Peekable<Chars>) -> String {
 
 /// Gets text content of an element when the iterator is set inside of it.
 #[allow(dead_code)]
-pub fn get_text(iter: &mut Peekable<Chars>) -> String {
+pub fn get_text(iter: &mut Source) -> String {
     let mut string = String::new();
     while let Some(character) = iter.peek() {
         if character.is_alphabetic() || character.is_numeric() || character.is_whitespace() {
@@ -230,7 +524,7 @@ pub fn get_text(iter: &mut Peekable<Chars>) -> String {
     string
 }
 
-fn parse_element_content(iter: &mut Peekable<Chars>) -> Vec<Element> {
+fn parse_element_content(iter: &mut Source) -> Vec<Element> {
     let mut elements: Vec<Element> = Vec::new();
     while let Some(character) = iter.peek() {
         match character {
@@ -246,12 +540,21 @@ fn parse_element_content(iter: &mut Peekable<Chars>) -> Vec<Element> {
                 None => panic!("Expected character after '<'"),
             },
             c => {
-                if let Some(v) = elements.last_mut() {
-                    v.inner_text.push(*c);
+                // Text after a child element belongs to a new text node, not to the child
+                let c = *c;
+                let start = iter.position;
+                iter.next();
+                if let Some(v) = elements
+                    .last_mut()
+                    .filter(|x| x.element_type == Tag::PlainText)
+                {
+                    v.inner_text.push(c);
+                    v.source_span.end = iter.position;
                 } else {
-                    elements.push(Element::new_with_text(Tag::PlainText, &c.to_string()));
+                    let mut text = Element::new_with_text(Tag::PlainText, &c.to_string());
+                    text.source_span = start..iter.position;
+                    elements.push(text);
                 }
-                iter.next();
             }
         }
     }
@@ -278,7 +581,7 @@ fn is_quotation(character: char) -> bool {
 }
 
 /// Gets a string without the quotation marks
-fn get_string(iter: &mut Peekable<Chars>) -> String {
+fn get_string(iter: &mut Source) -> String {
     assert!(is_quotation(iter.next().unwrap()));
     let mut output = String::new();
     loop {
@@ -291,7 +594,7 @@ fn get_string(iter: &mut Peekable<Chars>) -> String {
     output
 }
 
-fn parse_attributes(iter: &mut Peekable<Chars>) -> HashMap<String, String> {
+fn parse_attributes(iter: &mut Source) -> HashMap<String, String> {
     let mut output = HashMap::<String, String>::new();
     loop {
         match iter.peek() {
@@ -349,7 +652,15 @@ fn parse_attributes(iter: &mut Peekable<Chars>) -> HashMap<String, String> {
     output
 }
 
-fn parse_html_element(iter: &mut Peekable<Chars>) -> Element {
+fn parse_html_element(iter: &mut Source) -> Element {
+    let start = iter.position;
+    let mut element = parse_html_tag(iter);
+    element.source_span = start..iter.position;
+    element
+}
+
+/// Parses the element that starts at the iterator, see [parse_html_element]
+fn parse_html_tag(iter: &mut Source) -> Element {
     assert!(
         iter.next_if_eq(&'<').is_some(),
         "Expected 'Some('<')' Got: '{:?}'",
@@ -367,6 +678,7 @@ fn parse_html_element(iter: &mut Peekable<Chars>) -> Element {
         || tag == "br"
         || tag == "hr"
         || tag == "source"
+        || tag == "track"
     {
         if iter.peek() == Some(&'/') {
             iter.next();
@@ -425,7 +737,7 @@ fn parse_html_element(iter: &mut Peekable<Chars>) -> Element {
     element
 }
 
-fn parse_html_iter(iter: &mut Peekable<Chars>) -> Vec<Element> {
+fn parse_html_iter(iter: &mut Source) -> Vec<Element> {
     let mut elements = Vec::new();
 
     loop {
@@ -455,45 +767,102 @@ fn parse_html_iter(iter: &mut Peekable<Chars>) -> Vec<Element> {
     elements
 }
 
-fn remove_comments(code: &str) -> String {
-    let mut output = String::new();
-    let mut iter = code.chars().peekable();
+/// The html that is being parsed. It's used like a [std::iter::Peekable] iterator over the
+/// characters, but it also knows where it is in the html, so the elements can record their
+/// [Element::source_span].
+#[derive(Debug, Clone)]
+pub struct Source<'a> {
+    code: &'a str,
+    /// The byte offset of the next character
+    position: usize,
+    peeked: Option<char>,
+}
 
-    loop {
-        match iter.next() {
-            Some('<') => {
-                if iter.clone().next() == Some('!')
-                    && iter.clone().nth(1) == Some('-')
-                    && iter.clone().nth(2) == Some('-')
-                {
-                    while let Some(v) = iter.peek() {
-                        if *v == '-' {
-                            if iter.next() == Some('-')
-                                && iter.next() == Some('-')
-                                && iter.next() == Some('>')
-                            {
-                                break;
-                            }
-                        } else {
-                            iter.next();
-                        }
-                    }
-                } else {
-                    output.push('<');
-                }
-            }
+impl<'a> Source<'a> {
+    fn new(code: &'a str) -> Self {
+        Self {
+            code,
+            position: 0,
+            peeked: None,
+        }
+    }
 
-            Some(v) => output.push(v),
+    /// Returns the next character without moving past it
+    pub fn peek(&mut self) -> Option<&char> {
+        self.peeked = self.code[self.position..].chars().next();
+        self.peeked.as_ref()
+    }
 
-            None => break,
+    /// Moves past the next character if it's the expected one
+    pub fn next_if_eq(&mut self, expected: &char) -> Option<char> {
+        if self.peek() == Some(expected) {
+            self.next()
+        } else {
+            None
         }
     }
+}
+
+impl Iterator for Source<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.code[self.position..].chars().next()?;
+        self.position += character.len_utf8();
+        Some(character)
+    }
+}
 
-    output
+/// Removes the comments from the html. The positions where comments were removed are returned
+/// with the lengths of the comments, so the positions in the code without the comments can be
+/// turned back into positions in the original html.
+fn remove_comments(code: &str) -> (String, Vec<(usize, usize)>) {
+    let mut output = String::new();
+    let mut removed = Vec::new();
+    let mut rest = code;
+    while let Some(start) = rest.find("<!--") {
+        output += &rest[..start];
+        // A comment that isn't closed goes on until the end of the html
+        let end = rest[start..]
+            .find("-->")
+            .map_or(rest.len(), |x| start + x + "-->".len());
+        removed.push((output.len(), end - start));
+        rest = &rest[end..];
+    }
+    output += rest;
+    (output, removed)
+}
+
+/// Moves the source spans of the elements from the code without comments to the original html
+///
+/// * `removed` - The removed comments, see [remove_comments]
+fn restore_source_spans(element: &mut Element, removed: &[(usize, usize)]) {
+    // The comments right before the element aren't a part of it, and neither are the ones right
+    // after it
+    let removed_before = |position: usize, inclusive: bool| -> usize {
+        removed
+            .iter()
+            .filter(|(x, _)| *x < position || (inclusive && *x == position))
+            .map(|(_, length)| length)
+            .sum()
+    };
+    let Range { start, end } = element.source_span;
+    if start != end {
+        element.source_span = start + removed_before(start, true)..end + removed_before(end, false);
+    }
+    for child in &mut element.children {
+        restore_source_spans(child, removed);
+    }
 }
 
 pub fn parse_html(html: &str) -> Vec<Element> {
-    let code = remove_comments(html);
+    let (code, removed) = remove_comments(html);
     println!("{}", code);
-    parse_html_iter(&mut code.chars().peekable())
+    let mut elements = parse_html_iter(&mut Source::new(&code));
+    if !removed.is_empty() {
+        for element in &mut elements {
+            restore_source_spans(element, &removed);
+        }
+    }
+    elements
 }
@@ -0,0 +1,50 @@
+//! The icon of the browser window. The window shows the favicon of the active page, or the icon
+//! of the browser when the page doesn't have one.
+
+use image::RgbaImage;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use web::requests::get_bytes;
+use winit::window::Icon;
+
+/// Desktops use this to match the window with its `.desktop` file, so the right icon and name are
+/// shown in the taskbar
+pub const APP_ID: &str = "web-browser";
+
+const APP_ICON: &[u8] = include_bytes!("../textures/icon.png");
+
+/// Decodes an image in any of the formats favicons come in, such as png and ico
+pub fn decode_icon(bytes: &[u8]) -> Option<RgbaImage> {
+    image::load_from_memory(bytes).ok().map(|x| x.into_rgba8())
+}
+
+pub fn app_icon() -> RgbaImage {
+    decode_icon(APP_ICON).unwrap()
+}
+
+/// Fetches the favicon from the url. Returns [None] if it can't be fetched or decoded.
+///
+/// * `referrer` - The value of the `Referer` header
+pub fn fetch_favicon(url: &str, referrer: Option<&str>) -> Option<RgbaImage> {
+    decode_icon(&get_bytes(url, referrer)?)
+}
+
+/// Fetches and decodes the favicon on another thread, so a slow server doesn't freeze the window.
+/// The favicon is sent through the returned channel, see [fetch_favicon].
+pub fn fetch_favicon_in_background(
+    url: &str,
+    referrer: Option<&str>,
+) -> Receiver<Option<RgbaImage>> {
+    let (sender, receiver) = mpsc::channel();
+    let url = url.to_owned();
+    let referrer = referrer.map(|x| x.to_owned());
+    thread::spawn(move || {
+        // Nobody is waiting for the favicon anymore if the tab was closed or navigated elsewhere
+        let _ = sender.send(fetch_favicon(&url, referrer.as_deref()));
+    });
+    receiver
+}
+
+pub fn to_window_icon(image: &RgbaImage) -> Option<Icon> {
+    Icon::from_rgba(image.as_raw().clone(), image.width(), image.height()).ok()
+}
@@ -0,0 +1,96 @@
+//! Subresource integrity. A `<link>` or a `<script>` can have an `integrity` attribute with the
+//! hashes its file has to have, so a file that was changed on the server isn't used. Scripts
+//! aren't run, so only the stylesheets are checked for now.
+
+use crate::base64;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// The hash functions of the `integrity` attribute, from the weakest to the strongest
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Algorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Algorithm {
+    fn from_string(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Some(Self::Sha256),
+            "sha384" => Some(Self::Sha384),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Returns the hash of the body in base64
+    fn hash(self, body: &[u8]) -> String {
+        match self {
+            Self::Sha256 => base64::encode(&Sha256::digest(body)),
+            Self::Sha384 => base64::encode(&Sha384::digest(body)),
+            Self::Sha512 => base64::encode(&Sha512::digest(body)),
+        }
+    }
+}
+
+/// Checks the body against the `integrity` attribute, which is a list of `algorithm-hash` entries
+/// separated by whitespace. Only the entries of the strongest algorithm in the list count, and the
+/// body has to match one of them. Entries with unknown algorithms are skipped, and a list without
+/// any known ones lets every body through, like in the other browsers.
+pub fn matches(body: &[u8], integrity: &str) -> bool {
+    let entries: Vec<(Algorithm, &str)> = integrity
+        .split_whitespace()
+        .filter_map(|x| {
+            let (algorithm, hash) = x.split_once('-')?;
+            // Options can follow the hash after a `?`, but none are defined yet
+            let hash = hash.split('?').next().unwrap_or(hash);
+            Some((Algorithm::from_string(algorithm)?, hash))
+        })
+        .collect();
+    let Some(strongest) = entries.iter().map(|x| x.0).max() else {
+        return true;
+    };
+    let actual = strongest.hash(body);
+    entries
+        .iter()
+        .any(|(algorithm, hash)| *algorithm == strongest && *hash == actual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "sha256-ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=";
+    const SHA384: &str = "sha384-ywB1P0WjXou1oD1pmsZQBycsMqsO3tFjGotgWkP/W+2AhgcroefMI1i67KE0yCWn";
+
+    #[test]
+    fn matches_known_digests() {
+        assert!(matches(b"abc", SHA256));
+        assert!(matches(b"abc", SHA384));
+        assert!(!matches(b"abd", SHA256));
+        assert!(!matches(b"abd", SHA384));
+        // The body has to match one of the entries
+        assert!(matches(b"abc", &format!("sha256-wrong {SHA256}")));
+    }
+
+    #[test]
+    fn only_the_strongest_algorithm_counts() {
+        assert!(matches(b"abc", &format!("{SHA256} {SHA384}")));
+        assert!(!matches(b"abc", &format!("{SHA256} sha384-wrong")));
+        assert!(!matches(b"abc", &format!("{SHA384} sha512-wrong")));
+    }
+
+    #[test]
+    fn skips_unknown_algorithms() {
+        assert!(matches(b"abc", "md5-wrong"));
+        assert!(matches(b"abc", ""));
+        assert!(matches(b"abc", &format!("{SHA256} sha1024-wrong")));
+        assert!(!matches(b"abd", &format!("{SHA256} md5-wrong")));
+    }
+
+    #[test]
+    fn strips_options() {
+        assert!(matches(b"abc", &format!("{SHA256}?foo")));
+        assert!(!matches(b"abd", &format!("{SHA256}?foo")));
+    }
+}
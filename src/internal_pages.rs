@@ -0,0 +1,66 @@
+//! The `about:history` and `about:downloads` pages, which the browser generates from its own data.
+//! They are shown with the plain text viewer like `about:websocket`.
+
+use crate::downloads::{DownloadState, Downloads};
+use crate::format::{format_date_time, format_size, Locale};
+use crate::history::History;
+
+pub const HISTORY_PAGE_URL: &str = "about:history";
+pub const DOWNLOADS_PAGE_URL: &str = "about:downloads";
+
+/// Lists the visited pages with the latest visit first
+pub fn history_page(history: &History, locale: &Locale) -> String {
+    let mut entries: Vec<_> = history.iter().collect();
+    entries.sort_by_key(|x| std::cmp::Reverse(x.last_visit));
+
+    let mut output = String::from("History\n\n");
+    if entries.is_empty() {
+        output += "No pages have been visited yet\n";
+    }
+    for entry in entries {
+        let visits = match entry.visit_count {
+            1 => "1 visit".to_owned(),
+            v => format!("{} visits", v),
+        };
+        output += &format!(
+            "{}  {}  {}\n    {}\n",
+            format_date_time(entry.last_visit, locale),
+            visits,
+            entry.title.as_deref().unwrap_or(&entry.url),
+            entry.url
+        );
+    }
+    output
+}
+
+/// Lists the downloads with how much of them has been received
+pub fn downloads_page(downloads: &Downloads, locale: &Locale) -> String {
+    let mut output = String::from("Downloads\n\n");
+    if downloads.iter().next().is_none() {
+        output += "Nothing has been downloaded yet\n";
+    }
+    for download in downloads.iter() {
+        let size = match (download.state, download.total) {
+            (DownloadState::Finished, _) => format_size(download.received, locale),
+            (_, Some(total)) => format!(
+                "{} of {}",
+                format_size(download.received, locale),
+                format_size(total, locale)
+            ),
+            (_, None) => format_size(download.received, locale),
+        };
+        let state = match download.state {
+            DownloadState::InProgress => "downloading",
+            DownloadState::Interrupted => "interrupted",
+            DownloadState::Finished => "finished",
+        };
+        output += &format!(
+            "{}  {}  {}\n    {}\n",
+            download.file_name(),
+            size,
+            state,
+            download.url
+        );
+    }
+    output
+}
@@ -0,0 +1,250 @@
+//! The keyboard shortcuts of the browser. Every binding is listed here with a description, so the
+//! key handling and the help overlay that is opened with `?` can't get out of sync.
+
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+/// Something the user can do with the keyboard
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Action {
+    ScrollDown,
+    ScrollUp,
+    ScrollPageDown,
+    ScrollPageUp,
+    ScrollToTop,
+    ScrollToBottom,
+    /// Only does something on pages that are wider than the window
+    ScrollLeft,
+    ScrollRight,
+    /// Only does something in the plain text viewer
+    ToggleLineWrap,
+    /// Only does something in the plain text viewer
+    ToggleLineNumbers,
+    ToggleHelp,
+    CloseHelp,
+    TogglePageInfo,
+    ToggleReduceMotion,
+    ToggleForcedColors,
+    /// Puts labels on the links, see [crate::hints]
+    ShowHints,
+    /// The next letter that is typed is the name of the mark
+    SetMark,
+    JumpToMark,
+    /// Opens the html of the page in a new tab, with the element under the mouse highlighted
+    ViewSource,
+    /// Makes only the text bigger, the images and the widths of the boxes stay the same
+    TextZoomIn,
+    TextZoomOut,
+    ResetTextZoom,
+    /// Makes the whole page bigger, the images and the boxes too. The zoom is remembered for
+    /// every site, see [crate::zoom].
+    PageZoomIn,
+    PageZoomOut,
+    ResetPageZoom,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    /// Copies the url of the page to the clipboard and the primary selection
+    Copy,
+    /// Pastes the clipboard to the end of the url bar
+    Paste,
+}
+
+/// A key and the modifiers that have to be held down with it
+pub struct Binding {
+    pub key: Key<&'static str>,
+    pub control: bool,
+    pub shift: bool,
+    pub action: Action,
+    /// Shown in the help overlay
+    pub description: &'static str,
+}
+
+impl Binding {
+    const fn new(key: Key<&'static str>, action: Action, description: &'static str) -> Self {
+        Self {
+            key,
+            control: false,
+            shift: false,
+            action,
+            description,
+        }
+    }
+
+    const fn control(mut self) -> Self {
+        self.control = true;
+        self
+    }
+
+    const fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    /// Checks if the key press triggers this binding. The shift key is already a part of the
+    /// characters, such as `?`, so it only matters for the named keys.
+    pub fn matches(&self, key: &Key, modifiers: ModifiersState) -> bool {
+        let shift_matches = match self.key {
+            Key::Named(_) => self.shift == modifiers.shift_key(),
+            _ => true,
+        };
+        self.key == key.as_ref() && self.control == modifiers.control_key() && shift_matches
+    }
+
+    /// Returns the keys as they are written in the help overlay, such as `Ctrl+Shift+Tab`
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.control {
+            label += "Ctrl+";
+        }
+        if self.shift {
+            label += "Shift+";
+        }
+        match self.key {
+            Key::Character(v) => label += v,
+            Key::Named(v) => label += &format!("{:?}", v),
+            _ => (),
+        }
+        label
+    }
+}
+
+/// The bindings in the order they are listed in the help overlay
+pub const KEYMAP: [Binding; 34] = [
+    Binding::new(Key::Character("j"), Action::ScrollDown, "Scroll down"),
+    Binding::new(Key::Character("k"), Action::ScrollUp, "Scroll up"),
+    Binding::new(Key::Character("h"), Action::ScrollLeft, "Scroll left"),
+    Binding::new(Key::Character("l"), Action::ScrollRight, "Scroll right"),
+    Binding::new(
+        Key::Character("d"),
+        Action::ScrollPageUp,
+        "Scroll up by a screen",
+    ),
+    Binding::new(
+        Key::Character("u"),
+        Action::ScrollPageDown,
+        "Scroll down by a screen",
+    ),
+    Binding::new(
+        Key::Named(NamedKey::PageDown),
+        Action::ScrollPageDown,
+        "Scroll down by a screen",
+    ),
+    Binding::new(
+        Key::Named(NamedKey::PageUp),
+        Action::ScrollPageUp,
+        "Scroll up by a screen",
+    ),
+    Binding::new(
+        Key::Named(NamedKey::Home),
+        Action::ScrollToTop,
+        "Scroll to the top of the page",
+    ),
+    Binding::new(
+        Key::Named(NamedKey::End),
+        Action::ScrollToBottom,
+        "Scroll to the bottom of the page",
+    ),
+    Binding::new(
+        Key::Character("w"),
+        Action::ToggleLineWrap,
+        "Wrap long lines of plain text",
+    ),
+    Binding::new(
+        Key::Character("n"),
+        Action::ToggleLineNumbers,
+        "Show line numbers in plain text",
+    ),
+    Binding::new(
+        Key::Character("f"),
+        Action::ShowHints,
+        "Follow a link by typing its label, uppercase opens a new tab",
+    ),
+    Binding::new(
+        Key::Character("m"),
+        Action::SetMark,
+        "Save the scroll position, m and a letter",
+    ),
+    Binding::new(
+        Key::Character("'"),
+        Action::JumpToMark,
+        "Jump to a saved position, ' and its letter",
+    ),
+    Binding::new(Key::Character("?"), Action::ToggleHelp, "Show this help"),
+    Binding::new(
+        Key::Named(NamedKey::Escape),
+        Action::CloseHelp,
+        "Close this help",
+    ),
+    Binding::new(
+        Key::Character("u"),
+        Action::ViewSource,
+        "View the source of the element under the mouse",
+    )
+    .control(),
+    Binding::new(Key::Character("t"), Action::NewTab, "Open a new tab").control(),
+    Binding::new(Key::Character("w"), Action::CloseTab, "Close the tab").control(),
+    Binding::new(
+        Key::Named(NamedKey::Tab),
+        Action::NextTab,
+        "Switch to the next tab",
+    )
+    .control(),
+    Binding::new(
+        Key::Named(NamedKey::Tab),
+        Action::PreviousTab,
+        "Switch to the previous tab",
+    )
+    .control()
+    .shift(),
+    Binding::new(
+        Key::Character("i"),
+        Action::TogglePageInfo,
+        "Show information about the page",
+    )
+    .control(),
+    Binding::new(
+        Key::Character("m"),
+        Action::ToggleReduceMotion,
+        "Turn animations on or off",
+    )
+    .control(),
+    Binding::new(
+        Key::Character("h"),
+        Action::ToggleForcedColors,
+        "Turn the forced colors on or off",
+    )
+    .control(),
+    Binding::new(
+        Key::Character("]"),
+        Action::TextZoomIn,
+        "Make the text bigger",
+    )
+    .control(),
+    Binding::new(
+        Key::Character("["),
+        Action::TextZoomOut,
+        "Make the text smaller",
+    )
+    .control(),
+    Binding::new(
+        Key::Character("\\"),
+        Action::ResetTextZoom,
+        "Reset the text size",
+    )
+    .control(),
+    Binding::new(Key::Character("="), Action::PageZoomIn, "Zoom in").control(),
+    Binding::new(Key::Character("+"), Action::PageZoomIn, "Zoom in").control(),
+    Binding::new(Key::Character("-"), Action::PageZoomOut, "Zoom out").control(),
+    Binding::new(Key::Character("0"), Action::ResetPageZoom, "Reset the zoom").control(),
+    Binding::new(Key::Character("c"), Action::Copy, "Copy the url").control(),
+    Binding::new(Key::Character("v"), Action::Paste, "Paste into the url bar").control(),
+];
+
+/// Returns the action that the key press is bound to
+pub fn action_for(key: &Key, modifiers: ModifiersState) -> Option<Action> {
+    KEYMAP
+        .iter()
+        .find(|x| x.matches(key, modifiers))
+        .map(|x| x.action)
+}
@@ -0,0 +1,32 @@
+//! The engine of the browser: parsing, styling and laying out pages and fetching them. The
+//! window and the rest of the user interface are in the binary, and other programs can drive the
+//! engine through [browser::Browser].
+
+pub mod archive;
+pub mod base64;
+pub mod bound;
+pub mod browser;
+pub mod calc;
+pub mod color;
+pub mod css;
+pub mod diff;
+pub mod display_list;
+pub mod document;
+pub mod font;
+pub mod gauge;
+pub mod gradient;
+pub mod html;
+pub mod integrity;
+pub mod media;
+pub mod media_placeholder;
+pub mod plain_text;
+pub mod referrer;
+pub mod render_backend;
+pub mod render_layout;
+pub mod requests;
+pub mod rule_index;
+pub mod style;
+pub mod system_fonts;
+pub mod tab;
+pub mod transform;
+pub mod transition;
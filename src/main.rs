@@ -1,54 +1,105 @@
-use color::Color;
-use font::Font;
-use html::parse_html;
-use requests::get_site;
-use std::{fs::File, io::prelude::Read, path::Path};
+use std::path::Path;
+use web::archive::Archive;
+use web::browser::Browser;
+use web::color::Color;
+use web::diff::diff_pages;
+use web::font::{bundled_font_directory, FontRegistry};
+use web::render_layout::Size;
+use web::requests::{get_file, get_site};
+use web::system_fonts::SystemFonts;
 use window::Window;
 
-mod bound;
-mod color;
-mod css;
-mod document;
-mod font;
-mod html;
-mod render_layout;
-mod requests;
+mod clipboard;
+mod downloads;
+mod dropdown;
+mod format;
+mod hints;
+mod history;
+mod icon;
+mod internal_pages;
+mod keymap;
+mod migration;
+mod scroll_animation;
+mod scrollbar;
+mod session;
+mod settings;
+mod toast;
+mod visited;
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(feature = "websocket")]
+mod websocket_page;
 mod window;
+mod zoom;
 
-fn read_file(path: &Path) -> String {
-    let mut file = File::open(path).unwrap();
-    let mut content = String::new();
-    file.read_to_string(&mut content).unwrap();
-    content
-}
+/// The width and the height of the viewport that `--screenshot` lays the page out for
+const SCREENSHOT_SIZE: (i32, i32) = (1024, 768);
 
 fn from_file(path: &Path) {
-    let website_code = read_file(path);
-    let elements = parse_html(&website_code);
     let mut window = Window::new();
-    window.render(elements);
+    window.render_response(&get_file(path));
 }
 
 fn from_web(path: &str) {
-    let website_code = get_site(path);
-    let elements = parse_html(&website_code);
+    let response = get_site(path);
+    let mut window = Window::new();
+    window.render_response(&response);
+}
+
+/// Saves the page and its resources into a single file, which can be opened with `file://`
+fn save_archive(url: &str, path: &Path) {
+    let archive = Archive::fetch(url);
+    std::fs::write(path, archive.serialize()).unwrap();
+    println!("Saved {} into {}", url, path.display());
+}
+
+/// Shows the differences between the text of the two pages
+fn diff(old_url: &str, new_url: &str) {
+    let old = get_site(old_url);
+    let new = get_site(new_url);
     let mut window = Window::new();
-    window.render(elements);
+    window.render(diff_pages(old_url, &old, new_url, &new));
 }
 
 fn render_text(text: &str) {
-    let font = Font::load(Path::new(
-        "./fonts/liberation-sans/LiberationSans-Regular.ttf",
-    ))
-    .unwrap();
-    let image = font.render_string(text, 20.0, Color::black());
+    let fonts = FontRegistry::load(&bundled_font_directory(), &SystemFonts::default()).unwrap();
+    let image = fonts
+        .default_font()
+        .scaled(20.0)
+        .render_string(text, Color::black());
     image.save("output.png").unwrap();
 }
 
+/// Draws the page into a PNG file without opening a window, at the size of
+/// [SCREENSHOT_SIZE]
+fn screenshot(url: &str, path: &Path) {
+    let fonts = FontRegistry::load(&bundled_font_directory(), &SystemFonts::discover()).unwrap();
+    let mut browser = Browser::new();
+    browser.navigate(url);
+    if let Err(error) = browser.wait() {
+        println!("Couldn't load {}: {}", url, error);
+        std::process::exit(1);
+    }
+    let (width, height) = SCREENSHOT_SIZE;
+    let image = browser.render_to_image(Size::new(width, height), &fonts);
+    image.save(path).unwrap();
+    println!("Saved a screenshot of {} into {}", url, path.display());
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
-    if args.len() == 3 {
+    if args.len() == 4 && args[1] == "--diff" {
+        diff(&args[2], &args[3]);
+    } else if args.len() == 4 && args[1] == "--screenshot" {
+        screenshot(&args[2], Path::new(&args[3]));
+    } else if args.len() == 4 && args[1] == "--save-archive" {
+        save_archive(&args[2], Path::new(&args[3]));
+    } else if args.len() == 2 && args[1] == "--software-rendering" {
+        let mut window = Window::new();
+        window.use_software_rendering();
+        window.open_to_home_page();
+    } else if args.len() == 3 {
         if args[1] == "--from-file" {
             from_file(Path::new(&args[2]));
         } else if args[1] == "--from-web" {
@@ -0,0 +1,309 @@
+//! Media queries, which make parts of a stylesheet apply only to some kinds of windows.
+//!
+//! ## Example
+//!
+//! ```css
+//! @media screen and (max-width: 600px) {
+//!     p { margin: 0; }
+//! }
+//! ```
+
+use crate::css::Unit;
+use crate::render_layout::DEFAULT_FONT_SIZE;
+use crate::style::ForcedColors;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// The properties of the window that media queries are evaluated against
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MediaEnvironment {
+    pub width: i32,
+    pub color_scheme: ColorScheme,
+    /// Set when the user has asked for animations to be turned off
+    pub reduced_motion: bool,
+    /// The palette the colors of the page are replaced with, if the forced colors mode is on
+    pub forced_colors: Option<ForcedColors>,
+    /// What the font sizes of the page are multiplied with. It isn't a media feature, but the
+    /// styles have to be computed again when it changes, like with the others.
+    pub text_zoom: f32,
+    /// What the font sizes and the lengths of the page are multiplied with, see
+    /// [crate::style::ComputedStyle::zoom]. The width should already be divided by it, since the
+    /// page sees the window as smaller when it's zoomed in.
+    pub page_zoom: f32,
+}
+
+/// A single condition inside of parentheses, such as `(min-width: 600px)`
+#[derive(Debug, Clone)]
+pub enum MediaFeature {
+    MinWidth(Unit),
+    MaxWidth(Unit),
+    PrefersColorScheme(ColorScheme),
+    /// True for `reduce` and false for `no-preference`
+    PrefersReducedMotion(bool),
+    /// True for `active` and false for `none`
+    ForcedColors(bool),
+}
+
+/// Parses lengths used in media features. Relative units are relative to the default font size,
+/// since there is no element to take the font size from.
+fn parse_length(value: &str) -> Option<Unit> {
+    if value == "0" {
+        Some(Unit::Px(0))
+    } else if let Some(v) = value.strip_suffix("px") {
+        v.parse::<f32>().ok().map(|v| Unit::Px(v as i32))
+    } else if let Some(v) = value.strip_suffix("rem").or(value.strip_suffix("em")) {
+        v.parse::<f32>().ok().map(Unit::Em)
+    } else {
+        None
+    }
+}
+
+impl MediaFeature {
+    /// Parses the feature from the text between the parentheses. Returns [None] if the feature
+    /// isn't supported.
+    fn parse(feature: &str) -> Option<Self> {
+        let (name, value) = feature.split_once(':')?;
+        match (name.trim(), value.trim()) {
+            ("min-width", v) => parse_length(v).map(Self::MinWidth),
+            ("max-width", v) => parse_length(v).map(Self::MaxWidth),
+            ("prefers-color-scheme", "light") => Some(Self::PrefersColorScheme(ColorScheme::Light)),
+            ("prefers-color-scheme", "dark") => Some(Self::PrefersColorScheme(ColorScheme::Dark)),
+            ("prefers-reduced-motion", "reduce") => Some(Self::PrefersReducedMotion(true)),
+            ("prefers-reduced-motion", "no-preference") => Some(Self::PrefersReducedMotion(false)),
+            ("forced-colors", "active") => Some(Self::ForcedColors(true)),
+            ("forced-colors", "none") => Some(Self::ForcedColors(false)),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, environment: &MediaEnvironment) -> bool {
+        let width = environment.width;
+        match self {
+            Self::MinWidth(v) => width >= v.to_px(DEFAULT_FONT_SIZE, width),
+            Self::MaxWidth(v) => width <= v.to_px(DEFAULT_FONT_SIZE, width),
+            Self::PrefersColorScheme(v) => environment.color_scheme == *v,
+            Self::PrefersReducedMotion(v) => environment.reduced_motion == *v,
+            Self::ForcedColors(v) => environment.forced_colors.is_some() == *v,
+        }
+    }
+}
+
+/// A single query of a media query list, such as `not screen and (min-width: 600px)`
+#[derive(Debug, Clone)]
+pub struct MediaQuery {
+    negated: bool,
+    media_type_matches: bool,
+    features: Vec<MediaFeature>,
+}
+
+impl MediaQuery {
+    /// A query that never matches. Unsupported queries are turned into this, so the rules behind
+    /// them don't get applied by accident.
+    fn never() -> Self {
+        Self {
+            negated: false,
+            media_type_matches: false,
+            features: Vec::new(),
+        }
+    }
+
+    fn parse(query: &str) -> Self {
+        // Split the query into words, keeping the features in parentheses as single tokens
+        let mut tokens = Vec::new();
+        let mut iterator = query.chars().peekable();
+        while let Some(character) = iterator.next() {
+            if character.is_whitespace() {
+                continue;
+            }
+            let mut token = character.to_string();
+            while let Some(&v) = iterator.peek() {
+                let end = if character == '(' {
+                    token.ends_with(')')
+                } else {
+                    v.is_whitespace() || v == '('
+                };
+                if end {
+                    break;
+                }
+                token.push(v);
+                iterator.next();
+            }
+            tokens.push(token);
+        }
+
+        let mut tokens = tokens.iter().map(|x| x.as_str()).peekable();
+        let negated = tokens.next_if_eq(&"not").is_some();
+        tokens.next_if_eq(&"only");
+        let media_type_matches = match tokens.peek() {
+            Some(v) if v.starts_with('(') => true,
+            Some(&"all") | Some(&"screen") => {
+                tokens.next();
+                true
+            }
+            Some(_) => {
+                tokens.next();
+                false
+            }
+            None => return Self::never(),
+        };
+
+        let mut features = Vec::new();
+        let mut first = true;
+        while let Some(token) = tokens.next() {
+            // Every feature after the media type or the first feature is joined with `and`
+            let token = if first && token.starts_with('(') {
+                token
+            } else if token == "and" {
+                match tokens.next() {
+                    Some(v) => v,
+                    None => return Self::never(),
+                }
+            } else {
+                println!("Unsupported media query: {}", query);
+                return Self::never();
+            };
+            first = false;
+
+            let Some(feature) = token
+                .strip_prefix('(')
+                .and_then(|x| x.strip_suffix(')'))
+                .and_then(MediaFeature::parse)
+            else {
+                println!("Unsupported media feature: {}", token);
+                return Self::never();
+            };
+            features.push(feature);
+        }
+
+        Self {
+            negated,
+            media_type_matches,
+            features,
+        }
+    }
+
+    fn matches(&self, environment: &MediaEnvironment) -> bool {
+        let matches =
+            self.media_type_matches && self.features.iter().all(|x| x.matches(environment));
+        matches != self.negated
+    }
+}
+
+/// A comma separated list of media queries. The list matches if any of its queries match.
+#[derive(Debug, Clone)]
+pub struct MediaQueryList {
+    queries: Vec<MediaQuery>,
+}
+
+impl MediaQueryList {
+    pub fn parse(list: &str) -> Self {
+        let list = list.trim().to_lowercase();
+        if list.is_empty() {
+            return Self {
+                queries: Vec::new(),
+            };
+        }
+        Self {
+            queries: list.split(',').map(MediaQuery::parse).collect(),
+        }
+    }
+
+    pub fn matches(&self, environment: &MediaEnvironment) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|x| x.matches(environment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn environment(width: i32) -> MediaEnvironment {
+        MediaEnvironment {
+            width,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            forced_colors: None,
+            text_zoom: 1.0,
+            page_zoom: 1.0,
+        }
+    }
+
+    fn matches(list: &str, environment: &MediaEnvironment) -> bool {
+        MediaQueryList::parse(list).matches(environment)
+    }
+
+    #[test]
+    fn widths_include_their_boundary() {
+        assert!(matches("(min-width: 600px)", &environment(600)));
+        assert!(!matches("(min-width: 600px)", &environment(599)));
+        assert!(matches("(max-width: 600px)", &environment(600)));
+        assert!(!matches("(max-width: 600px)", &environment(601)));
+        // Relative units are relative to the default font size
+        let em = DEFAULT_FONT_SIZE as i32;
+        assert!(matches("(min-width: 10em)", &environment(10 * em)));
+        assert!(!matches("(min-width: 10em)", &environment(10 * em - 1)));
+    }
+
+    #[test]
+    fn every_feature_joined_with_and_has_to_match() {
+        let query = "screen and (min-width: 400px) and (max-width: 800px)";
+        assert!(matches(query, &environment(400)));
+        assert!(matches(query, &environment(800)));
+        assert!(!matches(query, &environment(399)));
+        assert!(!matches(query, &environment(801)));
+        assert!(matches(
+            "(min-width: 400px) and (max-width: 800px)",
+            &environment(600)
+        ));
+        // Other media types, such as print, never match a window
+        assert!(!matches("print and (min-width: 400px)", &environment(600)));
+    }
+
+    #[test]
+    fn any_query_of_a_comma_separated_list_can_match() {
+        let list = "(max-width: 300px), (min-width: 900px)";
+        assert!(matches(list, &environment(300)));
+        assert!(matches(list, &environment(900)));
+        assert!(!matches(list, &environment(600)));
+        // An empty list matches everything
+        assert!(matches("", &environment(600)));
+    }
+
+    #[test]
+    fn not_negates_the_whole_query() {
+        assert!(matches("not print", &environment(600)));
+        assert!(!matches("not screen", &environment(600)));
+        let query = "not screen and (max-width: 600px)";
+        assert!(!matches(query, &environment(500)));
+        assert!(matches(query, &environment(700)));
+    }
+
+    #[test]
+    fn matches_the_preferred_color_scheme() {
+        let light = environment(600);
+        let dark = MediaEnvironment {
+            color_scheme: ColorScheme::Dark,
+            ..light
+        };
+        assert!(matches("(prefers-color-scheme: light)", &light));
+        assert!(!matches("(prefers-color-scheme: light)", &dark));
+        assert!(matches("(prefers-color-scheme: dark)", &dark));
+        assert!(!matches("(prefers-color-scheme: dark)", &light));
+    }
+
+    #[test]
+    fn unknown_features_never_match() {
+        let environment = environment(600);
+        assert!(!matches("(hover: hover)", &environment));
+        assert!(!matches("(prefers-color-scheme: sepia)", &environment));
+        assert!(!matches("(min-width: 10vw)", &environment));
+        // Negating an unknown feature doesn't make it match either
+        assert!(!matches("not (hover: hover)", &environment));
+        // The other queries of the list still count
+        assert!(matches("(hover: hover), (min-width: 0)", &environment));
+    }
+}
@@ -0,0 +1,51 @@
+//! `<audio>` and `<video>`. They can't be played yet, so they are drawn as a box with the poster
+//! of the video and an empty controls bar, since the duration of the media isn't known. Clicking
+//! the box opens the media in another program.
+
+use crate::html::{Element, Tag};
+
+/// What is shown in the place of a media element
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPlaceholder {
+    /// The url of the `poster` of a video
+    pub poster: Option<String>,
+    /// The url of the media, from `src` or the first `<source>` that has one
+    pub source: Option<String>,
+}
+
+impl MediaPlaceholder {
+    /// The text shown in the box, which is the file name of the media
+    pub fn label(&self) -> String {
+        let Some(source) = self.source.as_deref() else {
+            return "No media source".to_owned();
+        };
+        let path = source.split(['?', '#']).next().unwrap_or(source);
+        let name = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|x| !x.is_empty())
+            .unwrap_or(path);
+        format!("\u{25BA} {}", name)
+    }
+}
+
+/// Returns the placeholder of an `<audio>` or a `<video>`. Returns [None] for the other elements.
+pub fn placeholder_of(element: &Element) -> Option<MediaPlaceholder> {
+    if !matches!(element.element_type, Tag::Audio | Tag::Video) {
+        return None;
+    }
+    let source = element.get_attribute("src").or_else(|| {
+        element
+            .children
+            .iter()
+            .filter(|x| x.element_type == Tag::Source)
+            .find_map(|x| x.get_attribute("src"))
+    });
+    Some(MediaPlaceholder {
+        poster: element
+            .get_attribute("poster")
+            .filter(|_| element.element_type == Tag::Video),
+        source: source.filter(|x| !x.trim().is_empty()),
+    })
+}
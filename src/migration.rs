@@ -0,0 +1,108 @@
+//! Versions of the formats of the files the browser keeps its data in. Every file starts with a
+//! `#version N` line, and the content of an older file is upgraded step by step when it's read, so
+//! a change to a format, like a new field or a renamed key, doesn't lose the data of the user. The
+//! files from before the versions were added have no version line, and they are version 0.
+
+use std::{fs, io, path::Path};
+
+/// Upgrades the content of a file from one version to the next one
+pub type Migration = fn(String) -> String;
+
+const VERSION_PREFIX: &str = "#version ";
+
+/// The upgrade to version 1. The first version only added the version line, so the content stays
+/// the same.
+pub fn add_version(content: String) -> String {
+    content
+}
+
+/// Returns the version of the content and the content without the version line
+fn split_version(content: &str) -> (usize, &str) {
+    let Some(rest) = content.strip_prefix(VERSION_PREFIX) else {
+        return (0, content);
+    };
+    let (line, body) = rest.split_once('\n').unwrap_or((rest, ""));
+    match line.trim().parse() {
+        Ok(version) => (version, body),
+        Err(_) => {
+            println!("Broken version line: {}", line);
+            (0, body)
+        }
+    }
+}
+
+/// Reads the file and upgrades its content to the newest version. `migrations[n]` upgrades
+/// version `n` to `n + 1`, so the newest version is the number of migrations. Returns [None] if
+/// the file can't be read.
+pub fn read(path: &Path, migrations: &[Migration]) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let (version, body) = split_version(&content);
+    if version > migrations.len() {
+        // The lines that are still understood are used, and the rest are skipped like broken lines
+        println!(
+            "{} was saved by a newer version of the browser",
+            path.display()
+        );
+        return Some(body.to_owned());
+    }
+    let mut body = body.to_owned();
+    for (index, migration) in migrations.iter().enumerate().skip(version) {
+        println!("Upgrading {} to version {}", path.display(), index + 1);
+        body = migration(body);
+    }
+    Some(body)
+}
+
+/// Writes the content into the file after the version line of the newest version
+pub fn write(path: &Path, content: &str, migrations: &[Migration]) -> io::Result<()> {
+    let version_line = format!("{}{}\n", VERSION_PREFIX, migrations.len());
+    fs::write(path, version_line + content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("web-browser-{}-{}", std::process::id(), name))
+    }
+
+    fn add_a(content: String) -> String {
+        content + "a"
+    }
+
+    fn add_b(content: String) -> String {
+        content + "b"
+    }
+
+    #[test]
+    fn splits_the_version_line() {
+        assert_eq!(split_version("#version 2\nbody\n"), (2, "body\n"));
+        assert_eq!(split_version("#version 2"), (2, ""));
+        // The files from before the versions have no version line
+        assert_eq!(split_version("body\n"), (0, "body\n"));
+        assert_eq!(split_version("#version x\nbody\n"), (0, "body\n"));
+    }
+
+    #[test]
+    fn runs_the_migrations_after_the_version_in_order() {
+        let path = temp_path("migration.txt");
+        let migrations: [Migration; 2] = [add_a, add_b];
+        fs::write(&path, "x").unwrap();
+        assert_eq!(read(&path, &migrations).as_deref(), Some("xab"));
+        fs::write(&path, "#version 1\nx").unwrap();
+        assert_eq!(read(&path, &migrations).as_deref(), Some("xb"));
+        fs::write(&path, "#version 2\nx").unwrap();
+        assert_eq!(read(&path, &migrations).as_deref(), Some("x"));
+        // A newer file is read as it is
+        fs::write(&path, "#version 3\nx").unwrap();
+        assert_eq!(read(&path, &migrations).as_deref(), Some("x"));
+
+        // Writing adds the newest version, so the migrations aren't run again
+        write(&path, "y", &migrations).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "#version 2\ny");
+        assert_eq!(read(&path, &migrations).as_deref(), Some("y"));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read(&path, &migrations), None);
+    }
+}
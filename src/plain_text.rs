@@ -0,0 +1,261 @@
+//! A viewer for plain text documents. The text is shown as is in a monospace font, instead of
+//! forcing it through the html parser.
+
+use crate::color::Color;
+use crate::css::{TextDecoration, TextDirection};
+use crate::font::{FontFace, FontMetrics, DEFAULT_FONT_FAMILY};
+use crate::render_layout::{Layout, Paragraph, Position, Sentence, Size, Word, DEFAULT_FONT_SIZE};
+use std::ops::Range;
+
+/// The background of the highlighted lines
+const HIGHLIGHT_COLOR: Color = Color {
+    r: 1.0,
+    g: 0.93,
+    b: 0.6,
+    a: 1.0,
+};
+
+/// The amount of spaces a tab character is expanded into
+const TAB_WIDTH: usize = 4;
+
+/// The maximum amount of characters rendered as a single word. Lines that aren't wrapped can be
+/// very long, so they are split up to keep the rendered images at a sensible size.
+const MAX_WORD_LENGTH: usize = 200;
+
+pub struct TextDocument {
+    lines: Vec<String>,
+    /// Wraps lines that don't fit into the viewport. Otherwise they continue past the right edge.
+    pub wrap_lines: bool,
+    pub line_numbers: bool,
+    /// The lines that are drawn with a highlighted background, see [Self::highlight]
+    highlighted_lines: Option<Range<usize>>,
+    /// Set until the page has been scrolled to the highlighted lines
+    pub scroll_to_highlight: bool,
+}
+
+impl TextDocument {
+    pub fn new(text: &str) -> Self {
+        let tab = " ".repeat(TAB_WIDTH);
+        Self {
+            lines: text.lines().map(|x| x.replace('\t', &tab)).collect(),
+            wrap_lines: true,
+            line_numbers: false,
+            highlighted_lines: None,
+            scroll_to_highlight: false,
+        }
+    }
+
+    /// Returns the text of the document, with the tabs expanded into spaces
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Highlights the lines that the byte span of the text is on, and scrolls to them when the
+    /// document is shown
+    ///
+    /// * `text` - The text the document was created from
+    pub fn highlight(&mut self, text: &str, span: Range<usize>) {
+        let line_of = |position: usize| text[..position.min(text.len())].matches('\n').count();
+        let last_line = line_of(span.end.saturating_sub(1).max(span.start));
+        self.highlighted_lines = Some(line_of(span.start)..last_line + 1);
+        self.scroll_to_highlight = true;
+    }
+
+    /// Returns the y coordinate of the first highlighted line in the layout
+    pub fn highlight_position(&self, layout: &Layout) -> Option<i32> {
+        let lines = self.highlighted_lines.as_ref()?;
+        Some(
+            layout
+                .paragraphs
+                .iter()
+                .take(lines.start)
+                .map(|x| x.height)
+                .sum(),
+        )
+    }
+
+    /// Splits a line into the rows it takes on the screen
+    fn split_into_rows(&self, line: &[char], columns: usize) -> Vec<Vec<char>> {
+        if !self.wrap_lines || line.is_empty() {
+            return vec![line.to_vec()];
+        }
+        line.chunks(columns).map(|x| x.to_vec()).collect()
+    }
+
+    /// Lays out the text with every line as its own paragraph. The positions start from 0, 0.
+    ///
+    /// * `text_zoom` - What the font size is multiplied with
+    pub fn layout(&self, viewport_size: Size, text_zoom: f32, font: &impl FontMetrics) -> Layout {
+        let font_size = DEFAULT_FONT_SIZE * 0.75 * text_zoom;
+        let cell_width = font.get_monospace_width(font_size);
+        let line_height = font.get_glyph_height(font_size);
+
+        // The line numbers are right aligned with a space between them and the text
+        let number_columns = self.lines.len().to_string().len();
+        let gutter_width = if self.line_numbers {
+            (number_columns + 1) as i32 * cell_width
+        } else {
+            0
+        };
+        let columns = ((viewport_size.width - gutter_width) / cell_width).max(1) as usize;
+
+        let mut paragraphs = Vec::new();
+        let mut y = 0;
+        for (index, line) in self.lines.iter().enumerate() {
+            let mut sentences = Vec::new();
+            if self.line_numbers {
+                let number = format!("{:>width$}", index + 1, width = number_columns);
+                sentences.push(Sentence {
+                    words: vec![Word::new(
+                        number,
+                        Position::new(0, y),
+                        number_columns as i32 * cell_width,
+                        line_height,
+                    )],
+                    href: None,
+                    text_color: Some(Color::gray()),
+                    text_decoration: TextDecoration::None,
+                    element_id: None,
+                    opacity: 1.0,
+                    face: FontFace::REGULAR,
+                    font_size,
+                    background_color: None,
+                });
+            }
+
+            let highlighted = self
+                .highlighted_lines
+                .as_ref()
+                .is_some_and(|x| x.contains(&index));
+            let characters: Vec<char> = line.chars().collect();
+            let rows = self.split_into_rows(&characters, columns);
+            let mut words = Vec::new();
+            for (row_index, row) in rows.iter().enumerate() {
+                let row_y = y + row_index as i32 * line_height;
+                for (word_index, word) in row.chunks(MAX_WORD_LENGTH).enumerate() {
+                    let x = gutter_width + (word_index * MAX_WORD_LENGTH) as i32 * cell_width;
+                    words.push(Word::new(
+                        word.iter().collect(),
+                        Position::new(x, row_y),
+                        word.len() as i32 * cell_width,
+                        line_height,
+                    ));
+                }
+            }
+            sentences.push(Sentence {
+                words,
+                href: None,
+                text_color: None,
+                text_decoration: TextDecoration::None,
+                element_id: None,
+                opacity: 1.0,
+                face: FontFace::REGULAR,
+                font_size,
+                background_color: highlighted.then_some(HIGHLIGHT_COLOR),
+            });
+
+            let height = rows.len() as i32 * line_height;
+            paragraphs.push(Paragraph {
+                sentences,
+                height,
+                font_size,
+                monospace: true,
+                font_family: DEFAULT_FONT_FAMILY.to_owned(),
+                direction: TextDirection::LeftToRight,
+                fixed: false,
+                clip: None,
+            });
+            y += height;
+        }
+
+        let mut layout = Layout::new();
+        layout.paragraphs = paragraphs;
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::MockFont;
+
+    /// The mock font makes the cells 15 pixels wide and the lines 30 pixels high
+    fn layout(document: &TextDocument, width: i32) -> Layout {
+        document.layout(Size::new(width, 1000), 1.0, &MockFont)
+    }
+
+    /// Returns the words of the last sentence of the line with their positions
+    fn words(layout: &Layout, line: usize) -> Vec<(&str, i32, i32)> {
+        let sentence = layout.paragraphs[line].sentences.last().unwrap();
+        sentence
+            .words
+            .iter()
+            .map(|x| (x.word.as_str(), x.position.x, x.position.y))
+            .collect()
+    }
+
+    #[test]
+    fn expands_tabs() {
+        let document = TextDocument::new("a\tb\n\tc");
+        assert_eq!(document.text(), "a    b\n    c");
+    }
+
+    #[test]
+    fn wraps_lines_into_rows() {
+        let mut document = TextDocument::new("0123456789abc\n\nend");
+        let layout = self::layout(&document, 150);
+        assert_eq!(words(&layout, 0), [("0123456789", 0, 0), ("abc", 0, 30)]);
+        assert_eq!(layout.paragraphs[0].height, 60);
+        // An empty line still takes a row
+        assert_eq!(words(&layout, 1), []);
+        assert_eq!(layout.paragraphs[1].height, 30);
+        assert_eq!(words(&layout, 2), [("end", 0, 90)]);
+
+        document.wrap_lines = false;
+        let layout = self::layout(&document, 150);
+        assert_eq!(words(&layout, 0), [("0123456789abc", 0, 0)]);
+        assert_eq!(words(&layout, 2), [("end", 0, 60)]);
+    }
+
+    #[test]
+    fn splits_long_lines_into_words() {
+        let mut document = TextDocument::new(&"a".repeat(MAX_WORD_LENGTH * 2 + 50));
+        document.wrap_lines = false;
+        let layout = layout(&document, 150);
+        let words: Vec<(usize, i32)> = layout.paragraphs[0].sentences[0]
+            .words
+            .iter()
+            .map(|x| (x.word.len(), x.position.x))
+            .collect();
+        assert_eq!(words, [(200, 0), (200, 3000), (50, 6000)]);
+    }
+
+    #[test]
+    fn puts_the_line_numbers_in_a_gutter() {
+        let mut document = TextDocument::new(&"line\n".repeat(10));
+        document.line_numbers = true;
+        let layout = layout(&document, 150);
+        let number = &layout.paragraphs[0].sentences[0].words[0];
+        assert_eq!((number.word.as_str(), number.position.x), (" 1", 0));
+        // The gutter has room for two digits and a space
+        assert_eq!(words(&layout, 0), [("line", 45, 0)]);
+        assert_eq!(layout.paragraphs[9].sentences[0].words[0].word, "10");
+    }
+
+    #[test]
+    fn highlights_the_lines_of_a_span() {
+        let text = "one\ntwo\nthree\nfour";
+        let mut document = TextDocument::new(text);
+        // From the `w` of `two` to the line break after `three`
+        document.highlight(text, 5..14);
+        assert!(document.scroll_to_highlight);
+        let layout = layout(&document, 150);
+        let highlighted: Vec<bool> = layout
+            .paragraphs
+            .iter()
+            .map(|x| x.sentences[0].background_color.is_some())
+            .collect();
+        assert_eq!(highlighted, [false, true, true, false]);
+        assert_eq!(document.highlight_position(&layout), Some(30));
+    }
+}
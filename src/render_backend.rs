@@ -0,0 +1,281 @@
+//! What a page is drawn with. [paint] goes through the [DisplayList] of a page and hands its items
+//! to a [RenderBackend]. The window draws them with OpenGL, and [SoftwareBackend] draws them into
+//! an image on the CPU, so pages can be drawn where there is no OpenGL.
+
+use crate::color::Color;
+use crate::css::Image;
+use crate::display_list::{DisplayItemKind, DisplayList, TextRun};
+use crate::font::FontRegistry;
+use crate::gradient;
+use crate::render_layout::{BackgroundImage, Position, Rect, Size};
+use image::{Rgba, RgbaImage};
+
+pub trait RenderBackend {
+    /// Makes the following draws only touch the part of the page inside of the rect. [None] lets
+    /// them draw anywhere on the page.
+    ///
+    /// * `fixed` - Set if the rect stays in place when the page is scrolled
+    fn set_clip(&mut self, clip: Option<Rect>, fixed: bool);
+
+    /// Multiplies the alpha of the following draws, see [crate::style::ComputedStyle::opacity]
+    fn set_opacity(&mut self, opacity: f32);
+
+    /// Checks if anything inside of the rect would be drawn. The rest of the items are skipped.
+    fn is_visible(&self, rect: Rect) -> bool;
+
+    fn fill_rect(&mut self, rect: Rect, color: Color);
+
+    /// Draws the text with the top left corner of its line at the position
+    fn draw_text(&mut self, text: &TextRun, position: Position);
+
+    /// Draws the tiles of the background image with the index in
+    /// [crate::render_layout::Layout::background_images]
+    fn draw_background_image(&mut self, index: usize);
+
+    /// Draws everything that is still waiting to be drawn
+    fn flush(&mut self) {}
+}
+
+/// Draws the items of the display list in order. Everything that isn't fixed is moved up by the
+/// vertical scroll of the page.
+pub fn paint(list: &DisplayList, scroll_y: i32, backend: &mut impl RenderBackend) {
+    for item in &list.items {
+        backend.set_clip(item.clip, item.fixed);
+        backend.set_opacity(item.opacity);
+        let scroll_y = if item.fixed { 0 } else { scroll_y };
+        let rect = Rect::new(
+            item.rect.position + Position::new(0, scroll_y),
+            item.rect.size,
+        );
+        // Culling
+        if !backend.is_visible(rect) {
+            continue;
+        }
+        match &item.kind {
+            DisplayItemKind::Rect(color) => backend.fill_rect(rect, *color),
+            DisplayItemKind::Text(text) => backend.draw_text(text, rect.position),
+            DisplayItemKind::BackgroundImage(index) => backend.draw_background_image(*index),
+        }
+    }
+    backend.flush();
+}
+
+/// Draws pages into an image on the CPU. The colors are blended like the alpha blending of the
+/// window, so the pages look the same as they do there.
+pub struct SoftwareBackend<'a> {
+    pub image: RgbaImage,
+    fonts: &'a FontRegistry,
+    background_images: &'a [BackgroundImage],
+    /// The pixels of the background images by their index. Gradients and images that couldn't be
+    /// loaded are [None].
+    image_pixels: &'a [Option<&'a RgbaImage>],
+    /// How far the page is scrolled
+    scroll: Position,
+    /// The part of the image that the page is drawn to
+    area: Rect,
+    /// The part of the area that the following draws can touch
+    clip: Rect,
+    /// Moves the following draws sideways by the horizontal scroll of the page, unless they are
+    /// fixed
+    offset_x: i32,
+    opacity: f32,
+}
+
+impl<'a> SoftwareBackend<'a> {
+    /// Makes an image of the size filled with the background
+    pub fn new(size: Size, background: Color, fonts: &'a FontRegistry, scroll: Position) -> Self {
+        let pixel = Rgba(background.as_8_bit());
+        Self {
+            image: RgbaImage::from_pixel(
+                size.width.max(1) as u32,
+                size.height.max(1) as u32,
+                pixel,
+            ),
+            fonts,
+            background_images: &[],
+            image_pixels: &[],
+            scroll,
+            area: Rect::new(Position::new(0, 0), size),
+            clip: Rect::new(Position::new(0, 0), size),
+            offset_x: 0,
+            opacity: 1.0,
+        }
+    }
+
+    /// Gives the background images of the layout and their pixels, which are indexed the same
+    pub fn set_background_images(
+        &mut self,
+        background_images: &'a [BackgroundImage],
+        image_pixels: &'a [Option<&'a RgbaImage>],
+    ) {
+        self.background_images = background_images;
+        self.image_pixels = image_pixels;
+    }
+
+    /// Only draws the page inside of the area. The whole image is used by default.
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+        self.clip = area;
+    }
+
+    /// Blends the color over the pixel, if it's inside of the clip
+    fn blend(&mut self, x: i32, y: i32, [r, g, b, a]: [f32; 4]) {
+        if !self.clip.contains(x, y) {
+            return;
+        }
+        let alpha = a * self.opacity;
+        let pixel = self.image.get_pixel_mut(x as u32, y as u32);
+        for (channel, value) in pixel.0.iter_mut().zip([r, g, b]) {
+            let blended = value * alpha + (*channel as f32 / 255.0) * (1.0 - alpha);
+            *channel = (blended * 255.0).round() as u8;
+        }
+        let blended_alpha = alpha + (pixel.0[3] as f32 / 255.0) * (1.0 - alpha);
+        pixel.0[3] = (blended_alpha * 255.0).round() as u8;
+    }
+
+    /// Blends the pixels of the image over the image of the page, with the top left corner at x
+    /// and y. The alpha of the pixels is multiplied by `alpha`.
+    fn blend_image(&mut self, pixels: &RgbaImage, x: i32, y: i32, alpha: f32) {
+        for (pixel_x, pixel_y, pixel) in pixels.enumerate_pixels() {
+            let [r, g, b, a] = pixel.0.map(|x| x as f32 / 255.0);
+            self.blend(x + pixel_x as i32, y + pixel_y as i32, [r, g, b, a * alpha]);
+        }
+    }
+}
+
+impl RenderBackend for SoftwareBackend<'_> {
+    fn set_clip(&mut self, clip: Option<Rect>, fixed: bool) {
+        let scroll = match fixed {
+            true => Position::new(0, 0),
+            false => self.scroll,
+        };
+        self.offset_x = scroll.x;
+        self.clip = match clip {
+            Some(clip) => Rect::new(clip.position + scroll, clip.size)
+                .intersection(&self.area)
+                .unwrap_or(Rect::new(self.area.position, Size::new(0, 0))),
+            None => self.area,
+        };
+    }
+
+    fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    fn is_visible(&self, rect: Rect) -> bool {
+        let rect = Rect::new(rect.position + Position::new(self.offset_x, 0), rect.size);
+        rect.intersection(&self.clip).is_some()
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = Rect::new(rect.position + Position::new(self.offset_x, 0), rect.size);
+        let Some(rect) = rect.intersection(&self.clip) else {
+            return;
+        };
+        for y in rect.position.y..rect.position.y + rect.size.height {
+            for x in rect.position.x..rect.position.x + rect.size.width {
+                self.blend(x, y, color.as_opengl_color());
+            }
+        }
+    }
+
+    fn draw_text(&mut self, text: &TextRun, position: Position) {
+        let font = self
+            .fonts
+            .get_face(&text.font_family, text.face)
+            .scaled(text.font_size);
+        let x = (position.x + self.offset_x) as f32;
+        let baseline = (position.y + font.ascent()) as f32;
+        let [r, g, b, a] = text.color.as_opengl_color();
+        for glyph in text.glyphs(font) {
+            let glyph_font = font.of(&glyph);
+            let (glyph_x, glyph_y) = (x + glyph.x, baseline + glyph.y as f32);
+            // The colored glyphs are drawn with their own colors, and only take the alpha of the
+            // text
+            if let Some((pixels, offset_x, offset_y)) = glyph_font.color_image(glyph.id) {
+                self.blend_image(
+                    &pixels,
+                    glyph_x as i32 + offset_x,
+                    glyph_y as i32 + offset_y,
+                    a,
+                );
+                continue;
+            }
+            let mut coverage = Vec::new();
+            glyph_font.coverage(glyph.id, glyph_x, glyph_y, |x, y, c| {
+                coverage.push((x, y, c))
+            });
+            for (x, y, c) in coverage {
+                self.blend(x, y, [r, g, b, a * c.min(1.0)]);
+            }
+        }
+    }
+
+    fn draw_background_image(&mut self, index: usize) {
+        let image = &self.background_images[index];
+        let pixels = self.image_pixels.get(index).copied().flatten();
+        let natural_size = match (&image.image, pixels) {
+            (Image::LinearGradient(_), _) => image.rect.size,
+            (Image::Url(_), Some(pixels)) => {
+                Size::new(pixels.width() as i32, pixels.height() as i32)
+            }
+            (Image::Url(_), None) => return,
+        };
+        let tile = image.tile(natural_size);
+        if tile.size.width <= 0 || tile.size.height <= 0 {
+            return;
+        }
+        let Some(area) = image.painted_area(tile) else {
+            return;
+        };
+        let offset = match image.fixed {
+            true => Position::new(0, 0),
+            false => self.scroll,
+        };
+        let Some(visible) = Rect::new(area.position + offset, area.size).intersection(&self.clip)
+        else {
+            return;
+        };
+
+        // Where the pixels are inside of a tile, from 0.0 to 1.0
+        let tile_origin = tile.position + offset;
+        let fraction = |value: i32, start: i32, length: i32| {
+            (value - start).rem_euclid(length) as f32 / length as f32
+        };
+        let gradient = match &image.image {
+            Image::LinearGradient(gradient) => {
+                let (width, height) = (tile.size.width as f32, tile.size.height as f32);
+                let angle = gradient.direction.degrees(width, height);
+                let line_length = gradient::line_length(angle, width, height).max(1.0);
+                let ramp = gradient::ramp(gradient, line_length, image.font_size);
+                Some((ramp, angle.to_radians(), line_length))
+            }
+            Image::Url(_) => None,
+        };
+        for y in visible.position.y..visible.position.y + visible.size.height {
+            for x in visible.position.x..visible.position.x + visible.size.width {
+                let u = fraction(x, tile_origin.x, tile.size.width);
+                let v = fraction(y, tile_origin.y, tile.size.height);
+                let pixel = match (&gradient, pixels) {
+                    (Some((ramp, angle, line_length)), _) => {
+                        // The distance along the gradient line from the center of the tile, with
+                        // the angle of 0 degrees going up
+                        let point_x = (u - 0.5) * tile.size.width as f32;
+                        let point_y = (0.5 - v) * tile.size.height as f32;
+                        let distance = point_x * angle.sin() + point_y * angle.cos();
+                        let t = (distance / line_length + 0.5).clamp(0.0, 1.0);
+                        let index = (t * (ramp.width() - 1) as f32).round() as u32;
+                        *ramp.get_pixel(index, 0)
+                    }
+                    (None, Some(pixels)) => *pixels.get_pixel(
+                        ((u * pixels.width() as f32) as u32).min(pixels.width() - 1),
+                        ((v * pixels.height() as f32) as u32).min(pixels.height() - 1),
+                    ),
+                    (None, None) => return,
+                };
+                let [r, g, b, a] = pixel.0.map(|x| x as f32 / 255.0);
+                self.blend(x, y, [r, g, b, a]);
+            }
+        }
+    }
+}
@@ -6,13 +6,41 @@
 //! way down until there are no children.
 
 use crate::color::Color;
-use crate::css::{Color as CssColor, Rule};
-use crate::font::Font;
+use crate::css::{
+    Alignment, BackgroundRepeat, BackgroundSize, BorderStyle, DisplayStyle, FlexDirection, Image,
+    LineHeight, Overflow, Position as CssPosition, TextDecoration, TextDirection, Unit, WhiteSpace,
+};
+use crate::font::{FontFace, FontMetrics, FontRegistry};
+use crate::gauge::{gauge_of, Gauge};
 use crate::html::{Element, Tag};
-use std::ops::Add;
+use crate::media_placeholder::{placeholder_of, MediaPlaceholder};
+use crate::style::{ComputedStyle, Edges};
+use crate::transform::Translate;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::{Add, Range};
+use unicode_bidi::{BidiInfo, Level};
 
-const DEFAULT_FONT_SIZE: f32 = 40.0;
-const DEFAULT_H1_SIZE: f32 = DEFAULT_FONT_SIZE * 2.0;
+pub const DEFAULT_FONT_SIZE: f32 = 40.0;
+
+/// A word that ends the line, used for the newlines of text whose white space is preserved
+const LINE_BREAK: &str = "\n";
+
+/// `&shy;`, which marks where a word can be hyphenated. It isn't drawn unless the word is broken
+/// there.
+const SOFT_HYPHEN: char = '\u{AD}';
+
+/// Pages with more paragraphs than this only lay out the paragraphs near the screen, see
+/// [Virtualization]
+const MIN_VIRTUALIZED_PARAGRAPHS: usize = 1000;
+
+/// Blocks with fewer paragraphs than this compile them one by one, because it's faster than
+/// handing them to other threads
+const MIN_PARALLEL_PARAGRAPHS: usize = 8;
+
+/// The space between the cells of a table without `cellspacing`, the same as between words, so
+/// the cells don't run into each other
+const CELL_SPACING: i32 = 10;
 
 /// A generic position vector implementation
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -35,7 +63,7 @@ impl Position {
 }
 
 /// A generic size vector implementation
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[allow(dead_code)]
 pub struct Size {
     pub width: i32,
@@ -48,6 +76,223 @@ impl Size {
     }
 }
 
+/// A generic rectangle implementation. The position is the top left corner of the rectangle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub position: Position,
+    pub size: Size,
+}
+
+impl Rect {
+    pub fn new(position: Position, size: Size) -> Self {
+        Self { position, size }
+    }
+
+    /// Returns the smallest rect that covers both of the rects
+    pub fn union(&self, other: &Rect) -> Rect {
+        let left = self.position.x.min(other.position.x);
+        let top = self.position.y.min(other.position.y);
+        let right = (self.position.x + self.size.width).max(other.position.x + other.size.width);
+        let bottom = (self.position.y + self.size.height).max(other.position.y + other.size.height);
+        Rect::new(
+            Position::new(left, top),
+            Size::new(right - left, bottom - top),
+        )
+    }
+
+    /// Returns the area that is inside of both rects, or [None] if they don't overlap
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let left = self.position.x.max(other.position.x);
+        let top = self.position.y.max(other.position.y);
+        let right = (self.position.x + self.size.width).min(other.position.x + other.size.width);
+        let bottom = (self.position.y + self.size.height).min(other.position.y + other.size.height);
+        (right > left && bottom > top).then(|| {
+            Rect::new(
+                Position::new(left, top),
+                Size::new(right - left, bottom - top),
+            )
+        })
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.position.x
+            && y >= self.position.y
+            && x < self.position.x + self.size.width
+            && y < self.position.y + self.size.height
+    }
+}
+
+/// Moves the clip rect of something that is moved, see [Paragraph::clip]
+fn move_clip(clip: &mut Option<Rect>, position: Position) {
+    if let Some(clip) = clip {
+        clip.position = clip.position + position;
+    }
+}
+
+/// Narrows the clip rect down to the part that is also inside of the rect. Nothing is drawn if the
+/// two don't overlap, so the clip becomes an empty rect then.
+fn narrow_clip(clip: &mut Option<Rect>, rect: Rect) {
+    let narrowed = match clip {
+        Some(v) => v.intersection(&rect),
+        None => Some(rect),
+    };
+    *clip = Some(narrowed.unwrap_or(Rect::new(rect.position, Size::new(0, 0))));
+}
+
+/// The border of a block box. The rect covers the entire border box, so the border lines are drawn
+/// inwards from its edges.
+#[derive(Debug, Clone)]
+pub struct Border {
+    pub rect: Rect,
+    pub widths: Edges<i32>,
+    pub color: Color,
+    /// See [ComputedStyle::opacity]
+    pub opacity: f32,
+    /// Fixed boxes stay in place when the page is scrolled
+    pub fixed: bool,
+    /// See [Paragraph::clip]
+    pub clip: Option<Rect>,
+}
+
+impl Border {
+    pub fn make_relative_to(&mut self, position: Position) {
+        self.rect.position = self.rect.position + position;
+        move_clip(&mut self.clip, position);
+    }
+}
+
+/// The `background-color` of a block box, which covers the entire border box
+#[derive(Debug, Clone)]
+pub struct Background {
+    pub rect: Rect,
+    pub color: Color,
+    /// See [ComputedStyle::opacity]
+    pub opacity: f32,
+    /// Fixed boxes stay in place when the page is scrolled
+    pub fixed: bool,
+    /// See [Paragraph::clip]
+    pub clip: Option<Rect>,
+}
+
+impl Background {
+    pub fn make_relative_to(&mut self, position: Position) {
+        self.rect.position = self.rect.position + position;
+        move_clip(&mut self.clip, position);
+    }
+}
+
+/// The bar of a `<progress>` or `<meter>`, which fills the content box of the element
+#[derive(Debug, Clone)]
+pub struct Bar {
+    pub rect: Rect,
+    pub gauge: Gauge,
+    /// See [ComputedStyle::opacity]
+    pub opacity: f32,
+    /// Fixed bars stay in place when the page is scrolled
+    pub fixed: bool,
+    /// See [Paragraph::clip]
+    pub clip: Option<Rect>,
+}
+
+impl Bar {
+    pub fn make_relative_to(&mut self, position: Position) {
+        self.rect.position = self.rect.position + position;
+        move_clip(&mut self.clip, position);
+    }
+}
+
+/// A `background-image` of a block box. The image is positioned inside of the padding box and
+/// doesn't go outside of it. The size of the image is only known when it's drawn, so the tiles
+/// are resolved then with [BackgroundImage::tile].
+#[derive(Debug, Clone)]
+pub struct BackgroundImage {
+    /// The padding box of the block
+    pub rect: Rect,
+    pub image: Image,
+    pub repeat: BackgroundRepeat,
+    pub size: BackgroundSize,
+    pub position: (Unit, Unit),
+    /// The font size of the block, which `em` lengths are relative to
+    pub font_size: f32,
+    /// See [ComputedStyle::opacity]
+    pub opacity: f32,
+    /// Fixed images stay in place when the page is scrolled
+    pub fixed: bool,
+    /// See [Paragraph::clip]
+    pub clip: Option<Rect>,
+}
+
+impl BackgroundImage {
+    pub fn make_relative_to(&mut self, position: Position) {
+        self.rect.position = self.rect.position + position;
+        move_clip(&mut self.clip, position);
+    }
+
+    /// Returns the rect of the tile the other tiles are repeated from
+    ///
+    /// * `image_size` - The natural size of the image. Gradients don't have one, so they use the
+    ///   size of the padding box.
+    pub fn tile(&self, image_size: Size) -> Rect {
+        let area = self.rect.size;
+        let is_gradient = matches!(self.image, Image::LinearGradient(_));
+        let image_width = image_size.width.max(1) as f32;
+        let image_height = image_size.height.max(1) as f32;
+        let scaled =
+            |scale: f32| Size::new((image_width * scale) as i32, (image_height * scale) as i32);
+        let size = match self.size {
+            BackgroundSize::Cover => {
+                scaled((area.width as f32 / image_width).max(area.height as f32 / image_height))
+            }
+            BackgroundSize::Contain => {
+                scaled((area.width as f32 / image_width).min(area.height as f32 / image_height))
+            }
+            // An `auto` side keeps the aspect ratio of the image
+            BackgroundSize::Size(width, height) => {
+                let width = width.map(|x| x.to_px(self.font_size, area.width));
+                let height = height.map(|x| x.to_px(self.font_size, area.height));
+                match (width, height) {
+                    (Some(width), Some(height)) => Size::new(width, height),
+                    (Some(width), None) if is_gradient => Size::new(width, area.height),
+                    (None, Some(height)) if is_gradient => Size::new(area.width, height),
+                    (Some(width), None) => scaled(width as f32 / image_width),
+                    (None, Some(height)) => scaled(height as f32 / image_height),
+                    (None, None) => image_size,
+                }
+            }
+        };
+
+        // A percentage lines up the same point of the image and the box
+        let offset = |position: Unit, free_space: i32| match position {
+            Unit::Percentage(v) => free_space * v / 100,
+            v => v.to_px(self.font_size, free_space),
+        };
+        let x = offset(self.position.0, area.width - size.width);
+        let y = offset(self.position.1, area.height - size.height);
+        Rect::new(self.rect.position + Position::new(x, y), size)
+    }
+
+    /// Returns the part of the padding box that is covered by the tiles. Returns [None] if the
+    /// image isn't visible at all.
+    pub fn painted_area(&self, tile: Rect) -> Option<Rect> {
+        let mut area = tile;
+        if matches!(
+            self.repeat,
+            BackgroundRepeat::Repeat | BackgroundRepeat::RepeatX
+        ) {
+            area.position.x = self.rect.position.x;
+            area.size.width = self.rect.size.width;
+        }
+        if matches!(
+            self.repeat,
+            BackgroundRepeat::Repeat | BackgroundRepeat::RepeatY
+        ) {
+            area.position.y = self.rect.position.y;
+            area.size.height = self.rect.size.height;
+        }
+        area.intersection(&self.rect)
+    }
+}
+
 /// A container for individual words.
 #[derive(Debug, Clone)]
 pub struct Word {
@@ -89,6 +334,19 @@ pub struct Sentence {
     pub words: Vec<Word>,
     pub href: Option<String>,
     pub text_color: Option<Color>,
+    pub text_decoration: TextDecoration,
+    /// The id of the element the text is in, if the text came from a document
+    pub element_id: Option<usize>,
+    /// See [ComputedStyle::opacity]. The background behind the text is drawn with the same
+    /// opacity.
+    pub opacity: f32,
+    /// The face of [Paragraph::font_family] the words are drawn with
+    pub face: FontFace,
+    /// The size the words are drawn at. Inline elements can have a different size than the rest
+    /// of the paragraph.
+    pub font_size: f32,
+    /// The background of the inline element the text is in
+    pub background_color: Option<Color>,
 }
 
 impl Sentence {
@@ -107,6 +365,48 @@ impl Sentence {
         }
         false
     }
+
+    /// Returns the rects that [Self::background_color] fills, one for each line. The words of a
+    /// line are covered from the first to the last, so the spaces between them are filled too.
+    pub fn background_rects(&self) -> Vec<Rect> {
+        group_into_lines(self.words.iter())
+            .into_iter()
+            .map(|x| x.rect)
+            .collect()
+    }
+}
+
+/// Groups the words into the lines they were laid out on. Words of different sizes on a line
+/// aren't at the same height and the words of right-to-left text aren't in the order of the text,
+/// but the words of a line always overlap vertically.
+fn group_into_lines<'a>(words: impl Iterator<Item = &'a Word>) -> Vec<LineBox> {
+    let mut lines: Vec<LineBox> = Vec::new();
+    for (index, word) in words.enumerate() {
+        let rect = Rect::new(word.position, Size::new(word.width, word.height));
+        match lines.last_mut() {
+            Some(line)
+                if word.position.y < line.rect.position.y + line.rect.size.height
+                    && word.position.y + word.height > line.rect.position.y =>
+            {
+                line.rect = line.rect.union(&rect);
+                line.words.end = index + 1;
+            }
+            _ => lines.push(LineBox {
+                rect,
+                words: index..index + 1,
+            }),
+        }
+    }
+    lines
+}
+
+/// A line of a laid out paragraph, see [Paragraph::line_boxes]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineBox {
+    /// Covers all of the words of the line
+    pub rect: Rect,
+    /// The indices of the words of the line in [Paragraph::words]
+    pub words: Range<usize>,
 }
 
 /// A container for multiple sentences.
@@ -115,7 +415,17 @@ pub struct Paragraph {
     pub sentences: Vec<Sentence>,
     pub height: i32,
     pub font_size: f32,
-    pub background_color: Option<Color>,
+    /// Draws every character in a cell of the same width. See [FontMetrics::get_monospace_width]
+    pub monospace: bool,
+    /// The family of the font in the [FontRegistry] that the text is drawn with
+    pub font_family: String,
+    /// The lines of right-to-left paragraphs are aligned to the right edge
+    pub direction: TextDirection,
+    /// Fixed paragraphs stay in place when the page is scrolled
+    pub fixed: bool,
+    /// Only the part inside of this rect is drawn. Set inside of boxes whose `overflow` isn't
+    /// `visible`.
+    pub clip: Option<Rect>,
 }
 
 impl Paragraph {
@@ -123,26 +433,88 @@ impl Paragraph {
         for sentence in &mut self.sentences {
             sentence.make_relative_to(position);
         }
+        move_clip(&mut self.clip, position);
+    }
+
+    /// Returns the words of all of the sentences in the order of the text, with the sentence each
+    /// word is in
+    pub fn words(&self) -> impl Iterator<Item = (&Sentence, &Word)> {
+        self.sentences
+            .iter()
+            .flat_map(|sentence| sentence.words.iter().map(move |word| (sentence, word)))
+    }
+
+    /// Splits the words into the lines they were laid out on
+    pub fn line_boxes(&self) -> Vec<LineBox> {
+        group_into_lines(self.words().map(|(_, word)| word))
     }
 
-    pub fn combine_sentences(&mut self, paragraph: Paragraph) {
-        self.sentences.extend(paragraph.sentences);
+    /// Returns the distance from the left edge of this paragraph to the right edge of its widest
+    /// line. The paragraph has to be positioned at x 0 for this to be correct.
+    pub fn content_width(&self) -> i32 {
+        let words = || self.sentences.iter().flat_map(|x| &x.words);
+        let right = words().map(|x| x.position.x + x.width).max().unwrap_or(0);
+        match self.direction {
+            TextDirection::LeftToRight => right,
+            // The lines end at the right edge, so the widest one is the one that starts first
+            TextDirection::RightToLeft => {
+                right - words().map(|x| x.position.x).min().unwrap_or(right)
+            }
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum Definition {
     Paragraph(ParagraphDefinition),
-    Table(TableDefinition),
+    Block(Box<ElementDefinition>),
 }
 
 /// A definition of an element rect that has not been created yet. This is a part of the
 /// preprocessing step and will be turned into a rect later on.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct ElementDefinition {
     pub tag: Tag,
+    /// The id of the element, see [Element::id]
+    pub id: usize,
     pub children: Vec<Definition>,
+    pub style: ComputedStyle,
+    /// The bullet or the number in front of a list item
+    pub marker: Option<ParagraphDefinition>,
+    /// Set for `<progress>` and `<meter>`
+    pub gauge: Option<Gauge>,
+    /// The rows of a `<table>`, which are laid out in the content box instead of the children
+    pub table: Option<TableDefinition>,
+    /// Set for `<audio>` and `<video>`
+    pub media: Option<MediaPlaceholder>,
+    /// The `src` of an `<img>`. The picture fills the content box.
+    pub image: Option<String>,
+}
+
+/// Returns the size of an `<img>` from its `width` and `height` attributes. The missing ones come
+/// from the size of the picture, keeping its aspect ratio. An image whose picture hasn't been
+/// loaded only takes the space its attributes give it.
+fn image_size(image: &Element) -> (i32, i32) {
+    let attribute = |name: &str| -> Option<i32> {
+        image
+            .get_attribute(name)?
+            .trim()
+            .trim_end_matches("px")
+            .parse()
+            .ok()
+    };
+    let natural = image
+        .natural_size
+        .map(|(width, height)| (width as i32, height as i32))
+        .filter(|(width, height)| *width > 0 && *height > 0);
+    match (attribute("width"), attribute("height"), natural) {
+        (Some(width), Some(height), _) => (width, height),
+        (Some(width), None, Some(natural)) => (width, width * natural.1 / natural.0),
+        (None, Some(height), Some(natural)) => (height * natural.0 / natural.1, height),
+        (None, None, Some(natural)) => natural,
+        (width, height, None) => (width.unwrap_or(0), height.unwrap_or(0)),
+    }
 }
 
 /// Connects the paragraphs of these elements. Returns the remainder elements of child if there is
@@ -154,17 +526,12 @@ pub fn connect_paragraphs(
     match parent.children.last_mut() {
         Some(Definition::Paragraph(parent_paragraph)) => {
             let mut iter = child.children.iter().peekable();
-            loop {
-                match iter.peek() {
-                    Some(Definition::Paragraph(paragraph)) => {
-                        iter.next();
-                        parent_paragraph
-                            .sentences
-                            .extend(paragraph.sentences.clone());
-                    }
-                    None => break,
-                    _ => todo!(),
-                }
+            // Blocks can't be connected to a paragraph, so they are left as the remainder
+            while let Some(Definition::Paragraph(paragraph)) = iter.peek() {
+                iter.next();
+                parent_paragraph
+                    .sentences
+                    .extend(paragraph.sentences.clone());
             }
             child.children = iter.cloned().collect();
             if child.children.is_empty() {
@@ -178,40 +545,112 @@ pub fn connect_paragraphs(
     }
 }
 
+/// Returns the background the text of the element is drawn on. Blocks paint their own background,
+/// so only the text of inline elements gets one.
+fn inline_background(element: &Element) -> Option<Color> {
+    element.style.background_color.filter(|_| {
+        element.style.is_visible()
+            && matches!(
+                element.style.display,
+                DisplayStyle::Inline | DisplayStyle::InlineBlock
+            )
+    })
+}
+
 /// Collects the different element definitions from the element
 pub fn collect_definition(element: &Element) -> ElementDefinition {
     let mut definition = ElementDefinition {
         tag: element.element_type,
+        id: element.id,
         children: Vec::new(),
+        style: element.style.clone(),
+        marker: None,
+        gauge: gauge_of(element),
+        table: None,
+        media: placeholder_of(element),
+        image: None,
     };
+    // The content of a bar is only shown by browsers that don't support it
+    if definition.gauge.is_some() {
+        return definition;
+    }
+    // The children of a media element are only shown by browsers that can't play it at all, so
+    // the box only gets its label
+    if let Some(media) = &definition.media {
+        let label = ParagraphDefinition::from_string(element, &media.label());
+        definition.children.push(Definition::Paragraph(label));
+        return definition;
+    }
+    if element.element_type == Tag::Img {
+        // The sizes of the picture and the attributes aren't in the style, so they are zoomed here
+        let (width, height) = image_size(element);
+        let zoomed = |x: i32| Unit::Px((x as f32 * element.style.zoom).round() as i32);
+        definition.style.width = definition.style.width.or(Some(zoomed(width)));
+        definition.style.height = definition.style.height.or(Some(zoomed(height)));
+        definition.image = element.get_attribute("src");
+        return definition;
+    }
+    if element.element_type == Tag::Table {
+        definition.table = TableDefinition::from_element(element).ok();
+        return definition;
+    }
+    // A text field has no children, so its text comes from its value
+    if element.is_text_field() {
+        let text = ParagraphDefinition::from_string(element, &field_text(element));
+        definition.children.push(Definition::Paragraph(text));
+        return definition;
+    }
     let mut allow_paragraph_connecting = false;
+    // The number of the next item of an ordered list
+    let mut list_number = match element.element_type {
+        Tag::Ol => element
+            .get_attribute("start")
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(1),
+        _ => 1,
+    };
+    let background_color = inline_background(element);
+    let href = element.get_attribute("href");
 
-    // Handle some css stuff
-    let mut background_color = None;
-    for rule in &element.inner_styles {
-        if let Rule::BackgroundColor(color) = rule {
-            match color {
-                CssColor::Hex(r, g, b) => {
-                    background_color = Some(Color {
-                        r: *r as f32 / 255.0,
-                        g: *g as f32 / 255.0,
-                        b: *b as f32 / 255.0,
-                        a: 1.0,
-                    });
-                }
-                _ => panic!("Color not implemented"),
-            }
+    for child in &element.children {
+        if child.style.display == DisplayStyle::None {
+            continue;
         }
-    }
 
-    for child in &element.children {
-        if child.element_type == Tag::PlainText {
-            let mut paragraph = ParagraphDefinition::from_string(element, &child.inner_text);
-            paragraph.background_color = background_color;
-            definition.children.push(Definition::Paragraph(paragraph));
+        if matches!(child.element_type, Tag::PlainText | Tag::Br) {
+            let paragraph = match child.element_type {
+                Tag::Br => ParagraphDefinition::line_break(child),
+                _ => ParagraphDefinition::from_string(element, &child.inner_text),
+            };
+            // Text that follows inline content continues the same paragraph
+            match definition.children.last_mut() {
+                Some(Definition::Paragraph(previous)) if allow_paragraph_connecting => {
+                    previous.sentences.extend(paragraph.sentences);
+                }
+                _ => definition.children.push(Definition::Paragraph(paragraph)),
+            }
             allow_paragraph_connecting = true;
-        } else if child.element_type == Tag::Span || child.element_type == Tag::A {
-            let child_definition = collect_definition(child);
+        } else if matches!(
+            child.style.display,
+            DisplayStyle::Inline | DisplayStyle::InlineBlock
+        ) && element.style.display != DisplayStyle::Flex
+            && !child.style.position.is_out_of_flow()
+        {
+            // The children of a flex container are all laid out as blocks, so they only get here
+            // in a normal flow. Absolutely positioned elements always get a box of their own.
+            //
+            // Inline blocks don't have a box of their own yet, so they flow like inline elements
+            let mut child_definition = collect_definition(child);
+            // The text of the child is inside of the link and on top of the background of this
+            // element, unless it has its own
+            for child in &mut child_definition.children {
+                if let Definition::Paragraph(paragraph) = child {
+                    for sentence in &mut paragraph.sentences {
+                        sentence.href = sentence.href.take().or_else(|| href.clone());
+                        sentence.background_color = sentence.background_color.or(background_color);
+                    }
+                }
+            }
             if allow_paragraph_connecting {
                 if let Some(remaining_children) =
                     connect_paragraphs(&mut definition, child_definition)
@@ -219,22 +658,49 @@ pub fn collect_definition(element: &Element) -> ElementDefinition {
                     definition.children.extend(remaining_children.children);
                 }
             } else {
-                let child_definition = collect_definition(child);
                 definition.children.extend(child_definition.children);
             }
             allow_paragraph_connecting = true;
-        } else if child.element_type == Tag::Table {
-            let table = TableDefinition::from_element(child).unwrap();
-            definition.children.push(Definition::Table(table));
         } else {
-            let child_definition = collect_definition(child);
-            definition.children.extend(child_definition.children);
+            let mut child_definition = collect_definition(child);
+            if child.style.display == DisplayStyle::ListItem {
+                // `value` changes the number of the item, and the items after it continue from it
+                if let Some(value) = child.get_attribute("value").and_then(|x| x.parse().ok()) {
+                    list_number = value;
+                }
+                child_definition.marker =
+                    child.style.list_style_type.marker(list_number).map(|x| {
+                        let mut marker = ParagraphDefinition::from_string(child, &x);
+                        marker.sentences[0].text_decoration = TextDecoration::None;
+                        marker
+                    });
+                list_number += 1;
+            }
+            definition
+                .children
+                .push(Definition::Block(Box::new(child_definition)));
             allow_paragraph_connecting = false;
         }
     }
     definition
 }
 
+/// Returns the text that is shown in a text field. The field is as wide as `size` characters, or
+/// 20 without it, so it can be seen and clicked even when it's empty.
+fn field_text(element: &Element) -> String {
+    let value = element
+        .get_attribute("value")
+        .filter(|x| !x.is_empty())
+        .or_else(|| element.get_attribute("placeholder"))
+        .unwrap_or_default();
+    let size = element
+        .get_attribute("size")
+        .and_then(|x| x.parse().ok())
+        .filter(|x| *x > 0)
+        .unwrap_or(20);
+    format!("{:<size$}", value)
+}
+
 #[derive(Clone, Debug)]
 #[allow(dead_code)]
 pub struct SentenceDefinition {
@@ -242,16 +708,16 @@ pub struct SentenceDefinition {
     pub words: Vec<String>,
     pub href: Option<String>,
     pub text_color: Option<Color>,
-}
-
-impl SentenceDefinition {
-    pub fn as_string(&self) -> String {
-        let mut sentence = String::new();
-        for word in &self.words {
-            sentence += word;
-        }
-        sentence
-    }
+    pub text_decoration: TextDecoration,
+    pub element_id: usize,
+    pub white_space: WhiteSpace,
+    /// Hidden text takes its space, but it has no words in the compiled paragraph, so it isn't
+    /// drawn and it can't be clicked
+    pub visible: bool,
+    pub opacity: f32,
+    pub face: FontFace,
+    pub font_size: f32,
+    pub background_color: Option<Color>,
 }
 
 /// A collection of elements that should be drawn inline
@@ -261,7 +727,12 @@ pub struct ParagraphDefinition {
     pub tag: Tag,
     pub sentences: Vec<SentenceDefinition>,
     pub font_size: f32,
-    pub background_color: Option<Color>,
+    pub line_height: LineHeight,
+    /// See [Paragraph::monospace]
+    pub monospace: bool,
+    /// The `font-family` list. The first family of it that is found is used.
+    pub font_family: Vec<String>,
+    pub direction: TextDirection,
 }
 
 impl ParagraphDefinition {
@@ -272,128 +743,404 @@ impl ParagraphDefinition {
     ///
     /// * `string` - The content this element contains
     pub fn from_string(element: &Element, string: &str) -> Self {
-        let words = string.split(" ").map(|x| x.to_owned()).collect();
-        let text_color = match element.element_type {
-            Tag::A => Some(Color::blue()),
-            _ => None,
-        };
+        let white_space = element.style.white_space;
         Self {
             tag: element.element_type,
             sentences: vec![SentenceDefinition {
-                words,
+                words: split_words(string, white_space),
                 tag: element.element_type,
                 href: element.get_attribute("href"),
-                text_color,
+                text_color: Some(element.style.color),
+                text_decoration: element.style.text_decoration,
+                element_id: element.id,
+                white_space,
+                visible: element.style.is_visible(),
+                opacity: element.style.opacity,
+                face: element.style.font_face(),
+                font_size: element.style.font_size,
+                background_color: inline_background(element),
             }],
-            font_size: match element.element_type {
-                Tag::H(1) => DEFAULT_H1_SIZE,
-                _ => DEFAULT_FONT_SIZE,
-            },
-            background_color: None,
+            font_size: element.style.font_size,
+            line_height: element.style.line_height,
+            monospace: element.style.is_monospace(),
+            font_family: element.style.font_family.clone(),
+            direction: element.style.direction,
         }
     }
 
-    /// Returns the width of this paragraph if all of the sentences were to be placed inline
-    pub fn get_width(&self, font: &Font) -> i32 {
-        let mut length = 0;
-        for sentence in &self.sentences {
-            length += font.get_word_width(&sentence.as_string(), DEFAULT_FONT_SIZE * 2.0);
-        }
-        length
+    /// Creates the paragraph of a `<br>`, which only has a [LINE_BREAK] in it. It ends the line
+    /// even when the white space isn't preserved.
+    pub fn line_break(element: &Element) -> Self {
+        let mut paragraph = Self::from_string(element, "");
+        paragraph.sentences[0].words = vec![LINE_BREAK.to_owned()];
+        paragraph
     }
 
     /// Returns a compiled version of this paragraph. The output hasn't yet been given a position,
     /// so it will be positioned at 0, 0
-    pub fn compile(&self, viewport_size: Size, font: &Font) -> Paragraph {
+    pub fn compile(
+        &self,
+        viewport_size: Size,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> Paragraph {
+        self.compile_lines(viewport_size, true, fonts)
+    }
+
+    /// Compiles the paragraph into lines of the given width. A word that is wider than a whole
+    /// line is broken between its letters only if `break_words` is set. It isn't set when the
+    /// paragraph is measured, so that the longest word still sets the narrowest width.
+    fn compile_lines(
+        &self,
+        viewport_size: Size,
+        break_words: bool,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> Paragraph {
         let seperation_width = 10;
-        let seperation_height = font.get_glyph_height(self.font_size);
+        let font_family = fonts.resolve(&self.font_family);
+        let font = fonts.get(font_family);
+        // Returns the height of the words of the size, and how far the line box has to reach above
+        // and below the baseline to fit them. The space left over in a line is split evenly above
+        // and below the text.
+        let metrics = |font_size: f32| {
+            let word_height = font.get_glyph_height(font_size);
+            let line_height = self.line_height.to_px(font_size, word_height);
+            let above = (line_height - word_height) / 2 + font.get_ascent(font_size);
+            (word_height, above, line_height - above)
+        };
+        // Every line is at least as tall as the text of the paragraph itself, even if the words on
+        // it are smaller
+        let (_, strut_above, strut_below) = metrics(self.font_size);
+        // Bold text is wider, so every sentence is measured with its own face
+        let word_width = |word: &str, face: FontFace, font_size: f32| {
+            let font = fonts.get_face(font_family, face);
+            match self.monospace {
+                true => word.chars().count() as i32 * font.get_monospace_width(font_size),
+                false => font.get_word_width(word, font_size),
+            }
+        };
         let mut x_position: i32 = 0;
         let mut sentences = Vec::new();
-        let mut y_position: i32 = 0;
+        // The space above and below the baseline of every line. The words are first placed with
+        // the index of their line as their y, since the baseline of a line is only known after all
+        // of its words have been placed.
+        let mut lines = vec![(strut_above, strut_below)];
+        let mut ends_with_break = false;
 
         for sentence in &self.sentences {
+            let (word_height, above, below) = metrics(sentence.font_size);
+            let width = |word: &str| word_width(word, sentence.face, sentence.font_size);
             let mut words = Vec::new();
-            for word in &sentence.words {
-                let word_width = font.get_word_width(word, self.font_size);
-                let word_height = font.get_glyph_height(self.font_size);
-                let mut right_edge = x_position + word_width;
-                if right_edge > viewport_size.width {
-                    y_position += seperation_height;
+            for (index, word) in sentence.words.iter().enumerate() {
+                ends_with_break = word == LINE_BREAK;
+                if ends_with_break {
+                    lines.push((strut_above, strut_below));
                     x_position = 0;
-                    right_edge = font.get_word_width(word, self.font_size);
+                    continue;
+                }
+                // The line can still be broken before the sentence, even if the text inside of it
+                // isn't wrapped
+                let can_wrap = sentence.white_space.wraps() || index == 0;
+                let mut rest = word.as_str();
+                loop {
+                    let text = rest.replace(SOFT_HYPHEN, "");
+                    let text_width = width(&text);
+                    let line = lines.len() as i32 - 1;
+                    if x_position + text_width > viewport_size.width && can_wrap {
+                        let available = viewport_size.width - x_position;
+                        let broken = match sentence.white_space.wraps() {
+                            true => {
+                                break_word(rest, available, break_words && x_position == 0, width)
+                            }
+                            false => None,
+                        };
+                        if let Some((head, tail)) = broken {
+                            if sentence.visible {
+                                let head_width = width(&head);
+                                words.push(Word::new(
+                                    head,
+                                    Position::new(x_position, line),
+                                    head_width,
+                                    word_height,
+                                ));
+                            }
+                            fit_line(lines.last_mut().unwrap(), above, below);
+                            lines.push((strut_above, strut_below));
+                            x_position = 0;
+                            rest = tail;
+                            continue;
+                        }
+                        if x_position > 0 {
+                            lines.push((strut_above, strut_below));
+                            x_position = 0;
+                            continue;
+                        }
+                    }
+                    if sentence.visible {
+                        words.push(Word::new(
+                            text,
+                            Position::new(x_position, line),
+                            text_width,
+                            word_height,
+                        ));
+                    }
+                    // Hidden text still takes its space
+                    fit_line(lines.last_mut().unwrap(), above, below);
+                    x_position += text_width + seperation_width;
+                    break;
                 }
-                words.push(Word::new(
-                    word.clone(),
-                    Position::new(x_position, y_position),
-                    word_width,
-                    word_height,
-                ));
-                x_position = right_edge + seperation_width;
             }
             sentences.push(Sentence {
                 words,
                 href: sentence.href.clone(),
                 text_color: sentence.text_color,
+                text_decoration: sentence.text_decoration,
+                element_id: Some(sentence.element_id),
+                opacity: sentence.opacity,
+                face: sentence.face,
+                font_size: sentence.font_size,
+                background_color: sentence.background_color,
             });
         }
 
+        // A break at the end of the paragraph doesn't start a new line, like in browsers, but
+        // a `<br>` alone still takes a line
+        if ends_with_break && lines.len() > 1 {
+            lines.pop();
+        }
+        reorder_lines(
+            &mut sentences,
+            self.direction,
+            viewport_size.width,
+            seperation_width,
+        );
+
+        let mut tops = Vec::with_capacity(lines.len());
+        let mut height = 0;
+        for (above, below) in &lines {
+            tops.push(height);
+            height += above + below;
+        }
+        // The words of a line share its baseline, whatever their size
+        for sentence in &mut sentences {
+            let ascent = font.get_ascent(sentence.font_size);
+            for word in &mut sentence.words {
+                let line = word.position.y as usize;
+                word.position.y = tops[line] + lines[line].0 - ascent;
+            }
+        }
+
         Paragraph {
             sentences,
-            height: y_position + seperation_height,
+            height,
             font_size: self.font_size,
-            background_color: self.background_color,
+            monospace: self.monospace,
+            font_family: font_family.to_owned(),
+            direction: self.direction,
+            fixed: false,
+            clip: None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct TableRowDefinition {
-    values: Vec<ParagraphDefinition>,
-}
+/// Puts the words of every line into the order they are drawn in, with the Unicode bidirectional
+/// algorithm. Runs of right-to-left text are reversed, both the order of their words and the
+/// letters inside of the words. The lines of a right-to-left paragraph are aligned to the right
+/// edge of the width. The words still have the index of their line as their y.
+fn reorder_lines(
+    sentences: &mut [Sentence],
+    direction: TextDirection,
+    width: i32,
+    seperation_width: i32,
+) {
+    // The words are joined with spaces, so that the spaces between them get levels too
+    let mut text = String::new();
+    let mut words = Vec::new();
+    for word in sentences.iter_mut().flat_map(|x| &mut x.words) {
+        words.push((text.len(), word));
+        text += &words.last().unwrap().1.word;
+        text.push(' ');
+    }
+    let base = match direction {
+        TextDirection::LeftToRight => Level::ltr(),
+        TextDirection::RightToLeft => Level::rtl(),
+    };
+    let info = BidiInfo::new(&text, Some(base));
+    // Left-to-right text is already in the right order
+    if !info.has_rtl() {
+        return;
+    }
 
-impl TableRowDefinition {
-    pub fn from_element(element: &Element) -> Result<Self, String> {
-        let mut values = Vec::new();
-        for child in &element.children {
-            let definition = collect_definition(child);
-            for def in definition.children {
-                match def {
-                    Definition::Paragraph(v) => values.push(v.clone()),
-
-                    Definition::Table(v) => {
-                        let paragraphs_vectors: Vec<Vec<ParagraphDefinition>> =
-                            v.rows.iter().map(|x| x.values.clone()).collect();
-                        let mut paragraphs = Vec::new();
-                        for paragraph_vector in paragraphs_vectors {
-                            paragraphs.extend(paragraph_vector);
-                        }
-                        values.extend(paragraphs);
-                    }
+    for line in words.chunk_by_mut(|a, b| a.1.position.y == b.1.position.y) {
+        let levels: Vec<u8> = line.iter().map(|x| info.levels[x.0].number()).collect();
+        for (start, word) in line.iter_mut() {
+            let Some(paragraph) = info.paragraphs.iter().find(|x| x.range.contains(start)) else {
+                continue;
+            };
+            let range = *start..*start + word.word.len();
+            word.word = info.reorder_line(paragraph, range).into_owned();
+        }
+
+        // From the highest level to the lowest odd level, every run of words at that level or
+        // higher is reversed
+        let mut order: Vec<usize> = (0..line.len()).collect();
+        let highest = levels.iter().copied().max().unwrap_or(0);
+        let lowest_odd = levels.iter().map(|x| x | 1).min().unwrap_or(1);
+        for level in (lowest_odd..=highest).rev() {
+            for run in order.chunk_by_mut(|a, b| (levels[*a] >= level) == (levels[*b] >= level)) {
+                if levels[run[0]] >= level {
+                    run.reverse();
                 }
             }
         }
 
-        Ok(Self { values })
+        let mut x = 0;
+        for index in &order {
+            let word = &mut line[*index].1;
+            word.position.x = x;
+            x += word.width + seperation_width;
+        }
+        if direction == TextDirection::RightToLeft {
+            let offset = width - (x - seperation_width);
+            for (_, word) in line.iter_mut() {
+                word.position.x += offset;
+            }
+        }
     }
 }
 
-/// Stores information needed to create a table. This needs to be a unique struct due to the table
-/// element's unique formatting rules.
-#[derive(Debug, Clone)]
-pub struct TableDefinition {
-    rows: Vec<TableRowDefinition>,
+/// Makes the line reach far enough above and below its baseline to fit the words of a sentence
+fn fit_line(line: &mut (i32, i32), above: i32, below: i32) {
+    line.0 = line.0.max(above);
+    line.1 = line.1.max(below);
 }
 
-pub struct Table {
-    paragraphs: Vec<Paragraph>,
+/// Breaks a word that doesn't fit into the space left in the line. Returns the part that is drawn
+/// on the line and the rest of the word. The word is broken at the last opportunity of the Unicode
+/// line breaking algorithm (UAX #14) that fits, which is between the characters of Chinese and
+/// Japanese text, after a hyphen or at a [SOFT_HYPHEN]. A word without one is broken between any
+/// two letters, but only if `anywhere` is set, which is when the word is at the start of a line and
+/// it is still too wide. Returns [None] if the word can't be broken.
+fn break_word(
+    word: &str,
+    available: i32,
+    anywhere: bool,
+    width: impl Fn(&str) -> i32,
+) -> Option<(String, &str)> {
+    // The heads get wider with every opportunity, so the search stops at the first that doesn't fit
+    let opportunity = unicode_linebreak::linebreaks(word)
+        .map(|(i, _)| i)
+        .filter(|i| *i < word.len())
+        .map(|i| {
+            let head = match word[..i].strip_suffix(SOFT_HYPHEN) {
+                Some(v) => format!("{}-", v.replace(SOFT_HYPHEN, "")),
+                None => word[..i].replace(SOFT_HYPHEN, ""),
+            };
+            (head, &word[i..])
+        })
+        .take_while(|(head, _)| width(head) <= available)
+        .last();
+    if opportunity.is_some() || !anywhere {
+        return opportunity;
+    }
+    // At least one letter is taken, so the line always moves forward
+    let mut end = word.chars().next()?.len_utf8();
+    for (i, _) in word.char_indices().skip(2) {
+        if width(&word[..i].replace(SOFT_HYPHEN, "")) > available {
+            break;
+        }
+        end = i;
+    }
+    match end < word.len() {
+        true => Some((word[..end].replace(SOFT_HYPHEN, ""), &word[end..])),
+        false => None,
+    }
 }
 
-impl Table {
-    pub fn new() -> Self {
-        Self {
-            paragraphs: Vec::new(),
+/// Checks if the character is a space that the line can be broken at. Non-breaking spaces keep the
+/// words around them together, so they are a part of the word.
+fn is_breaking_space(character: char) -> bool {
+    character.is_whitespace() && !matches!(character, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+/// Splits the text into the words of a sentence. Preserved newlines become [LINE_BREAK] words, and
+/// a line that can't be wrapped is kept as a single word with its spaces.
+fn split_words(string: &str, white_space: WhiteSpace) -> Vec<String> {
+    if !white_space.preserves_spaces() {
+        return string
+            .split(is_breaking_space)
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_owned())
+            .collect();
+    }
+    let mut words = Vec::new();
+    for (index, line) in string.split('\n').enumerate() {
+        if index > 0 {
+            words.push(LINE_BREAK.to_owned());
         }
+        if white_space.wraps() {
+            words.extend(line.split(' ').map(|x| x.to_owned()));
+        } else {
+            words.push(line.to_owned());
+        }
+    }
+    words
+}
+
+/// A cell of a table and the part of the grid of the table it covers
+#[derive(Debug, Clone)]
+struct TableCellDefinition {
+    block: ElementDefinition,
+    /// The first column of the cell
+    column: usize,
+    /// How many columns the cell covers, from `colspan`
+    columns: usize,
+    /// How many rows the cell covers, from `rowspan`
+    rows: usize,
+}
+
+/// A row of a table. Every cell is a block of its own, so it can hold any content, including
+/// other tables.
+#[derive(Debug, Clone)]
+pub struct TableRowDefinition {
+    cells: Vec<TableCellDefinition>,
+}
+
+/// Returns the `<tr>` elements of a `<thead>`, `<tbody>` or `<tfoot>`
+fn rows_of(section: &Element) -> impl Iterator<Item = &Element> {
+    section
+        .children
+        .iter()
+        .filter(|x| x.element_type == Tag::Tr)
+}
+
+/// Reads `colspan` or `rowspan` of a cell, or one of the other attributes that are numbers
+fn span_of(cell: &Element, name: &str) -> Option<usize> {
+    cell.get_attribute(name)?.trim().parse().ok()
+}
+
+/// Applies the attributes of the table that change the style of its cells. `cellpadding` sets
+/// the padding, and `border` draws lines around the cells that have no border of their own.
+fn apply_table_attributes(table: &Element, cell: &mut ElementDefinition) {
+    if let Some(padding) = span_of(table, "cellpadding") {
+        cell.style.padding = Edges::all(Unit::Px(padding as i32));
     }
+    let border =
+        span_of(table, "border").unwrap_or(usize::from(table.attributes.contains_key("border")));
+    if border > 0 && cell.style.border_style.is_none() {
+        cell.style.border_style = Some(BorderStyle::Solid);
+        cell.style.border_width = Edges::all(Unit::Px(1));
+        cell.style.border_color = Color::gray();
+    }
+}
+
+/// Stores information needed to create a table. This needs to be a unique struct due to the table
+/// element's unique formatting rules.
+#[derive(Debug, Clone)]
+pub struct TableDefinition {
+    rows: Vec<TableRowDefinition>,
+    column_count: usize,
+    /// The space between the cells, from `cellspacing`
+    spacing: i32,
 }
 
 impl TableDefinition {
@@ -402,101 +1149,2592 @@ impl TableDefinition {
             return Err(format!("Expected table. Got: '{:?}'", element.element_type));
         }
 
-        let mut rows = Vec::new();
+        let mut head = Vec::new();
+        let mut body = Vec::new();
+        let mut foot = Vec::new();
         for child in &element.children {
-            let row = TableRowDefinition::from_element(child).unwrap();
-            rows.push(row);
-        }
-        Ok(Self { rows })
-    }
-
-    /// Compile this table into a rendeable [Table]
-    pub fn compile(&self, font: &Font) -> Table {
-        // Calculate the column widths so the elements can be placed correctly
-        let mut max_column_widths = Vec::new();
-        for row in &self.rows {
-            for (index, sentence) in row.values.iter().enumerate() {
-                let sentence_length = sentence.get_width(font);
-                if let Some(v) = max_column_widths.get_mut(index) {
-                    *v = sentence_length;
-                } else {
-                    max_column_widths.push(sentence_length);
-                }
+            match child.element_type {
+                Tag::Tr => body.push(child),
+                Tag::Thead => head.extend(rows_of(child)),
+                Tag::Tbody => body.extend(rows_of(child)),
+                Tag::Tfoot => foot.extend(rows_of(child)),
+                _ => (),
             }
         }
+        // The header is at the top and the footer at the bottom wherever they are in the html
+        let rows: Vec<&Element> = head.into_iter().chain(body).chain(foot).collect();
 
-        // Compile into paragraphs
-        let mut output = Table::new();
-        let mut y = 0;
-        for row in &self.rows {
-            for (column_index, column) in row.values.iter().enumerate() {
-                // Get the x position for this column element
-                let mut x_position = 0;
-                for width in max_column_widths.iter().take(column_index) {
-                    x_position += width;
+        // The slots of the grid that are taken by cells from the rows above
+        let mut taken = HashSet::new();
+        let mut column_count = 0;
+        let mut output = Vec::new();
+        for (row_index, row) in rows.iter().enumerate() {
+            let remaining_rows = rows.len() - row_index;
+            let mut cells = Vec::new();
+            let mut column = 0;
+            for cell in row.children.iter().filter(|x| {
+                matches!(x.element_type, Tag::Td | Tag::Th) && x.style.display != DisplayStyle::None
+            }) {
+                while taken.contains(&(row_index, column)) {
+                    column += 1;
+                }
+                let columns = span_of(cell, "colspan")
+                    .filter(|x| *x > 0)
+                    .unwrap_or(1)
+                    .min(1000);
+                // A `rowspan` of 0 covers the rest of the rows
+                let row_span = match span_of(cell, "rowspan") {
+                    Some(0) => remaining_rows,
+                    Some(v) => v.min(remaining_rows),
+                    None => 1,
+                };
+                for taken_row in row_index..row_index + row_span {
+                    for taken_column in column..column + columns {
+                        taken.insert((taken_row, taken_column));
+                    }
                 }
+                let mut block = collect_definition(cell);
+                apply_table_attributes(element, &mut block);
+                cells.push(TableCellDefinition {
+                    block,
+                    column,
+                    columns,
+                    rows: row_span,
+                });
+                column += columns;
+                column_count = column_count.max(column);
+            }
+            output.push(TableRowDefinition { cells });
+        }
+        let spacing = span_of(element, "cellspacing").map_or(CELL_SPACING, |x| x as i32);
+        Ok(Self {
+            rows: output,
+            column_count,
+            spacing,
+        })
+    }
 
-                let mut paragraph = column.compile(Size::new(2000, 2000), font);
-                paragraph.make_relative_to(Position::new(x_position, y));
-                output.paragraphs.push(paragraph);
+    /// Returns the width of every column, measured by the widest cell in the column. A cell that
+    /// covers several columns makes them wider if they don't fit it otherwise.
+    fn measure_columns(&self, measure: impl Fn(&ElementDefinition) -> i32) -> Vec<i32> {
+        let mut widths = vec![0; self.column_count];
+        let mut spanning = Vec::new();
+        for cell in self.rows.iter().flat_map(|x| &x.cells) {
+            let width = measure(&cell.block);
+            if cell.columns == 1 {
+                widths[cell.column] = widths[cell.column].max(width);
+            } else {
+                spanning.push((cell, width));
             }
-            y += DEFAULT_FONT_SIZE as i32;
         }
+        for (cell, width) in spanning {
+            let columns = &mut widths[cell.column..cell.column + cell.columns];
+            let count = columns.len() as i32;
+            let available = columns.iter().sum::<i32>() + self.spacing * (count - 1);
+            // The missing space is split evenly between the columns
+            let missing = width - available;
+            if missing > 0 {
+                for (index, column) in columns.iter_mut().enumerate() {
+                    *column += missing / count + i32::from((index as i32) < missing % count);
+                }
+            }
+        }
+        widths
+    }
 
-        output
+    /// The space taken by the spacing between the columns
+    fn total_spacing(&self) -> i32 {
+        self.spacing * (self.column_count as i32 - 1).max(0)
     }
-}
 
-/// Holds the layout of the html
-#[derive(Debug)]
-pub struct Layout {
-    pub paragraphs: Vec<Paragraph>,
+    /// Returns the widths of the columns when the table is laid out in the width. The columns
+    /// get the widths of their content if it fits. Otherwise every column gets at least its
+    /// widest word, and the rest of the space is shared by how much more the columns would need.
+    fn column_widths(&self, width: i32, fonts: &FontRegistry<impl FontMetrics>) -> Vec<i32> {
+        let max = self.measure_columns(|x| block_max_content_width(x, width, fonts));
+        let available = width - self.total_spacing();
+        let max_total: i32 = max.iter().sum();
+        if max_total <= available {
+            return max;
+        }
+        let min = self.measure_columns(|x| block_min_content_width(x, width, fonts));
+        let min_total: i32 = min.iter().sum();
+        if min_total >= available {
+            return min;
+        }
+        let extra = (available - min_total) as i64;
+        let range = (max_total - min_total) as i64;
+        min.iter()
+            .zip(&max)
+            .map(|(min, max)| min + ((max - min) as i64 * extra / range) as i32)
+            .collect()
+    }
+
+    /// Returns the width of the table when none of its cells have to wrap their lines
+    fn max_content_width(
+        &self,
+        containing_width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        let widths = self.measure_columns(|x| block_max_content_width(x, containing_width, fonts));
+        widths.iter().sum::<i32>() + self.total_spacing()
+    }
+
+    /// Returns the width of the table when its cells wrap their lines as much as they can
+    fn min_content_width(
+        &self,
+        containing_width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        let widths = self.measure_columns(|x| block_min_content_width(x, containing_width, fonts));
+        widths.iter().sum::<i32>() + self.total_spacing()
+    }
 }
 
-impl Layout {
-    pub fn make_relative_to(&mut self, position: Position) {
-        for paragraph in &mut self.paragraphs {
-            paragraph.make_relative_to(position);
-        }
+/// Compiles the paragraphs among the children of a block, in the same order. The other children
+/// are skipped. Returns nothing if there are too few paragraphs to compile them in parallel.
+fn compile_paragraphs(
+    children: &[Definition],
+    width: i32,
+    fonts: &FontRegistry<impl FontMetrics>,
+) -> Vec<Paragraph> {
+    let paragraphs: Vec<&ParagraphDefinition> = children
+        .iter()
+        .filter_map(|x| match x {
+            Definition::Paragraph(v) => Some(v),
+            _ => None,
+        })
+        .collect();
+    if paragraphs.len() < MIN_PARALLEL_PARAGRAPHS {
+        return Vec::new();
     }
+    paragraphs
+        .par_iter()
+        .map(|x| x.compile(Size::new(width, 0), fonts))
+        .collect()
+}
 
-    pub fn from_body(element: &Element, viewport_size: Size, font: &Font) -> Self {
-        let mut definitions = Vec::new();
+/// Counts the paragraphs inside of the block and its children
+fn count_paragraphs(block: &ElementDefinition) -> usize {
+    let cells: usize = block
+        .table
+        .iter()
+        .flat_map(|x| &x.rows)
+        .flat_map(|x| &x.cells)
+        .map(|x| count_paragraphs(&x.block))
+        .sum();
+    let children: usize = block
+        .children
+        .iter()
+        .map(|x| match x {
+            Definition::Paragraph(_) => 1,
+            Definition::Block(v) => count_paragraphs(v),
+        })
+        .sum();
+    cells + children
+}
 
-        for child in &element.children {
-            definitions.push(collect_definition(child));
-        }
+/// Returns the combined width of the horizontal margins, borders and paddings of a block
+fn horizontal_edges(block: &ElementDefinition, containing_width: i32) -> i32 {
+    let font_size = block.style.font_size;
+    block
+        .style
+        .margin
+        .to_px(font_size, containing_width)
+        .horizontal()
+        + block
+            .style
+            .padding
+            .to_px(font_size, containing_width)
+            .horizontal()
+        + block.style.border_widths(font_size).horizontal()
+}
 
-        // Collect the paragraphs
-        let mut paragraphs = Vec::new();
-        for definition in definitions {
-            for child_definition in definition.children {
-                match child_definition {
-                    Definition::Paragraph(paragraph) => {
-                        paragraphs.push(paragraph.compile(viewport_size, font))
-                    }
+/// Checks if the definition is a block with a width set with `width`
+fn has_definite_width(definition: &Definition) -> bool {
+    matches!(definition, Definition::Block(block) if block.style.width.is_some())
+}
 
-                    Definition::Table(table) => {
-                        let table_values = table.compile(font);
-                        let mut paragraph = table_values.paragraphs.first().unwrap().clone();
-                        for i in 1..table_values.paragraphs.len() {
-                            paragraph.combine_sentences(table_values.paragraphs[i].clone());
-                        }
-                        paragraphs.push(paragraph);
-                    }
-                }
+/// Applies `min-width` and `max-width` to the width of the margin box of a flex item
+fn clamp_item_width(item: &Definition, width: i32, containing_width: i32) -> i32 {
+    let Definition::Block(block) = item else {
+        return width;
+    };
+    let edges = horizontal_edges(block, containing_width);
+    block
+        .style
+        .clamp_width(width - edges, block.style.font_size, containing_width)
+        + edges
+}
+
+/// Checks if the definition is a block that is taken out of the normal flow by `position`
+fn is_out_of_flow(definition: &Definition) -> bool {
+    matches!(definition, Definition::Block(block) if block.style.position.is_out_of_flow())
+}
+
+/// Returns the width the definition would take if none of its lines had to be wrapped, including
+/// margins. Blocks with a `width` take exactly that much space.
+///
+/// * `containing_width` - The width percentages are relative to
+fn max_content_width(
+    definition: &Definition,
+    containing_width: i32,
+    fonts: &FontRegistry<impl FontMetrics>,
+) -> i32 {
+    match definition {
+        Definition::Paragraph(paragraph) => paragraph
+            .compile(Size::new(i32::MAX / 2, 0), fonts)
+            .content_width(),
+        Definition::Block(block) => block_max_content_width(block, containing_width, fonts),
+    }
+}
+
+/// The block version of [max_content_width]. Positioned children don't take any space, so they
+/// are skipped.
+fn block_max_content_width(
+    block: &ElementDefinition,
+    containing_width: i32,
+    fonts: &FontRegistry<impl FontMetrics>,
+) -> i32 {
+    let font_size = block.style.font_size;
+    let content = match (block.style.width, &block.table) {
+        (Some(width), _) => width.to_px(font_size, containing_width),
+        (None, Some(table)) => table.max_content_width(containing_width, fonts),
+        (None, None) => {
+            let children = block
+                .children
+                .iter()
+                .filter(|x| !is_out_of_flow(x))
+                .map(|x| max_content_width(x, containing_width, fonts));
+            if block.style.display == DisplayStyle::Flex
+                && block.style.flex_direction == FlexDirection::Row
+            {
+                children.sum()
+            } else {
+                children.max().unwrap_or(0)
             }
         }
+    };
+    block
+        .style
+        .clamp_width(content, font_size, containing_width)
+        + horizontal_edges(block, containing_width)
+}
 
-        // Adjust the positions of the paragraphs
-        let mut current_y = 0;
-        let spacing: i32 = (font.get_glyph_height(DEFAULT_FONT_SIZE) as f32 / 2.0) as i32;
-        for paragraph in &mut paragraphs {
-            paragraph.make_relative_to(Position::new(0, current_y));
-            current_y += paragraph.height;
-            current_y += spacing;
-        }
+/// Returns the width of the widest part of the definition that can't be broken into lines, such
+/// as a long word, including margins
+fn min_content_width(
+    definition: &Definition,
+    containing_width: i32,
+    fonts: &FontRegistry<impl FontMetrics>,
+) -> i32 {
+    match definition {
+        Definition::Paragraph(paragraph) => paragraph
+            .compile_lines(Size::new(0, 0), false, fonts)
+            .content_width(),
+        Definition::Block(block) => block_min_content_width(block, containing_width, fonts),
+    }
+}
+
+/// The block version of [min_content_width]
+fn block_min_content_width(
+    block: &ElementDefinition,
+    containing_width: i32,
+    fonts: &FontRegistry<impl FontMetrics>,
+) -> i32 {
+    let font_size = block.style.font_size;
+    let content = match (block.style.width, &block.table) {
+        (Some(width), _) => width.to_px(font_size, containing_width),
+        (None, Some(table)) => table.min_content_width(containing_width, fonts),
+        (None, None) => block
+            .children
+            .iter()
+            .filter(|x| !is_out_of_flow(x))
+            .map(|x| min_content_width(x, containing_width, fonts))
+            .max()
+            .unwrap_or(0),
+    };
+    block
+        .style
+        .clamp_width(content, font_size, containing_width)
+        + horizontal_edges(block, containing_width)
+}
+
+/// The size of the box that percentages of a block are relative to
+#[derive(Copy, Clone, Debug)]
+struct ContainingBlock {
+    width: i32,
+    /// [None] if the height depends on the content
+    height: Option<i32>,
+}
+
+/// An absolutely positioned box that waits for the size of its containing block, which is only
+/// known once the nearest positioned ancestor has been laid out.
+#[derive(Debug)]
+struct AbsoluteBox {
+    block: ElementDefinition,
+    /// Where the box would have been placed in the normal flow. Used when the box has no offsets.
+    static_position: Position,
+}
+
+impl AbsoluteBox {
+    fn is_fixed(&self) -> bool {
+        self.block.style.position == CssPosition::Fixed
+    }
+}
+
+/// Returns how far a relatively positioned box is moved from its place in the flow. `left` wins
+/// over `right` and `top` wins over `bottom`.
+fn relative_offset(
+    style: &ComputedStyle,
+    font_size: f32,
+    containing_block: ContainingBlock,
+) -> Position {
+    let x = match (style.inset.left, style.inset.right) {
+        (Some(left), _) => left.to_px(font_size, containing_block.width),
+        (None, Some(right)) => -right.to_px(font_size, containing_block.width),
+        (None, None) => 0,
+    };
+    // Percentages can't be resolved against a height that depends on the content
+    let resolve_height = |unit: Unit| match (unit, containing_block.height) {
+        (v, None) if v.has_percentage() => 0,
+        (v, height) => v.to_px(font_size, height.unwrap_or(0)),
+    };
+    let y = match (style.inset.top, style.inset.bottom) {
+        (Some(top), _) => resolve_height(top),
+        (None, Some(bottom)) => -resolve_height(bottom),
+        (None, None) => 0,
+    };
+    Position::new(x, y)
+}
+
+/// Collapses two adjoining margins into the one that separates the boxes. The largest positive
+/// margin and the most negative margin are added together, so a negative margin pulls the boxes
+/// closer.
+fn collapse_margins(a: i32, b: i32) -> i32 {
+    a.max(b).max(0) + a.min(b).min(0)
+}
+
+/// Returns where the first item starts and how much space is put between the items when the free
+/// space of a flex line is distributed according to `justify-content`.
+fn distribute_free_space(alignment: Alignment, free_space: i32, item_count: usize) -> (i32, i32) {
+    let free_space = free_space.max(0);
+    let item_count = item_count.max(1) as i32;
+    match alignment {
+        Alignment::Start | Alignment::Stretch => (0, 0),
+        Alignment::End => (free_space, 0),
+        Alignment::Center => (free_space / 2, 0),
+        Alignment::SpaceBetween if item_count > 1 => (0, free_space / (item_count - 1)),
+        Alignment::SpaceBetween => (0, 0),
+        Alignment::SpaceAround => {
+            let space = free_space / item_count;
+            (space / 2, space)
+        }
+    }
+}
+
+/// Holds the layout of the html
+#[derive(Debug)]
+pub struct Layout {
+    pub paragraphs: Vec<Paragraph>,
+    /// Drawn below everything else. The backgrounds of parents come before the backgrounds of
+    /// their children.
+    pub backgrounds: Vec<Background>,
+    pub borders: Vec<Border>,
+    pub bars: Vec<Bar>,
+    /// Drawn before everything else. The images of parents come before the images of their
+    /// children.
+    pub background_images: Vec<BackgroundImage>,
+    /// The boxes that can be scrolled on their own. The inner boxes come before the boxes around
+    /// them.
+    pub scroll_boxes: Vec<ScrollBox>,
+    pub boxes: Vec<BlockBox>,
+    /// The index of the box that is being laid out in [Self::boxes]
+    current_box: Option<usize>,
+    /// Positioned boxes that haven't been laid out yet
+    absolute_boxes: Vec<AbsoluteBox>,
+    /// How far the scrollable boxes are scrolled, by the ids of their elements
+    scroll_offsets: HashMap<usize, i32>,
+    /// Set while a gigantic page is laid out
+    virtualization: Option<Virtualization>,
+}
+
+/// Keeps the layout of gigantic pages fast. Only the paragraphs near the screen are compiled, and
+/// the others just take up their estimated height. The heights of the compiled paragraphs are
+/// remembered, so the estimates get replaced with the real heights as the page is scrolled.
+#[derive(Debug, Default)]
+pub struct Virtualization {
+    /// The part of the page that is laid out properly, in the coordinates of the layout
+    pub visible: Range<i32>,
+    /// The heights of the compiled paragraphs by the id of their first element, the width and the
+    /// bits of the font size
+    heights: HashMap<(usize, i32, u32), i32>,
+}
+
+impl Virtualization {
+    /// Checks if the part of the page on the screen reaches outside of the part that was laid
+    /// out properly, so the page has to be laid out again. Always false for pages that aren't
+    /// gigantic.
+    pub fn is_outside(&self, screen: Range<i32>) -> bool {
+        !self.heights.is_empty()
+            && (screen.start < self.visible.start || screen.end > self.visible.end)
+    }
+
+    /// Returns the height of the paragraph if it's outside of the visible part of the page, so it
+    /// doesn't have to be compiled. The height is a guess if the paragraph hasn't been compiled
+    /// yet.
+    fn skipped_height(
+        &self,
+        paragraph: &ParagraphDefinition,
+        y: i32,
+        width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> Option<i32> {
+        let key = Self::key(paragraph, width)?;
+        let height = match self.heights.get(&key) {
+            Some(v) => *v,
+            None => estimate_height(paragraph, width, fonts),
+        };
+        let visible = y < self.visible.end && y + height > self.visible.start;
+        (!visible).then_some(height)
+    }
+
+    fn key(paragraph: &ParagraphDefinition, width: i32) -> Option<(usize, i32, u32)> {
+        let id = paragraph.sentences.first()?.element_id;
+        Some((id, width, paragraph.font_size.to_bits()))
+    }
+}
+
+/// Guesses the height of the paragraph without measuring its words
+fn estimate_height(
+    paragraph: &ParagraphDefinition,
+    width: i32,
+    fonts: &FontRegistry<impl FontMetrics>,
+) -> i32 {
+    let font = fonts.get(fonts.resolve(&paragraph.font_family));
+    let font_size = paragraph.font_size;
+    let line_height = paragraph
+        .line_height
+        .to_px(font_size, font.get_glyph_height(font_size));
+    let characters: usize = paragraph
+        .sentences
+        .iter()
+        .flat_map(|x| &x.words)
+        .map(|x| x.chars().count() + 1)
+        .sum();
+    let text_width = characters as i32 * font.get_glyph_width('n', font_size);
+    (text_width / width.max(1) + 1) * line_height
+}
+
+/// A block box after it has been laid out. The other lists of the layout are flat, so the boxes
+/// are what keeps the nesting and the sizes of the blocks. The boxes are in the order they were
+/// started in, so a box comes before the boxes inside of it.
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+pub struct BlockBox {
+    /// The id of the element of the box
+    pub id: usize,
+    pub tag: Tag,
+    /// The border box
+    pub rect: Rect,
+    /// The index of the box around this one in [Layout::boxes]
+    pub parent: Option<usize>,
+    /// See [Paragraph::fixed]
+    pub fixed: bool,
+    /// See [Paragraph::clip]
+    pub clip: Option<Rect>,
+}
+
+/// A heading of the page, see [Layout::outline]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    /// From 1 for `<h1>` to 6 for `<h6>`
+    pub level: u32,
+    /// The words of the heading separated by spaces
+    pub text: String,
+    /// The top of the heading on the page. Scrolling the page by this much brings the heading to
+    /// the top of the screen.
+    pub y: i32,
+    pub element_id: usize,
+}
+
+/// What is at a point of the page, see [Layout::hit_test]
+#[derive(Debug, Copy, Clone)]
+pub struct HitResult<'a> {
+    /// [None] if the point isn't on any text
+    pub word: Option<&'a Word>,
+    /// The sentence of [Self::word]
+    pub sentence: Option<&'a Sentence>,
+    /// Set if the word is a part of a link
+    pub href: Option<&'a str>,
+    /// The element of the word, or the element of the innermost box if no word was hit
+    pub element_id: Option<usize>,
+}
+
+/// A box with `overflow: scroll` or `overflow: auto`. Its content is already moved by the scroll
+/// offset in the layout.
+#[derive(Debug, Copy, Clone)]
+pub struct ScrollBox {
+    /// The id of the element of the box
+    pub id: usize,
+    /// The padding box, which is the part of the page that scrolls. It's clipped by the boxes
+    /// around it.
+    pub rect: Rect,
+    pub offset: i32,
+    /// How far the box can be scrolled, which is how much the content overflows it
+    pub max_offset: i32,
+    /// See [Paragraph::fixed]
+    pub fixed: bool,
+}
+
+/// The lengths of the lists of a [Layout] at some point, so the things that were added after it
+/// can be found
+#[derive(Debug, Copy, Clone, Default)]
+struct LayoutMark {
+    paragraphs: usize,
+    backgrounds: usize,
+    borders: usize,
+    bars: usize,
+    background_images: usize,
+    scroll_boxes: usize,
+    boxes: usize,
+    absolute_boxes: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self {
+            paragraphs: Vec::new(),
+            backgrounds: Vec::new(),
+            borders: Vec::new(),
+            bars: Vec::new(),
+            background_images: Vec::new(),
+            scroll_boxes: Vec::new(),
+            boxes: Vec::new(),
+            current_box: None,
+            absolute_boxes: Vec::new(),
+            scroll_offsets: HashMap::new(),
+            virtualization: None,
+        }
+    }
+
+    /// Creates an empty layout for laying out a part of this one separately
+    fn child_layout(&self) -> Self {
+        Self {
+            scroll_offsets: self.scroll_offsets.clone(),
+            ..Self::new()
+        }
+    }
+
+    pub fn make_relative_to(&mut self, position: Position) {
+        self.move_since(LayoutMark::default(), position);
+    }
+
+    fn mark(&self) -> LayoutMark {
+        LayoutMark {
+            paragraphs: self.paragraphs.len(),
+            backgrounds: self.backgrounds.len(),
+            borders: self.borders.len(),
+            bars: self.bars.len(),
+            background_images: self.background_images.len(),
+            scroll_boxes: self.scroll_boxes.len(),
+            boxes: self.boxes.len(),
+            absolute_boxes: self.absolute_boxes.len(),
+        }
+    }
+
+    /// Moves everything that was added after the mark
+    fn move_since(&mut self, mark: LayoutMark, by: Position) {
+        for paragraph in &mut self.paragraphs[mark.paragraphs..] {
+            paragraph.make_relative_to(by);
+        }
+        for background in &mut self.backgrounds[mark.backgrounds..] {
+            background.make_relative_to(by);
+        }
+        for border in &mut self.borders[mark.borders..] {
+            border.make_relative_to(by);
+        }
+        for bar in &mut self.bars[mark.bars..] {
+            bar.make_relative_to(by);
+        }
+        for image in &mut self.background_images[mark.background_images..] {
+            image.make_relative_to(by);
+        }
+        for scroll_box in &mut self.scroll_boxes[mark.scroll_boxes..] {
+            scroll_box.rect.position = scroll_box.rect.position + by;
+        }
+        for block_box in &mut self.boxes[mark.boxes..] {
+            block_box.rect.position = block_box.rect.position + by;
+            move_clip(&mut block_box.clip, by);
+        }
+        for absolute_box in &mut self.absolute_boxes[mark.absolute_boxes..] {
+            absolute_box.static_position = absolute_box.static_position + by;
+        }
+    }
+
+    /// Marks everything in this layout as fixed, so it doesn't move when the page is scrolled
+    fn make_fixed(&mut self) {
+        for paragraph in &mut self.paragraphs {
+            paragraph.fixed = true;
+        }
+        for background in &mut self.backgrounds {
+            background.fixed = true;
+        }
+        for border in &mut self.borders {
+            border.fixed = true;
+        }
+        for bar in &mut self.bars {
+            bar.fixed = true;
+        }
+        for image in &mut self.background_images {
+            image.fixed = true;
+        }
+        for scroll_box in &mut self.scroll_boxes {
+            scroll_box.fixed = true;
+        }
+        for block_box in &mut self.boxes {
+            block_box.fixed = true;
+        }
+    }
+
+    /// Clips everything that was added after the mark to the rect. Parts of the scroll boxes that
+    /// are clipped away can't be scrolled with the mouse either.
+    fn clip_since(&mut self, mark: LayoutMark, rect: Rect) {
+        for paragraph in &mut self.paragraphs[mark.paragraphs..] {
+            narrow_clip(&mut paragraph.clip, rect);
+        }
+        for background in &mut self.backgrounds[mark.backgrounds..] {
+            narrow_clip(&mut background.clip, rect);
+        }
+        for border in &mut self.borders[mark.borders..] {
+            narrow_clip(&mut border.clip, rect);
+        }
+        for bar in &mut self.bars[mark.bars..] {
+            narrow_clip(&mut bar.clip, rect);
+        }
+        for image in &mut self.background_images[mark.background_images..] {
+            narrow_clip(&mut image.clip, rect);
+        }
+        for block_box in &mut self.boxes[mark.boxes..] {
+            narrow_clip(&mut block_box.clip, rect);
+        }
+        for scroll_box in &mut self.scroll_boxes[mark.scroll_boxes..] {
+            scroll_box.rect = scroll_box
+                .rect
+                .intersection(&rect)
+                .unwrap_or(Rect::new(rect.position, Size::new(0, 0)));
+        }
+    }
+
+    /// Returns the bottom edge of the lowest thing that was added after the mark
+    fn bottom_since(&self, mark: LayoutMark) -> Option<i32> {
+        let paragraphs = self.paragraphs[mark.paragraphs..].iter().filter_map(|x| {
+            x.sentences
+                .iter()
+                .flat_map(|x| &x.words)
+                .map(|x| x.position.y + x.height)
+                .max()
+        });
+        let backgrounds = self.backgrounds[mark.backgrounds..]
+            .iter()
+            .map(|x| x.rect.position.y + x.rect.size.height);
+        let borders = self.borders[mark.borders..]
+            .iter()
+            .map(|x| x.rect.position.y + x.rect.size.height);
+        let bars = self.bars[mark.bars..]
+            .iter()
+            .map(|x| x.rect.position.y + x.rect.size.height);
+        let images = self.background_images[mark.background_images..]
+            .iter()
+            .map(|x| x.rect.position.y + x.rect.size.height);
+        paragraphs
+            .chain(backgrounds)
+            .chain(borders)
+            .chain(bars)
+            .chain(images)
+            .max()
+    }
+
+    /// Returns what is at the point, which is the word under it if there is one and the innermost
+    /// box otherwise. The content that is drawn last is on top, so it's checked first. Clipped
+    /// content can't be hit.
+    ///
+    /// * `scroll` - How far the page is scrolled, see [crate::tab::Tab::scroll]
+    pub fn hit_test(&self, x: i32, y: i32, scroll: Position) -> Option<HitResult<'_>> {
+        for paragraph in self.paragraphs.iter().rev() {
+            // Fixed paragraphs don't move with the page
+            let (x, y) = match paragraph.fixed {
+                true => (x, y),
+                false => (x - scroll.x, y - scroll.y),
+            };
+            if paragraph.clip.is_some_and(|c| !c.contains(x, y)) {
+                continue;
+            }
+            for sentence in &paragraph.sentences {
+                if let Some(word) = sentence.words.iter().find(|w| w.is_position_inside(x, y)) {
+                    return Some(HitResult {
+                        word: Some(word),
+                        sentence: Some(sentence),
+                        href: sentence.href.as_deref(),
+                        element_id: sentence.element_id,
+                    });
+                }
+            }
+        }
+        // Outside of the text the block under the point is hit, so its padding counts too
+        self.box_at(x, y, scroll).map(|x| HitResult {
+            word: None,
+            sentence: None,
+            href: None,
+            element_id: Some(x.id),
+        })
+    }
+
+    /// Returns the innermost box at the point. Fixed boxes are checked against the point on the
+    /// screen and the others against the point on the page.
+    ///
+    /// * `scroll` - How far the page is scrolled, see [crate::tab::Tab::scroll]
+    pub fn box_at(&self, x: i32, y: i32, scroll: Position) -> Option<&BlockBox> {
+        self.boxes.iter().rev().find(|x_box| {
+            let (x, y) = match x_box.fixed {
+                true => (x, y),
+                false => (x - scroll.x, y - scroll.y),
+            };
+            x_box.rect.contains(x, y) && x_box.clip.is_none_or(|c| c.contains(x, y))
+        })
+    }
+
+    /// Returns the y of the bottom edge of the content that scrolls with the page. The fixed
+    /// content is left out, since it stays on the screen anyway.
+    pub fn bottom(&self) -> i32 {
+        let boxes = self
+            .boxes
+            .iter()
+            .filter(|x| !x.fixed)
+            .map(|x| x.rect.position.y + x.rect.size.height);
+        let words = self
+            .paragraphs
+            .iter()
+            .filter(|x| !x.fixed)
+            .flat_map(|x| &x.sentences)
+            .flat_map(|x| &x.words)
+            .map(|x| x.position.y + x.height);
+        boxes.chain(words).max().unwrap_or(0)
+    }
+
+    /// Returns the headings of the page in the order they are on the page. Fixed headings are left
+    /// out, since they can't be scrolled to.
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        self.boxes
+            .iter()
+            .filter(|x| !x.fixed)
+            .filter_map(|heading| {
+                let Tag::H(level) = heading.tag else {
+                    return None;
+                };
+                let words: Vec<&str> = self
+                    .paragraphs
+                    .iter()
+                    .filter(|x| !x.fixed)
+                    .flat_map(|x| &x.sentences)
+                    .flat_map(|x| &x.words)
+                    .filter(|x| heading.rect.contains(x.position.x, x.position.y))
+                    .map(|x| x.word.as_str())
+                    .collect();
+                Some(OutlineEntry {
+                    level,
+                    text: words.join(" "),
+                    y: heading.rect.position.y,
+                    element_id: heading.id,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the x of the right edge of the content that scrolls with the page, see
+    /// [Self::bottom]
+    pub fn right(&self) -> i32 {
+        let boxes = self
+            .boxes
+            .iter()
+            .filter(|x| !x.fixed)
+            .map(|x| x.rect.position.x + x.rect.size.width);
+        let words = self
+            .paragraphs
+            .iter()
+            .filter(|x| !x.fixed)
+            .flat_map(|x| &x.sentences)
+            .flat_map(|x| &x.words)
+            .map(|x| x.position.x + x.width);
+        boxes.chain(words).max().unwrap_or(0)
+    }
+
+    /// Returns the lines of all of the paragraphs in the order they are drawn in, with the
+    /// paragraph each line is in
+    pub fn line_boxes(&self) -> impl Iterator<Item = (&Paragraph, LineBox)> {
+        self.paragraphs.iter().flat_map(|paragraph| {
+            paragraph
+                .line_boxes()
+                .into_iter()
+                .map(move |line| (paragraph, line))
+        })
+    }
+
+    /// Returns where the element is on the page. Blocks are found by their boxes and inline
+    /// elements by their first word.
+    pub fn position_of(&self, element_id: usize) -> Option<Position> {
+        let block = self.boxes.iter().find(|x| x.id == element_id);
+        if let Some(block) = block {
+            return Some(block.rect.position);
+        }
+        self.paragraphs
+            .iter()
+            .flat_map(|x| &x.sentences)
+            .filter(|x| x.element_id == Some(element_id))
+            .find_map(|x| x.words.first())
+            .map(|x| x.position)
+    }
+
+    /// Moves the contents of the other layout into this one
+    pub fn append(&mut self, mut other: Layout, position: Position) {
+        other.make_relative_to(position);
+        self.paragraphs.extend(other.paragraphs);
+        self.backgrounds.extend(other.backgrounds);
+        self.borders.extend(other.borders);
+        self.bars.extend(other.bars);
+        self.background_images.extend(other.background_images);
+        self.scroll_boxes.extend(other.scroll_boxes);
+        // The boxes of the other layout go inside of the box that is being laid out
+        let offset = self.boxes.len();
+        for mut block_box in other.boxes {
+            block_box.parent = block_box.parent.map(|x| x + offset).or(self.current_box);
+            self.boxes.push(block_box);
+        }
+        self.absolute_boxes.extend(other.absolute_boxes);
+    }
+
+    /// Lays out the body of a page
+    ///
+    /// * `scroll_offsets` - How far the scrollable boxes are scrolled, by the ids of their
+    ///   elements. See [ScrollBox].
+    ///
+    /// * `virtualization` - Used if the page is gigantic. It should be kept between the layouts
+    ///   of the same page.
+    pub fn from_body(
+        element: &Element,
+        viewport_size: Size,
+        fonts: &FontRegistry<impl FontMetrics>,
+        scroll_offsets: &HashMap<usize, i32>,
+        virtualization: &mut Virtualization,
+    ) -> Self {
+        let mut layout = Self::new();
+        layout.scroll_offsets = scroll_offsets.clone();
+
+        if element.style.display == DisplayStyle::None {
+            return layout;
+        }
+
+        let body = collect_definition(element);
+        if count_paragraphs(&body) > MIN_VIRTUALIZED_PARAGRAPHS {
+            layout.virtualization = Some(std::mem::take(virtualization));
+        }
+        let font_size = body.style.font_size;
+        let margin = body.style.margin.to_px(font_size, viewport_size.width);
+        let width = body.style.border_box_width(
+            viewport_size.width - margin.horizontal(),
+            font_size,
+            viewport_size.width,
+        );
+        let containing_block = ContainingBlock {
+            width: viewport_size.width,
+            height: None,
+        };
+        layout.layout_block(
+            &body,
+            Position::new(margin.left, margin.top),
+            width,
+            0,
+            containing_block,
+            fonts,
+        );
+
+        // Boxes without a positioned ancestor are placed against the viewport. Laying them out can
+        // leave fixed boxes from inside of them, so this goes on until none are left.
+        let viewport = Rect::new(Position::new(0, 0), viewport_size);
+        while !layout.absolute_boxes.is_empty() {
+            layout.layout_absolute_boxes(0, viewport, true, fonts);
+        }
+
+        if let Some(v) = layout.virtualization.take() {
+            *virtualization = v;
+        }
+        layout
+    }
+
+    /// Lays out a block and all of its children. The content of the block is placed inside of its
+    /// border and padding.
+    ///
+    /// * `position` - The top left corner of the border box of this block
+    ///
+    /// * `width` - The width of the border box of this block
+    ///
+    /// * `min_height` - The minimum height of the border box, used when the block is stretched
+    ///
+    /// * `containing_block` - The content box of the parent
+    ///
+    /// Returns the height of the border box. Content that doesn't fit into a block with a fixed
+    /// height overflows it.
+    fn layout_block(
+        &mut self,
+        block: &ElementDefinition,
+        position: Position,
+        width: i32,
+        min_height: i32,
+        containing_block: ContainingBlock,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        let mark = self.mark();
+        let box_index = self.boxes.len();
+        self.boxes.push(BlockBox {
+            id: block.id,
+            tag: block.tag,
+            rect: Rect::new(position, Size::new(width, 0)),
+            parent: self.current_box,
+            fixed: false,
+            clip: None,
+        });
+        let parent_box = self.current_box.replace(box_index);
+        let containing_height = containing_block.height;
+        let font_size = block.style.font_size;
+        let padding = block.style.padding.to_px(font_size, width);
+        let border = block.style.border_widths(font_size);
+        let content_position =
+            position + Position::new(border.left + padding.left, border.top + padding.top);
+        let content_width = width - border.horizontal() - padding.horizontal();
+        let definite_height = block.style.definite_height(font_size, containing_height);
+
+        let layout = (
+            &block.table,
+            block.style.display,
+            block.style.flex_direction,
+        );
+        let content_height = match layout {
+            (Some(table), _, _) => self.layout_table(table, content_position, content_width, fonts),
+            (None, DisplayStyle::Flex, FlexDirection::Row) => {
+                self.layout_flex_row(block, content_position, content_width, fonts)
+            }
+            (None, DisplayStyle::Flex, FlexDirection::Column) => {
+                self.layout_flex_column(block, content_position, content_width, fonts)
+            }
+            _ => self.layout_flow(
+                block,
+                content_position,
+                content_width,
+                definite_height,
+                fonts,
+            ),
+        };
+        let content_height = definite_height.unwrap_or_else(|| {
+            block
+                .style
+                .clamp_height(content_height, font_size, containing_height)
+        });
+
+        let height = (content_height + padding.vertical() + border.vertical()).max(min_height);
+        self.boxes[box_index].rect.size.height = height;
+        let padding_box = Rect::new(
+            position + Position::new(border.left, border.top),
+            Size::new(width - border.horizontal(), height - border.vertical()),
+        );
+        if block.style.overflow != Overflow::Visible {
+            self.clip_overflow(block, mark, padding_box, padding.bottom);
+        }
+        let visible = block.style.is_visible();
+        if let Some(color) = block.style.background_color.filter(|_| visible) {
+            // The background goes below the backgrounds of the children, which were added already
+            self.backgrounds.insert(
+                mark.backgrounds,
+                Background {
+                    rect: Rect::new(position, Size::new(width, height)),
+                    color,
+                    opacity: block.style.opacity,
+                    fixed: false,
+                    clip: None,
+                },
+            );
+        }
+        if let Some(image) = block.style.background_image.as_ref().filter(|_| visible) {
+            // The image goes below the images of the children, which were added already
+            self.background_images.insert(
+                mark.background_images,
+                BackgroundImage {
+                    rect: padding_box,
+                    image: image.clone(),
+                    repeat: block.style.background_repeat,
+                    size: block.style.background_size,
+                    position: block.style.background_position,
+                    font_size,
+                    opacity: block.style.opacity,
+                    fixed: false,
+                    clip: None,
+                },
+            );
+        }
+        if border != Edges::all(0) && visible {
+            self.borders.push(Border {
+                rect: Rect::new(position, Size::new(width, height)),
+                widths: border,
+                color: block.style.border_color,
+                opacity: block.style.opacity,
+                fixed: false,
+                clip: None,
+            });
+        }
+        if let Some(gauge) = block.gauge.filter(|_| visible) {
+            self.bars.push(Bar {
+                rect: Rect::new(content_position, Size::new(content_width, content_height)),
+                gauge,
+                opacity: block.style.opacity,
+                fixed: false,
+                clip: None,
+            });
+        }
+        if let Some(media) = block.media.as_ref().filter(|_| visible) {
+            let content_box = Rect::new(content_position, Size::new(content_width, content_height));
+            self.layout_media_controls(media, content_box, &block.style, mark);
+        }
+        if let Some(image) = block.image.as_ref().filter(|_| visible) {
+            self.background_images.push(BackgroundImage {
+                rect: Rect::new(content_position, Size::new(content_width, content_height)),
+                image: Image::Url(image.clone()),
+                repeat: BackgroundRepeat::NoRepeat,
+                size: BackgroundSize::Size(
+                    Some(Unit::Percentage(100)),
+                    Some(Unit::Percentage(100)),
+                ),
+                position: (Unit::Px(0), Unit::Px(0)),
+                font_size,
+                opacity: block.style.opacity,
+                fixed: false,
+                clip: None,
+            });
+        }
+        if let Some(marker) = &block.marker {
+            self.layout_marker(marker, mark, content_position, fonts);
+        }
+
+        // A transformed box holds its absolutely positioned descendants too, so they move with it
+        let transformed = block.style.transform != Translate::default();
+        if block.style.position != CssPosition::Static || transformed {
+            // The padding box is the containing block of the absolutely positioned descendants
+            self.layout_absolute_boxes(mark.absolute_boxes, padding_box, false, fonts);
+        }
+        if block.style.position == CssPosition::Relative {
+            let offset = relative_offset(&block.style, font_size, containing_block);
+            self.move_since(mark, offset);
+        }
+        if transformed {
+            let (x, y) = block.style.transform.to_px(font_size, width, height);
+            self.move_since(mark, Position::new(x, y));
+        }
+        self.current_box = parent_box;
+        height
+    }
+
+    /// Adds the poster and the controls bar of a media placeholder. The poster is fit into the
+    /// box below the label, and the bar is at the bottom of the box. The bar stays empty, since
+    /// the duration of the media isn't known.
+    ///
+    /// * `mark` - Where the content of the box starts in the layout
+    fn layout_media_controls(
+        &mut self,
+        media: &MediaPlaceholder,
+        content_box: Rect,
+        style: &ComputedStyle,
+        mark: LayoutMark,
+    ) {
+        if let Some(poster) = &media.poster {
+            self.background_images.insert(
+                mark.background_images,
+                BackgroundImage {
+                    rect: content_box,
+                    image: Image::Url(poster.clone()),
+                    repeat: BackgroundRepeat::NoRepeat,
+                    size: BackgroundSize::Contain,
+                    position: (Unit::Percentage(50), Unit::Percentage(50)),
+                    font_size: style.font_size,
+                    opacity: style.opacity,
+                    fixed: false,
+                    clip: None,
+                },
+            );
+        }
+        let bar_height = (style.font_size as i32 / 2).min(content_box.size.height);
+        self.bars.push(Bar {
+            rect: Rect::new(
+                content_box.position + Position::new(0, content_box.size.height - bar_height),
+                Size::new(content_box.size.width, bar_height),
+            ),
+            gauge: Gauge {
+                fraction: 0.0,
+                color: style.color,
+            },
+            opacity: style.opacity,
+            fixed: false,
+            clip: None,
+        });
+    }
+
+    /// Places the marker of a list item outside of it, on the left side of its first line
+    ///
+    /// * `mark` - Where the content of the list item starts in the layout
+    fn layout_marker(
+        &mut self,
+        marker: &ParagraphDefinition,
+        mark: LayoutMark,
+        content_position: Position,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) {
+        let mut paragraph = marker.compile(Size::new(i32::MAX / 2, 0), fonts);
+        let gap = fonts
+            .get(&paragraph.font_family)
+            .get_glyph_width(' ', marker.font_size);
+        let first_word = |paragraphs: &[Paragraph]| {
+            paragraphs
+                .iter()
+                .flat_map(|x| &x.sentences)
+                .flat_map(|x| &x.words)
+                .map(|x| x.position.y)
+                .next()
+        };
+        // The marker is lined up with the first line of text, or the top of the item if it has
+        // no text
+        let marker_y = first_word(std::slice::from_ref(&paragraph)).unwrap_or(0);
+        let y = match first_word(&self.paragraphs[mark.paragraphs..]) {
+            Some(v) => v - marker_y,
+            None => content_position.y,
+        };
+        let x = content_position.x - paragraph.content_width() - gap;
+        paragraph.make_relative_to(Position::new(x, y));
+        self.paragraphs.push(paragraph);
+    }
+
+    /// Clips the content of a box whose `overflow` isn't `visible` to its padding box. The content
+    /// of a scrollable box is moved by its scroll offset.
+    ///
+    /// * `mark` - Where the content of the box starts in the layout
+    ///
+    /// * `padding_bottom` - The bottom padding, which is scrolled into view after the content
+    fn clip_overflow(
+        &mut self,
+        block: &ElementDefinition,
+        mark: LayoutMark,
+        padding_box: Rect,
+        padding_bottom: i32,
+    ) {
+        if block.style.overflow.is_scrollable() {
+            let box_bottom = padding_box.position.y + padding_box.size.height;
+            let content_bottom = self.bottom_since(mark).unwrap_or(box_bottom) + padding_bottom;
+            let max_offset = (content_bottom - box_bottom).max(0);
+            let offset = self
+                .scroll_offsets
+                .get(&block.id)
+                .map_or(0, |x| (*x).clamp(0, max_offset));
+            self.move_since(mark, Position::new(0, -offset));
+            // The inner scroll boxes were added already, so this one goes after them
+            self.scroll_boxes.push(ScrollBox {
+                id: block.id,
+                rect: padding_box,
+                offset,
+                max_offset,
+                fixed: false,
+            });
+        }
+        self.clip_since(mark, padding_box);
+    }
+
+    /// Lays out the absolutely positioned boxes that were added after `start`.
+    ///
+    /// * `containing_block` - The padding box of the positioned ancestor
+    ///
+    /// * `include_fixed` - Fixed boxes are only placed when this is set, because they are always
+    ///   positioned against the viewport
+    fn layout_absolute_boxes(
+        &mut self,
+        start: usize,
+        containing_block: Rect,
+        include_fixed: bool,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) {
+        for absolute_box in self.absolute_boxes.split_off(start) {
+            if absolute_box.is_fixed() && !include_fixed {
+                self.absolute_boxes.push(absolute_box);
+            } else {
+                self.layout_absolute_box(&absolute_box, containing_block, fonts);
+            }
+        }
+    }
+
+    /// Places an absolutely positioned box with its offsets. A box without a `width` shrinks to
+    /// fit its content unless both `left` and `right` are set. Sides without an offset are taken
+    /// from the static position.
+    fn layout_absolute_box(
+        &mut self,
+        item: &AbsoluteBox,
+        containing_block: Rect,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) {
+        let block = &item.block;
+        let style = &block.style;
+        let font_size = block.style.font_size;
+        let size = containing_block.size;
+        let margin = style.margin.to_px(font_size, size.width);
+        let left = style.inset.left.map(|x| x.to_px(font_size, size.width));
+        let right = style.inset.right.map(|x| x.to_px(font_size, size.width));
+        // Unlike margins, the percentages of the top and bottom are relative to the height
+        let top = style.inset.top.map(|x| x.to_px(font_size, size.height));
+        let bottom = style.inset.bottom.map(|x| x.to_px(font_size, size.height));
+
+        let available = size.width - left.unwrap_or(0) - right.unwrap_or(0) - margin.horizontal();
+        let width = if style.width.is_none() && (left.is_none() || right.is_none()) {
+            (block_max_content_width(block, size.width, fonts) - margin.horizontal()).min(available)
+        } else {
+            style.border_box_width(available, font_size, size.width)
+        };
+        // A box that is anchored to the top and the bottom is stretched between them
+        let min_height = match (style.height, top, bottom) {
+            (None, Some(top), Some(bottom)) => size.height - top - bottom - margin.vertical(),
+            _ => 0,
+        };
+
+        let mut layout = self.child_layout();
+        let containing = ContainingBlock {
+            width: size.width,
+            height: Some(size.height),
+        };
+        let height = layout.layout_block(
+            block,
+            Position::new(0, 0),
+            width,
+            min_height,
+            containing,
+            fonts,
+        );
+
+        let origin = containing_block.position;
+        let x = match (left, right) {
+            (Some(left), _) => origin.x + left + margin.left,
+            (None, Some(right)) => origin.x + size.width - right - margin.right - width,
+            (None, None) => item.static_position.x + margin.left,
+        };
+        let y = match (top, bottom) {
+            (Some(top), _) => origin.y + top + margin.top,
+            (None, Some(bottom)) => origin.y + size.height - bottom - margin.bottom - height,
+            (None, None) => item.static_position.y + margin.top,
+        };
+        if item.is_fixed() {
+            layout.make_fixed();
+        }
+        self.append(layout, Position::new(x, y));
+    }
+
+    /// Takes the positioned children out of the items of a flex container. Their static position
+    /// is the start of the container.
+    fn in_flow_items<'a>(
+        &mut self,
+        block: &'a ElementDefinition,
+        content_position: Position,
+    ) -> Vec<&'a Definition> {
+        let mut items = Vec::new();
+        for child in &block.children {
+            match child {
+                Definition::Block(child) if child.style.position.is_out_of_flow() => {
+                    self.absolute_boxes.push(AbsoluteBox {
+                        block: child.as_ref().clone(),
+                        static_position: content_position,
+                    });
+                }
+                _ => items.push(child),
+            }
+        }
+        items
+    }
+
+    /// Stacks the children of the block on top of each other. Returns the height of the content.
+    ///
+    /// * `content_height` - The height of the content box if it doesn't depend on the children
+    fn layout_flow(
+        &mut self,
+        block: &ElementDefinition,
+        content_position: Position,
+        content_width: i32,
+        content_height: Option<i32>,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        // The minimum space between two children, so text doesn't get glued together when there
+        // are no margins.
+        let spacing: i32 =
+            (fonts.default_font().get_glyph_height(DEFAULT_FONT_SIZE) as f32 / 2.0) as i32;
+
+        // The paragraphs don't depend on each other, only their positions depend on what comes
+        // before them. A virtualized page only compiles the paragraphs near the screen, and those
+        // are only known when the children are gone through in order.
+        let mut compiled = match self.virtualization {
+            None => compile_paragraphs(&block.children, content_width, fonts),
+            Some(_) => Vec::new(),
+        }
+        .into_iter();
+
+        let mut y = 0;
+        // The bottom margin of the previous child. Margins of adjacent siblings collapse, see
+        // [collapse_margins].
+        let mut previous_margin = None;
+        for child in &block.children {
+            match child {
+                // Positioned boxes don't take any space in the flow
+                Definition::Block(child) if child.style.position.is_out_of_flow() => {
+                    self.absolute_boxes.push(AbsoluteBox {
+                        block: child.as_ref().clone(),
+                        static_position: content_position + Position::new(0, y),
+                    });
+                }
+
+                Definition::Paragraph(paragraph) => {
+                    if let Some(margin) = previous_margin {
+                        y += spacing.max(margin);
+                    }
+                    previous_margin = Some(0);
+                    let skipped_height = self.virtualization.as_ref().and_then(|x| {
+                        x.skipped_height(paragraph, content_position.y + y, content_width, fonts)
+                    });
+                    if let Some(height) = skipped_height {
+                        y += height;
+                        continue;
+                    }
+                    let definition = paragraph;
+                    let mut paragraph = compiled
+                        .next()
+                        .unwrap_or_else(|| paragraph.compile(Size::new(content_width, 0), fonts));
+                    paragraph.make_relative_to(content_position + Position::new(0, y));
+                    y += paragraph.height;
+                    if let Some(virtualization) = self.virtualization.as_mut() {
+                        if let Some(key) = Virtualization::key(definition, content_width) {
+                            virtualization.heights.insert(key, paragraph.height);
+                        }
+                    }
+                    self.paragraphs.push(paragraph);
+                }
+
+                Definition::Block(child) => {
+                    let font_size = child.style.font_size;
+                    let margin = child.style.margin.to_px(font_size, content_width);
+                    // A child that is wider than this block overflows it on the right
+                    let mut width = child.style.border_box_width(
+                        content_width - margin.horizontal(),
+                        font_size,
+                        content_width,
+                    );
+                    // A table without a width is only as wide as its content needs
+                    if child.table.is_some() && child.style.width.is_none() {
+                        let max_content = block_max_content_width(child, content_width, fonts);
+                        width = width.min(max_content - margin.horizontal());
+                    }
+                    y += match previous_margin {
+                        Some(previous_margin) => {
+                            collapse_margins(spacing.max(previous_margin), margin.top)
+                        }
+                        None => margin.top,
+                    };
+                    let height = self.layout_block(
+                        child,
+                        content_position + Position::new(margin.left, y),
+                        width,
+                        0,
+                        ContainingBlock {
+                            width: content_width,
+                            height: content_height,
+                        },
+                        fonts,
+                    );
+                    y += height;
+                    previous_margin = Some(margin.bottom);
+                }
+            }
+        }
+        // The margin of the last child stays inside of this block
+        y + previous_margin.unwrap_or(0)
+    }
+
+    /// Lays out the cells of a table as blocks. The widths of the columns come from
+    /// [TableDefinition::column_widths], and the rows are as tall as their tallest cell. A cell
+    /// that covers several rows makes the last one taller if it doesn't fit into them otherwise.
+    /// Returns the height of the table.
+    ///
+    /// * `width` - The width of the content box of the table
+    fn layout_table(
+        &mut self,
+        table: &TableDefinition,
+        position: Position,
+        width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        let widths = table.column_widths(width, fonts);
+        let mut lefts = Vec::new();
+        let mut x = 0;
+        for width in &widths {
+            lefts.push(x);
+            x += width + table.spacing;
+        }
+
+        let mut y = 0;
+        // The bottoms of the cells that cover several rows, with the index of their last row
+        let mut spanning_bottoms = Vec::new();
+        for (row_index, row) in table.rows.iter().enumerate() {
+            let mut bottom = y;
+            for cell in &row.cells {
+                let last = cell.column + cell.columns - 1;
+                let width = lefts[last] + widths[last] - lefts[cell.column];
+                let mark = self.mark();
+                let height = self.layout_block(
+                    &cell.block,
+                    position + Position::new(lefts[cell.column], y),
+                    width,
+                    0,
+                    ContainingBlock {
+                        width,
+                        height: None,
+                    },
+                    fonts,
+                );
+                // The text of header cells is centered
+                let free_space = width - block_max_content_width(&cell.block, width, fonts);
+                if cell.block.tag == Tag::Th && free_space > 0 {
+                    let offset = Position::new(free_space / 2, 0);
+                    for paragraph in &mut self.paragraphs[mark.paragraphs..] {
+                        for sentence in &mut paragraph.sentences {
+                            sentence.make_relative_to(offset);
+                        }
+                    }
+                }
+                match cell.rows {
+                    1 => bottom = bottom.max(y + height),
+                    rows => spanning_bottoms.push((row_index + rows - 1, y + height)),
+                }
+            }
+            for (last_row, cell_bottom) in &spanning_bottoms {
+                if *last_row == row_index {
+                    bottom = bottom.max(*cell_bottom);
+                }
+            }
+            y = bottom;
+        }
+        y
+    }
+
+    /// Lays out a single flex item into a layout of its own, positioned at 0, 0.
+    ///
+    /// * `width` - The width of the margin box of the item
+    ///
+    /// * `min_height` - The minimum height of the margin box of the item
+    ///
+    /// * `containing_width` - The width of the content box of the flex container
+    ///
+    /// Returns the layout and the height of the margin box.
+    fn layout_flex_item(
+        &self,
+        item: &Definition,
+        width: i32,
+        min_height: i32,
+        containing_width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> (Layout, i32) {
+        let mut layout = self.child_layout();
+        let height = match item {
+            Definition::Paragraph(paragraph) => {
+                let paragraph = paragraph.compile(Size::new(width, 0), fonts);
+                let height = paragraph.height;
+                layout.paragraphs.push(paragraph);
+                height
+            }
+            Definition::Block(block) => {
+                // Margins of flex items never collapse, so they are simply a part of the item
+                let margin = block.style.margin.to_px(block.style.font_size, width);
+                let height = layout.layout_block(
+                    block,
+                    Position::new(margin.left, margin.top),
+                    width - margin.horizontal(),
+                    min_height - margin.vertical(),
+                    ContainingBlock {
+                        width: containing_width,
+                        height: None,
+                    },
+                    fonts,
+                );
+                height + margin.vertical()
+            }
+        };
+        (layout, height)
+    }
+
+    /// Places the children of a flex container side by side on a single line. Returns the height
+    /// of the content.
+    fn layout_flex_row(
+        &mut self,
+        block: &ElementDefinition,
+        content_position: Position,
+        content_width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        let items = self.in_flow_items(block, content_position);
+        let style = &block.style;
+
+        // Every item starts from the width of its content and then grows or shrinks to fit the line
+        let mut widths: Vec<i32> = items
+            .iter()
+            .map(|x| max_content_width(x, content_width, fonts).min(content_width))
+            .collect();
+        let grow_factors: Vec<i32> = items
+            .iter()
+            .map(|x| match x {
+                Definition::Block(block) => block.style.flex_grow.max(0),
+                _ => 0,
+            })
+            .collect();
+        let total_width: i32 = widths.iter().sum();
+        let total_grow: i32 = grow_factors.iter().sum();
+        let mut free_space = content_width - total_width;
+        if free_space > 0 && total_grow > 0 {
+            for (width, grow) in widths.iter_mut().zip(&grow_factors) {
+                *width += free_space * grow / total_grow;
+            }
+            free_space = 0;
+        } else if free_space < 0 {
+            // Items shrink in proportion to their width, like with the default `flex-shrink: 1`
+            for width in widths.iter_mut() {
+                *width += free_space * *width / total_width;
+            }
+            free_space = 0;
+        }
+        for (width, item) in widths.iter_mut().zip(&items) {
+            *width = clamp_item_width(item, *width, content_width);
+        }
+
+        let mut item_layouts: Vec<(Layout, i32)> = items
+            .iter()
+            .zip(&widths)
+            .map(|(item, width)| self.layout_flex_item(item, *width, 0, content_width, fonts))
+            .collect();
+        let line_height = item_layouts.iter().map(|x| x.1).max().unwrap_or(0);
+
+        // Stretched items have to be laid out again, so their boxes cover the entire line
+        if style.align_items == Alignment::Stretch {
+            for (index, item) in items.iter().enumerate() {
+                if item_layouts[index].1 < line_height && matches!(item, Definition::Block(_)) {
+                    item_layouts[index] = self.layout_flex_item(
+                        item,
+                        widths[index],
+                        line_height,
+                        content_width,
+                        fonts,
+                    );
+                }
+            }
+        }
+
+        let (mut x, gap) = distribute_free_space(style.justify_content, free_space, items.len());
+        for ((layout, height), width) in item_layouts.into_iter().zip(widths) {
+            let y = match style.align_items {
+                Alignment::Center => (line_height - height) / 2,
+                Alignment::End => line_height - height,
+                _ => 0,
+            };
+            self.append(layout, content_position + Position::new(x, y));
+            x += width + gap;
+        }
+        line_height
+    }
+
+    /// Stacks the children of a flex container on top of each other. Returns the height of the
+    /// content.
+    fn layout_flex_column(
+        &mut self,
+        block: &ElementDefinition,
+        content_position: Position,
+        content_width: i32,
+        fonts: &FontRegistry<impl FontMetrics>,
+    ) -> i32 {
+        let mut y = 0;
+        for item in self.in_flow_items(block, content_position) {
+            // Items with a width of their own aren't stretched, and they can overflow the container
+            let width = if has_definite_width(item) {
+                max_content_width(item, content_width, fonts)
+            } else if block.style.align_items == Alignment::Stretch {
+                content_width
+            } else {
+                max_content_width(item, content_width, fonts).min(content_width)
+            };
+            let width = clamp_item_width(item, width, content_width);
+            let x = match block.style.align_items {
+                Alignment::Center => (content_width - width) / 2,
+                Alignment::End => content_width - width,
+                _ => 0,
+            };
+            let (layout, height) = self.layout_flex_item(item, width, 0, content_width, fonts);
+            self.append(layout, content_position + Position::new(x, y));
+            y += height;
+        }
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::{DisplayItemKind, DisplayList};
+    use crate::document::Document;
+    use crate::font::{MockFont, DEFAULT_FONT_FAMILY};
+    use crate::html::parse_html;
+    use crate::media::{ColorScheme, MediaEnvironment};
+
+    fn mock_fonts() -> FontRegistry<MockFont> {
+        FontRegistry::new(DEFAULT_FONT_FAMILY, MockFont)
+    }
+
+    /// Lays out the body of the page with [MockFont]
+    fn layout_of(html: &str, width: i32) -> Layout {
+        virtualized_layout_of(html, width, &mut Virtualization::default())
+    }
+
+    fn virtualized_layout_of(
+        html: &str,
+        width: i32,
+        virtualization: &mut Virtualization,
+    ) -> Layout {
+        zoomed_layout_of(html, width, 1.0, virtualization)
+    }
+
+    fn zoomed_layout_of(
+        html: &str,
+        width: i32,
+        page_zoom: f32,
+        virtualization: &mut Virtualization,
+    ) -> Layout {
+        let mut document = Document::new(parse_html(html), Vec::new());
+        document.parse_inline_css();
+        document.parse_style_sheets();
+        document.update_styles(MediaEnvironment {
+            width,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            forced_colors: None,
+            text_zoom: 1.0,
+            page_zoom,
+        });
+        let body = document.elements[0]
+            .children
+            .iter()
+            .find(|x| x.element_type == Tag::Body)
+            .unwrap();
+        Layout::from_body(
+            body,
+            Size::new(width, 1000),
+            &mock_fonts(),
+            &HashMap::new(),
+            virtualization,
+        )
+    }
+
+    /// Returns the words of the paragraph with their positions
+    fn words(paragraph: &Paragraph) -> Vec<(&str, i32, i32)> {
+        paragraph
+            .sentences
+            .iter()
+            .flat_map(|x| &x.words)
+            .map(|x| (x.word.as_str(), x.position.x, x.position.y))
+            .collect()
+    }
+
+    #[test]
+    fn wraps_words_at_the_width_of_the_viewport() {
+        let layout = layout_of("<html><body><div>aaaa bbbb cccc</div></body></html>", 200);
+        assert_eq!(layout.paragraphs.len(), 1);
+
+        // Every word is 4 * 20px wide with 10px between the words
+        let paragraph = &layout.paragraphs[0];
+        assert_eq!(
+            words(paragraph),
+            [("aaaa", 0, 0), ("bbbb", 90, 0), ("cccc", 0, 40)]
+        );
+        assert_eq!(paragraph.height, 80);
+    }
+
+    #[test]
+    fn text_without_spaces_wraps_between_its_characters() {
+        // Chinese and Japanese text can be broken after any character, even in the middle of a
+        // line, but a non-breaking space keeps its words together
+        let layout = layout_of(
+            "<html><body><div>a 日本語の文章</div><div>a bbb\u{a0}c</div></body></html>",
+            100,
+        );
+        assert_eq!(
+            words(&layout.paragraphs[0]),
+            [("a", 0, 0), ("日本語", 30, 0), ("の文章", 0, 40)]
+        );
+        assert_eq!(
+            words(&layout.paragraphs[1]),
+            [("a", 0, 100), ("bbb\u{a0}c", 0, 140)]
+        );
+    }
+
+    #[test]
+    fn gigantic_pages_only_lay_out_the_visible_paragraphs() {
+        let html = format!(
+            "<html><body>{}</body></html>",
+            "<div>word</div>".repeat(MIN_VIRTUALIZED_PARAGRAPHS + 1)
+        );
+        let mut virtualization = Virtualization {
+            visible: 0..400,
+            ..Default::default()
+        };
+        let layout = virtualized_layout_of(&html, 800, &mut virtualization);
+        // Every paragraph is 40px high with 20px between them
+        assert_eq!(layout.paragraphs.len(), 7);
+        assert_eq!(virtualization.heights.len(), 7);
+        // The page is laid out again once the screen leaves the compiled part
+        assert!(!virtualization.is_outside(0..400));
+        assert!(virtualization.is_outside(300..700));
+
+        // The paragraphs further down are compiled once they are scrolled to
+        virtualization.visible = 6000..6400;
+        let layout = virtualized_layout_of(&html, 800, &mut virtualization);
+        assert_eq!(words(&layout.paragraphs[0]), [("word", 0, 6000)]);
+        assert_eq!(virtualization.heights.len(), 14);
+    }
+
+    #[test]
+    fn list_items_get_markers_in_front_of_them() {
+        let layout = layout_of(
+            "<html><body><ol start=\"3\"><li>a</li><li>b</li></ol>\
+             <ul style=\"list-style: none\"><li>c</li></ul></body></html>",
+            800,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        // The list is indented by 40px, and the markers go to the left of the items with a space
+        // between them
+        assert_eq!(
+            words,
+            [
+                ("a", 40, 40),
+                ("3.", -20, 40),
+                ("b", 40, 100),
+                ("4.", -20, 100),
+                ("c", 40, 180)
+            ]
+        );
+    }
+
+    #[test]
+    fn closed_details_only_show_their_summary() {
+        let layout = layout_of(
+            "<html><body><details><summary>a</summary>b</details>\
+             <details open><summary>c</summary>d</details></body></html>",
+            800,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        assert_eq!(
+            words,
+            [
+                ("a", 0, 0),
+                ("▸", -40, 0),
+                ("c", 0, 60),
+                ("▾", -40, 60),
+                ("d", 0, 120)
+            ]
+        );
+    }
+
+    #[test]
+    fn text_fields_show_their_value_and_datalists_are_hidden() {
+        let layout = layout_of(
+            "<html><body><input value=\"ab\" size=\"4\" list=\"l\"> \
+             <input placeholder=\"c\" size=\"2\"><datalist id=\"l\"><option>d</option>\
+             </datalist></body></html>",
+            800,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        assert_eq!(words, [("ab  ", 0, 0), ("c ", 90, 0)]);
+    }
+
+    #[test]
+    fn table_cells_hold_blocks_and_other_tables() {
+        let layout = layout_of(
+            "<html><body><table><tr><td>a</td><td><div>b</div>\
+             <table><tbody><tr><td>c</td><td>dd</td></tr></tbody></table></td></tr>\
+             <tr><td>e</td></tr></table></body></html>",
+            800,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        assert_eq!(
+            words,
+            [
+                ("a", 1, 1),
+                ("b", 33, 1),
+                ("c", 34, 62),
+                ("dd", 66, 62),
+                ("e", 1, 105)
+            ]
+        );
+    }
+
+    #[test]
+    fn table_cells_span_rows_and_columns() {
+        let layout = layout_of(
+            "<html><body><table><tfoot><tr><td>f</td></tr></tfoot>\
+             <thead><tr><th colspan=\"2\">h</th></tr></thead>\
+             <tr><td rowspan=\"2\">a</td><td>bbbbb</td></tr><tr><td>c</td></tr>\
+             </table></body></html>",
+            800,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        assert_eq!(
+            words,
+            [
+                ("h", 57, 1),
+                ("a", 1, 43),
+                ("bbbbb", 33, 43),
+                ("c", 33, 85),
+                ("f", 1, 127)
+            ]
+        );
+    }
+
+    #[test]
+    fn table_columns_share_the_width_and_wrap_their_text() {
+        let layout = layout_of(
+            "<html><body><table border=\"1\" cellpadding=\"0\" cellspacing=\"4\">\
+             <tr><td>aa bb</td><td>cccc dddd</td></tr></table></body></html>",
+            200,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        assert_eq!(
+            words,
+            [
+                ("aa", 2, 2),
+                ("bb", 2, 42),
+                ("cccc", 73, 2),
+                ("dddd", 73, 42)
+            ]
+        );
+        let borders: Vec<(i32, i32, i32, i32)> = layout
+            .borders
+            .iter()
+            .map(|x| {
+                (
+                    x.rect.position.x,
+                    x.rect.position.y,
+                    x.rect.size.width,
+                    x.rect.size.height,
+                )
+            })
+            .collect();
+        assert_eq!(borders, [(1, 1, 67, 82), (72, 1, 127, 82), (0, 0, 200, 84)]);
+    }
+
+    #[test]
+    fn paragraphs_compiled_in_parallel_stay_in_order() {
+        let text: Vec<String> = (0..10).map(|x| format!("w{}", x)).collect();
+        let layout = layout_of(
+            &format!("<html><body>{}</body></html>", text.join("<div></div>")),
+            800,
+        );
+        let words: Vec<(&str, i32, i32)> = layout.paragraphs.iter().flat_map(words).collect();
+        let expected: Vec<(&str, i32, i32)> = text
+            .iter()
+            .enumerate()
+            .map(|(index, x)| (x.as_str(), 0, index as i32 * 80))
+            .collect();
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn block_boxes_keep_their_nesting() {
+        let layout = layout_of(
+            "<html><body><div style=\"padding: 10px\"><div>a</div></div>\
+             <div style=\"position: relative; top: 5px\"><p>b</p></div></body></html>",
+            800,
+        );
+        let boxes: Vec<(i32, i32, i32, i32, Option<usize>)> = layout
+            .boxes
+            .iter()
+            .map(|x| {
+                let (position, size) = (x.rect.position, x.rect.size);
+                (position.x, position.y, size.width, size.height, x.parent)
+            })
+            .collect();
+        assert_eq!(
+            boxes,
+            [
+                (0, 0, 800, 200, None),
+                (0, 0, 800, 60, Some(0)),
+                (10, 10, 780, 40, Some(1)),
+                (0, 85, 800, 120, Some(0)),
+                (0, 125, 800, 40, Some(3))
+            ]
+        );
+        let inner = layout.box_at(15, 15, Position::new(0, 0)).unwrap();
+        assert_eq!(inner.parent, Some(1));
+        assert_eq!(
+            layout.box_at(5, 5, Position::new(0, 0)).unwrap().id,
+            layout.boxes[1].id
+        );
+    }
+
+    #[test]
+    fn opacity_is_multiplied_with_the_opacity_of_the_parent() {
+        let layout = layout_of(
+            "<html><body><div style=\"opacity: 0.5; border: 1px solid black\">a \
+             <span style=\"opacity: 50%\">b</span></div></body></html>",
+            800,
+        );
+        let opacities: Vec<f32> = layout.paragraphs[0]
+            .sentences
+            .iter()
+            .map(|x| x.opacity)
+            .collect();
+        assert_eq!(opacities, [0.5, 0.25]);
+        assert_eq!(layout.borders[0].opacity, 0.5);
+    }
+
+    #[test]
+    fn progress_and_meter_fill_their_content_box() {
+        let layout = layout_of(
+            "<html><body><progress value=\"3\" max=\"4\">75%</progress>\
+             <meter value=\"0.2\" low=\"0.3\" optimum=\"1\"></meter></body></html>",
+            800,
+        );
+        // The fallback text isn't shown
+        assert!(layout.paragraphs.is_empty());
+        let bars: Vec<(i32, i32, i32, i32, f32)> = layout
+            .bars
+            .iter()
+            .map(|x| {
+                let Rect { position, size } = x.rect;
+                (
+                    position.x,
+                    position.y,
+                    size.width,
+                    size.height,
+                    x.gauge.fraction,
+                )
+            })
+            .collect();
+        assert_eq!(bars, [(0, 0, 400, 40, 0.75), (0, 60, 400, 40, 0.2)]);
+    }
+
+    #[test]
+    fn white_space_controls_the_wrapping_of_lines() {
+        let layout = layout_of(
+            "<html><body><div style=\"white-space: nowrap\">aaaa bbbb cccc</div>\
+             <pre>a b\nc</pre></body></html>",
+            200,
+        );
+        let [nowrap, pre] = &layout.paragraphs[..] else {
+            panic!("Expected two paragraphs");
+        };
+        assert_eq!(
+            words(nowrap),
+            [("aaaa", 0, 0), ("bbbb", 90, 0), ("cccc", 180, 0)]
+        );
+        // The spaces stay in the words, and the newline starts a new line
+        let pre_top = pre.sentences[0].words[0].position.y;
+        let pre_words: Vec<(&str, i32, i32)> = words(pre)
+            .into_iter()
+            .map(|(word, x, y)| (word, x, y - pre_top))
+            .collect();
+        assert_eq!(pre_words, [("a b", 0, 0), ("c", 0, 40)]);
+    }
+
+    #[test]
+    fn hidden_text_keeps_its_space() {
+        let layout = layout_of(
+            "<html><body><div>a <span style=\"visibility: hidden\">b <b style=\"visibility: \
+             visible\">c</b></span> d</div></body></html>",
+            800,
+        );
+        assert_eq!(
+            words(&layout.paragraphs[0]),
+            [("a", 0, 0), ("c", 60, 0), ("d", 90, 0)]
+        );
+    }
+
+    #[test]
+    fn headings_get_their_size_from_the_user_agent_stylesheet() {
+        let layout = layout_of(
+            "<html><body><h1>Title</h1><h6>Small</h6></body></html>",
+            800,
+        );
+        let sizes: Vec<f32> = layout.paragraphs.iter().map(|x| x.font_size).collect();
+        assert_eq!(sizes, [DEFAULT_FONT_SIZE * 2.0, DEFAULT_FONT_SIZE * 0.67]);
+
+        let title = &layout.paragraphs[0].sentences[0].words[0];
+        assert_eq!(title.width, 5 * 40);
+    }
+
+    #[test]
+    fn every_heading_level_is_smaller_than_the_one_above_it() {
+        let layout = layout_of(
+            "<html><body><h1>a</h1><h2>a</h2><h3>a</h3><h4>a</h4><h5>a</h5><h6>a</h6></body></html>",
+            800,
+        );
+        let sizes: Vec<f32> = layout.paragraphs.iter().map(|x| x.font_size).collect();
+        assert_eq!(sizes.len(), 6);
+        assert!(sizes.windows(2).all(|x| x[0] > x[1]), "{:?}", sizes);
+        assert_eq!(sizes[3], DEFAULT_FONT_SIZE);
+    }
+
+    #[test]
+    fn margins_of_siblings_collapse() {
+        let layout = layout_of("<html><body><p>a</p><p>b</p></body></html>", 800);
+        let [first, second] = &layout.paragraphs[..] else {
+            panic!("Expected two paragraphs");
+        };
+        // The 1em margins between the paragraphs collapse into a single margin
+        let first_y = first.sentences[0].words[0].position.y;
+        let second_y = second.sentences[0].words[0].position.y;
+        assert_eq!(second_y - first_y, first.height + 40);
+    }
+
+    #[test]
+    fn negative_margins_pull_blocks_up_and_left() {
+        let layout = layout_of(
+            "<html><body><div>a</div><div>b</div>\
+             <div style=\"margin-top: -10px; margin-left: -1em\">c</div></body></html>",
+            800,
+        );
+        let first_words: Vec<(&str, i32, i32)> =
+            layout.paragraphs.iter().map(|x| words(x)[0]).collect();
+        let [(_, _, a_y), (_, b_x, b_y), (_, c_x, c_y)] = first_words[..] else {
+            panic!("Expected three paragraphs");
+        };
+        assert_eq!(b_x, 0);
+        assert_eq!(c_x, -(DEFAULT_FONT_SIZE as i32));
+        assert_eq!(c_y - b_y, b_y - a_y - 10);
+    }
+
+    #[test]
+    fn percentage_widths_are_relative_to_the_containing_block() {
+        let html = "<html><body><div style=\"width: 50%\">aaaa bbbb</div></body></html>";
+        let layout = layout_of(html, 300);
+        // The two words don't fit into 150px, so the second one goes on the next line
+        assert_eq!(
+            words(&layout.paragraphs[0]),
+            [("aaaa", 0, 0), ("bbbb", 0, 40)]
+        );
+    }
+
+    #[test]
+    fn rem_is_relative_to_the_root_font_size() {
+        let html = "<html style=\"font-size: 20px\"><body>\
+            <div style=\"font-size: 10px\"><p style=\"font-size: 2rem\">a</p></div>\
+            <div style=\"width: 4rem\">aaaa bbbb</div></body></html>";
+        let layout = layout_of(html, 800);
+        assert_eq!(layout.paragraphs[0].font_size, 40.0);
+        // 4rem is 80px, so the 40px words don't fit on one line
+        let [(_, _, first_y), (_, second_x, second_y)] = words(&layout.paragraphs[1])[..] else {
+            panic!("Expected two words");
+        };
+        assert_eq!((second_x, second_y - first_y), (0, 20));
+    }
+
+    #[test]
+    fn monospace_text_uses_cells() {
+        let layout = layout_of("<html><body><div><code>ab</code></div></body></html>", 800);
+        let paragraph = &layout.paragraphs[0];
+        assert!(paragraph.monospace);
+        let cell = MockFont.get_monospace_width(DEFAULT_FONT_SIZE);
+        assert_eq!(paragraph.sentences[0].words[0].width, 2 * cell);
+    }
+
+    #[test]
+    fn words_of_different_sizes_share_a_baseline() {
+        let layout = layout_of(
+            "<html><body>a <span style=\"font-size: 80px\">b</span> \
+             <span style=\"font-size: 20px\">c</span></body></html>",
+            800,
+        );
+        let paragraph = &layout.paragraphs[0];
+        // The baseline is 64px down, which is the ascent of the biggest word
+        assert_eq!(
+            words(paragraph),
+            [("a", 0, 32), ("b", 30, 0), ("c", 80, 48)]
+        );
+        assert_eq!(paragraph.height, 80);
+    }
+
+    #[test]
+    fn right_to_left_text_is_reversed() {
+        let layout = layout_of(
+            "<html><body><div>a \u{5d0}\u{5d1} \u{5d2}\u{5d3} b</div>\
+             <div dir=\"rtl\">\u{5d0}\u{5d1} \u{5d2}\u{5d3}</div></body></html>",
+            800,
+        );
+        // The words stay in the order of the text, but the Hebrew words and their letters are
+        // drawn in the opposite order inside of the English text
+        assert_eq!(
+            words(&layout.paragraphs[0]),
+            [
+                ("a", 0, 0),
+                ("\u{5d1}\u{5d0}", 80, 0),
+                ("\u{5d3}\u{5d2}", 30, 0),
+                ("b", 130, 0)
+            ]
+        );
+        // A right-to-left paragraph starts from the right edge
+        assert_eq!(
+            words(&layout.paragraphs[1]),
+            [("\u{5d1}\u{5d0}", 760, 60), ("\u{5d3}\u{5d2}", 710, 60)]
+        );
+        assert_eq!(layout.paragraphs[1].content_width(), 90);
+    }
+
+    #[test]
+    fn line_boxes_cover_the_words_of_each_line() {
+        let layout = layout_of(
+            "<html><body><div>one two three</div><div>four</div></body></html>",
+            150,
+        );
+        let lines: Vec<(Rect, Range<usize>)> = layout
+            .line_boxes()
+            .map(|(_, x)| (x.rect, x.words))
+            .collect();
+        let rect = |x, y, width| Rect::new(Position::new(x, y), Size::new(width, 40));
+        assert_eq!(
+            lines,
+            [
+                (rect(0, 0, 130), 0..2),
+                (rect(0, 40, 100), 2..3),
+                (rect(0, 100, 80), 0..1)
+            ]
+        );
+    }
+
+    #[test]
+    fn compiles_a_paragraph_into_lines() {
+        let element = Element::new(Tag::Paragraph);
+        let definition = ParagraphDefinition::from_string(&element, "one two three");
+        let paragraph = definition.compile(Size::new(150, 0), &mock_fonts());
+        assert_eq!(
+            words(&paragraph),
+            [("one", 0, 0), ("two", 70, 0), ("three", 0, 40)]
+        );
+    }
+
+    #[test]
+    fn line_breaks_start_new_lines() {
+        let layout = layout_of(
+            "<html><body><div>aa<br>bb <b>cc<br></b><br>dd<br></div></body></html>",
+            1000,
+        );
+        let paragraph = &layout.paragraphs[0];
+        assert_eq!(
+            words(paragraph),
+            [("aa", 0, 0), ("bb", 0, 40), ("cc", 50, 40), ("dd", 0, 120)]
+        );
+        // The break at the end doesn't leave an empty line
+        assert_eq!(paragraph.height, 160);
+    }
+
+    #[test]
+    fn breaks_words_that_are_wider_than_the_line() {
+        let layout = layout_of(
+            "<html><body><div>aa bbbbbbbbbbbbbbbbbbbbbbbb cc\u{AD}dd\u{AD}eeeeee</div></body></html>",
+            200,
+        );
+        // A line fits 10 letters. The long word gets lines of its own, and the last word is
+        // hyphenated at the last soft hyphen that fits.
+        assert_eq!(
+            words(&layout.paragraphs[0]),
+            [
+                ("aa", 0, 0),
+                ("bbbbbbbbbb", 0, 40),
+                ("bbbbbbbbbb", 0, 80),
+                ("bbbb", 0, 120),
+                ("ccdd-", 90, 120),
+                ("eeeeee", 0, 160)
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_elements_of_url_fragments() {
+        let html = "<html><body><div>a</div><div id=\"second\">b</div>\
+                    <div>c <a name=\"third\">d</a></div></body></html>";
+        let document = Document::new(parse_html(html), Vec::new());
+        let layout = layout_of(html, 800);
+        let position_of = |fragment: &str| {
+            let id = document.fragment_target(fragment)?;
+            layout.position_of(id).map(|x| (x.x, x.y))
+        };
+        assert_eq!(position_of("second"), Some((0, 60)));
+        assert_eq!(position_of("third"), Some((30, 120)));
+        assert_eq!(position_of("missing"), None);
+    }
+
+    #[test]
+    fn negative_insets_move_positioned_boxes_up_and_left() {
+        let layout = layout_of(
+            "<html><body><div style=\"position: relative; top: -5px; left: -10px\">a</div>\
+             <div style=\"position: absolute; top: -20px; left: -30px\">b</div></body></html>",
+            800,
+        );
+        let [relative, absolute] = &layout.paragraphs[..] else {
+            panic!("Expected two paragraphs");
+        };
+        assert_eq!(words(relative), [("a", -10, -5)]);
+        assert_eq!(words(absolute), [("b", -30, -20)]);
+    }
+
+    #[test]
+    fn transforms_move_boxes_without_moving_their_siblings() {
+        let layout = layout_of(
+            "<html><body><div style=\"transform: translate(50%, -10px)\">a\
+             <div style=\"position: absolute; top: 0\">b</div></div><div>c</div></body></html>",
+            800,
+        );
+        let [a, b, c] = &layout.paragraphs[..] else {
+            panic!("Expected three paragraphs");
+        };
+        assert_eq!(words(a), [("a", 400, -10)]);
+        // The transformed box is the containing block of the absolute box, so it moves too
+        assert_eq!(words(b), [("b", 400, -10)]);
+        assert_eq!(words(c), [("c", 0, 60)]);
+    }
+
+    #[test]
+    fn the_bottom_of_the_page_leaves_out_fixed_boxes() {
+        let layout = layout_of(
+            "<html><body><div>a</div><div>b</div>\
+             <div style=\"position: fixed; top: 500px\">c</div></body></html>",
+            800,
+        );
+        assert_eq!(layout.bottom(), 100);
+    }
+
+    #[test]
+    fn hit_tests_find_words_links_and_boxes() {
+        let layout = layout_of(
+            "<html><body><div style=\"padding: 10px\">a <a href=\"/b\">bb</a></div></body></html>",
+            800,
+        );
+        let hit = layout.hit_test(45, 20, Position::new(0, 0)).unwrap();
+        assert_eq!(hit.word.map(|x| x.word.as_str()), Some("bb"));
+        assert_eq!(hit.href, Some("/b"));
+        // The padding of the block has no words
+        let hit = layout.hit_test(5, 5, Position::new(0, 0)).unwrap();
+        assert!(hit.word.is_none() && hit.href.is_none());
+        assert_eq!(hit.element_id, Some(layout.boxes[1].id));
+        // Scrolling moves the page under the point
+        assert!(layout
+            .hit_test(45, 20, Position::new(0, -35))
+            .unwrap()
+            .href
+            .is_none());
+        assert!(layout.hit_test(900, 20, Position::new(0, 0)).is_none());
+        // Scrolling to the right moves the page to the left under the point
+        let hit = layout.hit_test(25, 20, Position::new(-20, 0)).unwrap();
+        assert_eq!(hit.href, Some("/b"));
+    }
+
+    #[test]
+    fn bold_and_italic_text_keeps_its_face() {
+        let layout = layout_of(
+            "<html><body><div>a <b>b <i>c</i></b> <em>d</em></div></body></html>",
+            800,
+        );
+        let faces: Vec<(&str, FontFace)> = layout.paragraphs[0]
+            .sentences
+            .iter()
+            .flat_map(|x| x.words.iter().map(|y| (y.word.as_str(), x.face)))
+            .collect();
+        let face = |bold, italic| FontFace { bold, italic };
+        assert_eq!(
+            faces,
+            [
+                ("a", face(false, false)),
+                ("b", face(true, false)),
+                ("c", face(true, true)),
+                ("d", face(false, true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn media_elements_are_drawn_as_placeholders() {
+        let layout = layout_of(
+            "<html><body><video poster=\"cover.png\"><source src=\"/clips/intro.webm?x=1\">\
+             <track src=\"subtitles.vtt\">Fallback</video><audio src=\"a.ogg\"></audio>\
+             </body></html>",
+            800,
+        );
+        // The audio has no controls, so it isn't shown, and neither is the fallback text
+        let [label] = &layout.paragraphs[..] else {
+            panic!("Expected only the label of the video");
+        };
+        assert_eq!(words(label), [("\u{25BA}", 1, 1), ("intro.webm", 31, 1)]);
+
+        let bar = &layout.bars[0];
+        assert_eq!(bar.gauge.fraction, 0.0);
+        let rect = bar.rect;
+        assert_eq!(
+            (
+                rect.position.x,
+                rect.position.y,
+                rect.size.width,
+                rect.size.height
+            ),
+            (1, 131, 300, 20)
+        );
+        let poster = &layout.background_images[0];
+        assert!(matches!(&poster.image, Image::Url(x) if x == "cover.png"));
+        assert_eq!(
+            (poster.rect.size.width, poster.rect.size.height),
+            (300, 150)
+        );
+    }
+
+    #[test]
+    fn images_take_the_size_of_their_attributes_or_their_picture() {
+        let layout = layout_of(
+            "<html><body><img src=\"a.png\" width=\"100\" height=\"50\">\
+             <img src=\"b.png\" width=\"30px\"></body></html>",
+            800,
+        );
+        let images: Vec<(&str, i32, i32, i32, i32)> = layout
+            .background_images
+            .iter()
+            .map(|x| match &x.image {
+                Image::Url(url) => (
+                    url.as_str(),
+                    x.rect.position.x,
+                    x.rect.position.y,
+                    x.rect.size.width,
+                    x.rect.size.height,
+                ),
+                Image::LinearGradient(_) => panic!("Expected the pictures of the images"),
+            })
+            .collect();
+        // The second picture hasn't been loaded, so its height is still unknown
+        assert_eq!(images, [("a.png", 0, 0, 100, 50), ("b.png", 0, 70, 30, 0)]);
+
+        let mut image = Element::new(Tag::Img);
+        image.attributes.insert("width".to_owned(), "30".to_owned());
+        image.natural_size = Some((60, 40));
+        assert_eq!(image_size(&image), (30, 20));
+        image.attributes.clear();
+        assert_eq!(image_size(&image), (60, 40));
+    }
+
+    #[test]
+    fn blocks_and_inline_text_paint_their_backgrounds() {
+        let layout = layout_of(
+            "<html><body><div style=\"background-color: red; padding: 10px\">\
+             <span style=\"background-color: blue\">a b</span></div></body></html>",
+            800,
+        );
+        let backgrounds: Vec<(i32, i32, i32, i32)> = layout
+            .backgrounds
+            .iter()
+            .map(|x| {
+                (
+                    x.rect.position.x,
+                    x.rect.position.y,
+                    x.rect.size.width,
+                    x.rect.size.height,
+                )
+            })
+            .collect();
+        assert_eq!(backgrounds, [(0, 0, 800, 60)]);
+
+        // The words of a line share one rect, so the space between them is filled too
+        let sentence = &layout.paragraphs[0].sentences[0];
+        assert_eq!(
+            sentence.background_color.map(|x| x.to_hex()),
+            Some("#0000ff".to_owned())
+        );
+        assert_eq!(
+            sentence.background_rects(),
+            [Rect::new(Position::new(10, 10), Size::new(50, 40))]
+        );
+    }
+
+    #[test]
+    fn styled_text_inside_of_headings_and_cells_keeps_its_style() {
+        let layout = layout_of(
+            "<html><body><h1>Go <a href=\"/home\" style=\"background-color: blue\">home              <b>now</b></a> please</h1><table><tr><td>A <span style=\"color: red\">red</span>              cell</td></tr></table></body></html>",
+            800,
+        );
+        let sentences: Vec<(String, Option<String>, Option<String>)> = layout
+            .paragraphs
+            .iter()
+            .flat_map(|x| &x.sentences)
+            .map(|x| {
+                let text: Vec<&str> = x.words.iter().map(|x| x.word.as_str()).collect();
+                (
+                    text.join(" "),
+                    x.href.clone(),
+                    x.background_color.map(|x| x.to_hex()),
+                )
+            })
+            .collect();
+        let link = Some("/home".to_owned());
+        let blue = Some("#0000ff".to_owned());
+        assert_eq!(
+            sentences,
+            [
+                ("Go".to_owned(), None, None),
+                ("home".to_owned(), link.clone(), blue.clone()),
+                ("now".to_owned(), link, blue),
+                ("please".to_owned(), None, None),
+                ("A".to_owned(), None, None),
+                ("red".to_owned(), None, None),
+                ("cell".to_owned(), None, None),
+            ]
+        );
+        let red = &layout.paragraphs[1].sentences[1];
+        assert_eq!(
+            red.text_color.map(|x| x.to_hex()),
+            Some("#ff0000".to_owned())
+        );
+    }
+
+    #[test]
+    fn the_display_list_paints_boxes_below_their_text() {
+        let layout = layout_of(
+            "<html><body><div style=\"background-color: red; border: 2px solid blue\">\
+             <a href=\"/\">a</a></div></body></html>",
+            800,
+        );
+        let items: Vec<(String, i32, i32, i32, i32)> = DisplayList::new(&layout, &mock_fonts())
+            .items
+            .iter()
+            .map(|x| {
+                let name = match &x.kind {
+                    DisplayItemKind::Rect(color) => color.to_hex(),
+                    DisplayItemKind::Text(text) => text.text.clone(),
+                    DisplayItemKind::BackgroundImage(_) => "image".to_owned(),
+                };
+                let Rect { position, size } = x.rect;
+                (name, position.x, position.y, size.width, size.height)
+            })
+            .collect();
+        let item = |name: &str, x, y, width, height| (name.to_owned(), x, y, width, height);
+        assert_eq!(
+            items,
+            [
+                item("#ff0000", 0, 0, 800, 44),
+                item("#0000ff", 0, 0, 800, 2),
+                item("#0000ff", 0, 42, 800, 2),
+                item("#0000ff", 0, 2, 2, 40),
+                item("#0000ff", 798, 2, 2, 40),
+                item("a", 2, 2, 20, 40),
+                // The underline is below the baseline
+                item("#8080ff", 2, 36, 20, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_and_nested_lists_are_indented() {
+        let layout = layout_of(
+            "<html><body><blockquote type=\"cite\">a</blockquote>\
+             <ul><li>b<ul><li>c</li></ul></li></ul></body></html>",
+            800,
+        );
+        let border = &layout.borders[0];
+        assert_eq!(
+            (border.widths.top, border.widths.left, border.widths.right),
+            (0, 2, 0)
+        );
+        let positions: Vec<i32> = layout
+            .paragraphs
+            .iter()
+            .filter_map(|x| x.sentences[0].words.first())
+            .map(|x| x.position.x)
+            .collect();
+        // The quote is indented by its bar and its padding, and every list by its own padding. The
+        // markers of the items come last and hang in the padding.
+        assert_eq!(positions, [42, 40, 80, 40, 0]);
+    }
+
+    #[test]
+    fn the_outline_has_the_headings_of_the_page() {
+        let layout = layout_of(
+            "<html><body><h1>The <a href=\"#\">title</a></h1><p>a</p><h2>Part</h2>\
+             <div style=\"position: fixed\"><h3>Menu</h3></div></body></html>",
+            800,
+        );
+        let outline = layout.outline();
+        let outline: Vec<(u32, &str, i32)> = outline
+            .iter()
+            .map(|x| (x.level, x.text.as_str(), x.y))
+            .collect();
+        assert_eq!(outline, [(1, "The title", 53), (2, "Part", 275)]);
+    }
+
+    #[test]
+    fn horizontal_rules_span_the_width() {
+        let layout = layout_of(
+            "<html><body><p>a</p><hr><hr style=\"border-width: 4px\"></body></html>",
+            800,
+        );
+        let rules: Vec<_> = layout
+            .borders
+            .iter()
+            .map(|x| (x.rect.size.width, x.rect.size.height, x.widths.top))
+            .collect();
+        assert_eq!(rules, [(800, 2, 1), (800, 8, 4)]);
+        // The rule is below the paragraph, after the larger of the two margins
+        let rule_y = layout.borders[0].rect.position.y;
+        assert_eq!(rule_y, 40 + 40 + 40);
+    }
+
+    #[test]
+    fn page_zoom_scales_the_lengths_and_the_text() {
+        let html =
+            "<html><body><p>a</p><hr style=\"width: 100px; border-width: 4px\"></body></html>";
+        let rules = |page_zoom| {
+            let layout = zoomed_layout_of(html, 800, page_zoom, &mut Virtualization::default());
+            let rule = &layout.borders[0];
+            (
+                rule.rect.size.width,
+                rule.rect.size.height,
+                rule.widths.top,
+                rule.rect.position.y,
+            )
+        };
+        assert_eq!(rules(1.0), (108, 8, 4, 120));
+        assert_eq!(rules(2.0), (216, 16, 8, 240));
+    }
 
-        Self { paragraphs }
+    #[test]
+    fn descendant_selectors_only_style_the_nested_elements() {
+        let layout = layout_of(
+            "<html><head><style>nav span { display: none; }</style></head><body>\
+             <nav><div><span>a</span> b</div></nav><div><span>c</span></div></body></html>",
+            800,
+        );
+        let words: Vec<_> = layout
+            .paragraphs
+            .iter()
+            .flat_map(words)
+            .map(|x| x.0)
+            .collect();
+        assert_eq!(words, ["b", "c"]);
     }
 }
@@ -1,8 +1,226 @@
-pub fn get_site(url: &str) -> String {
-    ureq::get(url.to_owned())
-        .call()
-        .unwrap()
+use std::{
+    fs::File,
+    io::prelude::Read,
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// The content of a fetched site
+pub struct Response {
+    pub mime_type: Option<String>,
+    pub body: String,
+    /// Set if the response can't be shown as a page, so it has to be downloaded. The body isn't
+    /// read in that case.
+    pub download: bool,
+    /// Set by the `X-Content-Type-Options: nosniff` header. The type of the response can't be
+    /// guessed then, so only the mime type the server sent counts.
+    pub nosniff: bool,
+}
+
+impl Response {
+    /// Checks if the mime type is one of the types that have a viewer
+    pub fn has_known_type(&self) -> bool {
+        matches!(
+            self.mime_type.as_deref(),
+            Some("text/plain" | "text/html" | "multipart/related")
+        )
+    }
+
+    /// Checks if the response is shown as text. The responses of other types are guessed to be
+    /// html, unless the server forbids guessing with `nosniff`.
+    pub fn is_plain_text(&self) -> bool {
+        match self.has_known_type() {
+            true => self.mime_type.as_deref() == Some("text/plain"),
+            false => self.nosniff,
+        }
+    }
+
+    /// Checks if the response is a page archive, see [crate::archive]
+    pub fn is_archive(&self) -> bool {
+        self.mime_type.as_deref() == Some("multipart/related")
+    }
+}
+
+/// Reads a local file. The mime type is guessed from the extension of the file.
+pub fn get_file(path: &Path) -> Response {
+    let mut file = File::open(path).unwrap();
+    let mut body = String::new();
+    file.read_to_string(&mut body).unwrap();
+
+    let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    let mime_type = match extension {
+        "txt" => "text/plain",
+        "mhtml" | "mht" => "multipart/related",
+        "css" => "text/css",
+        _ => "text/html",
+    };
+    Response {
+        mime_type: Some(mime_type.to_owned()),
+        body,
+        download: false,
+        nosniff: false,
+    }
+}
+
+/// Fetches a binary file, such as an image. Returns [None] if the file can't be fetched, since
+/// these are optional parts of a page.
+///
+/// * `referrer` - The value of the `Referer` header, see [crate::referrer]
+pub fn get_bytes(url: &str, referrer: Option<&str>) -> Option<Vec<u8>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return std::fs::read(path).ok();
+    }
+
+    let mut request = ureq::get(url.to_owned());
+    if let Some(referrer) = referrer {
+        request = request.header("Referer", referrer);
+    }
+    let mut response = request.call().ok()?;
+    response.body_mut().read_to_vec().ok()
+}
+
+pub fn get_site(url: &str) -> Response {
+    try_get_site(url, None).unwrap()
+}
+
+/// Like [get_site], but returns an error instead of panicking when the site can't be fetched
+///
+/// * `referrer` - The value of the `Referer` header, see [crate::referrer]
+pub fn try_get_site(url: &str, referrer: Option<&str>) -> Result<Response, String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        if !Path::new(path).is_file() {
+            return Err(format!("No such file: {}", path));
+        }
+        return Ok(get_file(Path::new(path)));
+    }
+
+    let mut request = ureq::get(url.to_owned());
+    if let Some(referrer) = referrer {
+        request = request.header("Referer", referrer);
+    }
+    let mut response = request.call().map_err(|x| x.to_string())?;
+    let mime_type = response.body().mime_type().map(|x| x.to_owned());
+    let disposition = response
+        .headers()
+        .get("content-disposition")
+        .and_then(|x| x.to_str().ok());
+    let nosniff = response
+        .headers()
+        .get("x-content-type-options")
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.trim().eq_ignore_ascii_case("nosniff"));
+    if is_download(mime_type.as_deref(), disposition) {
+        return Ok(Response {
+            mime_type,
+            body: String::new(),
+            download: true,
+            nosniff,
+        });
+    }
+    let body = response
         .body_mut()
         .read_to_string()
-        .unwrap()
+        .map_err(|x| x.to_string())?;
+    Ok(Response {
+        mime_type,
+        body,
+        download: false,
+        nosniff,
+    })
+}
+
+/// Checks if a response has to be saved into a file instead of being shown as a page
+///
+/// * `disposition` - The `Content-Disposition` header, which can ask for a download
+fn is_download(mime_type: Option<&str>, disposition: Option<&str>) -> bool {
+    if disposition.is_some_and(|x| x.trim_start().starts_with("attachment")) {
+        return true;
+    }
+    match mime_type {
+        Some(v) => !(v.starts_with("text/") || v == "multipart/related"),
+        None => false,
+    }
+}
+
+/// Fetches the site on another thread, so the window stays responsive during slow loads. The
+/// result is sent through the returned channel once the fetch is done.
+pub fn get_site_in_background(
+    url: &str,
+    referrer: Option<&str>,
+) -> Receiver<Result<Response, String>> {
+    let (sender, receiver) = mpsc::channel();
+    let url = url.to_owned();
+    let referrer = referrer.map(|x| x.to_owned());
+    thread::spawn(move || {
+        // Nobody is waiting for the result anymore if the tab was closed or navigated elsewhere
+        let _ = sender.send(try_get_site(&url, referrer.as_deref()));
+    });
+    receiver
+}
+
+/// Removes the `.` and `..` segments from the path of an url
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "." => (),
+            ".." => {
+                // The first segment is the empty one before the leading `/`
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+            }
+            v => segments.push(v),
+        }
+    }
+    // A path ending with a dot segment still points to a directory
+    if path.ends_with("/.") || path.ends_with("/..") {
+        segments.push("");
+    }
+    segments.join("/")
+}
+
+/// Resolves a link found on a page into an absolute url
+///
+/// * `base` - The url of the page the link is on
+///
+/// * `link` - The value of the link, for example the `href` attribute
+pub fn resolve_url(base: &str, link: &str) -> String {
+    if link.contains("://") {
+        return link.to_owned();
+    }
+
+    let scheme_end = base.find("://").map(|x| x + 3).unwrap_or(0);
+    let origin_end = base[scheme_end..]
+        .find('/')
+        .map(|x| x + scheme_end)
+        .unwrap_or(base.len());
+
+    if let Some(link) = link.strip_prefix("//") {
+        format!("{}{}", &base[..scheme_end], link)
+    } else if link.starts_with('/') {
+        format!("{}{}", &base[..origin_end], link)
+    } else if link.starts_with('#') {
+        // Another part of the same page
+        let document_end = base.find('#').unwrap_or(base.len());
+        format!("{}{}", &base[..document_end], link)
+    } else if link.starts_with('?') {
+        // The same page with another query
+        let path_end = base.find(['?', '#']).unwrap_or(base.len());
+        format!("{}{}", &base[..path_end], link)
+    } else {
+        // Relative to the directory of the page
+        let directory_end = base[origin_end..]
+            .rfind('/')
+            .map(|x| x + origin_end + 1)
+            .unwrap_or(base.len());
+        let directory = &base[origin_end..directory_end];
+        let path = if directory.ends_with('/') {
+            format!("{}{}", directory, link)
+        } else {
+            format!("{}/{}", directory, link)
+        };
+        format!("{}{}", &base[..origin_end], remove_dot_segments(&path))
+    }
 }
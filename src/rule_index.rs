@@ -0,0 +1,216 @@
+//! Finds the rulesets that can match an element without trying every selector of the stylesheets
+//! on every element. Each selector is put into a bucket by the one thing an element has to have
+//! for it to match, which is the tag or the name of an attribute. An element only has to be
+//! matched against the buckets of its own tag and attributes, and the selectors that fit into no
+//! bucket, such as `*` or `:hover`.
+//!
+//! Descendant selectors such as `nav a` also need the ancestors of the element to have things.
+//! What the ancestors have is kept in an [AncestorFilter], so the selectors that can't match are
+//! skipped without walking up the ancestors.
+
+use crate::css::{RuleSet, Selector};
+use crate::html::{Element, Tag};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// What an element needs to have for the selector to be able to match it
+#[derive(Hash)]
+enum Key<'a> {
+    Tag(Tag),
+    Attribute(&'a str),
+    Any,
+}
+
+impl<'a> Key<'a> {
+    fn of(selector: &'a Selector) -> Self {
+        match selector {
+            Selector::Element(tag) => Key::Tag(*tag),
+            Selector::Attribute(attribute) => Key::Attribute(&attribute.name),
+            // The tag is the most selective, and it comes first in a compound selector
+            Selector::Compound(selectors) => selectors
+                .iter()
+                .map(Key::of)
+                .find(|x| !matches!(x, Key::Any))
+                .unwrap_or(Key::Any),
+            // The element itself is matched by the last selector
+            Selector::Descendant(selectors) => selectors.last().map_or(Key::Any, Key::of),
+            Selector::Universal | Selector::PseudoClass(_) => Key::Any,
+        }
+    }
+
+    fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(self, &mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Returns the hashes of what the ancestors of an element need to have for the selector to match
+/// it. Only descendant selectors need anything from the ancestors.
+fn ancestor_keys(selector: &Selector) -> Vec<u64> {
+    let Selector::Descendant(selectors) = selector else {
+        return Vec::new();
+    };
+    let ancestors = &selectors[..selectors.len().saturating_sub(1)];
+    ancestors
+        .iter()
+        .map(Key::of)
+        .filter(|x| !matches!(x, Key::Any))
+        .map(|x| x.hash())
+        .collect()
+}
+
+/// The number of bits in an [AncestorFilter]
+const FILTER_BITS: usize = 512;
+
+/// A Bloom filter of the tags and the attribute names of the ancestors of an element. It can only
+/// be wrong about the ancestors having something, never about them not having it, so a selector
+/// that needs something the filter doesn't have can be skipped.
+#[derive(Debug, Clone, Default)]
+pub struct AncestorFilter {
+    bits: [u64; FILTER_BITS / 64],
+}
+
+impl AncestorFilter {
+    /// Returns the filter for the children of the element, which has the element added to it
+    pub fn with_parent(&self, element: &Element) -> Self {
+        let mut filter = self.clone();
+        filter.insert(Key::Tag(element.element_type).hash());
+        // The names in the selectors are in lowercase, see [crate::css::AttributeSelector]
+        for name in element.attributes.keys() {
+            filter.insert(Key::Attribute(&name.to_lowercase()).hash());
+        }
+        filter
+    }
+
+    /// The two bits of the hash. They come from different parts of the hash, so they are
+    /// independent of each other.
+    fn bits_of(hash: u64) -> [usize; 2] {
+        [
+            (hash % FILTER_BITS as u64) as usize,
+            ((hash >> 32) % FILTER_BITS as u64) as usize,
+        ]
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for bit in Self::bits_of(hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn might_contain(&self, hash: u64) -> bool {
+        Self::bits_of(hash)
+            .iter()
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// The rulesets of a document in buckets. The rulesets are referred to by their positions in the
+/// list they were given in, so the order of the cascade can be restored.
+pub struct RuleIndex<'a> {
+    rule_sets: Vec<&'a RuleSet>,
+    by_tag: HashMap<Tag, Vec<usize>>,
+    by_attribute: HashMap<&'a str, Vec<usize>>,
+    any: Vec<usize>,
+    /// What the ancestors need to have for each selector of the rulesets, see [ancestor_keys]
+    ancestor_keys: Vec<Vec<Vec<u64>>>,
+}
+
+impl<'a> RuleIndex<'a> {
+    pub fn new(rule_sets: Vec<&'a RuleSet>) -> Self {
+        let mut index = Self {
+            rule_sets: Vec::new(),
+            by_tag: HashMap::new(),
+            by_attribute: HashMap::new(),
+            any: Vec::new(),
+            ancestor_keys: Vec::new(),
+        };
+        for (position, rule_set) in rule_sets.iter().enumerate() {
+            for selector in &rule_set.selectors {
+                let bucket = match Key::of(selector) {
+                    Key::Tag(tag) => index.by_tag.entry(tag).or_default(),
+                    Key::Attribute(name) => index.by_attribute.entry(name).or_default(),
+                    Key::Any => &mut index.any,
+                };
+                // A ruleset with several selectors in the same bucket is only put there once
+                if bucket.last() != Some(&position) {
+                    bucket.push(position);
+                }
+            }
+            index
+                .ancestor_keys
+                .push(rule_set.selectors.iter().map(ancestor_keys).collect());
+        }
+        index.rule_sets = rule_sets;
+        index
+    }
+
+    /// Returns the rulesets that can match the element in the order they were given in. They
+    /// still have to be matched against the element.
+    ///
+    /// * `ancestors` - The filter of the ancestors of the element
+    pub fn candidates(
+        &self,
+        element: &Element,
+        ancestors: &AncestorFilter,
+    ) -> impl Iterator<Item = &'a RuleSet> + '_ {
+        let mut positions: Vec<usize> = self.any.clone();
+        if let Some(bucket) = self.by_tag.get(&element.element_type) {
+            positions.extend(bucket);
+        }
+        // The names in the selectors are in lowercase, see [crate::css::AttributeSelector]
+        for name in element.attributes.keys() {
+            if let Some(bucket) = self.by_attribute.get(name.to_lowercase().as_str()) {
+                positions.extend(bucket);
+            }
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        // A ruleset is left out if none of its selectors can match the ancestors
+        positions.retain(|x| {
+            self.ancestor_keys[*x]
+                .iter()
+                .any(|keys| keys.iter().all(|key| ancestors.might_contain(*key)))
+        });
+        positions.into_iter().map(|x| self.rule_sets[x])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parse_css;
+
+    fn element(tag: Tag, attributes: &[&str]) -> Element {
+        let mut element = Element::new(tag);
+        for name in attributes {
+            element.attributes.insert(name.to_string(), String::new());
+        }
+        element
+    }
+
+    #[test]
+    fn the_filter_has_the_ancestors() {
+        let nav = element(Tag::Nav, &["Data-Menu"]);
+        let filter = AncestorFilter::default().with_parent(&nav);
+        assert!(filter.might_contain(Key::Tag(Tag::Nav).hash()));
+        assert!(filter.might_contain(Key::Attribute("data-menu").hash()));
+        assert!(!AncestorFilter::default().might_contain(Key::Tag(Tag::Nav).hash()));
+    }
+
+    #[test]
+    fn skips_descendant_selectors_that_the_ancestors_cant_match() {
+        let rule_sets =
+            parse_css("nav a { color: red; } a { color: blue; } [href] a { color: green; }");
+        let index = RuleIndex::new(rule_sets.iter().collect());
+        let link = element(Tag::A, &[]);
+        let count = |filter: &AncestorFilter| index.candidates(&link, filter).count();
+
+        assert_eq!(count(&AncestorFilter::default()), 1);
+        let in_nav = AncestorFilter::default().with_parent(&element(Tag::Nav, &[]));
+        assert_eq!(count(&in_nav), 2);
+        let in_link = in_nav.with_parent(&element(Tag::A, &["href"]));
+        assert_eq!(count(&in_link), 3);
+    }
+}
@@ -0,0 +1,52 @@
+//! Smooth scrolling. Instead of jumping, the page slides to where it's scrolled to, slowing down
+//! as it gets there.
+
+use std::time::{Duration, Instant};
+
+/// How long it takes for the page to slide to where it's scrolled to
+const SCROLL_DURATION: Duration = Duration::from_millis(150);
+
+/// The vertical scroll of a page on its way from one position to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollAnimation {
+    from: i32,
+    to: i32,
+    started: Instant,
+    /// The position the page was last moved to by [ScrollAnimation::step]
+    shown: i32,
+}
+
+impl ScrollAnimation {
+    pub fn new(from: i32, to: i32, now: Instant) -> Self {
+        Self {
+            from,
+            to,
+            started: now,
+            shown: from,
+        }
+    }
+
+    /// Where the page ends up
+    pub fn target(&self) -> i32 {
+        self.to
+    }
+
+    pub fn shown(&self) -> i32 {
+        self.shown
+    }
+
+    /// Returns where the page is at the moment. It moves fast at first, and slows down towards
+    /// the end.
+    pub fn step(&mut self, now: Instant) -> i32 {
+        let progress = (now.saturating_duration_since(self.started).as_secs_f32()
+            / SCROLL_DURATION.as_secs_f32())
+        .min(1.0);
+        let eased = 1.0 - (1.0 - progress).powi(3);
+        self.shown = self.from + ((self.to - self.from) as f32 * eased).round() as i32;
+        self.shown
+    }
+
+    pub fn is_finished(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.started) >= SCROLL_DURATION
+    }
+}
@@ -0,0 +1,197 @@
+//! The scrollbars along the right and the bottom edges of the page. The thumb is as long compared
+//! to the track as the visible part of the page is compared to the whole page, and it can be
+//! dragged to scroll the page.
+
+use web::render_layout::{Position, Rect, Size};
+
+/// How thick the scrollbars are
+pub const SCROLLBAR_WIDTH: i32 = 12;
+/// The thumb doesn't get shorter than this on long pages, so it can still be grabbed
+const MIN_THUMB_LENGTH: i32 = 24;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Axis {
+    Vertical,
+    Horizontal,
+}
+
+/// A scrollbar of the page, in the coordinates of the window
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Scrollbar {
+    pub axis: Axis,
+    /// The part of the window the thumb moves in
+    pub track: Rect,
+    /// How many pixels of the page are visible along the axis
+    visible_length: i32,
+    /// How far the page can be scrolled along the axis
+    max_scroll: i32,
+    /// How far the page is scrolled along the axis, from 0 to `max_scroll`
+    scroll: i32,
+}
+
+impl Scrollbar {
+    /// Returns [None] if the page fits in the window along the axis, since there is nothing to
+    /// scroll then
+    pub fn new(
+        axis: Axis,
+        track: Rect,
+        visible_length: i32,
+        max_scroll: i32,
+        scroll: i32,
+    ) -> Option<Self> {
+        if max_scroll <= 0 || track.size.width <= 0 || track.size.height <= 0 {
+            return None;
+        }
+        Some(Self {
+            axis,
+            track,
+            visible_length: visible_length.max(1),
+            max_scroll,
+            scroll: scroll.clamp(0, max_scroll),
+        })
+    }
+
+    /// Returns the start and the length of the track along the axis
+    fn track_span(&self) -> (i32, i32) {
+        match self.axis {
+            Axis::Vertical => (self.track.position.y, self.track.size.height),
+            Axis::Horizontal => (self.track.position.x, self.track.size.width),
+        }
+    }
+
+    fn thumb_length(&self) -> i32 {
+        let (_, length) = self.track_span();
+        let total = self.visible_length as i64 + self.max_scroll as i64;
+        let thumb = (length as i64 * self.visible_length as i64 / total) as i32;
+        thumb.max(MIN_THUMB_LENGTH).min(length)
+    }
+
+    /// Returns the rect of the thumb inside of the track
+    pub fn thumb(&self) -> Rect {
+        let (start, length) = self.track_span();
+        let thumb_length = self.thumb_length();
+        let offset =
+            ((length - thumb_length) as i64 * self.scroll as i64 / self.max_scroll as i64) as i32;
+        match self.axis {
+            Axis::Vertical => Rect::new(
+                Position::new(self.track.position.x, start + offset),
+                Size::new(self.track.size.width, thumb_length),
+            ),
+            Axis::Horizontal => Rect::new(
+                Position::new(start + offset, self.track.position.y),
+                Size::new(thumb_length, self.track.size.height),
+            ),
+        }
+    }
+
+    /// Returns the coordinate of the position along the axis
+    pub fn along(&self, position: Position) -> i32 {
+        match self.axis {
+            Axis::Vertical => position.y,
+            Axis::Horizontal => position.x,
+        }
+    }
+
+    /// Returns how far the thumb was grabbed from its start, if the position is on it. Pressing
+    /// the track elsewhere grabs the thumb from its middle, so the thumb jumps under the cursor.
+    /// Returns [None] if the position isn't on the track.
+    pub fn grab(&self, position: Position) -> Option<i32> {
+        if !self.track.contains(position.x, position.y) {
+            return None;
+        }
+        let thumb = self.thumb();
+        let grabbed = self.along(position) - self.along(thumb.position);
+        if thumb.contains(position.x, position.y) {
+            Some(grabbed)
+        } else {
+            Some(self.thumb_length() / 2)
+        }
+    }
+
+    /// Returns how far the page is scrolled when the thumb is dragged to the position
+    ///
+    /// * `grab` - How far the thumb was grabbed from its start, see [Scrollbar::grab]
+    pub fn scroll_at(&self, position: Position, grab: i32) -> i32 {
+        let (start, length) = self.track_span();
+        let room = length - self.thumb_length();
+        if room <= 0 {
+            return 0;
+        }
+        let offset = (self.along(position) - grab - start).clamp(0, room);
+        (offset as i64 * self.max_scroll as i64 / room as i64) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A vertical scrollbar with a track of 400 pixels, starting at y = 50
+    fn vertical(visible_length: i32, max_scroll: i32, scroll: i32) -> Scrollbar {
+        let track = Rect::new(Position::new(100, 50), Size::new(SCROLLBAR_WIDTH, 400));
+        Scrollbar::new(Axis::Vertical, track, visible_length, max_scroll, scroll).unwrap()
+    }
+
+    #[test]
+    fn only_scrollable_pages_have_scrollbars() {
+        let track = Rect::new(Position::new(100, 50), Size::new(SCROLLBAR_WIDTH, 400));
+        assert!(Scrollbar::new(Axis::Vertical, track, 400, 0, 0).is_none());
+        let track = Rect::new(Position::new(100, 50), Size::new(SCROLLBAR_WIDTH, 0));
+        assert!(Scrollbar::new(Axis::Vertical, track, 400, 100, 0).is_none());
+    }
+
+    #[test]
+    fn thumb_follows_the_scroll() {
+        // Half of the page is visible, so the thumb is half of the track
+        let thumb = |scroll| {
+            let thumb = vertical(400, 400, scroll).thumb();
+            (thumb.position.y, thumb.size.height)
+        };
+        assert_eq!(thumb(0), (50, 200));
+        assert_eq!(thumb(200), (150, 200));
+        assert_eq!(thumb(400), (250, 200));
+        // The scroll is clamped to the page
+        assert_eq!(thumb(-100), (50, 200));
+        assert_eq!(thumb(1000), (250, 200));
+        // The thumb of a long page can still be grabbed
+        assert_eq!(
+            vertical(10, 100_000, 0).thumb().size.height,
+            MIN_THUMB_LENGTH
+        );
+
+        let track = Rect::new(Position::new(0, 500), Size::new(300, SCROLLBAR_WIDTH));
+        let scrollbar = Scrollbar::new(Axis::Horizontal, track, 300, 600, 300).unwrap();
+        assert_eq!(
+            scrollbar.thumb(),
+            Rect::new(Position::new(100, 500), Size::new(100, SCROLLBAR_WIDTH))
+        );
+    }
+
+    #[test]
+    fn grabs_the_thumb() {
+        let scrollbar = vertical(400, 400, 0);
+        assert_eq!(scrollbar.grab(Position::new(105, 60)), Some(10));
+        // The track outside of the thumb grabs the middle of the thumb
+        assert_eq!(scrollbar.grab(Position::new(105, 400)), Some(100));
+        assert_eq!(scrollbar.grab(Position::new(50, 60)), None);
+        assert_eq!(scrollbar.grab(Position::new(105, 500)), None);
+    }
+
+    #[test]
+    fn maps_dragging_to_scrolling() {
+        let scrollbar = vertical(400, 400, 0);
+        let scroll_at = |y| scrollbar.scroll_at(Position::new(105, y), 10);
+        assert_eq!(scroll_at(60), 0);
+        assert_eq!(scroll_at(160), 200);
+        assert_eq!(scroll_at(260), 400);
+        // Dragging past the ends of the track stops at the ends of the page
+        assert_eq!(scroll_at(0), 0);
+        assert_eq!(scroll_at(1000), 400);
+
+        // A thumb that fills the whole track can't be dragged
+        let track = Rect::new(Position::new(100, 50), Size::new(SCROLLBAR_WIDTH, 20));
+        let scrollbar = Scrollbar::new(Axis::Vertical, track, 400, 400, 0).unwrap();
+        assert_eq!(scrollbar.thumb().size.height, 20);
+        assert_eq!(scrollbar.scroll_at(Position::new(105, 60), 0), 0);
+    }
+}
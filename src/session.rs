@@ -0,0 +1,51 @@
+//! Saves the tabs into a session file, so they can be restored when the browser is started again.
+
+use crate::migration::{self, Migration};
+use std::path::Path;
+use web::tab::{Tab, Tabs};
+
+pub const SESSION_PATH: &str = "./session.txt";
+/// The changes to the format of the session file, see [crate::migration]
+const MIGRATIONS: [Migration; 1] = [migration::add_version];
+
+/// Writes the tabs in their order. Every line is `pinned\turl\ttitle`, where pinned is `1` or `0`.
+pub fn save_session(tabs: &Tabs, path: &Path) {
+    let mut content = String::new();
+    for tab in tabs.iter() {
+        // Tabs and line breaks would break the format, so they are turned into spaces
+        let title = tab
+            .title
+            .as_deref()
+            .unwrap_or("")
+            .replace(['\t', '\n'], " ");
+        content += &format!("{}\t{}\t{}\n", tab.pinned as u8, tab.url, title);
+    }
+    if let Err(error) = migration::write(path, &content, &MIGRATIONS) {
+        println!("Couldn't save the session: {}", error);
+    }
+}
+
+/// Returns the pinned tabs of the saved session, in their saved order. The pages aren't loaded
+/// until the tabs are opened.
+pub fn load_pinned_tabs(path: &Path) -> Vec<Tab> {
+    let Some(content) = migration::read(path, &MIGRATIONS) else {
+        return Vec::new();
+    };
+
+    let mut tabs = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [pinned, url, title] = fields[..] else {
+            println!("Skipping a broken session line: {}", line);
+            continue;
+        };
+        if pinned != "1" {
+            continue;
+        }
+        let mut tab = Tab::new(url);
+        tab.pinned = true;
+        tab.title = (!title.is_empty()).then(|| title.to_owned());
+        tabs.push(tab);
+    }
+    tabs
+}
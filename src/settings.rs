@@ -0,0 +1,163 @@
+//! The preferences of the user, which are kept in a settings file between runs.
+
+use crate::migration::{self, Migration};
+use std::path::Path;
+use web::color::Color;
+use web::css::collect_hex_color;
+use web::referrer::ReferrerPolicy;
+use web::style::ForcedColors;
+
+pub const SETTINGS_PATH: &str = "./settings.txt";
+/// The changes to the format of the settings file, see [crate::migration]
+const MIGRATIONS: [Migration; 1] = [migration::add_version];
+/// The limits of [Settings::text_zoom]
+pub const MIN_TEXT_ZOOM: f32 = 0.5;
+pub const MAX_TEXT_ZOOM: f32 = 3.0;
+/// How much the text zoom changes with one key press
+pub const TEXT_ZOOM_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Turns off animations, such as smooth scrolling and css transitions. Pages can check this
+    /// with the `prefers-reduced-motion` media feature.
+    pub reduce_motion: bool,
+    /// Replaces the colors of pages with [Self::forced_colors_palette]
+    pub forced_colors: bool,
+    pub forced_colors_palette: ForcedColors,
+    /// Used for pages that don't set a policy of their own
+    pub referrer_policy: ReferrerPolicy,
+    /// What the font sizes of pages are multiplied with. Unlike zooming the whole page, the
+    /// images and the widths of the boxes keep their size.
+    pub text_zoom: f32,
+    /// The space between the edges of the window and the page
+    pub page_padding: i32,
+    /// How wide the page can get. The page is centered in the windows that are wider than this.
+    pub max_content_width: Option<i32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            reduce_motion: false,
+            forced_colors: false,
+            forced_colors_palette: ForcedColors::default(),
+            referrer_policy: ReferrerPolicy::default(),
+            text_zoom: 1.0,
+            page_padding: 40,
+            max_content_width: None,
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a width in pixels, or `none` for no limit
+fn parse_max_width(value: &str) -> Option<Option<i32>> {
+    match value {
+        "none" => Some(None),
+        _ => value.parse::<i32>().ok().filter(|x| *x > 0).map(Some),
+    }
+}
+
+/// Parses a `#rgb` or `#rrggbb` color
+fn parse_color(value: &str) -> Option<Color> {
+    let digits = value.strip_prefix('#')?;
+    if !matches!(digits.len(), 3 | 6) || !digits.chars().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+    collect_hex_color(&mut value.chars().peekable()).and_then(Color::from_css)
+}
+
+impl Settings {
+    /// Loads the settings from a file. Missing or broken settings keep their default values.
+    pub fn load(path: &Path) -> Self {
+        let mut settings = Self::default();
+        let Some(content) = migration::read(path, &MIGRATIONS) else {
+            return settings;
+        };
+
+        // Every line is `key = value`
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let palette = &mut settings.forced_colors_palette;
+            let valid = match key.trim() {
+                "reduce_motion" => parse_bool(value.trim()).map(|x| settings.reduce_motion = x),
+                "forced_colors" => parse_bool(value.trim()).map(|x| settings.forced_colors = x),
+                "forced_colors.text" => parse_color(value.trim()).map(|x| palette.text = x),
+                "forced_colors.background" => {
+                    parse_color(value.trim()).map(|x| palette.background = x)
+                }
+                "forced_colors.link" => parse_color(value.trim()).map(|x| palette.link = x),
+                "forced_colors.visited" => parse_color(value.trim()).map(|x| palette.visited = x),
+                "forced_colors.focus" => parse_color(value.trim()).map(|x| palette.focus = x),
+                "referrer_policy" => {
+                    ReferrerPolicy::from_string(value).map(|x| settings.referrer_policy = x)
+                }
+                "text_zoom" => value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|x| (MIN_TEXT_ZOOM..=MAX_TEXT_ZOOM).contains(x))
+                    .map(|x| settings.text_zoom = x),
+                "page_padding" => value
+                    .trim()
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|x| *x >= 0)
+                    .map(|x| settings.page_padding = x),
+                "max_content_width" => {
+                    parse_max_width(value.trim()).map(|x| settings.max_content_width = x)
+                }
+                _ => None,
+            };
+            if valid.is_none() {
+                println!("Skipping a broken setting: {}", line);
+            }
+        }
+        settings
+    }
+
+    /// Returns the x coordinate of the left edge of the page and its width in a window that is
+    /// `window_width` wide. The padding is kept on both sides.
+    pub fn content_column(&self, window_width: i32) -> (i32, i32) {
+        let mut width = (window_width - self.page_padding * 2).max(0);
+        if let Some(max_width) = self.max_content_width {
+            width = width.min(max_width);
+        }
+        ((window_width - width) / 2, width)
+    }
+
+    pub fn save(&self, path: &Path) {
+        let palette = &self.forced_colors_palette;
+        let mut content = String::new();
+        content += &format!("reduce_motion = {}\n", self.reduce_motion);
+        content += &format!("forced_colors = {}\n", self.forced_colors);
+        content += &format!("forced_colors.text = {}\n", palette.text.to_hex());
+        content += &format!(
+            "forced_colors.background = {}\n",
+            palette.background.to_hex()
+        );
+        content += &format!("forced_colors.link = {}\n", palette.link.to_hex());
+        content += &format!("forced_colors.visited = {}\n", palette.visited.to_hex());
+        content += &format!("forced_colors.focus = {}\n", palette.focus.to_hex());
+        content += &format!("referrer_policy = {}\n", self.referrer_policy.as_str());
+        content += &format!("text_zoom = {}\n", self.text_zoom);
+        content += &format!("page_padding = {}\n", self.page_padding);
+        let max_content_width = match self.max_content_width {
+            Some(v) => v.to_string(),
+            None => "none".to_owned(),
+        };
+        content += &format!("max_content_width = {}\n", max_content_width);
+        if let Err(error) = migration::write(path, &content, &MIGRATIONS) {
+            println!("Couldn't save the settings: {}", error);
+        }
+    }
+}
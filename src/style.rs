@@ -0,0 +1,471 @@
+//! Computes the final style of the elements from the css rules that apply to them. The layout only
+//! reads the computed style, so it doesn't have to care where a rule came from.
+
+use crate::color::Color;
+use crate::css::{
+    Alignment, BackgroundRepeat, BackgroundSize, BorderStyle, Color as CssColor, DisplayStyle,
+    FlexDirection, FontStyle, FontWeight, Image, LineHeight, ListStyleType, Overflow, Position,
+    Rule, TextDecoration, TextDirection, Unit, Visibility, WhiteSpace,
+};
+use crate::font::FontFace;
+use crate::html::Tag;
+use crate::render_layout::DEFAULT_FONT_SIZE;
+use crate::transform::Translate;
+use crate::transition::Transition;
+
+/// A value for each of the four sides of a box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Edges<T> {
+    pub top: T,
+    pub right: T,
+    pub bottom: T,
+    pub left: T,
+}
+
+impl<T: Copy> Edges<T> {
+    pub fn all(value: T) -> Self {
+        Self {
+            top: value,
+            right: value,
+            bottom: value,
+            left: value,
+        }
+    }
+
+    /// Applies the function to every side
+    pub fn map<U>(self, f: impl Fn(T) -> U) -> Edges<U> {
+        Edges {
+            top: f(self.top),
+            right: f(self.right),
+            bottom: f(self.bottom),
+            left: f(self.left),
+        }
+    }
+}
+
+impl Edges<Unit> {
+    /// Resolves every side into pixels. Percentages are relative to the width of the containing
+    /// block, even for the top and bottom sides.
+    pub fn to_px(self, font_size: f32, containing_width: i32) -> Edges<i32> {
+        Edges {
+            top: self.top.to_px(font_size, containing_width),
+            right: self.right.to_px(font_size, containing_width),
+            bottom: self.bottom.to_px(font_size, containing_width),
+            left: self.left.to_px(font_size, containing_width),
+        }
+    }
+}
+
+impl Edges<i32> {
+    /// The combined size of the left and right sides
+    pub fn horizontal(&self) -> i32 {
+        self.left + self.right
+    }
+
+    /// The combined size of the top and bottom sides
+    pub fn vertical(&self) -> i32 {
+        self.top + self.bottom
+    }
+}
+
+/// Returns how the element is displayed when no css rule says otherwise
+pub fn default_display(tag: Tag) -> DisplayStyle {
+    match tag {
+        Tag::Span
+        | Tag::A
+        | Tag::B
+        | Tag::Strong
+        | Tag::I
+        | Tag::Em
+        | Tag::U
+        | Tag::Small
+        | Tag::Cite
+        | Tag::Abbr
+        | Tag::Dfn
+        | Tag::Q
+        | Tag::Sup
+        | Tag::Bdi
+        | Tag::Code
+        | Tag::Label
+        | Tag::Img
+        | Tag::Br
+        | Tag::Wbr => DisplayStyle::Inline,
+        Tag::Li => DisplayStyle::ListItem,
+        Tag::Button | Tag::Input | Tag::Select => DisplayStyle::InlineBlock,
+        Tag::Head
+        | Tag::Title
+        | Tag::Meta
+        | Tag::Link
+        | Tag::Script
+        | Tag::Style
+        | Tag::Datalist
+        | Tag::Source
+        | Tag::Track => DisplayStyle::None,
+        _ => DisplayStyle::Block,
+    }
+}
+
+/// Resolves `font-size` into pixels. `em` and percentages are relative to the font size of the
+/// parent element.
+fn resolve_font_size(unit: Unit, parent_font_size: f32) -> f32 {
+    match unit {
+        Unit::Em(v) => v * parent_font_size,
+        Unit::Percentage(v) => v as f32 * parent_font_size / 100.0,
+        v => v.to_px(parent_font_size, parent_font_size as i32) as f32,
+    }
+}
+
+/// The palette of the forced colors mode, which replaces the colors of the page with a few high
+/// contrast colors
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ForcedColors {
+    pub text: Color,
+    pub background: Color,
+    pub link: Color,
+    pub visited: Color,
+    pub focus: Color,
+}
+
+impl Default for ForcedColors {
+    fn default() -> Self {
+        Self {
+            text: Color::white(),
+            background: Color::black(),
+            link: Color::from_css(CssColor::Hex(255, 255, 0)).unwrap(),
+            visited: Color::from_css(CssColor::Hex(200, 160, 255)).unwrap(),
+            focus: Color::from_css(CssColor::Hex(0, 255, 255)).unwrap(),
+        }
+    }
+}
+
+/// The style of a single element after all of the css rules have been applied to it.
+#[derive(Debug, Clone)]
+pub struct ComputedStyle {
+    pub display: DisplayStyle,
+    pub background_color: Option<Color>,
+    pub background_image: Option<Image>,
+    pub background_repeat: BackgroundRepeat,
+    pub background_size: BackgroundSize,
+    /// See [Rule::BackgroundPosition]
+    pub background_position: (Unit, Unit),
+    /// The color of the text. This is inherited from the parent element.
+    pub color: Color,
+    /// The size of the content box. [None] is `auto`.
+    pub width: Option<Unit>,
+    pub height: Option<Unit>,
+    pub min_width: Unit,
+    pub max_width: Option<Unit>,
+    pub min_height: Unit,
+    pub max_height: Option<Unit>,
+    pub position: Position,
+    pub overflow: Overflow,
+    /// The offsets of a positioned box. [None] is `auto`.
+    pub inset: Edges<Option<Unit>>,
+    /// The font size in pixels. This and the other font properties are inherited from the parent
+    /// element.
+    pub font_size: f32,
+    /// The font size of the root element, which `rem` is relative to
+    pub root_font_size: f32,
+    pub font_weight: FontWeight,
+    pub font_style: FontStyle,
+    /// See [Rule::FontFamily]. Empty uses the default font.
+    pub font_family: Vec<String>,
+    /// Inherited from the parent element like the color
+    pub line_height: LineHeight,
+    /// The decoration is drawn over all of the text inside of the element, so it's passed down to
+    /// the children like an inherited property
+    pub text_decoration: TextDecoration,
+    /// The marker of the list items. Inherited, so it can be set on the whole list.
+    pub list_style_type: ListStyleType,
+    /// Inherited, so it applies to all of the text inside of the element
+    pub white_space: WhiteSpace,
+    /// Inherited, so the `dir` attribute of the page sets it for everything
+    pub direction: TextDirection,
+    /// Inherited, but a child can make itself visible inside of a hidden parent
+    pub visibility: Visibility,
+    /// The opacity of the element multiplied with the opacities of its ancestors, so it's what
+    /// the element is drawn with
+    pub opacity: f32,
+    /// The `opacity` of the element itself, which transitions animate
+    pub own_opacity: f32,
+    pub margin: Edges<Unit>,
+    pub padding: Edges<Unit>,
+    pub border_width: Edges<Unit>,
+    pub border_style: Option<BorderStyle>,
+    pub border_color: Color,
+    pub flex_direction: FlexDirection,
+    pub justify_content: Alignment,
+    pub align_items: Alignment,
+    pub flex_grow: i32,
+    pub transitions: Vec<Transition>,
+    /// Moves the box without changing the layout around it. Not inherited.
+    pub transform: Translate,
+    /// What the lengths of the style were multiplied with by [ComputedStyle::zoom]. The sizes
+    /// that don't come from the style, like the sizes of images, are multiplied with this too.
+    pub zoom: f32,
+}
+
+impl Default for ComputedStyle {
+    fn default() -> Self {
+        Self {
+            display: DisplayStyle::Block,
+            background_color: None,
+            background_image: None,
+            background_repeat: BackgroundRepeat::Repeat,
+            background_size: BackgroundSize::Size(None, None),
+            background_position: (Unit::Percentage(0), Unit::Percentage(0)),
+            color: Color::black(),
+            width: None,
+            height: None,
+            min_width: Unit::Px(0),
+            max_width: None,
+            min_height: Unit::Px(0),
+            max_height: None,
+            position: Position::Static,
+            overflow: Overflow::Visible,
+            inset: Edges::all(None),
+            font_size: DEFAULT_FONT_SIZE,
+            root_font_size: DEFAULT_FONT_SIZE,
+            font_weight: FontWeight::Normal,
+            font_style: FontStyle::Normal,
+            font_family: Vec::new(),
+            line_height: LineHeight::Normal,
+            text_decoration: TextDecoration::None,
+            list_style_type: ListStyleType::Disc,
+            white_space: WhiteSpace::Normal,
+            direction: TextDirection::LeftToRight,
+            visibility: Visibility::Visible,
+            opacity: 1.0,
+            own_opacity: 1.0,
+            margin: Edges::all(Unit::Px(0)),
+            padding: Edges::all(Unit::Px(0)),
+            border_width: Edges::all(Unit::Px(0)),
+            border_style: None,
+            border_color: Color::black(),
+            flex_direction: FlexDirection::Row,
+            justify_content: Alignment::Start,
+            align_items: Alignment::Stretch,
+            flex_grow: 0,
+            transitions: Vec::new(),
+            transform: Translate::default(),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl ComputedStyle {
+    /// Computes the style of an element from the given rules. Later rules override the earlier
+    /// ones.
+    ///
+    /// * `parent` - The style of the parent element, which the inherited properties are taken from
+    pub fn from_rules<'a>(
+        tag: Tag,
+        parent: Option<&ComputedStyle>,
+        rules: impl Iterator<Item = &'a Rule>,
+    ) -> Self {
+        let is_root = parent.is_none();
+        let defaults = Self::default();
+        let parent = parent.unwrap_or(&defaults);
+        let mut style = Self {
+            display: default_display(tag),
+            color: parent.color,
+            font_size: parent.font_size,
+            root_font_size: parent.root_font_size,
+            font_weight: parent.font_weight,
+            font_style: parent.font_style,
+            font_family: parent.font_family.clone(),
+            line_height: parent.line_height,
+            text_decoration: parent.text_decoration,
+            list_style_type: parent.list_style_type,
+            white_space: parent.white_space,
+            direction: parent.direction,
+            visibility: parent.visibility,
+            ..Default::default()
+        };
+        for rule in rules {
+            match rule.resolve_rem(parent.root_font_size) {
+                // Relative font sizes are relative to the font size of the parent, not to the
+                // size that an earlier rule gave to this element
+                Rule::FontSize(v) => style.font_size = resolve_font_size(v, parent.font_size),
+                v => style.apply(&v),
+            }
+        }
+        style.opacity *= parent.opacity;
+        // The root element sets the size that `rem` is relative to for the whole page
+        if is_root {
+            style.root_font_size = style.font_size;
+        }
+        style
+    }
+
+    /// Multiplies the font sizes and the lengths of the style with the factor, so the whole page
+    /// gets bigger or smaller, see [crate::media::MediaEnvironment::page_zoom]
+    pub fn zoom(&mut self, factor: f32) {
+        let zoom = |x: Unit| x.zoom(factor);
+        self.zoom *= factor;
+        self.font_size *= factor;
+        self.root_font_size *= factor;
+        self.width = self.width.map(zoom);
+        self.height = self.height.map(zoom);
+        self.min_width = zoom(self.min_width);
+        self.max_width = self.max_width.map(zoom);
+        self.min_height = zoom(self.min_height);
+        self.max_height = self.max_height.map(zoom);
+        self.inset = self.inset.map(|x| x.map(zoom));
+        self.transform = self.transform.zoom(factor);
+        self.margin = self.margin.map(zoom);
+        self.padding = self.padding.map(zoom);
+        self.border_width = self.border_width.map(zoom);
+        self.background_position = (
+            zoom(self.background_position.0),
+            zoom(self.background_position.1),
+        );
+        if let BackgroundSize::Size(width, height) = self.background_size {
+            self.background_size = BackgroundSize::Size(width.map(zoom), height.map(zoom));
+        }
+        if let LineHeight::Length(v) = self.line_height {
+            self.line_height = LineHeight::Length(zoom(v));
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visibility == Visibility::Visible
+    }
+
+    /// The face of the font family the text is drawn with
+    pub fn font_face(&self) -> FontFace {
+        FontFace {
+            bold: self.font_weight == FontWeight::Bold,
+            italic: self.font_style == FontStyle::Italic,
+        }
+    }
+
+    /// Checks if the text is drawn in a monospace font. Only the generic family is known, so the
+    /// names of specific fonts before it are skipped.
+    pub fn is_monospace(&self) -> bool {
+        self.font_family.iter().any(|x| x == "monospace")
+    }
+
+    fn apply(&mut self, rule: &Rule) {
+        match rule {
+            Rule::Display(v) => self.display = *v,
+            Rule::BackgroundColor(color) => self.background_color = Color::from_css(*color),
+            Rule::BackgroundImage(v) => self.background_image = v.clone(),
+            Rule::BackgroundRepeat(v) => self.background_repeat = *v,
+            Rule::BackgroundSize(v) => self.background_size = *v,
+            Rule::BackgroundPosition(x, y) => self.background_position = (*x, *y),
+            Rule::Color(color) => {
+                if let Some(color) = Color::from_css(*color) {
+                    self.color = color;
+                }
+            }
+            Rule::Width(v) => self.width = *v,
+            Rule::Height(v) => self.height = *v,
+            Rule::MinWidth(v) => self.min_width = *v,
+            Rule::MaxWidth(v) => self.max_width = *v,
+            Rule::MinHeight(v) => self.min_height = *v,
+            Rule::MaxHeight(v) => self.max_height = *v,
+            Rule::Position(v) => self.position = *v,
+            Rule::Overflow(v) => self.overflow = *v,
+            Rule::Top(v) => self.inset.top = *v,
+            Rule::Right(v) => self.inset.right = *v,
+            Rule::Bottom(v) => self.inset.bottom = *v,
+            Rule::Left(v) => self.inset.left = *v,
+            Rule::FontWeight(v) => self.font_weight = *v,
+            Rule::FontStyle(v) => self.font_style = *v,
+            Rule::FontFamily(v) => self.font_family = v.clone(),
+            Rule::LineHeight(v) => self.line_height = *v,
+            Rule::TextDecoration(v) => self.text_decoration = *v,
+            Rule::MarginTop(v) => self.margin.top = *v,
+            Rule::MarginRight(v) => self.margin.right = *v,
+            Rule::MarginBottom(v) => self.margin.bottom = *v,
+            Rule::MarginLeft(v) => self.margin.left = *v,
+            Rule::PaddingTop(v) => self.padding.top = *v,
+            Rule::PaddingRight(v) => self.padding.right = *v,
+            Rule::PaddingBottom(v) => self.padding.bottom = *v,
+            Rule::PaddingLeft(v) => self.padding.left = *v,
+            Rule::BorderWidth(v) => self.border_width = Edges::all(*v),
+            Rule::BorderTopWidth(v) => self.border_width.top = *v,
+            Rule::BorderRightWidth(v) => self.border_width.right = *v,
+            Rule::BorderBottomWidth(v) => self.border_width.bottom = *v,
+            Rule::BorderLeftWidth(v) => self.border_width.left = *v,
+            Rule::BorderStyle(v) => self.border_style = Some(*v),
+            Rule::BorderColor(color) => {
+                if let Some(color) = Color::from_css(*color) {
+                    self.border_color = color;
+                }
+            }
+            Rule::FlexDirection(v) => self.flex_direction = *v,
+            Rule::JustifyContent(v) => self.justify_content = *v,
+            Rule::AlignItems(v) => self.align_items = *v,
+            Rule::FlexGrow(v) => self.flex_grow = *v,
+            Rule::ListStyleType(v) => self.list_style_type = *v,
+            Rule::WhiteSpace(v) => self.white_space = *v,
+            Rule::Direction(v) => self.direction = *v,
+            Rule::Visibility(v) => self.visibility = *v,
+            Rule::Opacity(v) => {
+                self.opacity = *v;
+                self.own_opacity = *v;
+            }
+            Rule::Transition(v) => self.transitions = v.clone(),
+            Rule::Transform(v) => self.transform = *v,
+            _ => (),
+        }
+    }
+
+    /// Applies `min-width` and `max-width` to the width of the content box. If the two conflict,
+    /// `min-width` wins.
+    pub fn clamp_width(&self, width: i32, font_size: f32, containing_width: i32) -> i32 {
+        let max_width = self.max_width.map(|x| x.to_px(font_size, containing_width));
+        width
+            .min(max_width.unwrap_or(i32::MAX))
+            .max(self.min_width.to_px(font_size, containing_width))
+    }
+
+    /// Resolves the width of the border box from `width`, `min-width` and `max-width`.
+    /// Percentages are relative to the width of the containing block.
+    ///
+    /// * `available` - The width of the border box when `width` is `auto`
+    pub fn border_box_width(&self, available: i32, font_size: f32, containing_width: i32) -> i32 {
+        let edges = self.padding.to_px(font_size, containing_width).horizontal()
+            + self.border_widths(font_size).horizontal();
+        let width = match self.width {
+            Some(v) => v.to_px(font_size, containing_width),
+            None => available - edges,
+        };
+        self.clamp_width(width, font_size, containing_width) + edges
+    }
+
+    /// Resolves `height` into the height of the content box. Percentages are relative to the
+    /// height of the containing block, so they only work if that height is known. Returns [None]
+    /// if the height depends on the content.
+    pub fn definite_height(&self, font_size: f32, containing_height: Option<i32>) -> Option<i32> {
+        let height = match (self.height?, containing_height) {
+            (v, None) if v.has_percentage() => return None,
+            (v, containing_height) => v.to_px(font_size, containing_height.unwrap_or(0)),
+        };
+        Some(self.clamp_height(height, font_size, containing_height))
+    }
+
+    /// Applies `min-height` and `max-height` to the height of the content box. Percentages that
+    /// can't be resolved are ignored.
+    pub fn clamp_height(&self, height: i32, font_size: f32, containing_height: Option<i32>) -> i32 {
+        let resolve = |unit: Unit| match (unit, containing_height) {
+            (v, None) if v.has_percentage() => None,
+            (v, containing_height) => Some(v.to_px(font_size, containing_height.unwrap_or(0))),
+        };
+        let max_height = self.max_height.and_then(resolve);
+        height
+            .min(max_height.unwrap_or(i32::MAX))
+            .max(resolve(self.min_height).unwrap_or(0))
+    }
+
+    /// Returns the border widths in pixels. A border without a style isn't drawn, so its width is
+    /// zero.
+    pub fn border_widths(&self, font_size: f32) -> Edges<i32> {
+        match self.border_style {
+            Some(_) => self.border_width.to_px(font_size, 0),
+            None => Edges::all(0),
+        }
+    }
+}
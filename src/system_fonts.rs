@@ -0,0 +1,214 @@
+//! Finds the fonts that are installed on the system with fontconfig, so that pages can use them by
+//! their family names. The bundled fonts are still used when fontconfig isn't installed or it
+//! doesn't have a family, see [crate::font::FontRegistry::load].
+
+use crate::font::FontFace;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The generic families that are looked up from fontconfig. `sans-serif` falls back to the
+/// bundled Liberation Sans when fontconfig doesn't have one.
+const GENERIC_FAMILIES: [&str; 3] = ["serif", "sans-serif", "monospace"];
+
+/// The emoji font that is used when fontconfig doesn't pick one for `emoji`
+const DEFAULT_EMOJI_FAMILY: &str = "noto color emoji";
+
+/// The weights of fontconfig that the regular and the bold faces have
+const REGULAR_WEIGHT: u32 = 80;
+const BOLD_WEIGHT: u32 = 200;
+
+/// The slant of fontconfig that upright faces have. Italic and oblique faces are both used as the
+/// italic face.
+const ROMAN_SLANT: u32 = 0;
+
+/// The width of fontconfig that faces that aren't condensed or expanded have
+const NORMAL_WIDTH: u32 = 100;
+
+/// A face of a family that is installed on the system
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemFont {
+    /// The lowercase name of the family
+    pub family: String,
+    pub face: FontFace,
+    pub path: PathBuf,
+    /// The index of the face in a font collection, such as a `.ttc` file
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemFonts {
+    pub fonts: Vec<SystemFont>,
+    /// The families that fontconfig picks for the generic families, such as `serif`, by the
+    /// lowercase name of the generic family
+    pub generic_families: Vec<(String, String)>,
+    /// The lowercase name of the family that fontconfig picks for emoji, see
+    /// [SystemFonts::emoji_font]
+    pub emoji_family: Option<String>,
+}
+
+impl SystemFonts {
+    /// Asks fontconfig for the fonts of the system. Nothing is found if it isn't installed.
+    pub fn discover() -> Self {
+        let Some(list) = run_fc_list(&[
+            "--format",
+            "%{family[0]}\t%{weight}\t%{slant}\t%{width}\t%{index}\t%{file}\n",
+            ":scalable=true",
+        ]) else {
+            println!("Couldn't run fc-list, only the bundled fonts are used");
+            return Self::default();
+        };
+        let generic_families = GENERIC_FAMILIES
+            .iter()
+            .filter_map(|generic| {
+                let family = run_fc_match(generic)?;
+                Some((generic.to_string(), family.to_lowercase()))
+            })
+            .collect();
+        // fontconfig picks some font for every pattern, even if none of them has emoji
+        let emoji_family = run_fc_match("emoji")
+            .map(|x| x.to_lowercase())
+            .filter(|x| x.contains("emoji"));
+        Self {
+            fonts: parse_font_list(&list),
+            generic_families,
+            emoji_family,
+        }
+    }
+
+    /// Returns the font that the emoji are drawn with when the browser doesn't come with one. It's
+    /// the family that fontconfig picks for emoji, or Noto Color Emoji if it's installed.
+    pub fn emoji_font(&self) -> Option<&SystemFont> {
+        let mut families = self
+            .emoji_family
+            .iter()
+            .map(|x| x.as_str())
+            .chain([DEFAULT_EMOJI_FAMILY]);
+        families.find_map(|family| {
+            self.fonts
+                .iter()
+                .find(|x| x.family == family && x.face == FontFace::REGULAR)
+        })
+    }
+}
+
+/// Reads the lines of `fc-list` that have the family, the weight, the slant, the width, the index
+/// and the file of a face, separated by tabs. Only the TrueType and the OpenType fonts that are
+/// one of the four faces of [FontFace] are kept.
+pub fn parse_font_list(list: &str) -> Vec<SystemFont> {
+    let mut fonts: Vec<SystemFont> = Vec::new();
+    for line in list.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [family, weight, slant, width, index, path] = fields[..] else {
+            continue;
+        };
+        // Variable fonts have ranges instead of numbers, and they aren't used
+        let (Ok(weight), Ok(slant), Ok(width), Ok(index)) = (
+            weight.parse::<u32>(),
+            slant.parse::<u32>(),
+            width.parse::<u32>(),
+            index.parse::<u32>(),
+        ) else {
+            continue;
+        };
+        let bold = match weight {
+            REGULAR_WEIGHT => false,
+            BOLD_WEIGHT => true,
+            _ => continue,
+        };
+        let path = PathBuf::from(path);
+        let extension = path.extension().map(|x| x.to_string_lossy().to_lowercase());
+        if width != NORMAL_WIDTH || !matches!(extension.as_deref(), Some("ttf" | "otf" | "ttc")) {
+            continue;
+        }
+        let font = SystemFont {
+            family: family.to_lowercase(),
+            face: FontFace {
+                bold,
+                italic: slant != ROMAN_SLANT,
+            },
+            path,
+            index,
+        };
+        // Only the first file of a face is used
+        if !fonts
+            .iter()
+            .any(|x| x.family == font.family && x.face == font.face)
+        {
+            fonts.push(font);
+        }
+    }
+    fonts
+}
+
+fn run_fc_list(arguments: &[&str]) -> Option<String> {
+    let output = Command::new("fc-list").args(arguments).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// The name of the family that fontconfig picks for the pattern, such as `DejaVu Serif` for
+/// `serif`
+fn run_fc_match(pattern: &str) -> Option<String> {
+    let output = Command::new("fc-match")
+        .args(["--format", "%{family[0]}", pattern])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout)
+        .ok()
+        .filter(|x| !x.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT_LIST: &str = "DejaVu Sans\t80\t0\t100\t0\t/fonts/DejaVuSans.ttf\n\
+        DejaVu Sans\t200\t0\t100\t0\t/fonts/DejaVuSans-Bold.ttf\n\
+        DejaVu Sans\t80\t0\t75\t0\t/fonts/DejaVuSansCondensed.ttf\n\
+        Noto Color Emoji\t80\t0\t100\t0\t/fonts/NotoColorEmoji.ttf\n\
+        Twemoji\t80\t0\t100\t0\t/fonts/Twemoji.ttf\n\
+        Cantarell\t0 200\t0\t100\t0\t/fonts/Cantarell-VF.otf\n\
+        Fixed\t80\t0\t100\t0\t/fonts/fixed.pcf.gz\n";
+
+    #[test]
+    fn keeps_the_regular_and_bold_faces_of_normal_width() {
+        let fonts = parse_font_list(FONT_LIST);
+        let fonts: Vec<(&str, bool, &str)> = fonts
+            .iter()
+            .map(|x| {
+                let path = x.path.file_name().unwrap().to_str().unwrap();
+                (x.family.as_str(), x.face.bold, path)
+            })
+            .collect();
+        assert_eq!(
+            fonts,
+            [
+                ("dejavu sans", false, "DejaVuSans.ttf"),
+                ("dejavu sans", true, "DejaVuSans-Bold.ttf"),
+                ("noto color emoji", false, "NotoColorEmoji.ttf"),
+                ("twemoji", false, "Twemoji.ttf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_emoji_font_that_fontconfig_picks() {
+        let mut system = SystemFonts {
+            fonts: parse_font_list(FONT_LIST),
+            ..Default::default()
+        };
+        let family = |system: &SystemFonts| system.emoji_font().map(|x| x.family.clone());
+        assert_eq!(family(&system).as_deref(), Some("noto color emoji"));
+
+        system.emoji_family = Some("twemoji".to_owned());
+        assert_eq!(family(&system).as_deref(), Some("twemoji"));
+
+        system.fonts.retain(|x| !x.family.contains("emoji"));
+        assert_eq!(family(&system), None);
+    }
+}
@@ -0,0 +1,627 @@
+//! The tabs of the browser window. Every tab has a page of its own, and only the active one is
+//! shown. Pinned tabs are always kept in front of the other tabs.
+
+use crate::archive::Archive;
+use crate::display_list::DisplayList;
+use crate::document::Document;
+use crate::html::{parse_html, Element};
+use crate::plain_text::TextDocument;
+use crate::render_layout::{Layout, Position, Rect, Virtualization};
+use crate::requests::{get_site_in_background, Response};
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Instant;
+
+/// Urls that start with this show the html of the page as text, such as `view-source:https://a.b`
+pub const VIEW_SOURCE_PREFIX: &str = "view-source:";
+
+/// Splits the url into the address of the document and the fragment after the `#`
+pub fn split_fragment(url: &str) -> (&str, Option<&str>) {
+    match url.split_once('#') {
+        Some((document, fragment)) => (document, Some(fragment)),
+        None => (url, None),
+    }
+}
+
+/// What a layout was made for besides the page itself. The page is laid out again when this
+/// changes, see [Tab::layout_is_current].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LayoutKey {
+    /// Where the page is placed in the window
+    pub area: Rect,
+    pub text_zoom: f32,
+    pub page_zoom: f32,
+}
+
+/// A page that is being fetched in the background
+pub struct PageLoad {
+    pub url: String,
+    /// The value of the `Referer` header
+    pub referrer: Option<String>,
+    pub started: Instant,
+    receiver: Receiver<Result<Response, String>>,
+}
+
+impl PageLoad {
+    /// Starts fetching the page
+    ///
+    /// * `referrer` - The value of the `Referer` header
+    pub fn start(url: &str, referrer: Option<&str>) -> Self {
+        Self {
+            url: url.to_owned(),
+            referrer: referrer.map(|x| x.to_owned()),
+            started: Instant::now(),
+            receiver: get_site_in_background(
+                url.strip_prefix(VIEW_SOURCE_PREFIX).unwrap_or(url),
+                referrer,
+            ),
+        }
+    }
+
+    /// Returns the result of the fetch once it's done
+    pub fn poll(&self) -> Option<Result<Response, String>> {
+        match self.receiver.try_recv() {
+            Ok(v) => Some(v),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("The request was interrupted".to_owned())),
+        }
+    }
+
+    /// Blocks until the fetch is done and returns its result
+    pub fn wait(&self) -> Result<Response, String> {
+        self.receiver
+            .recv()
+            .unwrap_or_else(|_| Err("The request was interrupted".to_owned()))
+    }
+}
+
+/// The favicon of a page that is being fetched and decoded in the background after the page has
+/// loaded
+pub struct FaviconLoad {
+    pub url: String,
+    /// When the page started loading, so the spinner of the tab goes on where it was
+    pub started: Instant,
+    receiver: Receiver<Option<RgbaImage>>,
+}
+
+impl FaviconLoad {
+    /// Waits for the favicon from the receiver, which gets [None] if it can't be fetched or
+    /// decoded
+    pub fn new(url: &str, started: Instant, receiver: Receiver<Option<RgbaImage>>) -> Self {
+        Self {
+            url: url.to_owned(),
+            started,
+            receiver,
+        }
+    }
+
+    /// Returns the favicon once the fetch is done. The inner value is [None] if it failed.
+    pub fn poll(&self) -> Option<Option<RgbaImage>> {
+        match self.receiver.try_recv() {
+            Ok(v) => Some(v),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(None),
+        }
+    }
+}
+
+pub struct Tab {
+    /// The url of the page. Empty for the home page.
+    pub url: String,
+    pub title: Option<String>,
+    /// The url from `<link rel="canonical">` of the page
+    pub canonical_url: Option<String>,
+    pub favicon: Option<RgbaImage>,
+    /// Where [Tab::favicon] was fetched from. Its texture is cached by this.
+    pub favicon_url: Option<String>,
+    pub favicon_load: Option<FaviconLoad>,
+    /// Pinned tabs are drawn compact, can't be closed with Ctrl+W and are restored when the
+    /// browser starts.
+    pub pinned: bool,
+    pub document: Option<Document>,
+    pub text_document: Option<TextDocument>,
+    pub layout: Option<Layout>,
+    /// What is drawn for [Tab::layout]. It's made together with the layout.
+    pub display_list: Option<DisplayList>,
+    /// What [Tab::layout] was made for. [None] if the layout is outdated.
+    layout_key: Option<LayoutKey>,
+    pub scroll_y: i32,
+    /// How far the page is scrolled to the right, as a negative number like [Tab::scroll_y]. Only
+    /// content that is wider than the window can be scrolled sideways.
+    pub scroll_x: i32,
+    /// The fragment of the url that the page is scrolled to once it has been laid out, see
+    /// [Tab::scroll_to_fragment]
+    pub fragment: Option<String>,
+    /// The scroll offsets of the `overflow: scroll` boxes of the page by their element ids
+    pub box_scroll: HashMap<usize, i32>,
+    /// The `<details>` the user has opened or closed by their element ids. They stay that way
+    /// when the document is parsed again.
+    pub details_open: HashMap<usize, bool>,
+    /// The heights of the paragraphs that have been laid out, if the page is gigantic
+    pub virtualization: Virtualization,
+    /// The html the document was parsed from. The elements know where they are in it, see
+    /// [crate::html::Element::source_span].
+    pub source: Option<String>,
+    /// The scroll positions saved with `m{a-z}`, by the url of the page and the letter of the
+    /// mark. They are kept while the tab is open, so the marks of a page are still there after
+    /// going back to it.
+    marks: HashMap<String, HashMap<char, i32>>,
+    /// The page that is being loaded into the tab. The old page stays visible until it's done.
+    pub loading: Option<PageLoad>,
+    /// When the tab was last switched to. The documents of the tabs that haven't been looked at
+    /// for the longest time are discarded first.
+    pub last_active: Instant,
+}
+
+impl Tab {
+    /// Creates a tab that hasn't loaded its page yet
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            title: None,
+            canonical_url: None,
+            favicon: None,
+            favicon_url: None,
+            favicon_load: None,
+            pinned: false,
+            document: None,
+            text_document: None,
+            layout: None,
+            display_list: None,
+            layout_key: None,
+            scroll_y: 0,
+            scroll_x: 0,
+            fragment: None,
+            box_scroll: HashMap::new(),
+            details_open: HashMap::new(),
+            virtualization: Virtualization::default(),
+            source: None,
+            marks: HashMap::new(),
+            loading: None,
+            last_active: Instant::now(),
+        }
+    }
+
+    /// Saves the current scroll position of the page under the letter
+    pub fn set_mark(&mut self, letter: char) {
+        self.marks
+            .entry(self.url.clone())
+            .or_default()
+            .insert(letter, self.scroll_y);
+    }
+
+    /// Scrolls back to the position that was saved under the letter. Returns false if the page
+    /// has no such mark.
+    pub fn jump_to_mark(&mut self, letter: char) -> bool {
+        let mark = self.marks.get(&self.url).and_then(|x| x.get(&letter));
+        match mark {
+            Some(scroll_y) => {
+                self.scroll_y = *scroll_y;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns when the spinner of the tab started, if the tab is still fetching its page or its
+    /// favicon
+    pub fn loading_since(&self) -> Option<Instant> {
+        match (&self.loading, &self.favicon_load) {
+            (Some(load), _) => Some(load.started),
+            (None, Some(load)) => Some(load.started),
+            (None, None) => None,
+        }
+    }
+
+    /// Forgets the favicon of the page and stops fetching it
+    pub fn clear_favicon(&mut self) {
+        self.favicon = None;
+        self.favicon_url = None;
+        self.favicon_load = None;
+    }
+
+    /// Checks if the url points into the page that is shown, so following it only has to scroll
+    /// to its fragment instead of loading the page again
+    pub fn is_same_document(&self, url: &str) -> bool {
+        let (document, fragment) = split_fragment(url);
+        fragment.is_some() && self.is_loaded() && split_fragment(&self.url).0 == document
+    }
+
+    /// Scrolls the element of [Tab::fragment] to the top of the page. An empty fragment and `#top`
+    /// scroll to the start of the page. Does nothing if the page hasn't been laid out yet, and the
+    /// fragment is dropped if there is no such element.
+    ///
+    /// * `top` - The y of the start of the page in the layout
+    pub fn scroll_to_fragment(&mut self, top: i32) {
+        let (Some(document), Some(layout)) = (self.document.as_ref(), self.layout.as_ref()) else {
+            return;
+        };
+        let Some(fragment) = self.fragment.take() else {
+            return;
+        };
+        if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") {
+            self.scroll_y = 0;
+            return;
+        }
+        let position = document
+            .fragment_target(&fragment)
+            .and_then(|x| layout.position_of(x));
+        match position {
+            Some(v) => self.scroll_y = -(v.y - top),
+            None => println!("The page has no element for #{}", fragment),
+        }
+    }
+
+    /// Checks if the layout of the page was made for the key and nothing has changed since
+    pub fn layout_is_current(&self, key: LayoutKey) -> bool {
+        self.layout.is_some() && self.layout_key == Some(key)
+    }
+
+    pub fn set_layout(&mut self, layout: Layout, display_list: DisplayList, key: LayoutKey) {
+        self.layout = Some(layout);
+        self.display_list = Some(display_list);
+        self.layout_key = Some(key);
+    }
+
+    /// Checks if the page has changed since it was laid out, for example because a transition is
+    /// running, so it has to be drawn again
+    pub fn needs_layout(&self) -> bool {
+        self.is_loaded() && self.layout_key.is_none()
+    }
+
+    /// Makes the page be laid out again before it's drawn the next time
+    pub fn invalidate_layout(&mut self) {
+        self.layout_key = None;
+    }
+
+    /// Returns how far the page is scrolled in both directions
+    pub fn scroll(&self) -> Position {
+        Position::new(self.scroll_x, self.scroll_y)
+    }
+
+    /// Keeps the page from being scrolled past its start or its end. Does nothing if the page
+    /// hasn't been laid out yet.
+    ///
+    /// * `visible_end` - The point on the screen that the ends of the page are scrolled to at most
+    pub fn clamp_scroll(&mut self, visible_end: Position) {
+        let Some(max_scroll) = self.max_scroll(visible_end) else {
+            return;
+        };
+        self.scroll_y = self.scroll_y.clamp(-max_scroll.y, 0);
+        self.scroll_x = self.scroll_x.clamp(-max_scroll.x, 0);
+    }
+
+    /// Returns how far the page can be scrolled right and down, see [Tab::clamp_scroll]. Returns
+    /// [None] if the page hasn't been laid out yet.
+    pub fn max_scroll(&self, visible_end: Position) -> Option<Position> {
+        let layout = self.layout.as_ref()?;
+        Some(Position::new(
+            (layout.right() - visible_end.x).max(0),
+            (layout.bottom() - visible_end.y).max(0),
+        ))
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.document.is_some() || self.text_document.is_some()
+    }
+
+    /// Drops the parsed document to save memory. The html is kept, so the document can be parsed
+    /// again with [Tab::restore_document]. Returns false if the document can't be discarded.
+    pub fn discard_document(&mut self) -> bool {
+        if self.document.is_none() || self.source.is_none() {
+            return false;
+        }
+        self.document = None;
+        self.layout = None;
+        self.display_list = None;
+        self.virtualization = Virtualization::default();
+        true
+    }
+
+    /// Parses the html again after [Tab::discard_document]. The scroll positions are kept.
+    pub fn restore_document(&mut self) {
+        if self.is_loaded() {
+            return;
+        }
+        let Some(source) = self.source.take() else {
+            return;
+        };
+        let box_scroll = std::mem::take(&mut self.box_scroll);
+        let details_open = std::mem::take(&mut self.details_open);
+        self.set_elements(parse_html(&source));
+        self.source = Some(source);
+        self.box_scroll = box_scroll;
+        if let Some(document) = self.document.as_mut() {
+            for (id, open) in &details_open {
+                document.set_details_open(*id, *open);
+            }
+        }
+        self.details_open = details_open;
+    }
+
+    pub fn set_elements(&mut self, elements: Vec<Element>) {
+        self.invalidate_layout();
+        self.text_document = None;
+        self.source = None;
+        self.box_scroll.clear();
+        self.details_open.clear();
+        self.virtualization = Virtualization::default();
+        let mut document = Document::new(elements, Vec::new());
+        document.parse_inline_css();
+        document.parse_style_sheets();
+        self.document = Some(document);
+    }
+
+    /// Shows the text as is with the plain text viewer instead of parsing it as html
+    pub fn set_plain_text(&mut self, text: &str) {
+        self.invalidate_layout();
+        self.document = None;
+        self.source = None;
+        self.text_document = Some(TextDocument::new(text));
+    }
+
+    /// Shows the html of the page as text. The lines of the span are highlighted, if one is given.
+    pub fn set_source(&mut self, source: &str, span: Option<Range<usize>>) {
+        self.set_plain_text(source);
+        if let (Some(text_document), Some(span)) = (self.text_document.as_mut(), span) {
+            text_document.highlight(source, span);
+        }
+    }
+
+    /// Shows the response with the viewer that fits its mime type
+    pub fn set_response(&mut self, response: &Response) {
+        if response.nosniff && !response.has_known_type() {
+            println!(
+                "Showing {} as text, since its type {:?} can't be guessed with nosniff",
+                self.url, response.mime_type
+            );
+        }
+        if response.is_plain_text() {
+            self.set_plain_text(&response.body);
+        } else if response.is_archive() {
+            match Archive::from_string(&response.body) {
+                Ok(archive) => self.set_elements(archive.into_elements()),
+                Err(error) => {
+                    println!("Couldn't open the archive: {}", error);
+                    self.set_plain_text(&response.body);
+                }
+            }
+        } else {
+            self.set_elements(parse_html(&response.body));
+            self.source = Some(response.body.clone());
+        }
+    }
+
+    /// The text shown in the tab strip
+    pub fn label(&self) -> &str {
+        match (&self.title, self.url.as_str()) {
+            (Some(title), _) => title,
+            (None, "") => "Home",
+            (None, url) => url,
+        }
+    }
+}
+
+/// The tabs in the order they are shown in the tab strip
+pub struct Tabs {
+    tabs: Vec<Tab>,
+    active: usize,
+}
+
+impl Tabs {
+    /// Creates the tab list. There always has to be at least one tab, and the pinned tabs have to
+    /// be in front of the others.
+    pub fn new(tabs: Vec<Tab>, active: usize) -> Self {
+        assert!(!tabs.is_empty(), "Expected at least one tab");
+        Self { tabs, active }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tab> {
+        self.tabs.iter()
+    }
+
+    // There is always at least one tab, so the list is never empty
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn get(&self, index: usize) -> &Tab {
+        &self.tabs[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Tab {
+        &mut self.tabs[index]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    pub fn set_active(&mut self, index: usize) {
+        self.active = index.min(self.tabs.len() - 1);
+        self.tabs[self.active].last_active = Instant::now();
+    }
+
+    pub fn pinned_count(&self) -> usize {
+        self.tabs.iter().take_while(|x| x.pinned).count()
+    }
+
+    /// Adds the tab after all of the other tabs and makes it active
+    pub fn open(&mut self, tab: Tab) {
+        self.tabs.push(tab);
+        self.active = self.tabs.len() - 1;
+    }
+
+    /// Closes the tab and returns it. Pinned tabs and the last tab can't be closed.
+    pub fn close(&mut self, index: usize) -> Option<Tab> {
+        if self.tabs[index].pinned || self.tabs.len() == 1 {
+            return None;
+        }
+        let tab = self.tabs.remove(index);
+        if self.active > index || self.active == self.tabs.len() {
+            self.active -= 1;
+        }
+        Some(tab)
+    }
+
+    /// Moves the tab to another position. A tab can only be moved among the tabs with the same
+    /// pin state, so the pinned tabs stay in front. Returns the new index of the tab.
+    pub fn move_tab(&mut self, from: usize, to: usize) -> usize {
+        let pinned_count = self.pinned_count();
+        let to = if self.tabs[from].pinned {
+            to.min(pinned_count - 1)
+        } else {
+            to.clamp(pinned_count, self.tabs.len() - 1)
+        };
+        self.reorder(from, to);
+        to
+    }
+
+    /// Moves the tab from one index to another and keeps the same tab active
+    fn reorder(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        let active_is_moved = self.active == from;
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to, tab);
+        if active_is_moved {
+            self.active = to;
+        } else if from < self.active && self.active <= to {
+            self.active -= 1;
+        } else if to <= self.active && self.active < from {
+            self.active += 1;
+        }
+    }
+
+    /// Pins or unpins the tab. Pinned tabs move to the end of the pinned tabs and unpinned tabs to
+    /// the start of the other tabs.
+    pub fn toggle_pin(&mut self, index: usize) {
+        let pinned_count = self.pinned_count();
+        let pinned = !self.tabs[index].pinned;
+        self.tabs[index].pinned = pinned;
+        // The tab is on the wrong side of the boundary between the pinned and the other tabs, so
+        // it only has to be moved next to the boundary
+        let target = if pinned {
+            pinned_count
+        } else {
+            pinned_count - 1
+        };
+        self.reorder(index, target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates tabs for the urls `a`, `b`, `c`... where the first ones are pinned
+    fn tabs(count: usize, pinned: usize, active: usize) -> Tabs {
+        let tabs = (0..count)
+            .map(|x| {
+                let mut tab = Tab::new(&((b'a' + x as u8) as char).to_string());
+                tab.pinned = x < pinned;
+                tab
+            })
+            .collect();
+        Tabs::new(tabs, active)
+    }
+
+    fn urls(tabs: &Tabs) -> String {
+        tabs.iter().map(|x| x.url.as_str()).collect()
+    }
+
+    #[test]
+    fn closes_tabs() {
+        let mut tabs = tabs(4, 1, 2);
+        // Pinned tabs can't be closed
+        assert!(tabs.close(0).is_none());
+        // Closing a tab before the active one keeps the same tab active
+        assert_eq!(tabs.close(1).unwrap().url, "b");
+        assert_eq!(
+            (urls(&tabs).as_str(), tabs.active().url.as_str()),
+            ("acd", "c")
+        );
+        // Closing a tab after the active one doesn't change it
+        tabs.close(2);
+        assert_eq!(
+            (urls(&tabs).as_str(), tabs.active().url.as_str()),
+            ("ac", "c")
+        );
+        // Closing the active tab at the end activates the one before it
+        tabs.close(1);
+        assert_eq!((urls(&tabs).as_str(), tabs.active_index()), ("a", 0));
+
+        // The last tab can't be closed, even if it isn't pinned
+        let mut tabs = self::tabs(1, 0, 0);
+        assert!(tabs.close(0).is_none());
+        assert_eq!(tabs.len(), 1);
+    }
+
+    #[test]
+    fn closing_the_active_tab_activates_the_next_one() {
+        let mut tabs = tabs(3, 0, 1);
+        tabs.close(1);
+        assert_eq!(
+            (urls(&tabs).as_str(), tabs.active().url.as_str()),
+            ("ac", "c")
+        );
+    }
+
+    #[test]
+    fn moves_tabs_among_tabs_with_the_same_pin_state() {
+        let mut tabs = tabs(5, 2, 3);
+        // An unpinned tab can't be moved in front of the pinned ones
+        assert_eq!(tabs.move_tab(4, 0), 2);
+        assert_eq!(urls(&tabs), "abecd");
+        assert_eq!(tabs.active().url, "d");
+        // A pinned tab can't be moved behind the unpinned ones
+        assert_eq!(tabs.move_tab(0, 4), 1);
+        assert_eq!(urls(&tabs), "baecd");
+        assert_eq!(tabs.active().url, "d");
+        // The active tab is followed when it's moved
+        assert_eq!(tabs.move_tab(4, 2), 2);
+        assert_eq!(urls(&tabs), "badec");
+        assert_eq!(tabs.active_index(), 2);
+        // The active tab stays the same when other tabs are moved across it
+        tabs.move_tab(4, 2);
+        assert_eq!(urls(&tabs), "bacde");
+        assert_eq!(tabs.active_index(), 3);
+        tabs.move_tab(2, 4);
+        assert_eq!(urls(&tabs), "badec");
+        assert_eq!(tabs.active_index(), 2);
+        assert_eq!(tabs.move_tab(1, 1), 1);
+        assert_eq!(urls(&tabs), "badec");
+    }
+
+    #[test]
+    fn pinning_moves_tabs_to_the_boundary() {
+        let mut tabs = tabs(4, 1, 3);
+        tabs.toggle_pin(3);
+        assert_eq!(urls(&tabs), "adbc");
+        assert_eq!(tabs.pinned_count(), 2);
+        assert_eq!(tabs.active().url, "d");
+
+        tabs.toggle_pin(0);
+        assert_eq!(urls(&tabs), "dabc");
+        assert_eq!(tabs.pinned_count(), 1);
+        assert_eq!(tabs.active().url, "d");
+        assert!(!tabs.get(1).pinned);
+
+        // A tab next to the boundary stays where it is
+        tabs.toggle_pin(1);
+        assert_eq!(urls(&tabs), "dabc");
+        assert_eq!(tabs.pinned_count(), 2);
+    }
+}
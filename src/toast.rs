@@ -0,0 +1,94 @@
+//! Small notifications that slide in over the bottom right corner of the page and go away by
+//! themselves. They tell about things that happened outside of the page, such as network errors.
+
+use std::time::{Duration, Instant};
+
+/// How long a toast is shown before it's dismissed automatically
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+/// How long it takes for a toast to slide in
+const SLIDE_DURATION: Duration = Duration::from_millis(200);
+/// Older toasts are dismissed when a new one would go over this
+const MAX_TOASTS: usize = 3;
+
+/// What happens when a toast is clicked. The toast is dismissed either way.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToastAction {
+    OpenLink(String),
+    /// Continues the interrupted download with the index, see [crate::downloads::Downloads]
+    ResumeDownload(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub action: Option<ToastAction>,
+    created: Instant,
+}
+
+impl Toast {
+    /// Returns how far the toast has slid in, from 0.0 to 1.0. With reduced motion the toast
+    /// appears at once.
+    pub fn slide_progress(&self, now: Instant, reduce_motion: bool) -> f32 {
+        if reduce_motion {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(self.created);
+        (elapsed.as_secs_f32() / SLIDE_DURATION.as_secs_f32()).min(1.0)
+    }
+}
+
+/// The toasts that are shown. The newest toast is the last one.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, message: &str, action: Option<ToastAction>) {
+        println!("{}", message);
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.toasts.push(Toast {
+            message: message.to_owned(),
+            action,
+            created: Instant::now(),
+        });
+    }
+
+    /// Checks if any of the toasts is still sliding in, so the screen has to be drawn again on
+    /// every frame
+    pub fn is_sliding(&self, now: Instant, reduce_motion: bool) -> bool {
+        self.toasts
+            .iter()
+            .any(|x| x.slide_progress(now, reduce_motion) < 1.0)
+    }
+
+    /// Returns when the next toast is dismissed automatically
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.toasts.iter().map(|x| x.created + TOAST_DURATION).min()
+    }
+
+    /// Dismisses the toasts that have been shown for long enough
+    pub fn remove_expired(&mut self, now: Instant) {
+        self.toasts
+            .retain(|x| now.saturating_duration_since(x.created) < TOAST_DURATION);
+    }
+
+    /// Removes the toast and returns it, so its action can be run
+    pub fn dismiss(&mut self, index: usize) -> Option<Toast> {
+        (index < self.toasts.len()).then(|| self.toasts.remove(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.toasts.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.toasts.iter()
+    }
+}
@@ -0,0 +1,155 @@
+//! The `transform` property. Only the translations are supported, so a transform moves the box and
+//! everything inside of it without changing the layout around it, like a relatively positioned
+//! box. Transitions can animate it, see [crate::transition].
+
+use crate::calc::Calc;
+use crate::css::Value;
+
+/// How far a box is moved from its place. The percentages are relative to the size of the border
+/// box of the box itself.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Translate {
+    pub x: Calc,
+    pub y: Calc,
+}
+
+impl Translate {
+    /// Resolves the translation into pixels
+    ///
+    /// * `width`, `height` - The size of the border box, which the percentages are relative to
+    pub fn to_px(self, font_size: f32, width: i32, height: i32) -> (i32, i32) {
+        (
+            self.x.to_px(font_size, width),
+            self.y.to_px(font_size, height),
+        )
+    }
+
+    /// See [crate::css::Unit::resolve_rem]
+    pub fn resolve_rem(self, root_font_size: f32) -> Self {
+        Self {
+            x: self.x.resolve_rem(root_font_size),
+            y: self.y.resolve_rem(root_font_size),
+        }
+    }
+
+    /// Multiplies the absolute lengths with the factor, see [crate::css::Unit::zoom]
+    pub fn zoom(self, factor: f32) -> Self {
+        let zoom = |x: Calc| Calc {
+            px: x.px * factor,
+            rem: x.rem * factor,
+            ..x
+        };
+        Self {
+            x: zoom(self.x),
+            y: zoom(self.y),
+        }
+    }
+
+    /// Returns the translation that is the part of the way to the other one given by the progress
+    pub fn interpolate(self, to: Self, progress: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * progress;
+        let mix_calc = |a: Calc, b: Calc| Calc {
+            px: mix(a.px, b.px),
+            em: mix(a.em, b.em),
+            rem: mix(a.rem, b.rem),
+            percentage: mix(a.percentage, b.percentage),
+        };
+        Self {
+            x: mix_calc(self.x, to.x),
+            y: mix_calc(self.y, to.y),
+        }
+    }
+}
+
+/// Parses the value of `transform`, such as `translate(10px, 50%) translateY(1em)`. The
+/// translations are added together, and `none` doesn't move the box. Returns [None] if the value
+/// has other functions, such as `rotate()`, so the declaration is dropped.
+pub fn parse_transform(value: &str) -> Option<Translate> {
+    let value = value.trim();
+    let mut translate = Translate::default();
+    if value == "none" {
+        return Some(translate);
+    }
+    let mut rest = value;
+    while !rest.is_empty() {
+        let (name, after_name) = rest.split_once('(')?;
+        let (arguments, after_function) = after_name.split_once(')')?;
+        let lengths = arguments
+            .split(',')
+            .map(parse_length)
+            .collect::<Option<Vec<Calc>>>()?;
+        let (x, y) = match (name.trim().to_lowercase().as_str(), &lengths[..]) {
+            ("translate", [x]) => (*x, Calc::default()),
+            ("translate", [x, y]) => (*x, *y),
+            ("translatex", [x]) => (*x, Calc::default()),
+            ("translatey", [y]) => (Calc::default(), *y),
+            _ => return None,
+        };
+        translate.x = translate.x.add(x, 1.0);
+        translate.y = translate.y.add(y, 1.0);
+        rest = after_function.trim_start();
+    }
+    Some(translate)
+}
+
+/// Parses a length of a translation. A plain `0` is a length too.
+fn parse_length(value: &str) -> Option<Calc> {
+    match Value::from_string(value.trim())? {
+        Value::Unit(v) => Some(Calc::from(v)),
+        Value::Number(0) => Some(Calc::default()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn px(x: f32, y: f32) -> Translate {
+        Translate {
+            x: Calc {
+                px: x,
+                ..Default::default()
+            },
+            y: Calc {
+                px: y,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn parses_translations() {
+        assert_eq!(parse_transform("none"), Some(Translate::default()));
+        assert_eq!(parse_transform("translate(10px)"), Some(px(10.0, 0.0)));
+        assert_eq!(
+            parse_transform("translate(10px, -5px)"),
+            Some(px(10.0, -5.0))
+        );
+        assert_eq!(parse_transform("translateY(0)"), Some(px(0.0, 0.0)));
+        // The translations are added together
+        assert_eq!(
+            parse_transform("translateX(10px) translateY(20px) translate(1px, 2px)"),
+            Some(px(11.0, 22.0))
+        );
+        let translate = parse_transform("translate(50%, 1em)").unwrap();
+        assert_eq!(translate.to_px(20.0, 200, 100), (100, 20));
+    }
+
+    #[test]
+    fn drops_transforms_that_arent_translations() {
+        assert_eq!(parse_transform("rotate(45deg)"), None);
+        assert_eq!(parse_transform("translate(10px) scale(2)"), None);
+        assert_eq!(parse_transform("translate(1px, 2px, 3px)"), None);
+        assert_eq!(parse_transform("translate(10px"), None);
+    }
+
+    #[test]
+    fn interpolates_translations() {
+        let from = px(0.0, 10.0);
+        let to = px(100.0, -10.0);
+        assert_eq!(from.interpolate(to, 0.0), from);
+        assert_eq!(from.interpolate(to, 0.25), px(25.0, 5.0));
+        assert_eq!(from.interpolate(to, 1.0), to);
+    }
+}
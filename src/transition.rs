@@ -0,0 +1,493 @@
+//! CSS transitions. When the computed style of an element changes, for example because the mouse
+//! moved over it, the properties listed in its `transition` are animated from the old value to the
+//! new one instead of changing at once. The colors, the opacity and the translations of
+//! `transform` can be animated.
+
+use crate::color::Color;
+use crate::html::Element;
+use crate::style::ComputedStyle;
+use crate::transform::Translate;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A property that can be named in `transition`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransitionProperty {
+    All,
+    Color,
+    BackgroundColor,
+    BorderColor,
+    Opacity,
+    Transform,
+}
+
+impl TransitionProperty {
+    /// The properties that are animated, so everything except [Self::All]
+    const ANIMATABLE: [Self; 5] = [
+        Self::Color,
+        Self::BackgroundColor,
+        Self::BorderColor,
+        Self::Opacity,
+        Self::Transform,
+    ];
+
+    fn from_string(name: &str) -> Option<Self> {
+        match name {
+            "all" => Some(Self::All),
+            "color" => Some(Self::Color),
+            "background-color" => Some(Self::BackgroundColor),
+            "border-color" => Some(Self::BorderColor),
+            "opacity" => Some(Self::Opacity),
+            "transform" => Some(Self::Transform),
+            _ => None,
+        }
+    }
+
+    fn includes(self, property: Self) -> bool {
+        self == Self::All || self == property
+    }
+
+    fn get(self, style: &ComputedStyle) -> AnimatedValue {
+        match self {
+            Self::Color => AnimatedValue::Color(Some(style.color)),
+            Self::BackgroundColor => AnimatedValue::Color(style.background_color),
+            Self::BorderColor => AnimatedValue::Color(Some(style.border_color)),
+            Self::Opacity => AnimatedValue::Number(style.own_opacity),
+            Self::Transform => AnimatedValue::Translate(style.transform),
+            Self::All => unreachable!("All is only used to name the other properties"),
+        }
+    }
+}
+
+/// The value of an animatable property
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AnimatedValue {
+    /// [None] is transparent
+    Color(Option<Color>),
+    Number(f32),
+    Translate(Translate),
+}
+
+/// Returns the value that is the part of the way from one value to the other given by the
+/// progress. Values of different kinds can't be mixed, so they jump to the new value.
+fn interpolate(from: AnimatedValue, to: AnimatedValue, progress: f32) -> AnimatedValue {
+    let mix = |a: f32, b: f32| a + (b - a) * progress;
+    match (from, to) {
+        (AnimatedValue::Number(from), AnimatedValue::Number(to)) => {
+            AnimatedValue::Number(mix(from, to))
+        }
+        (AnimatedValue::Translate(from), AnimatedValue::Translate(to)) => {
+            AnimatedValue::Translate(from.interpolate(to, progress))
+        }
+        (AnimatedValue::Color(from), AnimatedValue::Color(to)) => {
+            // A transparent color fades from or into the other color
+            let (from, to) = match (from, to) {
+                (Some(from), Some(to)) => (from, to),
+                (Some(from), None) => (from, Color { a: 0.0, ..from }),
+                (None, Some(to)) => (Color { a: 0.0, ..to }, to),
+                (None, None) => return AnimatedValue::Color(None),
+            };
+            AnimatedValue::Color(Some(Color {
+                r: mix(from.r, to.r),
+                g: mix(from.g, to.g),
+                b: mix(from.b, to.b),
+                a: mix(from.a, to.a),
+            }))
+        }
+        _ => to,
+    }
+}
+
+/// How the progress of a transition is spread over its duration
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimingFunction {
+    Linear,
+    Ease,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl TimingFunction {
+    fn from_string(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Self::Linear),
+            "ease" => Some(Self::Ease),
+            "ease-in" => Some(Self::EaseIn),
+            "ease-out" => Some(Self::EaseOut),
+            "ease-in-out" => Some(Self::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// Turns the elapsed part of the duration into the progress of the value, both from 0.0 to
+    /// 1.0
+    fn apply(self, time: f32) -> f32 {
+        let (x1, y1, x2, y2) = match self {
+            Self::Linear => return time,
+            Self::Ease => (0.25, 0.1, 0.25, 1.0),
+            Self::EaseIn => (0.42, 0.0, 1.0, 1.0),
+            Self::EaseOut => (0.0, 0.0, 0.58, 1.0),
+            Self::EaseInOut => (0.42, 0.0, 0.58, 1.0),
+        };
+        cubic_bezier(x1, y1, x2, y2, time)
+    }
+}
+
+/// Evaluates the curve from (0, 0) to (1, 1) with the two control points at `x`. The x of the
+/// curve only grows, so the point can be found by bisection.
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let bezier = |a: f32, b: f32, t: f32| {
+        3.0 * a * t * (1.0 - t).powi(2) + 3.0 * b * t.powi(2) * (1.0 - t) + t.powi(3)
+    };
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..20 {
+        let middle = (low + high) / 2.0;
+        if bezier(x1, x2, middle) < x {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    bezier(y1, y2, (low + high) / 2.0)
+}
+
+/// A single entry of `transition`, such as `color 0.3s ease-in`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transition {
+    pub property: TransitionProperty,
+    pub duration: Duration,
+    pub delay: Duration,
+    pub timing_function: TimingFunction,
+}
+
+/// Parses a time such as `0.3s` or `300ms`
+fn parse_time(value: &str) -> Option<Duration> {
+    let seconds = match value.strip_suffix("ms") {
+        Some(v) => v.parse::<f32>().ok()? / 1000.0,
+        None => value.strip_suffix('s')?.parse::<f32>().ok()?,
+    };
+    Duration::try_from_secs_f32(seconds).ok()
+}
+
+/// Parses the comma separated list of the `transition` shorthand. The first time is the duration
+/// and the second one is the delay. Entries for properties that can't be animated are skipped.
+pub fn parse_transitions(value: &str) -> Vec<Transition> {
+    let mut transitions = Vec::new();
+    for entry in value.split(',') {
+        let mut transition = Transition {
+            property: TransitionProperty::All,
+            duration: Duration::ZERO,
+            delay: Duration::ZERO,
+            timing_function: TimingFunction::Ease,
+        };
+        let mut times = 0;
+        let mut supported = true;
+        for part in entry.split_whitespace() {
+            let part = part.to_lowercase();
+            if part == "none" {
+                return Vec::new();
+            } else if let Some(time) = parse_time(&part) {
+                match times {
+                    0 => transition.duration = time,
+                    _ => transition.delay = time,
+                }
+                times += 1;
+            } else if let Some(v) = TimingFunction::from_string(&part) {
+                transition.timing_function = v;
+            } else if let Some(v) = TransitionProperty::from_string(&part) {
+                transition.property = v;
+            } else {
+                println!("Unsupported transition value: {}", part);
+                supported = false;
+            }
+        }
+        if supported {
+            transitions.push(transition);
+        }
+    }
+    transitions
+}
+
+/// The values of the animatable properties of the elements before their styles were computed
+/// again, by the id of the element
+pub type PreviousValues = HashMap<usize, [AnimatedValue; 5]>;
+
+/// Collects the values of the animatable properties of the element and its children
+pub fn collect_values(element: &Element, output: &mut PreviousValues) {
+    output.insert(
+        element.id,
+        TransitionProperty::ANIMATABLE.map(|x| x.get(&element.style)),
+    );
+    for child in &element.children {
+        collect_values(child, output);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Animation {
+    element_id: usize,
+    property: TransitionProperty,
+    from: AnimatedValue,
+    to: AnimatedValue,
+    start: Instant,
+    transition: Transition,
+}
+
+impl Animation {
+    /// Returns the value at the moment and whether the animation has finished
+    fn value_at(&self, now: Instant) -> (AnimatedValue, bool) {
+        let elapsed = now
+            .saturating_duration_since(self.start)
+            .saturating_sub(self.transition.delay);
+        if elapsed >= self.transition.duration {
+            return (self.to, true);
+        }
+        let time = elapsed.as_secs_f32() / self.transition.duration.as_secs_f32();
+        let progress = self.transition.timing_function.apply(time);
+        (interpolate(self.from, self.to, progress), false)
+    }
+}
+
+/// The transitions that are running in a document
+#[derive(Debug, Default)]
+pub struct Animations {
+    running: Vec<Animation>,
+}
+
+impl Animations {
+    pub fn clear(&mut self) {
+        self.running.clear();
+    }
+
+    /// Starts the transitions of the properties that changed when the styles were computed
+    /// again. A transition that is interrupted starts again from the value it had reached.
+    pub fn start(&mut self, element: &Element, previous: &PreviousValues, now: Instant) {
+        if let Some(old_values) = previous.get(&element.id) {
+            for (property, old) in TransitionProperty::ANIMATABLE.into_iter().zip(old_values) {
+                let new = property.get(&element.style);
+                if *old == new {
+                    continue;
+                }
+                self.running
+                    .retain(|x| x.element_id != element.id || x.property != property);
+                // The last entry that names the property wins, like in browsers
+                let transition = element
+                    .style
+                    .transitions
+                    .iter()
+                    .rev()
+                    .find(|x| x.property.includes(property));
+                if let Some(transition) = transition.filter(|x| !x.duration.is_zero()) {
+                    self.running.push(Animation {
+                        element_id: element.id,
+                        property,
+                        from: *old,
+                        to: new,
+                        start: now,
+                        transition: *transition,
+                    });
+                }
+            }
+        }
+        for child in &element.children {
+            self.start(child, previous, now);
+        }
+    }
+
+    /// Sets the animated properties of the elements and their children to their values at the
+    /// moment. The finished transitions are removed. Returns true if any are still running.
+    pub fn apply(&mut self, elements: &mut [Element], now: Instant) -> bool {
+        for element in elements {
+            self.apply_to(element, 1.0, now);
+        }
+        self.running.retain(|x| !x.value_at(now).1);
+        !self.running.is_empty()
+    }
+
+    /// * `parent_opacity` - The opacity that the parent of the element is drawn with, see
+    ///   [ComputedStyle::opacity]
+    fn apply_to(&self, element: &mut Element, parent_opacity: f32, now: Instant) {
+        for animation in &self.running {
+            if animation.element_id != element.id {
+                continue;
+            }
+            match (animation.property, animation.value_at(now).0) {
+                (TransitionProperty::Color, AnimatedValue::Color(value)) => {
+                    let old = element.style.color;
+                    set_color(element, old, value.unwrap_or(old));
+                }
+                (TransitionProperty::BackgroundColor, AnimatedValue::Color(value)) => {
+                    element.style.background_color = value
+                }
+                (TransitionProperty::BorderColor, AnimatedValue::Color(value)) => {
+                    element.style.border_color = value.unwrap_or(element.style.border_color)
+                }
+                (TransitionProperty::Opacity, AnimatedValue::Number(value)) => {
+                    element.style.own_opacity = value
+                }
+                (TransitionProperty::Transform, AnimatedValue::Translate(value)) => {
+                    element.style.transform = value
+                }
+                _ => (),
+            }
+        }
+        // The children are drawn with the opacity of the element multiplied in, so they fade
+        // with it
+        element.style.opacity = parent_opacity * element.style.own_opacity;
+        for child in &mut element.children {
+            self.apply_to(child, element.style.opacity, now);
+        }
+    }
+}
+
+/// Sets the text color of the element. The color is inherited, so the children that have the same
+/// color as the element get the new color too.
+fn set_color(element: &mut Element, old: Color, new: Color) {
+    element.style.color = new;
+    for child in &mut element.children {
+        if child.style.color == old {
+            set_color(child, old, new);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::Tag;
+    use crate::transform::parse_transform;
+
+    const TIMING_FUNCTIONS: [TimingFunction; 5] = [
+        TimingFunction::Linear,
+        TimingFunction::Ease,
+        TimingFunction::EaseIn,
+        TimingFunction::EaseOut,
+        TimingFunction::EaseInOut,
+    ];
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 0.005,
+            "Expected {}, got {}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn timing_functions_start_at_zero_and_end_at_one() {
+        for function in TIMING_FUNCTIONS {
+            assert_near(function.apply(0.0), 0.0);
+            assert_near(function.apply(1.0), 1.0);
+        }
+        assert_near(cubic_bezier(0.1, 0.7, 1.0, 0.1, 0.0), 0.0);
+        assert_near(cubic_bezier(0.1, 0.7, 1.0, 0.1, 1.0), 1.0);
+    }
+
+    #[test]
+    fn timing_functions_only_grow() {
+        for function in TIMING_FUNCTIONS {
+            let values: Vec<f32> = (0..=100)
+                .map(|x| function.apply(x as f32 / 100.0))
+                .collect();
+            assert!(
+                values.windows(2).all(|x| x[1] >= x[0] - 0.0001),
+                "{:?} goes backwards",
+                function
+            );
+        }
+    }
+
+    #[test]
+    fn ease_matches_the_curve_of_browsers() {
+        assert_near(TimingFunction::Ease.apply(0.25), 0.4094);
+        assert_near(TimingFunction::Ease.apply(0.5), 0.8024);
+        assert_near(TimingFunction::Ease.apply(0.75), 0.9604);
+        // The symmetric curves pass through the middle
+        assert_near(TimingFunction::EaseInOut.apply(0.5), 0.5);
+        assert_near(TimingFunction::Linear.apply(0.3), 0.3);
+        // Easing in starts slowly, and easing out ends slowly
+        assert!(TimingFunction::EaseIn.apply(0.5) < 0.5);
+        assert!(TimingFunction::EaseOut.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn interpolates_colors() {
+        let black = AnimatedValue::Color(Some(Color::black()));
+        let white = AnimatedValue::Color(Some(Color::white()));
+        let gray = Color {
+            r: 0.5,
+            g: 0.5,
+            b: 0.5,
+            a: 1.0,
+        };
+        assert_eq!(interpolate(black, white, 0.0), black);
+        assert_eq!(
+            interpolate(black, white, 0.5),
+            AnimatedValue::Color(Some(gray))
+        );
+        assert_eq!(interpolate(black, white, 1.0), white);
+        // Transparent fades into the color
+        assert_eq!(
+            interpolate(AnimatedValue::Color(None), white, 0.5),
+            AnimatedValue::Color(Some(Color {
+                a: 0.5,
+                ..Color::white()
+            }))
+        );
+    }
+
+    #[test]
+    fn animates_the_opacity_of_the_children_too() {
+        let transitions = parse_transitions("opacity 1s linear");
+        assert_eq!(transitions[0].property, TransitionProperty::Opacity);
+
+        let mut child = Element::new(Tag::Span);
+        child.id = 1;
+        let mut element = Element::new(Tag::Div);
+        element.style.transitions = transitions;
+        element.children.push(child);
+        let mut previous = PreviousValues::new();
+        collect_values(&element, &mut previous);
+
+        // The styles are computed again with the element hidden
+        element.style.own_opacity = 0.0;
+        element.style.opacity = 0.0;
+        element.children[0].style.opacity = 0.0;
+        let mut animations = Animations::default();
+        let start = Instant::now();
+        animations.start(&element, &previous, start);
+
+        let mut elements = [element];
+        assert!(animations.apply(&mut elements, start + Duration::from_millis(250)));
+        assert_near(elements[0].style.opacity, 0.75);
+        assert_near(elements[0].children[0].style.opacity, 0.75);
+        assert!(!animations.apply(&mut elements, start + Duration::from_secs(1)));
+        assert_eq!(elements[0].style.opacity, 0.0);
+        assert_eq!(elements[0].children[0].style.opacity, 0.0);
+    }
+
+    #[test]
+    fn animates_translations() {
+        let transitions = parse_transitions("transform 1s linear");
+        assert_eq!(transitions[0].property, TransitionProperty::Transform);
+
+        let mut element = Element::new(Tag::Div);
+        element.style.transitions = transitions;
+        let mut previous = PreviousValues::new();
+        collect_values(&element, &mut previous);
+
+        let to = parse_transform("translate(100px, -20px)").unwrap();
+        element.style.transform = to;
+        let mut animations = Animations::default();
+        let start = Instant::now();
+        animations.start(&element, &previous, start);
+
+        let mut elements = [element];
+        assert!(animations.apply(&mut elements, start + Duration::from_millis(250)));
+        assert_eq!(elements[0].style.transform.to_px(0.0, 0, 0), (25, -5));
+        assert!(!animations.apply(&mut elements, start + Duration::from_secs(1)));
+        assert_eq!(elements[0].style.transform, to);
+    }
+}
@@ -0,0 +1,136 @@
+//! The set of visited links, which `:visited` is matched against. Only salted hashes of the urls
+//! are stored. The salt is saved in the same file, so anyone with the file can still check if a
+//! given url was visited. The hashes only keep the urls from being looked up from a precomputed
+//! table.
+
+use crate::migration::{self, Migration};
+use std::{
+    collections::{hash_map::RandomState, HashSet},
+    hash::{BuildHasher, Hasher},
+    path::Path,
+};
+
+pub const VISITED_PATH: &str = "./visited.txt";
+/// The changes to the format of the visited links file, see [crate::migration]
+const MIGRATIONS: [Migration; 1] = [migration::add_version];
+
+pub struct VisitedLinks {
+    /// Mixed into every hash, so the hashes of common urls can't be looked up from a table
+    salt: u64,
+    hashes: HashSet<u64>,
+}
+
+impl VisitedLinks {
+    /// Creates an empty set with a random salt
+    pub fn new() -> Self {
+        Self {
+            salt: RandomState::new().build_hasher().finish(),
+            hashes: HashSet::new(),
+        }
+    }
+
+    /// Loads the set from a file. A missing or broken file gives an empty set.
+    pub fn load(path: &Path) -> Self {
+        let Some(content) = migration::read(path, &MIGRATIONS) else {
+            return Self::new();
+        };
+
+        // The first line is the salt and the rest of the lines are the hashes
+        let mut lines = content.lines();
+        let Some(Ok(salt)) = lines.next().map(|x| u64::from_str_radix(x, 16)) else {
+            println!("The visited links file is broken");
+            return Self::new();
+        };
+        let hashes = lines
+            .filter_map(|x| u64::from_str_radix(x, 16).ok())
+            .collect();
+        Self { salt, hashes }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let mut content = format!("{:016x}\n", self.salt);
+        for hash in &self.hashes {
+            content += &format!("{:016x}\n", hash);
+        }
+        if let Err(error) = migration::write(path, &content, &MIGRATIONS) {
+            println!("Couldn't save the visited links: {}", error);
+        }
+    }
+
+    /// Hashes the url with 64 bit FNV-1a. The standard library hashers aren't guaranteed to stay
+    /// the same between versions, so they can't be used for hashes that are saved.
+    fn hash(&self, url: &str) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in self.salt.to_le_bytes().iter().chain(url.as_bytes()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    pub fn add(&mut self, url: &str) {
+        self.hashes.insert(self.hash(url));
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.hashes.contains(&self.hash(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A file in the temporary directory that is only used by the test
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("web-browser-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn hashes_stay_the_same_after_saving() {
+        let path = temp_path("visited.txt");
+        let mut visited = VisitedLinks::new();
+        visited.add("https://example.com/");
+        visited.add("https://example.com/page");
+        visited.save(&path);
+        let loaded = VisitedLinks::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.salt, visited.salt);
+        assert_eq!(loaded.hashes, visited.hashes);
+        assert_eq!(loaded.hash("https://a.b/"), visited.hash("https://a.b/"));
+        assert!(loaded.contains("https://example.com/"));
+        assert!(loaded.contains("https://example.com/page"));
+        assert!(!loaded.contains("https://example.com/other"));
+    }
+
+    #[test]
+    fn hashes_depend_on_the_salt() {
+        let visited = |salt| VisitedLinks {
+            salt,
+            hashes: HashSet::new(),
+        };
+        assert_eq!(
+            visited(1).hash("https://a.b/"),
+            visited(1).hash("https://a.b/")
+        );
+        assert_ne!(
+            visited(1).hash("https://a.b/"),
+            visited(2).hash("https://a.b/")
+        );
+        assert_ne!(
+            visited(1).hash("https://a.b/"),
+            visited(1).hash("https://a.c/")
+        );
+    }
+
+    #[test]
+    fn broken_salt_gives_an_empty_set() {
+        let path = temp_path("broken-visited.txt");
+        fs::write(&path, "#version 1\nnot a salt\n00000000000000ff\n").unwrap();
+        let loaded = VisitedLinks::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.hashes.is_empty());
+    }
+}
@@ -0,0 +1,558 @@
+//! A small WebSocket client that follows RFC 6455. Only unencrypted `ws://` urls are supported for
+//! now. The socket doesn't block after the handshake, so it can be polled from the event loop.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use web::base64;
+
+/// Appended to the key of the handshake before it's hashed, see [accept_key]
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Servers that send a longer response to the handshake are most likely not WebSocket servers
+const MAX_HANDSHAKE_LENGTH: usize = 16 * 1024;
+/// Longer messages are refused, so a server can't make the browser buffer without a limit
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+/// The status code of closing the connection because a message is too long
+const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// The status code and the reason for closing the connection
+    Close(Option<u16>, String),
+}
+
+pub struct WebSocket {
+    stream: TcpStream,
+    /// Bytes that have been read but don't make a whole frame yet
+    buffer: Vec<u8>,
+    /// The opcode and the payload so far of a message that is split into several frames
+    fragments: Option<(u8, Vec<u8>)>,
+    close_sent: bool,
+    closed: bool,
+}
+
+impl WebSocket {
+    /// Connects to the server and does the opening handshake
+    pub fn connect(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("ws://")
+            .ok_or_else(|| format!("Only ws:// urls are supported: {}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let address = if authority.contains(':') {
+            authority.to_owned()
+        } else {
+            format!("{}:80", authority)
+        };
+        let address = address
+            .to_socket_addrs()
+            .map_err(|x| x.to_string())?
+            .next()
+            .ok_or_else(|| format!("Couldn't resolve {}", authority))?;
+        let mut stream =
+            TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|x| x.to_string())?;
+        stream
+            .set_read_timeout(Some(CONNECT_TIMEOUT))
+            .map_err(|x| x.to_string())?;
+
+        let key = base64::encode(&random_bytes::<16>());
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, authority, key
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|x| x.to_string())?;
+
+        // The response is read a byte at a time, so none of the frames after it are read by
+        // accident
+        let mut response = Vec::new();
+        let mut byte = [0];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() > MAX_HANDSHAKE_LENGTH {
+                return Err("The response to the handshake is too long".to_owned());
+            }
+            stream.read_exact(&mut byte).map_err(|x| x.to_string())?;
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let mut lines = response.lines();
+        let status_line = lines.next().unwrap_or("");
+        if status_line.split_whitespace().nth(1) != Some("101") {
+            return Err(format!("The server refused the upgrade: {}", status_line));
+        }
+        let accept = lines
+            .filter_map(|x| x.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-accept"))
+            .map(|(_, value)| value.trim().to_owned());
+        if accept.as_deref() != Some(accept_key(&key).as_str()) {
+            return Err("The server sent a wrong Sec-WebSocket-Accept".to_owned());
+        }
+
+        stream.set_nonblocking(true).map_err(|x| x.to_string())?;
+        Ok(Self {
+            stream,
+            buffer: Vec::new(),
+            fragments: None,
+            close_sent: false,
+            closed: false,
+        })
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let (opcode, payload) = match message {
+            Message::Text(v) => (OP_TEXT, v.as_bytes().to_vec()),
+            Message::Binary(v) => (OP_BINARY, v.clone()),
+            Message::Ping(v) => (OP_PING, v.clone()),
+            Message::Pong(v) => (OP_PONG, v.clone()),
+            Message::Close(code, reason) => {
+                self.close_sent = true;
+                let mut payload = Vec::new();
+                if let Some(code) = code {
+                    payload.extend(code.to_be_bytes());
+                    payload.extend(reason.as_bytes());
+                }
+                (OP_CLOSE, payload)
+            }
+        };
+        self.write_frame(opcode, &payload)
+    }
+
+    /// Reads the messages that have arrived without waiting for more. Pings and closing the
+    /// connection are answered automatically.
+    pub fn poll(&mut self) -> Result<Vec<Message>, String> {
+        let mut chunk = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(length) => self.buffer.extend(&chunk[..length]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error.to_string()),
+            }
+        }
+
+        let mut messages = Vec::new();
+        loop {
+            let frame = match RawFrame::parse(&self.buffer) {
+                Ok(Some(v)) => v,
+                Ok(None) => break,
+                Err(error) => return Err(self.refuse_message(error)),
+            };
+            self.buffer.drain(..frame.length);
+            let (opcode, payload) = match (frame.opcode, self.fragments.take()) {
+                (OP_CONTINUATION, Some((opcode, mut payload))) => {
+                    if payload.len() + frame.payload.len() > MAX_MESSAGE_LENGTH {
+                        let error =
+                            format!("A message is longer than {} bytes", MAX_MESSAGE_LENGTH);
+                        return Err(self.refuse_message(error));
+                    }
+                    payload.extend(frame.payload);
+                    (opcode, payload)
+                }
+                (OP_CONTINUATION, None) => return Err("Unexpected continuation frame".to_owned()),
+                (opcode, fragments) => {
+                    // Control frames can come between the fragments of a message
+                    self.fragments = fragments;
+                    (opcode, frame.payload)
+                }
+            };
+            if !frame.fin {
+                self.fragments = Some((opcode, payload));
+                continue;
+            }
+
+            let message = match opcode {
+                OP_TEXT => Message::Text(String::from_utf8_lossy(&payload).into_owned()),
+                OP_BINARY => Message::Binary(payload),
+                OP_PING => {
+                    self.write_frame(OP_PONG, &payload)?;
+                    Message::Ping(payload)
+                }
+                OP_PONG => Message::Pong(payload),
+                OP_CLOSE => {
+                    let code = payload.get(..2).map(|x| u16::from_be_bytes([x[0], x[1]]));
+                    let reason = String::from_utf8_lossy(payload.get(2..).unwrap_or(&[]));
+                    if !self.close_sent {
+                        self.send(&Message::Close(code, String::new()))?;
+                    }
+                    self.closed = true;
+                    Message::Close(code, reason.into_owned())
+                }
+                v => return Err(format!("Unknown opcode {:#x}", v)),
+            };
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    /// Closes the connection because of a message that is too long. Returns the error.
+    fn refuse_message(&mut self, error: String) -> String {
+        self.buffer.clear();
+        self.fragments = None;
+        if !self.close_sent {
+            // The connection is given up either way, so it doesn't matter if this fails
+            let _ = self.send(&Message::Close(Some(CLOSE_MESSAGE_TOO_BIG), String::new()));
+        }
+        self.closed = true;
+        error
+    }
+
+    /// Checks if the connection has been closed by either side
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), String> {
+        let frame = encode_frame(opcode, payload, random_bytes::<4>());
+
+        // The whole frame has to be written, even if the send buffer of the socket is full
+        self.stream
+            .set_nonblocking(false)
+            .map_err(|x| x.to_string())?;
+        let result = self.stream.write_all(&frame).map_err(|x| x.to_string());
+        self.stream
+            .set_nonblocking(true)
+            .map_err(|x| x.to_string())?;
+        result
+    }
+}
+
+/// A single frame read from the socket
+struct RawFrame {
+    /// Set on the last frame of a message
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+    /// The length of the whole frame with the header
+    length: usize,
+}
+
+impl RawFrame {
+    /// Parses the frame at the start of the bytes. Returns [None] if the frame hasn't been fully
+    /// received yet, and an error as soon as the header shows that the payload is longer than
+    /// [MAX_MESSAGE_LENGTH].
+    fn parse(bytes: &[u8]) -> Result<Option<Self>, String> {
+        let Some((payload_length, mask, offset)) = Self::parse_header(bytes) else {
+            return Ok(None);
+        };
+        if payload_length > MAX_MESSAGE_LENGTH as u64 {
+            return Err(format!(
+                "A frame of {} bytes is longer than {} bytes",
+                payload_length, MAX_MESSAGE_LENGTH
+            ));
+        }
+        let length = offset + payload_length as usize;
+        let Some(payload) = bytes.get(offset..length) else {
+            return Ok(None);
+        };
+        let payload = match mask {
+            Some(mask) => payload
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x ^ mask[i % 4])
+                .collect(),
+            None => payload.to_vec(),
+        };
+        Ok(Some(Self {
+            fin: bytes[0] & 0x80 != 0,
+            opcode: bytes[0] & 0x0f,
+            payload,
+            length,
+        }))
+    }
+
+    /// Parses the header of the frame at the start of the bytes. Returns the length of the
+    /// payload, the mask and where the payload starts, or [None] if the header hasn't been fully
+    /// received yet.
+    fn parse_header(bytes: &[u8]) -> Option<(u64, Option<[u8; 4]>, usize)> {
+        let second = *bytes.get(1)?;
+        let (payload_length, mut offset) = match second & 0x7f {
+            126 => (
+                u16::from_be_bytes(bytes.get(2..4)?.try_into().ok()?) as u64,
+                4,
+            ),
+            127 => (u64::from_be_bytes(bytes.get(2..10)?.try_into().ok()?), 10),
+            length => (length as u64, 2),
+        };
+        // Servers shouldn't mask their frames, but it doesn't hurt to accept them
+        let mask: Option<[u8; 4]> = if second & 0x80 != 0 {
+            let mask = bytes.get(offset..offset + 4)?.try_into().ok()?;
+            offset += 4;
+            Some(mask)
+        } else {
+            None
+        };
+        Some((payload_length, mask, offset))
+    }
+}
+
+/// Makes the last frame of a message. Frames from the client are always masked.
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length if length < 126 => frame.push(0x80 | length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend((length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(0x80 | 127);
+            frame.extend((length as u64).to_be_bytes());
+        }
+    }
+    frame.extend(mask);
+    frame.extend(payload.iter().enumerate().map(|(i, x)| x ^ mask[i % 4]));
+    frame
+}
+
+/// Returns the `Sec-WebSocket-Accept` value that the server has to answer the key with
+fn accept_key(key: &str) -> String {
+    base64::encode(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()))
+}
+
+/// Makes bytes that are hard to guess. The keys only have to keep proxies from mistaking the
+/// frames for something else, so this doesn't have to be cryptographically secure.
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut output = [0; N];
+    for chunk in output.chunks_mut(8) {
+        // Every new RandomState has different keys
+        let hash = RandomState::new().build_hasher().finish().to_le_bytes();
+        chunk.copy_from_slice(&hash[..chunk.len()]);
+    }
+    output
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        for index in 16..80 {
+            words[index] =
+                (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16])
+                    .rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, new) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(new);
+        }
+    }
+
+    let mut output = [0; 20];
+    for (bytes, value) in output.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    /// Parses a frame that has been fully received
+    fn parse(bytes: &[u8]) -> RawFrame {
+        RawFrame::parse(bytes)
+            .unwrap()
+            .expect("Expected a whole frame")
+    }
+
+    /// Connects a [WebSocket] to a local socket without the handshake. The other end of the
+    /// connection plays the server.
+    fn connected() -> (WebSocket, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let socket = WebSocket {
+            stream,
+            buffer: Vec::new(),
+            fragments: None,
+            close_sent: false,
+            closed: false,
+        };
+        (socket, server)
+    }
+
+    /// Polls the socket until a message arrives
+    fn next_messages(socket: &mut WebSocket) -> Vec<Message> {
+        let start = Instant::now();
+        loop {
+            let messages = socket.poll().unwrap();
+            if !messages.is_empty() {
+                return messages;
+            }
+            assert!(start.elapsed() < CONNECT_TIMEOUT, "No message arrived");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn hashes_with_sha1() {
+        let hex =
+            |bytes: [u8; 20]| -> String { bytes.iter().map(|x| format!("{:02x}", x)).collect() };
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        // Long enough to need a second block for the padding
+        assert_eq!(
+            hex(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn accepts_the_key_of_the_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn parses_every_form_of_the_payload_length() {
+        // 7-bit, 16-bit and 64-bit lengths
+        for (length, header_length) in [(0, 6), (125, 6), (126, 8), (65535, 8), (65536, 14)] {
+            let payload: Vec<u8> = (0..length).map(|x| x as u8).collect();
+            let bytes = encode_frame(OP_BINARY, &payload, [1, 2, 3, 4]);
+            assert_eq!(bytes.len(), header_length + length);
+
+            let frame = parse(&bytes);
+            assert!(frame.fin);
+            assert_eq!(frame.opcode, OP_BINARY);
+            assert_eq!(frame.payload, payload);
+            assert_eq!(frame.length, bytes.len());
+
+            // Nothing is parsed until the whole frame has arrived
+            assert!(RawFrame::parse(&bytes[..bytes.len() - 1])
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn parses_a_frame_at_a_time() {
+        let mut bytes = encode_frame(OP_TEXT, b"Hello", [0; 4]);
+        bytes[0] &= 0x7f;
+        bytes.extend(encode_frame(OP_CONTINUATION, b" world", [5, 6, 7, 8]));
+
+        let first = parse(&bytes);
+        assert!(!first.fin);
+        assert_eq!(first.opcode, OP_TEXT);
+        assert_eq!(first.payload, b"Hello");
+
+        let second = parse(&bytes[first.length..]);
+        assert!(second.fin);
+        assert_eq!(second.opcode, OP_CONTINUATION);
+        assert_eq!(second.payload, b" world");
+        assert_eq!(first.length + second.length, bytes.len());
+    }
+
+    #[test]
+    fn joins_fragmented_messages() {
+        let (mut socket, mut server) = connected();
+        let mut bytes = encode_frame(OP_TEXT, b"Hel", [0; 4]);
+        bytes[0] &= 0x7f;
+        // Control frames can come between the fragments
+        bytes.extend(encode_frame(OP_PONG, b"", [0; 4]));
+        let mut middle = encode_frame(OP_CONTINUATION, b"lo ", [0; 4]);
+        middle[0] &= 0x7f;
+        bytes.extend(middle);
+        bytes.extend(encode_frame(OP_CONTINUATION, b"world", [0; 4]));
+        server.write_all(&bytes).unwrap();
+
+        let mut messages = Vec::new();
+        while messages.len() < 2 {
+            messages.extend(next_messages(&mut socket));
+        }
+        assert_eq!(
+            messages,
+            [
+                Message::Pong(Vec::new()),
+                Message::Text("Hello world".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_messages_that_are_too_long() {
+        // Only the header of the frame has arrived, with the largest possible length
+        let mut header = vec![0x80 | OP_BINARY, 127];
+        header.extend(u64::MAX.to_be_bytes());
+        assert!(RawFrame::parse(&header).is_err());
+
+        let (mut socket, mut server) = connected();
+        server.write_all(&header).unwrap();
+        let start = Instant::now();
+        let error = loop {
+            match socket.poll() {
+                Ok(_) => assert!(
+                    start.elapsed() < CONNECT_TIMEOUT,
+                    "The frame wasn't refused"
+                ),
+                Err(error) => break error,
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert!(error.contains("longer than"), "{}", error);
+        assert!(socket.is_closed());
+
+        // The client closes the connection with 1009
+        let mut close = [0; 8];
+        server.read_exact(&mut close).unwrap();
+        let frame = parse(&close);
+        assert_eq!(frame.opcode, OP_CLOSE);
+        assert_eq!(frame.payload, CLOSE_MESSAGE_TOO_BIG.to_be_bytes());
+    }
+}
@@ -0,0 +1,166 @@
+//! The `about:websocket` developer page. It connects to a WebSocket server and shows the frames
+//! that go back and forth. Pages can't take input yet, so the commands are given in the query of
+//! the url, for example `about:websocket?send=hello`.
+
+use crate::websocket::{Message, WebSocket};
+
+pub const WEBSOCKET_PAGE_URL: &str = "about:websocket";
+/// The oldest frames are dropped from the log after this
+const MAX_LOG_LINES: usize = 500;
+/// Binary payloads are shown in hex up to this many bytes
+const MAX_SHOWN_BYTES: usize = 32;
+
+#[derive(Default)]
+pub struct WebSocketPage {
+    socket: Option<WebSocket>,
+    status: String,
+    log: Vec<String>,
+}
+
+impl WebSocketPage {
+    pub fn new() -> Self {
+        Self {
+            status: "Not connected".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Checks if the page has a connection that frames can arrive from
+    pub fn is_connected(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// Runs the command in the query of the url. The query is everything after
+    /// [WEBSOCKET_PAGE_URL], such as `?connect=ws://localhost:8080/`.
+    pub fn run_command(&mut self, query: &str) {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let (command, argument) = query.split_once('=').unwrap_or((query, ""));
+        let argument = percent_decode(argument);
+        match command {
+            "" => (),
+            "connect" => self.connect(&argument),
+            "send" => self.send(Message::Text(argument)),
+            "ping" => self.send(Message::Ping(argument.into_bytes())),
+            "close" => self.send(Message::Close(Some(1000), argument)),
+            v => self.log.push(format!("Unknown command: {}", v)),
+        }
+    }
+
+    fn connect(&mut self, url: &str) {
+        self.log.push(format!("Connecting to {}", url));
+        match WebSocket::connect(url) {
+            Ok(socket) => {
+                self.socket = Some(socket);
+                self.status = format!("Connected to {}", url);
+            }
+            Err(error) => {
+                self.socket = None;
+                self.status = format!("Couldn't connect to {}: {}", url, error);
+            }
+        }
+        self.log.push(self.status.clone());
+    }
+
+    fn send(&mut self, message: Message) {
+        let Some(socket) = self.socket.as_mut() else {
+            self.log.push("Not connected".to_owned());
+            return;
+        };
+        match socket.send(&message) {
+            Ok(()) => self.log.push(format!("> {}", describe(&message))),
+            Err(error) => self.disconnect(&error),
+        }
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        self.socket = None;
+        self.status = format!("Disconnected: {}", reason);
+        self.log.push(self.status.clone());
+    }
+
+    /// Reads the frames that have arrived. Returns true if the page changed.
+    pub fn poll(&mut self) -> bool {
+        let Some(socket) = self.socket.as_mut() else {
+            return false;
+        };
+        let log_length = self.log.len();
+        match socket.poll() {
+            Ok(messages) => {
+                let closed = socket.is_closed();
+                self.log
+                    .extend(messages.iter().map(|x| format!("< {}", describe(x))));
+                if closed {
+                    self.disconnect("The connection was closed");
+                }
+            }
+            Err(error) => self.disconnect(&error),
+        }
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.drain(..self.log.len() - MAX_LOG_LINES);
+        }
+        self.log.len() != log_length
+    }
+
+    /// Returns the content of the page, which is shown with the plain text viewer
+    pub fn text(&self) -> String {
+        let mut output = format!("WebSocket\n\nStatus: {}\n\n", self.status);
+        output += "Commands, typed into the address bar:\n";
+        output += &format!("  {}?connect=ws://host:port/path\n", WEBSOCKET_PAGE_URL);
+        output += &format!("  {}?send=text (use %20 for spaces)\n", WEBSOCKET_PAGE_URL);
+        output += &format!("  {}?ping\n", WEBSOCKET_PAGE_URL);
+        output += &format!("  {}?close\n\n", WEBSOCKET_PAGE_URL);
+        output += "Frames:\n";
+        for line in &self.log {
+            output += &format!("  {}\n", line);
+        }
+        output
+    }
+}
+
+/// Returns a line for the log that tells what kind of a frame the message is and what's in it
+fn describe(message: &Message) -> String {
+    let bytes = |payload: &[u8]| {
+        let hex: Vec<String> = payload
+            .iter()
+            .take(MAX_SHOWN_BYTES)
+            .map(|x| format!("{:02x}", x))
+            .collect();
+        let more = if payload.len() > MAX_SHOWN_BYTES {
+            " ..."
+        } else {
+            ""
+        };
+        format!("{} bytes [{}{}]", payload.len(), hex.join(" "), more)
+    };
+    match message {
+        Message::Text(v) => format!("text: {}", v),
+        Message::Binary(v) => format!("binary: {}", bytes(v)),
+        Message::Ping(v) => format!("ping: {}", bytes(v)),
+        Message::Pong(v) => format!("pong: {}", bytes(v)),
+        Message::Close(Some(code), reason) => format!("close: {} {}", code, reason),
+        Message::Close(None, _) => "close".to_owned(),
+    }
+}
+
+/// Decodes the `%xx` escapes, so the argument of a command can have spaces and other characters
+/// that can't be typed into the url
+fn percent_decode(value: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut iterator = value.bytes();
+    while let Some(byte) = iterator.next() {
+        let decoded = (byte == b'%')
+            .then(|| {
+                let digits = [iterator.clone().next()?, iterator.clone().nth(1)?];
+                u8::from_str_radix(std::str::from_utf8(&digits).ok()?, 16).ok()
+            })
+            .flatten();
+        match decoded {
+            Some(v) => {
+                bytes.push(v);
+                iterator.nth(1);
+            }
+            None => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
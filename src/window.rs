@@ -1,36 +1,118 @@
-use crate::color::Color;
-use crate::document::Document;
-use crate::font::Font;
-use crate::html::{parse_html, Element, Tag};
-use crate::render_layout::{Layout, Position, Size};
-use crate::requests::get_site;
-use glium::backend::glutin::glutin;
+use crate::clipboard::Clipboard;
+use crate::downloads::{DownloadEvent, DownloadState, Downloads, DOWNLOADS_PATH};
+use crate::dropdown::Dropdown;
+use crate::format::Locale;
+use crate::hints::{HintMode, HintResult};
+use crate::history::{self, History, HISTORY_PATH};
+use crate::icon::{self, APP_ID};
+use crate::internal_pages::{downloads_page, history_page, DOWNLOADS_PAGE_URL, HISTORY_PAGE_URL};
+use crate::keymap::{self, Action, KEYMAP};
+use crate::scroll_animation::ScrollAnimation;
+use crate::scrollbar::{Axis, Scrollbar, SCROLLBAR_WIDTH};
+use crate::session::{self, SESSION_PATH};
+use crate::settings::{Settings, MAX_TEXT_ZOOM, MIN_TEXT_ZOOM, SETTINGS_PATH, TEXT_ZOOM_STEP};
+use crate::toast::{Toast, ToastAction, Toasts};
+use crate::visited::{VisitedLinks, VISITED_PATH};
+#[cfg(feature = "websocket")]
+use crate::websocket_page::{WebSocketPage, WEBSOCKET_PAGE_URL};
+use crate::zoom::{self, SiteZooms, ZOOM_PATH};
 use glium::{
     backend::glutin::Display,
     glutin::{
-        context::{ContextAttributes, NotCurrentGlContext},
-        display::GlDisplay,
-        surface::WindowSurface,
+        config::{Config, ConfigTemplateBuilder},
+        context::{ContextApi, ContextAttributesBuilder, GlProfile, NotCurrentGlContext, Version},
+        display::{GetGlDisplay, GlDisplay},
+        surface::{SurfaceAttributesBuilder, WindowSurface},
     },
     implement_vertex,
-    index::PrimitiveType,
+    index::{NoIndices, PrimitiveType},
     program,
     texture::RawImage2d,
-    uniform, Blend, DrawParameters, Frame, IndexBuffer, Program, Surface, Texture2d, VertexBuffer,
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction},
+    Blend, DrawParameters, Frame, IndexBuffer, Program, Surface, Texture2d, VertexBuffer,
 };
+use glutin_winit::{ApiPreference, DisplayBuilder, GlWindow};
 use image::RgbaImage;
 use nalgebra::{Matrix4, Vector3};
-use std::{num::NonZero, path::Path};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    num::NonZeroU32,
+    path::Path,
+    process::Command,
+    rc::Rc,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+use web::color::Color;
+use web::css::{Color as CssColor, Image, LinearGradient};
+use web::display_list::{DisplayList, TextRun};
+use web::font::{
+    bundled_font_directory, subpixel_position, AtlasGlyph, Font, FontFace, FontMetrics,
+    FontRegistry, GlyphAtlas, ScaledFont, ShapedGlyph, DEFAULT_FONT_FAMILY, GLYPH_ATLAS_SIZE,
+};
+use web::gradient;
+use web::html::{parse_html, Element, Tag};
+use web::media::{ColorScheme, MediaEnvironment};
+use web::media_placeholder::placeholder_of;
+use web::referrer::ReferrerPolicy;
+use web::render_backend::{paint, RenderBackend, SoftwareBackend};
+use web::render_layout::{BackgroundImage, Layout, Position, Rect, Size};
+use web::requests::{get_bytes, resolve_url, Response};
+use web::system_fonts::SystemFonts;
+use web::tab::{split_fragment, FaviconLoad, LayoutKey, PageLoad, Tab, Tabs, VIEW_SOURCE_PREFIX};
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalPosition,
-    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    dpi::{LogicalPosition, LogicalSize},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    keyboard::{Key, NamedKey},
-    raw_window_handle::{HasDisplayHandle, HasWindowHandle},
-    window::{Cursor, CursorIcon, Window as WinitWindow, WindowId},
+    keyboard::{Key, ModifiersState, NamedKey},
+    raw_window_handle::HasWindowHandle,
+    window::{Cursor, CursorIcon, Theme, Window as WinitWindow, WindowId},
+};
+
+/// The maximum amount of history suggestions shown below the url bar
+const MAX_SUGGESTIONS: usize = 5;
+
+/// The widths of the tabs in the tab strip. Pinned tabs only show the first letter of their title.
+const TAB_WIDTH: i32 = 200;
+const PINNED_TAB_WIDTH: i32 = 40;
+/// The color of the track that the thumb of a scrollbar moves in
+const SCROLLBAR_TRACK_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.1,
 };
 
+/// The size of a toast notification and the space around it
+const TOAST_WIDTH: i32 = 500;
+const TOAST_HEIGHT: i32 = 40;
+const TOAST_MARGIN: i32 = 10;
+
+/// Loads that take longer than this are announced with a toast when they finish
+const LONG_LOAD: Duration = Duration::from_secs(5);
+/// The number of dots in the loading spinner of a tab
+const SPINNER_DOTS: usize = 8;
+/// How long it takes for the spinner to move to the next dot
+const SPINNER_STEP: Duration = Duration::from_millis(100);
+
+/// The resources that haven't been used recently are released once there has been no input for
+/// this long, and again every time this much more time has passed
+const IDLE_DELAY: Duration = Duration::from_secs(10);
+/// Images that haven't been drawn for this long are released when idle
+const UNUSED_IMAGE_TIME: Duration = Duration::from_secs(60);
+/// How many bytes of images are kept on the GPU after the idle cleanup
+const IMAGE_BUDGET: usize = 64 * 1024 * 1024;
+/// How often the running downloads and the `about:websocket` page are checked while nothing else
+/// happens
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The documents of the background tabs after this many are discarded when idle. They are parsed
+/// again from their html when the tab is switched to.
+const MAX_PARSED_BACKGROUND_TABS: usize = 4;
+
 const HOME_PAGE: &str =
     "<html><body><h1> Web Browser </h1><p> Welcome to the home page! </p></body></html>";
 
@@ -41,6 +123,68 @@ struct Vertex {
 }
 implement_vertex!(Vertex, a_position, a_tex_coord);
 
+/// A corner of a quad that is drawn with [Window::quad_program]
+#[derive(Copy, Clone)]
+struct QuadVertex {
+    a_position: [f32; 2],
+    a_tex_coord: [f32; 2],
+    /// The opacity is already multiplied into the alpha
+    a_color: [f32; 4],
+}
+implement_vertex!(QuadVertex, a_position, a_tex_coord, a_color);
+
+/// Rects and glyphs that are drawn together with a single draw call, see [Window::draw_quads]
+#[derive(Default)]
+struct QuadBatch {
+    vertices: Vec<QuadVertex>,
+    /// Every quad of the batch is clipped by the same rect
+    scissor: Option<glium::Rect>,
+}
+
+/// An image in [Window::images]
+struct CachedImage {
+    /// [None] while the image is being fetched, or if it couldn't be loaded
+    pixels: Option<RgbaImage>,
+    /// Receives the pixels from the thread that fetches and decodes the image, see
+    /// [fetch_image_in_background]. [None] once they have arrived.
+    loading: Option<Receiver<Option<RgbaImage>>>,
+    /// Uploaded from the pixels when the image is drawn. Dropped when the graphics are created
+    /// again, see [Window::release_graphics].
+    texture: Option<Texture2d>,
+    last_drawn: Instant,
+}
+
+impl CachedImage {
+    /// The size of the image in memory and on the GPU
+    fn byte_size(&self) -> usize {
+        let pixels = self.pixels.as_ref().map_or(0, |x| x.as_raw().len());
+        let texture = self
+            .texture
+            .as_ref()
+            .map_or(0, |x| x.width() as usize * x.height() as usize * 4);
+        pixels + texture
+    }
+}
+
+/// Fetches and decodes the image on another thread, so a page with many images doesn't freeze the
+/// window. The pixels are sent through the returned channel, or [None] if the image can't be
+/// loaded.
+///
+/// * `referrer` - The value of the `Referer` header
+fn fetch_image_in_background(url: &str, referrer: Option<&str>) -> Receiver<Option<RgbaImage>> {
+    let (sender, receiver) = mpsc::channel();
+    let url = url.to_owned();
+    let referrer = referrer.map(|x| x.to_owned());
+    thread::spawn(move || {
+        let pixels = get_bytes(&url, referrer.as_deref())
+            .and_then(|x| image::load_from_memory(&x).ok())
+            .map(|x| x.into_rgba8());
+        // Nobody is waiting for the image anymore if it was released
+        let _ = sender.send(pixels);
+    });
+    receiver
+}
+
 struct Rectangle {
     vao: VertexBuffer<Vertex>,
     ebo: IndexBuffer<u16>,
@@ -78,158 +222,264 @@ pub struct Toolbar {
     height: i32,
     url: String,
     home_button_width: i32,
+    /// Urls from the history that match the typed url
+    suggestions: Dropdown,
+    /// The tab strip is drawn right below the url bar
+    tab_strip_height: i32,
+}
+
+impl Toolbar {
+    /// Returns the y where the tab strip ends, which is where the page starts
+    fn bottom(&self) -> i32 {
+        self.height + self.tab_strip_height
+    }
 }
 
+/// The surface that the window is drawn to on the CPU, see [Window::redraw_in_software]
+type SoftwareSurface = softbuffer::Surface<Rc<WinitWindow>, Rc<WinitWindow>>;
+
 pub struct Window {
-    window: Option<WinitWindow>,
+    window: Option<Rc<WinitWindow>>,
     mouse_position: Position,
     display: Option<Display<WindowSurface>>,
+    /// The OpenGL config that the window was made for, see [Window::create_window]. It's [None]
+    /// where there is no OpenGL, and the window is drawn through [Window::software_surface].
+    gl_config: Option<Config>,
+    /// Puts the window on the screen without OpenGL. It's only created when OpenGL can't be used.
+    software_surface: Option<SoftwareSurface>,
     rect: Option<Rectangle>,
-    program: Option<Program>,
+    /// Draws rects and the glyphs of text from [Window::atlas_texture] in batches, see [QuadBatch]
+    quad_program: Option<Program>,
     solid_color_program: Option<Program>,
-    document: Option<Document>,
-    scroll_y: i32,
-    font: Option<Font>,
-    layout: Option<Layout>,
+    /// Draws an image that can be scaled and repeated inside of the rectangle
+    image_program: Option<Program>,
+    /// Draws a linear gradient from its color ramp, repeated like the images
+    gradient_program: Option<Program>,
+    /// The images that have been fetched, by their url
+    images: HashMap<String, CachedImage>,
+    /// The textures of the favicons of the tabs by their url, see [Tab::favicon_url]
+    favicon_textures: HashMap<String, Texture2d>,
+    /// Where the glyphs are in [Window::atlas_texture]
+    glyph_atlas: RefCell<GlyphAtlas>,
+    /// The glyphs are written into this when they are added to the atlas
+    atlas_texture: Option<Texture2d>,
+    tabs: Tabs,
+    fonts: Option<FontRegistry>,
     focused_on_toolbar: bool,
     toolbar: Toolbar,
+    history: History,
+    visited: VisitedLinks,
+    site_zooms: SiteZooms,
+    /// Shows the url, title and canonical url of the page over the page
+    show_page_info: bool,
+    /// The overlay that lists the key bindings, see [crate::keymap]
+    show_keymap_help: bool,
+    /// Set while the links have labels on them, see [crate::hints]
+    hint_mode: Option<HintMode>,
+    /// Set after `m` or `'` was pressed, until the letter of the mark is typed
+    pending_mark: Option<Action>,
+    /// Only the part of the screen inside of this is drawn to. See [Window::set_clip].
+    scissor: Cell<Option<glium::Rect>>,
+    /// The alpha of the following draws is multiplied with this, see [web::style::ComputedStyle::opacity]
+    opacity: Cell<f32>,
+    /// Moves the following draws sideways by the horizontal scroll of the page, see
+    /// [Window::set_clip]
+    offset_x: Cell<i32>,
+    settings: Settings,
+    modifiers: ModifiersState,
+    /// The index of the tab that is being dragged in the tab strip
+    dragged_tab: Option<usize>,
+    /// The scrollbar whose thumb is being dragged, and how far from its start the thumb was
+    /// grabbed. See [Scrollbar::grab].
+    dragged_scrollbar: Option<(Axis, i32)>,
+    /// The page of the active tab sliding to where it was scrolled to, see [Window::scroll_to]
+    scroll_animation: Option<ScrollAnimation>,
+    /// When the user last pressed a key or used the mouse
+    last_input: Instant,
+    /// When the unused resources were last released, see [Window::clean_up_when_idle]
+    last_cleanup: Instant,
+    /// Set when something that is drawn has changed. The window is only drawn again when this is
+    /// set, so a page that doesn't change takes no time at all. See [Window::about_to_wait].
+    dirty: bool,
+    /// When the window was last drawn
+    last_frame: Instant,
+    /// Draws the page on the CPU instead of with OpenGL, see [Window::use_software_rendering]
+    software_rendering: bool,
+    toasts: Toasts,
+    /// The options of the `<datalist>` of the focused text field that match its text
+    field_suggestions: Dropdown,
+    clipboard: Clipboard,
+    downloads: Downloads,
+    /// How the sizes and dates of the internal pages are written
+    locale: Locale,
+    #[cfg(feature = "websocket")]
+    websocket_page: WebSocketPage,
 }
 
-impl ApplicationHandler for Window {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        self.window = Some(
-            event_loop
-                .create_window(WinitWindow::default_attributes())
-                .unwrap(),
-        );
-
-        let raw_display_handle = self
-            .window
-            .as_ref()
-            .unwrap()
-            .display_handle()
-            .unwrap()
-            .as_raw();
-        let raw_window_handle = self
-            .window
-            .as_ref()
-            .unwrap()
-            .window_handle()
-            .unwrap()
-            .as_raw();
-
-        let display = Some(glutin::display::Display::Egl(
-            unsafe { glutin::api::egl::display::Display::new(raw_display_handle) }.unwrap(),
-        ));
-
-        let config = unsafe {
-            &display
-                .as_ref()
-                .unwrap()
-                .find_configs(glutin::config::ConfigTemplate::default())
-                .unwrap()
-                .nth(0)
-                .unwrap()
-        };
+/// Draws the items of the page with OpenGL, see [paint]. The rects and the glyphs are drawn in
+/// batches that only end when the clip changes or an image is drawn.
+struct GlBackend<'a> {
+    window: &'a Window,
+    frame: &'a mut Frame,
+    batch: QuadBatch,
+    layout: &'a Layout,
+    /// The urls of the background images of the layout, by their index
+    image_urls: &'a [Option<String>],
+}
 
-        let surface = unsafe {
-            display.as_ref().unwrap().create_window_surface(
-                config,
-                &glutin::surface::SurfaceAttributesBuilder::<WindowSurface>::new().build(
-                    raw_window_handle,
-                    NonZero::new(100).unwrap(),
-                    NonZero::new(100).unwrap(),
-                ),
-            )
+impl RenderBackend for GlBackend<'_> {
+    fn set_clip(&mut self, clip: Option<Rect>, fixed: bool) {
+        self.window.set_clip(clip, fixed);
+        if self.batch.scissor != self.window.scissor.get() {
+            self.flush();
+            self.batch.scissor = self.window.scissor.get();
         }
-        .unwrap();
+    }
 
-        let context = unsafe {
-            display
-                .as_ref()
-                .unwrap()
-                .create_context(config, &ContextAttributes::default())
-                .unwrap()
-                .make_current(&surface)
-        }
-        .unwrap();
+    fn set_opacity(&mut self, opacity: f32) {
+        self.window.opacity.set(opacity);
+    }
 
-        self.display = Some(glium::backend::glutin::Display::new(context, surface).unwrap());
+    fn is_visible(&self, rect: Rect) -> bool {
+        self.window
+            .is_visible_vertically(rect.position.y, rect.size.height)
+    }
 
-        self.rect = Some(Rectangle::create(self.display.as_ref().unwrap()));
+    fn fill_rect(&mut self, rect: Rect, color: Color) {
+        self.window.push_rect(&mut self.batch, rect, color);
+    }
 
-        self.program = Some(
-            program!(self.display.as_ref().unwrap(),
-            330 => {
-                vertex: r#"
-                    #version 330 core
-                    layout (location=0) in vec3 a_position;
-                    layout (location=1) in vec2 a_tex_coord;
-                    uniform mat4 transform;
-                    out vec2 texCoord;
-                    void main() {
-                        gl_Position = transform * vec4(a_position, 1.0);
-                        texCoord = a_tex_coord;
-                    }
-                "#,
-                fragment: r#"
-                    #version 330 core
-                    out vec4 color;
-                    in vec2 texCoord;
-                    uniform sampler2D font_texture;
-                    uniform vec4 background_color;
-                    void main() {
-                        color = texture(font_texture, texCoord);
-                        color.x = color.x;
-                        color.y = color.y;
-                        color.z = color.z;
-
-                        // 0.1 is like a toggle switch for the time being
-                        if(color.a < 0.1 && background_color.a > 0.1) {
-                            color = background_color;
-                        }
-                    }
-                "#
-            })
-            .unwrap(),
+    fn draw_text(&mut self, text: &TextRun, position: Position) {
+        let font = self
+            .window
+            .fonts()
+            .get_face(&text.font_family, text.face)
+            .scaled(text.font_size * self.window.scale_factor());
+        let glyphs = text.glyphs(font);
+        self.window.push_glyphs(
+            self.frame,
+            &mut self.batch,
+            font,
+            &glyphs,
+            position,
+            text.color,
         );
+    }
 
-        self.solid_color_program = Some(
-            program!(self.display.as_ref().unwrap(),
-            330 => {
-                vertex: r#"
-                    #version 330 core
-                    layout (location=0) in vec3 a_position;
-                    uniform mat4 transform;
-                    void main() {
-                        gl_Position = transform * vec4(a_position, 1.0);
-                    }
-                "#,
-                fragment: r#"
-                    #version 330 core
-                    out vec4 color;
-                    uniform vec4 in_color;
-                    void main() {
-                        color = in_color;
-                    }
-                "#
-            })
-            .unwrap(),
-        );
+    fn draw_background_image(&mut self, index: usize) {
+        self.flush();
+        let image = &self.layout.background_images[index];
+        self.window
+            .render_background_image(self.frame, image, self.image_urls[index].as_deref());
+    }
+
+    fn flush(&mut self) {
+        self.window.draw_quads(self.frame, &mut self.batch);
+    }
+}
+
+impl ApplicationHandler for Window {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // The window and the fonts are kept while the app is suspended, and only the graphics
+        // have to be created again
+        if self.window.is_none() {
+            self.create_window(event_loop);
+            self.load_font();
+        }
+        self.create_graphics();
+    }
 
-        self.load_font();
+    fn suspended(&mut self, _: &ActiveEventLoop) {
+        // The surface may be destroyed while the app is suspended, so nothing is drawn until the
+        // graphics are created again when it's resumed
+        self.release_graphics();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::MouseInput { .. }
+        ) {
+            self.last_input = Instant::now();
+        }
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::Resized(_)
+                | WindowEvent::ScaleFactorChanged { .. }
+                | WindowEvent::ThemeChanged(_)
+                | WindowEvent::Focused(_)
+        ) {
+            self.dirty = true;
+        }
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                // A minimized window has no size, and the surface can't be made empty
+                if size.width == 0 || size.height == 0 {
+                    return;
+                }
+                // The surface doesn't follow the size of the window on every platform
+                if let Some(display) = self.display.as_ref() {
+                    display.resize(size.into());
+                }
+                // The text is wrapped to the new width and the labels would be in the wrong places
+                for index in 0..self.tabs.len() {
+                    self.tabs.get_mut(index).invalidate_layout();
+                }
+                self.hint_mode = None;
+            }
+            // The window is the same size in logical pixels, but the text is rasterized again at
+            // the new density and the page is laid out for the new logical width
+            WindowEvent::ScaleFactorChanged { .. } => {
+                for index in 0..self.tabs.len() {
+                    self.tabs.get_mut(index).invalidate_layout();
+                }
+                self.hint_mode = None;
+            }
             WindowEvent::RedrawRequested => {
-                let mut frame = self.display.as_ref().unwrap().draw();
-                frame.clear(None, Some((0.8, 0.8, 0.8, 1.0)), true, None, None);
+                self.dirty = false;
+                self.last_frame = Instant::now();
+                if self.software_surface.is_some() {
+                    self.redraw_in_software();
+                    return;
+                }
+                // Nothing can be drawn while the app is suspended
+                let Some(display) = self.display.as_ref() else {
+                    return;
+                };
+                // The graphics are created again, and the next frame is drawn with them. That can
+                // fall back to drawing in software, so there may not be a display anymore.
+                if display.is_context_lost() {
+                    self.recreate_graphics();
+                    self.dirty = true;
+                    return;
+                }
+                let mut frame = display.draw();
+                let [r, g, b, a] = self.background().as_opengl_color();
+                frame.clear(None, Some((r, g, b, a)), true, None, None);
+                self.step_scroll_animation(Instant::now());
                 self.render_current_page(&mut frame);
+                self.render_scrollbars(&mut frame);
+                self.render_hints(&mut frame);
+                self.render_field_suggestions(&mut frame);
                 self.render_toolbar(&mut frame);
-                frame.finish().expect("Failed to finish frame draw");
-                self.window.as_ref().unwrap().request_redraw();
+                self.render_page_info(&mut frame);
+                self.render_toasts(&mut frame);
+                self.render_keymap_help(&mut frame);
+                // Any failure to show the frame leaves the context in an unknown state, so it's
+                // treated like a lost context
+                if let Err(error) = frame.finish() {
+                    println!("Failed to finish frame draw: {:?}", error);
+                    self.recreate_graphics();
+                    self.dirty = true;
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -240,30 +490,47 @@ impl ApplicationHandler for Window {
                     },
                 ..
             } => {
-                if !self.focused_on_toolbar {
-                    match key.as_ref() {
-                        Key::Character("j") => self.scroll_y -= 10,
-                        Key::Character("k") => self.scroll_y += 10,
-                        Key::Character("d") => {
-                            self.scroll_y +=
-                                self.window.as_ref().unwrap().inner_size().height as i32
-                        }
-                        Key::Character("u") => {
-                            self.scroll_y -=
-                                self.window.as_ref().unwrap().inner_size().height as i32
-                        }
-                        _ => (),
+                if self.hint_mode.is_some() && !self.modifiers.control_key() {
+                    self.type_hint(&key);
+                    return;
+                }
+                if let Some(action) = self.pending_mark.take() {
+                    self.type_mark(action, &key);
+                    return;
+                }
+                if !self.modifiers.control_key()
+                    && !self.focused_on_toolbar
+                    && self.type_into_field(&key)
+                {
+                    return;
+                }
+                // The shortcuts with Ctrl work even while typing into the toolbar
+                let action = keymap::action_for(&key, self.modifiers);
+                if self.modifiers.control_key() || !self.focused_on_toolbar {
+                    if let Some(action) = action {
+                        self.run_action(action);
                     }
                 } else {
                     match key.as_ref() {
-                        Key::Character(character) => self.toolbar.url += character,
+                        Key::Character(character) => {
+                            self.toolbar.url += character;
+                            self.update_suggestions();
+                        }
                         Key::Named(NamedKey::Backspace) => {
                             if !self.toolbar.url.is_empty() {
                                 self.toolbar.url.pop();
                             }
+                            self.update_suggestions();
                         }
+                        Key::Named(NamedKey::ArrowDown) => self.toolbar.suggestions.select_next(),
+                        Key::Named(NamedKey::ArrowUp) => self.toolbar.suggestions.select_previous(),
                         Key::Named(NamedKey::Enter) => {
-                            let url = self.toolbar.url.clone();
+                            let url = self
+                                .toolbar
+                                .suggestions
+                                .selected_item()
+                                .unwrap_or(&self.toolbar.url)
+                                .to_owned();
                             self.open_link(&url);
                         }
                         _ => (),
@@ -271,14 +538,64 @@ impl ApplicationHandler for Window {
                 }
             }
 
-            WindowEvent::CursorMoved {
-                position: PhysicalPosition { x, y },
-                ..
-            } => {
+            WindowEvent::CursorMoved { position, .. } => {
+                let LogicalPosition { x, y } =
+                    position.to_logical::<f64>(self.scale_factor().into());
                 self.update_cursor(x as i32, y as i32);
+                self.drag_tab();
+                self.drag_scrollbar();
+            }
+
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                // Positive deltas mean scrolling up or left
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => ((x * -40.0) as i32, (y * -40.0) as i32),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        let position = position.to_logical::<f64>(self.scale_factor().into());
+                        (-position.x as i32, -position.y as i32)
+                    }
+                };
+                // The wheel scrolls sideways while shift is held, like in the other browsers
+                if self.modifiers.shift_key() {
+                    self.scroll_sideways(x + y);
+                } else if y != 0 {
+                    self.scroll_at_mouse(y);
+                } else {
+                    self.scroll_sideways(x);
+                }
             }
 
             WindowEvent::MouseInput { button, state, .. } => {
+                if self.handle_tab_strip_input(button, state) {
+                    return;
+                }
+                if self.handle_scrollbar_input(button, state) {
+                    return;
+                }
+                // The suggestions of a text field are over the page, and picking one keeps the
+                // focus in the field
+                if button == MouseButton::Left
+                    && state == ElementState::Pressed
+                    && self.pick_field_suggestion()
+                {
+                    return;
+                }
+                // Middle-clicking the toolbar pastes the primary selection, like in the other
+                // programs on Linux
+                if button == MouseButton::Middle
+                    && state == ElementState::Pressed
+                    && self.mouse_position.y < 50
+                {
+                    if let Some(text) = self.clipboard.paste_selection() {
+                        self.paste_into_toolbar(&text);
+                    }
+                    return;
+                }
+                if button == MouseButton::Left {
+                    self.set_active(state == ElementState::Pressed);
+                }
                 if button == MouseButton::Left && state == ElementState::Pressed {
                     self.handle_click();
                 }
@@ -287,143 +604,1223 @@ impl ApplicationHandler for Window {
             _ => (),
         }
     }
+
+    /// Checks the work that happens in the background after the events have been handled, and
+    /// draws the window again only if something has changed. The event loop sleeps until the next
+    /// event, or until the next time the background work has to be checked.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_none() {
+            return;
+        }
+        let now = Instant::now();
+        self.poll_loads();
+        self.poll_images();
+        self.poll_downloads();
+        #[cfg(feature = "websocket")]
+        self.poll_websocket_page();
+        self.clean_up_when_idle();
+        let toasts = self.toasts.len();
+        self.toasts.remove_expired(now);
+        if self.toasts.len() != toasts || self.is_animating(now) {
+            self.dirty = true;
+        }
+        // The spinners of the loading tabs only move once every step
+        let loading = self.tabs.iter().any(|x| x.loading_since().is_some());
+        if loading && now - self.last_frame >= SPINNER_STEP {
+            self.dirty = true;
+        }
+        // Nothing can be drawn while the app is suspended, and it's drawn once it's resumed
+        if self.dirty && (self.display.is_some() || self.software_surface.is_some()) {
+            self.window.as_ref().unwrap().request_redraw();
+        }
+        event_loop.set_control_flow(match self.next_wake_up(now) {
+            Some(v) => ControlFlow::WaitUntil(v),
+            None => ControlFlow::Wait,
+        });
+    }
 }
 
 impl Window {
     pub fn new() -> Self {
-        Self {
+        let mut window = Self {
             window: None,
             display: None,
+            gl_config: None,
+            software_surface: None,
             rect: None,
-            program: None,
+            quad_program: None,
             solid_color_program: None,
-            scroll_y: 0,
-            document: None,
-            font: None,
-            layout: None,
+            image_program: None,
+            gradient_program: None,
+            images: HashMap::new(),
+            favicon_textures: HashMap::new(),
+            glyph_atlas: RefCell::new(GlyphAtlas::default()),
+            atlas_texture: None,
+            tabs: Self::restore_tabs(),
+            fonts: None,
             mouse_position: Position::new(0, 0),
             focused_on_toolbar: false,
             toolbar: Toolbar {
                 height: 50,
                 home_button_width: 40,
                 url: "NoURL".to_string(),
+                suggestions: Dropdown::new(30),
+                tab_strip_height: 30,
             },
+            history: History::load(Path::new(HISTORY_PATH)),
+            visited: VisitedLinks::load(Path::new(VISITED_PATH)),
+            site_zooms: SiteZooms::load(Path::new(ZOOM_PATH)),
+            show_page_info: false,
+            show_keymap_help: false,
+            hint_mode: None,
+            pending_mark: None,
+            scissor: Cell::new(None),
+            opacity: Cell::new(1.0),
+            offset_x: Cell::new(0),
+            settings: Settings::load(Path::new(SETTINGS_PATH)),
+            modifiers: ModifiersState::empty(),
+            dragged_tab: None,
+            dragged_scrollbar: None,
+            scroll_animation: None,
+            last_input: Instant::now(),
+            last_cleanup: Instant::now(),
+            dirty: true,
+            last_frame: Instant::now(),
+            software_rendering: false,
+            toasts: Toasts::new(),
+            field_suggestions: Dropdown::new(30),
+            clipboard: Clipboard::new(),
+            downloads: Downloads::load(Path::new(DOWNLOADS_PATH)),
+            locale: Locale::from_env(),
+            #[cfg(feature = "websocket")]
+            websocket_page: WebSocketPage::new(),
+        };
+        // The downloads that were cut off when the browser was closed can be continued
+        let interrupted: Vec<(usize, String)> = window
+            .downloads
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.state == DownloadState::Interrupted)
+            .map(|(index, x)| (index, x.file_name()))
+            .collect();
+        for (index, name) in interrupted {
+            let message = format!("The download of {} was interrupted. Click to resume.", name);
+            window
+                .toasts
+                .push(&message, Some(ToastAction::ResumeDownload(index)));
         }
+        window
     }
 
-    /// Transforms screen coordinates into a -1.0 - 1.0 scale
-    pub fn screen_to_opengl_coordinates(&self, x: i32, y: i32) -> [f32; 2] {
-        let inner_size = self.window.as_ref().unwrap().inner_size();
-        [
-            (x as f32 / inner_size.width as f32 - 0.5) * 2.0,
-            (y as f32 / inner_size.height as f32 - 0.5) * -2.0,
-        ]
+    /// Restores the pinned tabs of the last session and opens a new tab after them
+    fn restore_tabs() -> Tabs {
+        let mut tabs = session::load_pinned_tabs(Path::new(SESSION_PATH));
+        tabs.push(Tab::new(""));
+        let active = tabs.len() - 1;
+        Tabs::new(tabs, active)
     }
 
-    pub fn update_cursor(&mut self, x: i32, y: i32) {
-        self.mouse_position.x = x;
-        self.mouse_position.y = y;
-        if let Some(layout) = self.layout.as_ref() {
-            let mut cursor_mode = CursorIcon::Default;
-            for paragraph in &layout.paragraphs {
-                for sentence in &paragraph.sentences {
-                    if sentence.href.is_some() && sentence.is_position_inside(x, y - self.scroll_y)
-                    {
-                        cursor_mode = CursorIcon::Pointer;
-                        break;
-                    }
+    fn save_session(&self) {
+        session::save_session(&self.tabs, Path::new(SESSION_PATH));
+    }
+
+    /// Does what the key binding asks for, see [crate::keymap]
+    pub fn run_action(&mut self, action: Action) {
+        let screen_height = self.screen_size().height as i32;
+        let page_top = self.page_top();
+        // A page up or down keeps a line of the previous screen visible
+        let page_step = screen_height - page_top - 40;
+        let tab = self.tabs.active_mut();
+        // The scroll position is clamped to the page when the page is laid out again
+        match action {
+            Action::ScrollDown => self.scroll_by(10),
+            Action::ScrollUp => self.scroll_by(-10),
+            Action::ScrollPageDown => self.scroll_by(page_step),
+            Action::ScrollPageUp => self.scroll_by(-page_step),
+            Action::ScrollToTop => self.scroll_to(0),
+            Action::ScrollToBottom => self.scroll_to(i32::MIN),
+            Action::ScrollLeft => tab.scroll_x += 40,
+            Action::ScrollRight => tab.scroll_x -= 40,
+            Action::ToggleLineWrap => {
+                if let Some(text_document) = tab.text_document.as_mut() {
+                    text_document.wrap_lines = !text_document.wrap_lines;
                 }
+                tab.invalidate_layout();
+            }
+            Action::ToggleLineNumbers => {
+                if let Some(text_document) = tab.text_document.as_mut() {
+                    text_document.line_numbers = !text_document.line_numbers;
+                }
+                tab.invalidate_layout();
+            }
+            Action::ToggleHelp => self.show_keymap_help = !self.show_keymap_help,
+            Action::CloseHelp => self.show_keymap_help = false,
+            Action::TogglePageInfo => self.show_page_info = !self.show_page_info,
+            Action::SetMark | Action::JumpToMark => self.pending_mark = Some(action),
+            Action::ViewSource => self.view_source(),
+            Action::ShowHints => {
+                self.hint_mode = tab
+                    .layout
+                    .as_ref()
+                    .map(|x| HintMode::new(x, tab.scroll(), page_top, screen_height))
+                    .filter(|x| !x.hints.is_empty());
+            }
+            Action::ToggleReduceMotion => {
+                self.settings.reduce_motion = !self.settings.reduce_motion;
+                self.settings.save(Path::new(SETTINGS_PATH));
+                println!("Reduce motion: {}", self.settings.reduce_motion);
+            }
+            Action::ToggleForcedColors => {
+                self.settings.forced_colors = !self.settings.forced_colors;
+                self.settings.save(Path::new(SETTINGS_PATH));
+                println!("Forced colors: {}", self.settings.forced_colors);
+            }
+            Action::TextZoomIn => self.set_text_zoom(self.settings.text_zoom + TEXT_ZOOM_STEP),
+            Action::TextZoomOut => self.set_text_zoom(self.settings.text_zoom - TEXT_ZOOM_STEP),
+            Action::ResetTextZoom => self.set_text_zoom(1.0),
+            Action::PageZoomIn => self.set_page_zoom(zoom::zoom_in(self.page_zoom())),
+            Action::PageZoomOut => self.set_page_zoom(zoom::zoom_out(self.page_zoom())),
+            Action::ResetPageZoom => self.set_page_zoom(1.0),
+            Action::Copy => self.clipboard.copy(&self.toolbar.url),
+            Action::Paste => {
+                if let Some(text) = self.clipboard.paste() {
+                    self.paste_into_toolbar(&text);
+                }
+            }
+            Action::NewTab => {
+                self.tabs.open(Tab::new(""));
+                self.activate_tab(self.tabs.active_index());
+                self.focused_on_toolbar = true;
+                self.save_session();
+            }
+            Action::CloseTab => {
+                // Pinned tabs can't be closed with the shortcut
+                if self.tabs.close(self.tabs.active_index()).is_none() {
+                    return;
+                }
+                self.dragged_tab = None;
+                self.activate_tab(self.tabs.active_index());
+                self.save_session();
+            }
+            Action::NextTab | Action::PreviousTab => {
+                let count = self.tabs.len();
+                let active = self.tabs.active_index();
+                let index = if action == Action::PreviousTab {
+                    (active + count - 1) % count
+                } else {
+                    (active + 1) % count
+                };
+                self.activate_tab(index);
             }
-            self.window
-                .as_ref()
-                .unwrap()
-                .set_cursor(Cursor::Icon(cursor_mode));
         }
     }
 
-    pub fn handle_click(&mut self) {
-        // Check if the toolbar was clicked first
-        if self.mouse_position.y < 50 {
-            if self.mouse_position.x < self.toolbar.home_button_width {
-                self.focused_on_toolbar = false;
+    /// Changes the size of the text without zooming the rest of the page, and saves it into the
+    /// settings
+    fn set_text_zoom(&mut self, text_zoom: f32) {
+        // Rounded so the steps don't drift away from the round numbers
+        let text_zoom = (text_zoom * 100.0).round() / 100.0;
+        self.settings.text_zoom = text_zoom.clamp(MIN_TEXT_ZOOM, MAX_TEXT_ZOOM);
+        self.settings.save(Path::new(SETTINGS_PATH));
+        println!("Text zoom: {}%", (self.settings.text_zoom * 100.0).round());
+    }
+
+    /// Returns the zoom of the site of the active tab
+    fn page_zoom(&self) -> f32 {
+        self.site_zooms.get(&self.tabs.active().url)
+    }
+
+    /// Zooms every page of the site of the active tab, and saves the zoom for the next time
+    fn set_page_zoom(&mut self, zoom: f32) {
+        let url = self.tabs.active().url.clone();
+        self.site_zooms.set(&url, zoom);
+        self.site_zooms.save(Path::new(ZOOM_PATH));
+        println!("Page zoom: {}%", (zoom * 100.0).round());
+    }
+
+    /// Switches to the tab. The page of the tab is loaded if it hasn't been loaded yet.
+    pub fn activate_tab(&mut self, index: usize) {
+        self.tabs.set_active(index);
+        self.tabs.active_mut().restore_document();
+        self.hint_mode = None;
+        let tab = self.tabs.active();
+        self.toolbar.url = tab.loading.as_ref().map_or(&tab.url, |x| &x.url).clone();
+        self.toolbar.suggestions.clear();
+        self.field_suggestions.clear();
+        let tab = self.tabs.active();
+        if !tab.is_loaded() && tab.loading.is_none() {
+            let url = tab.url.clone();
+            if url.is_empty() {
                 self.load_home_page();
             } else {
-                self.focused_on_toolbar = true;
+                self.open_link(&url);
             }
+        }
+        self.update_window_icon();
+    }
+
+    /// Shows the favicon of the active tab as the window icon
+    pub fn update_window_icon(&self) {
+        let Some(window) = self.window.as_ref() else {
             return;
-        } else {
-            self.focused_on_toolbar = false;
+        };
+        let image = match &self.tabs.active().favicon {
+            Some(favicon) => favicon.clone(),
+            None => icon::app_icon(),
+        };
+        window.set_window_icon(icon::to_window_icon(&image));
+    }
+
+    /// Returns the index of the tab at the x coordinate of the tab strip
+    pub fn tab_at(&self, x: i32) -> Option<usize> {
+        let mut tab_x = 0;
+        for (index, tab) in self.tabs.iter().enumerate() {
+            tab_x += if tab.pinned {
+                PINNED_TAB_WIDTH
+            } else {
+                TAB_WIDTH
+            };
+            if x < tab_x {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Returns the index of the history suggestion at the y coordinate
+    pub fn suggestion_at(&self, y: i32) -> Option<usize> {
+        if !self.focused_on_toolbar {
+            return None;
         }
+        self.toolbar.suggestions.item_at(self.toolbar.height, y)
+    }
 
-        if let Some(layout) = self.layout.as_ref() {
-            let x = self.mouse_position.x;
-            let y = self.mouse_position.y;
-            let mut new_elements = None;
-            for paragraph in &layout.paragraphs {
-                for sentence in &paragraph.sentences {
-                    if sentence.href.is_some() && sentence.is_position_inside(x, y - self.scroll_y)
-                    {
-                        let link = sentence.href.clone().unwrap();
-                        self.open_link(&link);
-                        return;
-                    }
+    /// Handles the mouse buttons on the tab strip. Left click switches to the tab and starts
+    /// dragging it, and right click pins or unpins it. Returns true if the input was used.
+    pub fn handle_tab_strip_input(&mut self, button: MouseButton, state: ElementState) -> bool {
+        if button == MouseButton::Left
+            && state == ElementState::Released
+            && self.dragged_tab.take().is_some()
+        {
+            return true;
+        }
+
+        // The suggestions are drawn on top of the tab strip
+        let tab_strip_top = self.toolbar.height;
+        let y = self.mouse_position.y;
+        if state != ElementState::Pressed
+            || y < tab_strip_top
+            || y >= tab_strip_top + self.toolbar.tab_strip_height
+            || self.suggestion_at(y).is_some()
+        {
+            return false;
+        }
+
+        if let Some(index) = self.tab_at(self.mouse_position.x) {
+            match button {
+                MouseButton::Left => {
+                    self.focused_on_toolbar = false;
+                    self.activate_tab(index);
+                    self.dragged_tab = Some(index);
                 }
-            }
-            if let Some(elements) = new_elements.take() {
-                self.set_elements(elements);
+                MouseButton::Right => {
+                    self.tabs.toggle_pin(index);
+                    self.save_session();
+                }
+                _ => (),
             }
         }
+        true
     }
 
-    pub fn open_link(&mut self, link: &str) {
-        println!("Getting {:?}", link);
-        if let Some(elements) = Some(parse_html(&get_site(link))) {
-            self.set_elements(elements);
+    /// Moves the dragged tab to the position under the cursor
+    pub fn drag_tab(&mut self) {
+        let Some(from) = self.dragged_tab else {
+            return;
+        };
+        let to = self
+            .tab_at(self.mouse_position.x)
+            .unwrap_or(self.tabs.len() - 1);
+        if to != from {
+            self.dragged_tab = Some(self.tabs.move_tab(from, to));
+            self.save_session();
         }
-        self.toolbar.url = link.to_owned();
-        println!("Content received!");
     }
 
-    /// Transforms screen coordinates into a 0.0 - 1.0 scale
-    pub fn screen_to_relative_coordinates(&self, x: i32, y: i32) -> [f32; 2] {
-        let inner_size = self.window.as_ref().unwrap().inner_size();
-        [
-            x as f32 / inner_size.width as f32,
-            y as f32 / inner_size.height as f32,
-        ]
+    /// Returns the scrollbars of the page of the active tab. A bar is only there if the page
+    /// doesn't fit in the window along its axis, see [Scrollbar::new].
+    fn scrollbars(&self) -> Vec<Scrollbar> {
+        let tab = self.tabs.active();
+        let Some(max_scroll) = tab.max_scroll(self.visible_end()) else {
+            return Vec::new();
+        };
+        let (max_scroll_x, max_scroll_y) = (max_scroll.x, max_scroll.y);
+        let area = self.page_area();
+        let right = area.position.x + area.size.width;
+        let bottom = area.position.y + area.size.height;
+        // The bars leave the corner between them empty
+        let corner = |max_scroll: i32| if max_scroll > 0 { SCROLLBAR_WIDTH } else { 0 };
+        let vertical = Scrollbar::new(
+            Axis::Vertical,
+            Rect::new(
+                Position::new(right - SCROLLBAR_WIDTH, area.position.y),
+                Size::new(SCROLLBAR_WIDTH, area.size.height - corner(max_scroll_x)),
+            ),
+            area.size.height,
+            max_scroll_y,
+            -tab.scroll_y,
+        );
+        let horizontal = Scrollbar::new(
+            Axis::Horizontal,
+            Rect::new(
+                Position::new(area.position.x, bottom - SCROLLBAR_WIDTH),
+                Size::new(area.size.width - corner(max_scroll_y), SCROLLBAR_WIDTH),
+            ),
+            area.size.width,
+            max_scroll_x,
+            -tab.scroll_x,
+        );
+        vertical.into_iter().chain(horizontal).collect()
     }
 
-    pub fn set_elements(&mut self, elements: Vec<Element>) {
-        self.document = Some(Document::new(elements, Vec::new()));
-        self.document.as_mut().unwrap().parse_inline_css();
+    /// Handles the left mouse button on the scrollbars. Pressing a scrollbar starts dragging its
+    /// thumb, and releasing the button stops it. Returns true if the input was used.
+    pub fn handle_scrollbar_input(&mut self, button: MouseButton, state: ElementState) -> bool {
+        if button != MouseButton::Left {
+            return false;
+        }
+        if state == ElementState::Released {
+            return self.dragged_scrollbar.take().is_some();
+        }
+        let grabbed = self
+            .scrollbars()
+            .into_iter()
+            .find_map(|x| Some((x.axis, x.grab(self.mouse_position)?)));
+        if grabbed.is_none() {
+            return false;
+        }
+        self.dragged_scrollbar = grabbed;
+        self.drag_scrollbar();
+        true
     }
 
-    pub fn render(&mut self, elements: Vec<Element>) {
-        self.set_elements(elements);
-        self.open();
+    /// Scrolls the page to where the dragged thumb of a scrollbar is under the cursor
+    pub fn drag_scrollbar(&mut self) {
+        let Some((axis, grab)) = self.dragged_scrollbar else {
+            return;
+        };
+        let Some(scrollbar) = self.scrollbars().into_iter().find(|x| x.axis == axis) else {
+            return;
+        };
+        let scroll = scrollbar.scroll_at(self.mouse_position, grab);
+        // The labels would be left behind by the links
+        self.hint_mode = None;
+        let tab = self.tabs.active_mut();
+        match axis {
+            Axis::Vertical => tab.scroll_y = -scroll,
+            Axis::Horizontal => tab.scroll_x = -scroll,
+        }
     }
 
-    pub fn load_home_page(&mut self) {
-        self.scroll_y = 0;
-        let elements = parse_html(&HOME_PAGE);
-        self.set_elements(elements);
+    /// Adds the text to the end of the url bar and focuses it. A url can't have line breaks, so
+    /// the lines of the text are joined together.
+    pub fn paste_into_toolbar(&mut self, text: &str) {
+        self.focused_on_toolbar = true;
+        self.toolbar.url += &text.lines().map(str::trim).collect::<String>();
+        self.update_suggestions();
     }
 
-    pub fn open_to_home_page(&mut self) {
-        self.load_home_page();
-        self.open();
+    /// Searches the history for the typed url
+    pub fn update_suggestions(&mut self) {
+        let urls = if self.toolbar.url.trim().is_empty() {
+            Vec::new()
+        } else {
+            self.history
+                .search(&self.toolbar.url, history::now())
+                .into_iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|x| x.url.clone())
+                .collect()
+        };
+        self.toolbar.suggestions.set_items(urls);
     }
 
-    pub fn render_rect(&mut self, frame: &mut Frame, x: i32, y: i32, w: i32, h: i32, color: Color) {
-        let position = self.screen_to_opengl_coordinates(x, y);
-        let size = self.screen_to_relative_coordinates(w, h);
+    /// How many physical pixels of the display there are in a pixel of the window. Everything
+    /// is laid out and placed in the logical pixels of the window, and only turned into physical
+    /// pixels when it's drawn, so the pages are as big on high density displays as on the others.
+    pub fn scale_factor(&self) -> f32 {
+        self.window.as_ref().unwrap().scale_factor() as f32
+    }
+
+    /// The size of the window in logical pixels, see [Window::scale_factor]
+    pub fn screen_size(&self) -> LogicalSize<u32> {
+        let window = self.window.as_ref().unwrap();
+        window.inner_size().to_logical(window.scale_factor())
+    }
+
+    /// Transforms screen coordinates into physical pixels, moved by [Window::offset_x]
+    fn screen_to_physical_coordinates(&self, x: i32, y: i32) -> [f32; 2] {
+        let scale_factor = self.scale_factor();
+        let x = x + self.offset_x.get();
+        [x as f32 * scale_factor, y as f32 * scale_factor]
+    }
+
+    /// Transforms physical pixels into a -1.0 - 1.0 scale
+    fn physical_to_opengl_coordinates(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let inner_size = self.window.as_ref().unwrap().inner_size();
+        [
+            (x / inner_size.width as f32 - 0.5) * 2.0,
+            (y / inner_size.height as f32 - 0.5) * -2.0,
+        ]
+    }
+
+    /// Transforms screen coordinates into a -1.0 - 1.0 scale
+    pub fn screen_to_opengl_coordinates(&self, x: i32, y: i32) -> [f32; 2] {
+        self.physical_to_opengl_coordinates(self.screen_to_physical_coordinates(x, y))
+    }
+
+    pub fn update_cursor(&mut self, x: i32, y: i32) {
+        self.mouse_position.x = x;
+        self.mouse_position.y = y;
+        let tab = self.tabs.active_mut();
+        if let Some(layout) = tab.layout.as_ref() {
+            let hit = layout.hit_test(x, y, tab.scroll());
+            let cursor_mode = match hit.and_then(|x| x.href) {
+                Some(_) => CursorIcon::Pointer,
+                None => CursorIcon::Default,
+            };
+            // Outside of the text the innermost block under the mouse is hovered, so `:hover`
+            // works on the padding of the blocks too
+            let hovered_element = hit.and_then(|x| x.element_id);
+            self.window
+                .as_ref()
+                .unwrap()
+                .set_cursor(Cursor::Icon(cursor_mode));
+
+            if let Some(document) = tab.document.as_mut() {
+                let mut interaction = document.interaction().clone();
+                interaction.hovered = hovered_element
+                    .map(|x| document.path_to(x))
+                    .unwrap_or_default();
+                document.set_interaction(interaction);
+            }
+        }
+    }
+
+    /// Types the key into the focused text field of the page. The arrow keys and enter pick one
+    /// of the suggestions from the `<datalist>` of the field. Returns false if no text field has
+    /// the focus or the key isn't used by it.
+    pub fn type_into_field(&mut self, key: &Key) -> bool {
+        let Some(document) = self.tabs.active_mut().document.as_mut() else {
+            return false;
+        };
+        let Some(id) = document.interaction().focused else {
+            return false;
+        };
+        let Some(mut value) = document
+            .element(id)
+            .filter(|x| x.is_text_field())
+            .map(|x| x.get_attribute("value").unwrap_or_default())
+        else {
+            return false;
+        };
+        match key.as_ref() {
+            Key::Character(character) => value += character,
+            Key::Named(NamedKey::Space) => value.push(' '),
+            Key::Named(NamedKey::Backspace) => {
+                value.pop();
+            }
+            Key::Named(NamedKey::ArrowDown) => {
+                self.field_suggestions.select_next();
+                return true;
+            }
+            Key::Named(NamedKey::ArrowUp) => {
+                self.field_suggestions.select_previous();
+                return true;
+            }
+            Key::Named(NamedKey::Enter) => {
+                if let Some(item) = self.field_suggestions.selected_item() {
+                    document.set_field_value(id, item);
+                    self.field_suggestions.clear();
+                }
+                return true;
+            }
+            Key::Named(NamedKey::Escape) if !self.field_suggestions.is_empty() => {
+                self.field_suggestions.clear();
+                return true;
+            }
+            _ => return false,
+        }
+        document.set_field_value(id, &value);
+        self.field_suggestions
+            .set_items(document.datalist_suggestions(id));
+        true
+    }
+
+    /// Returns where the suggestions of the focused text field are drawn on the screen. The
+    /// rect is the size of one suggestion right below the field.
+    pub fn field_dropdown_rect(&self) -> Option<Rect> {
+        let tab = self.tabs.active();
+        let id = tab.document.as_ref()?.interaction().focused?;
+        tab.layout
+            .as_ref()?
+            .paragraphs
+            .iter()
+            .find_map(|paragraph| {
+                let scroll = match paragraph.fixed {
+                    true => Position::new(0, 0),
+                    false => tab.scroll(),
+                };
+                let sentence = paragraph
+                    .sentences
+                    .iter()
+                    .find(|x| x.element_id == Some(id))?;
+                let word = sentence.words.first()?;
+                Some(Rect::new(
+                    word.position + scroll + Position::new(0, word.height),
+                    Size::new(word.width, self.field_suggestions.item_height),
+                ))
+            })
+    }
+
+    /// Puts the suggestion under the mouse into the focused text field. Returns false if the
+    /// mouse isn't over a suggestion.
+    pub fn pick_field_suggestion(&mut self) -> bool {
+        let Some(rect) = self.field_dropdown_rect() else {
+            return false;
+        };
+        let Position { x, y } = self.mouse_position;
+        let right = rect.position.x + rect.size.width;
+        let Some(index) = self
+            .field_suggestions
+            .item_at(rect.position.y, y)
+            .filter(|_| x >= rect.position.x && x < right)
+        else {
+            return false;
+        };
+        let item = self.field_suggestions.items[index].clone();
+        let tab = self.tabs.active_mut();
+        if let Some(document) = tab.document.as_mut() {
+            if let Some(id) = document.interaction().focused {
+                document.set_field_value(id, &item);
+            }
+        }
+        self.field_suggestions.clear();
+        true
+    }
+
+    /// Draws the suggestions of the focused text field below it
+    pub fn render_field_suggestions(&self, frame: &mut Frame) {
+        if let Some(rect) = self.field_dropdown_rect() {
+            let Position { x, y } = rect.position;
+            self.render_dropdown(frame, &self.field_suggestions, x, y, rect.size.width);
+        }
+    }
+
+    /// Makes the hovered elements active while the mouse button is pressed. Pressing also moves
+    /// the focus to the pressed element if it can be focused.
+    pub fn set_active(&mut self, pressed: bool) {
+        let Some(document) = self.tabs.active_mut().document.as_mut() else {
+            return;
+        };
+        let mut interaction = document.interaction().clone();
+        if pressed {
+            self.field_suggestions.clear();
+            interaction.active = interaction.hovered.clone();
+            interaction.focused = interaction
+                .hovered
+                .iter()
+                .rev()
+                .copied()
+                .find(|x| document.element(*x).is_some_and(|x| x.is_focusable()));
+        } else {
+            interaction.active.clear();
+        }
+        document.set_interaction(interaction);
+    }
+
+    pub fn handle_click(&mut self) {
+        // Toasts are drawn over everything else
+        if let Some(index) = self.toast_at(self.mouse_position.x, self.mouse_position.y) {
+            match self.toasts.dismiss(index).and_then(|x| x.action) {
+                Some(ToastAction::OpenLink(url)) => self.open_link(&url),
+                Some(ToastAction::ResumeDownload(index)) => {
+                    self.downloads.resume(index, None);
+                    self.downloads.save(Path::new(DOWNLOADS_PATH));
+                }
+                None => (),
+            }
+            return;
+        }
+
+        // The suggestions are on top of the page, so they are checked first
+        if let Some(index) = self.suggestion_at(self.mouse_position.y) {
+            let url = self.toolbar.suggestions.items[index].clone();
+            self.focused_on_toolbar = false;
+            self.open_link(&url);
+            return;
+        }
+
+        // Check if the toolbar was clicked first
+        if self.mouse_position.y < 50 {
+            if self.mouse_position.x < self.toolbar.home_button_width {
+                self.focused_on_toolbar = false;
+                self.load_home_page();
+            } else {
+                // The whole url counts as selected when the url bar is focused, like in other
+                // browsers, so it can be pasted elsewhere with the middle button
+                self.focused_on_toolbar = true;
+                self.clipboard.select(&self.toolbar.url);
+            }
+            return;
+        } else {
+            self.focused_on_toolbar = false;
+        }
+
+        let tab = self.tabs.active();
+        let Some(layout) = tab.layout.as_ref() else {
+            return;
+        };
+        let Position { x, y } = self.mouse_position;
+        let Some(hit) = layout.hit_test(x, y, tab.scroll()) else {
+            return;
+        };
+        // Media can't be played in the browser, so clicking it opens it in another program
+        let media_source = hit
+            .element_id
+            .and_then(|x| tab.document.as_ref()?.element(x))
+            .and_then(placeholder_of)
+            .and_then(|x| x.source);
+        if let Some(source) = media_source {
+            open_externally(&resolve_url(&tab.url, &source));
+            return;
+        }
+        let (link, element_id) = (hit.href.map(|x| x.to_owned()), hit.element_id);
+        let on_text = hit.word.is_some();
+        if let Some(link) = link {
+            let noreferrer = self.is_noreferrer(element_id);
+            self.follow_link(&link, noreferrer);
+        } else if let Some(id) = element_id.filter(|_| on_text) {
+            self.toggle_details(id);
+        }
+    }
+
+    /// Returns the referrer policy of the active page. The policy of the page wins over the one in
+    /// the settings.
+    pub fn referrer_policy(&self) -> ReferrerPolicy {
+        self.referrer_policy_of(self.tabs.active_index())
+    }
+
+    /// Like [Window::referrer_policy], but for the page of any tab
+    pub fn referrer_policy_of(&self, index: usize) -> ReferrerPolicy {
+        self.tabs
+            .get(index)
+            .document
+            .as_ref()
+            .and_then(|x| x.referrer_policy())
+            .unwrap_or(self.settings.referrer_policy)
+    }
+
+    /// Opens a link that was clicked on the page. The page is sent as the referrer, unless the
+    /// referrer policy or `noreferrer` says otherwise.
+    pub fn follow_link(&mut self, href: &str, noreferrer: bool) {
+        let page = self.tabs.active().url.clone();
+        let url = resolve_url(&page, href);
+        let referrer = if noreferrer {
+            None
+        } else {
+            self.referrer_policy().referrer(&page, &url)
+        };
+        self.navigate(&url, referrer.as_deref());
+    }
+
+    /// Opens the link in a new tab, which becomes the active tab. The referrer is taken from the
+    /// current page like with [Window::follow_link].
+    pub fn follow_link_in_new_tab(&mut self, href: &str, noreferrer: bool) {
+        let page = self.tabs.active().url.clone();
+        let url = resolve_url(&page, href);
+        let referrer = if noreferrer {
+            None
+        } else {
+            self.referrer_policy().referrer(&page, &url)
+        };
+        println!("Getting {:?} in a new tab", url);
+        let mut tab = Tab::new(&url);
+        tab.loading = Some(PageLoad::start(&url, referrer.as_deref()));
+        self.tabs.open(tab);
+        self.activate_tab(self.tabs.active_index());
+        self.save_session();
+    }
+
+    /// Opens or closes the `<details>` if the clicked element is inside of its `<summary>`. The
+    /// state is kept for as long as the page stays open in the tab.
+    fn toggle_details(&mut self, element_id: usize) {
+        let tab = self.tabs.active_mut();
+        let Some(document) = tab.document.as_mut() else {
+            return;
+        };
+        let Some(details) = document.details_toggled_by(element_id) else {
+            return;
+        };
+        let open = !document
+            .element(details)
+            .is_some_and(|x| x.attributes.contains_key("open"));
+        document.set_details_open(details, open);
+        tab.details_open.insert(details, open);
+    }
+
+    /// Checks if the link around the element has `rel="noreferrer"`, which hides the page from
+    /// the site the link goes to
+    fn is_noreferrer(&self, element_id: Option<usize>) -> bool {
+        element_id
+            .and_then(|id| self.tabs.active().document.as_ref()?.link_around(id))
+            .and_then(|x| x.get_attribute("rel"))
+            .is_some_and(|x| x.split_whitespace().any(|x| x == "noreferrer"))
+    }
+
+    /// Sets or jumps to the mark named by the key. Only the letters from a to z can be marks.
+    ///
+    /// * `action` - Either [Action::SetMark] or [Action::JumpToMark]
+    fn type_mark(&mut self, action: Action, key: &Key) {
+        let letter = match key.as_ref() {
+            Key::Character(v) => v.chars().next().filter(|x| x.is_ascii_lowercase()),
+            // Still waiting for the letter
+            Key::Named(NamedKey::Shift) => {
+                self.pending_mark = Some(action);
+                return;
+            }
+            _ => None,
+        };
+        let Some(letter) = letter else {
+            return;
+        };
+        let tab = self.tabs.active_mut();
+        if action == Action::SetMark {
+            tab.set_mark(letter);
+            println!("Saved the mark {}", letter);
+        } else if !tab.jump_to_mark(letter) {
+            self.toasts
+                .push(&format!("There is no mark {} on this page", letter), None);
+        }
+    }
+
+    /// Opens the html of the active page in a new tab. The element under the mouse is highlighted
+    /// in it, using the position that the parser recorded for it.
+    pub fn view_source(&mut self) {
+        let tab = self.tabs.active();
+        if tab.url.is_empty() || tab.url.starts_with(VIEW_SOURCE_PREFIX) {
+            return;
+        }
+        let url = format!("{}{}", VIEW_SOURCE_PREFIX, tab.url);
+        let mut source_tab = Tab::new(&url);
+        match (tab.source.as_ref(), tab.document.as_ref()) {
+            (Some(source), Some(document)) => {
+                let span = document
+                    .interaction()
+                    .hovered
+                    .iter()
+                    .rev()
+                    .filter_map(|x| document.element(*x))
+                    // The text is shown with the element it's in
+                    .find(|x| x.element_type != Tag::PlainText)
+                    .map(|x| x.source_span.clone())
+                    .filter(|x| !x.is_empty());
+                source_tab.set_source(source, span);
+            }
+            // Pages that weren't parsed from html, such as archives, are fetched again
+            _ => source_tab.loading = Some(PageLoad::start(&url, None)),
+        }
+        self.tabs.open(source_tab);
+        self.activate_tab(self.tabs.active_index());
+        self.save_session();
+    }
+
+    /// Handles a key press while the links have labels on them
+    fn type_hint(&mut self, key: &Key) {
+        let Some(hint_mode) = self.hint_mode.as_mut() else {
+            return;
+        };
+        let result = match key.as_ref() {
+            Key::Character(characters) => characters
+                .chars()
+                .map(|x| hint_mode.type_character(x))
+                .find(|x| *x != HintResult::Pending)
+                .unwrap_or(HintResult::Pending),
+            Key::Named(NamedKey::Backspace) => {
+                hint_mode.backspace();
+                HintResult::Pending
+            }
+            Key::Named(NamedKey::Shift) => HintResult::Pending,
+            _ => HintResult::Cancelled,
+        };
+        match result {
+            HintResult::Pending => (),
+            HintResult::Cancelled => self.hint_mode = None,
+            HintResult::Follow { index, new_tab } => {
+                let hint = self.hint_mode.take().unwrap().hints.swap_remove(index);
+                let noreferrer = self.is_noreferrer(hint.element_id);
+                if new_tab {
+                    self.follow_link_in_new_tab(&hint.href, noreferrer);
+                } else {
+                    self.follow_link(&hint.href, noreferrer);
+                }
+            }
+        }
+    }
+
+    /// Opens a url that the user gave, so no referrer is sent
+    pub fn open_link(&mut self, link: &str) {
+        self.navigate(link, None);
+    }
+
+    /// Starts loading the page into the active tab. The page is fetched in the background and
+    /// shown by [Window::poll_loads] once it arrives.
+    ///
+    /// * `referrer` - The value of the `Referer` header
+    pub fn navigate(&mut self, link: &str, referrer: Option<&str>) {
+        #[cfg(feature = "websocket")]
+        if let Some(query) = link.strip_prefix(WEBSOCKET_PAGE_URL) {
+            self.websocket_page.run_command(query);
+            self.show_websocket_page();
+            return;
+        }
+        if link == HISTORY_PAGE_URL {
+            let text = history_page(&self.history, &self.locale);
+            self.show_internal_page(HISTORY_PAGE_URL, "History", &text);
+            return;
+        }
+        if link == DOWNLOADS_PAGE_URL {
+            let text = downloads_page(&self.downloads, &self.locale);
+            self.show_internal_page(DOWNLOADS_PAGE_URL, "Downloads", &text);
+            return;
+        }
+
+        // A link into the page that is shown only scrolls to its fragment
+        let page_top = self.page_top();
+        let tab = self.tabs.active_mut();
+        if tab.is_same_document(link) {
+            tab.url = link.to_owned();
+            tab.fragment = split_fragment(link).1.map(|x| x.to_owned());
+            tab.loading = None;
+            // The page is already laid out, so it can slide to the fragment
+            let from = tab.scroll_y;
+            tab.scroll_to_fragment(page_top);
+            let to = std::mem::replace(&mut tab.scroll_y, from);
+            self.scroll_to(to);
+            self.toolbar.url = link.to_owned();
+            self.toolbar.suggestions.clear();
+            self.save_session();
+            return;
+        }
+
+        println!("Getting {:?}", link);
+        self.hint_mode = None;
+        self.tabs.active_mut().loading = Some(PageLoad::start(link, referrer));
+        self.toolbar.url = link.to_owned();
+        self.toolbar.suggestions.clear();
+    }
+
+    /// Shows the `about:websocket` page in the active tab
+    #[cfg(feature = "websocket")]
+    pub fn show_websocket_page(&mut self) {
+        let text = self.websocket_page.text();
+        self.show_internal_page(WEBSOCKET_PAGE_URL, "WebSocket", &text);
+    }
+
+    /// Shows a page that the browser generated, see [crate::internal_pages]
+    pub fn show_internal_page(&mut self, url: &str, title: &str, text: &str) {
+        let tab = self.tabs.active_mut();
+        tab.loading = None;
+        tab.url = url.to_owned();
+        tab.title = Some(title.to_owned());
+        tab.canonical_url = None;
+        tab.clear_favicon();
+        tab.set_plain_text(text);
+        self.toolbar.url = url.to_owned();
+        self.toolbar.suggestions.clear();
+        self.save_session();
+        self.update_window_icon();
+    }
+
+    /// Reads the frames that have arrived to the `about:websocket` page and updates the tabs that
+    /// show it
+    #[cfg(feature = "websocket")]
+    pub fn poll_websocket_page(&mut self) {
+        if !self.websocket_page.poll() {
+            return;
+        }
+        self.dirty = true;
+        let text = self.websocket_page.text();
+        for index in 0..self.tabs.len() {
+            let tab = self.tabs.get_mut(index);
+            if tab.url == WEBSOCKET_PAGE_URL {
+                tab.set_plain_text(&text);
+            }
+        }
+    }
+
+    /// Downloads the url into the downloads directory. The response that found out the url is a
+    /// download isn't reused, since the download has to be able to ask for byte ranges.
+    pub fn start_download(&mut self, url: &str, referrer: Option<&str>) {
+        let index = self.downloads.start(url, referrer);
+        self.downloads.save(Path::new(DOWNLOADS_PATH));
+        let name = self.downloads.get(index).unwrap().file_name();
+        self.toasts.push(&format!("Downloading {}", name), None);
+    }
+
+    /// Tells about the downloads that finished or failed
+    pub fn poll_downloads(&mut self) {
+        let events = self.downloads.poll();
+        if events.is_empty() {
+            return;
+        }
+        self.dirty = true;
+        for event in events {
+            match event {
+                DownloadEvent::Finished(index) => {
+                    let download = self.downloads.get(index).unwrap();
+                    let message = match download.state {
+                        DownloadState::Finished => format!("Downloaded {}", download.file_name()),
+                        _ => format!("Couldn't save {}", download.file_name()),
+                    };
+                    self.toasts.push(&message, None);
+                }
+                DownloadEvent::Failed(index, error) => {
+                    let download = self.downloads.get(index).unwrap();
+                    println!("Download of {} failed: {}", download.url, error);
+                    let message = format!(
+                        "The download of {} was interrupted. Click to resume.",
+                        download.file_name()
+                    );
+                    self.toasts
+                        .push(&message, Some(ToastAction::ResumeDownload(index)));
+                }
+            }
+        }
+        self.downloads.save(Path::new(DOWNLOADS_PATH));
+    }
+
+    /// Finishes the page loads that have received their response
+    pub fn poll_loads(&mut self) {
+        for index in 0..self.tabs.len() {
+            let tab = self.tabs.get_mut(index);
+            let Some(result) = tab.loading.as_ref().and_then(|x| x.poll()) else {
+                continue;
+            };
+            let load = tab.loading.take().unwrap();
+            self.finish_load(index, load, result);
+            self.dirty = true;
+        }
+        for index in 0..self.tabs.len() {
+            let tab = self.tabs.get_mut(index);
+            let Some(favicon) = tab.favicon_load.as_ref().and_then(|x| x.poll()) else {
+                continue;
+            };
+            let load = tab.favicon_load.take().unwrap();
+            self.finish_favicon_load(index, load, favicon);
+            self.dirty = true;
+        }
+    }
+
+    /// Shows the favicon that has arrived in the tab strip, and as the window icon if the tab is
+    /// active
+    fn finish_favicon_load(&mut self, index: usize, load: FaviconLoad, favicon: Option<RgbaImage>) {
+        if favicon.is_none() {
+            println!("Couldn't load the favicon {}", load.url);
+        }
+        let tab = self.tabs.get_mut(index);
+        tab.favicon_url = favicon.is_some().then_some(load.url);
+        tab.favicon = favicon;
+        self.update_favicon_textures();
+        if index == self.tabs.active_index() {
+            self.update_window_icon();
+        }
+    }
+
+    /// Uploads the textures of the favicons that the tabs don't have yet, and drops the ones that
+    /// no tab shows anymore. The tab strip only draws from [Window::favicon_textures].
+    fn update_favicon_textures(&mut self) {
+        if self.display.is_none() {
+            return;
+        }
+        let urls: HashSet<&String> = self
+            .tabs
+            .iter()
+            .filter_map(|x| x.favicon_url.as_ref())
+            .collect();
+        self.favicon_textures.retain(|url, _| urls.contains(url));
+        let textures: Vec<(String, Texture2d)> = self
+            .tabs
+            .iter()
+            .filter_map(|x| Some((x.favicon_url.as_ref()?, x.favicon.as_ref()?)))
+            .filter(|(url, _)| !self.favicon_textures.contains_key(*url))
+            .map(|(url, favicon)| (url.clone(), self.rgba_image_to_texture(favicon)))
+            .collect();
+        self.favicon_textures.extend(textures);
+    }
+
+    /// Checks if the window has to be drawn on every frame, because a toast is sliding in or the
+    /// page is running a transition
+    fn is_animating(&self, now: Instant) -> bool {
+        self.toasts.is_sliding(now, self.settings.reduce_motion)
+            || self.tabs.active().needs_layout()
+            || self.scroll_animation.is_some()
+    }
+
+    /// Returns when the event loop has to wake up by itself. The spinner of a loading tab moves
+    /// on, downloads, images and sockets have to be checked, toasts expire and the unused
+    /// resources are released when idle.
+    fn next_wake_up(&self, now: Instant) -> Option<Instant> {
+        let loading = self.tabs.iter().any(|x| x.loading_since().is_some());
+        #[cfg(feature = "websocket")]
+        let polling = self.downloads.is_running() || self.websocket_page.is_connected();
+        #[cfg(not(feature = "websocket"))]
+        let polling = self.downloads.is_running();
+        let polling = polling || self.images.values().any(|x| x.loading.is_some());
+        let cleanup = self.last_input.max(self.last_cleanup) + IDLE_DELAY;
+        [
+            loading.then_some(self.last_frame + SPINNER_STEP),
+            polling.then_some(now + POLL_INTERVAL),
+            self.toasts.next_expiry(),
+            Some(cleanup),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Shows the fetched page in the tab, or tells about the error if the fetch failed
+    fn finish_load(&mut self, index: usize, load: PageLoad, result: Result<Response, String>) {
+        let link = load.url.as_str();
+        let response = match result {
+            Ok(v) => v,
+            Err(error) => {
+                println!("{}", error);
+                let message = format!("Couldn't load {}. Click to retry.", link);
+                // A tab that has nothing to show gets the error as its page
+                let tab = self.tabs.get_mut(index);
+                if !tab.is_loaded() {
+                    tab.set_plain_text(&format!("Couldn't load {}: {}", link, error));
+                }
+                self.toasts
+                    .push(&message, Some(ToastAction::OpenLink(link.to_owned())));
+                return;
+            }
+        };
+        if response.download {
+            self.start_download(link, load.referrer.as_deref());
+            let tab = self.tabs.get_mut(index);
+            if !tab.is_loaded() {
+                tab.set_plain_text(&format!("Downloading {}", link));
+            }
+            return;
+        }
+        let tab = self.tabs.get_mut(index);
+        if link.starts_with(VIEW_SOURCE_PREFIX) {
+            tab.set_source(&response.body, None);
+        } else {
+            tab.set_response(&response);
+        }
+
+        let policy = self.referrer_policy_of(index);
+        let tab = self.tabs.get_mut(index);
+        tab.scroll_y = 0;
+        tab.scroll_x = 0;
+        tab.url = link.to_owned();
+        tab.fragment = split_fragment(link).1.map(|x| x.to_owned());
+        tab.title = tab.document.as_ref().and_then(|x| x.title());
+        tab.canonical_url = tab.document.as_ref().and_then(|x| x.canonical_url(link));
+        // The spinner stays until the favicon has been fetched
+        tab.clear_favicon();
+        let favicon_url = tab.document.as_ref().and_then(|x| x.favicon_url(link));
+        tab.favicon_load = favicon_url.map(|x| {
+            let receiver =
+                icon::fetch_favicon_in_background(&x, policy.referrer(link, &x).as_deref());
+            FaviconLoad::new(&x, load.started, receiver)
+        });
+        // Copies of a page are recorded under the url of the original, so they don't show up as
+        // separate suggestions
+        let now = history::now();
+        let history_url = tab.canonical_url.as_deref().unwrap_or(link);
+        self.history.coalesce(link, history_url, now);
+        self.history.add_visit(history_url, tab.title.clone(), now);
+        self.history.save(Path::new(HISTORY_PATH));
+        self.visited.add(link);
+        self.visited.save(Path::new(VISITED_PATH));
+        self.save_session();
+
+        if let Some(document) = self.tabs.get_mut(index).document.as_mut() {
+            let visited = &self.visited;
+            document.set_visited_links(|href| visited.contains(&resolve_url(link, href)));
+        }
+        if index == self.tabs.active_index() {
+            self.update_window_icon();
+        }
+        if load.started.elapsed() > LONG_LOAD {
+            let message = format!("Finished loading {}", self.tabs.get(index).label());
+            self.toasts.push(&message, None);
+        }
+        println!("Content received!");
+    }
+
+    /// Transforms screen coordinates into a 0.0 - 1.0 scale
+    pub fn screen_to_relative_coordinates(&self, x: i32, y: i32) -> [f32; 2] {
+        let inner_size = self.screen_size();
+        [
+            x as f32 / inner_size.width as f32,
+            y as f32 / inner_size.height as f32,
+        ]
+    }
+
+    pub fn set_elements(&mut self, elements: Vec<Element>) {
+        self.tabs.active_mut().set_elements(elements);
+    }
+
+    /// Shows the response with the viewer that fits its mime type
+    pub fn set_response(&mut self, response: &Response) {
+        self.tabs.active_mut().set_response(response);
+    }
+
+    pub fn render_response(&mut self, response: &Response) {
+        self.set_response(response);
+        self.open();
+    }
+
+    pub fn render(&mut self, elements: Vec<Element>) {
+        self.set_elements(elements);
+        self.open();
+    }
+
+    pub fn load_home_page(&mut self) {
+        let tab = self.tabs.active_mut();
+        tab.scroll_y = 0;
+        tab.scroll_x = 0;
+        tab.url.clear();
+        tab.title = None;
+        tab.canonical_url = None;
+        tab.clear_favicon();
+        let elements = parse_html(&HOME_PAGE);
+        self.set_elements(elements);
+        self.save_session();
+        self.update_window_icon();
+    }
+
+    pub fn open_to_home_page(&mut self) {
+        self.load_home_page();
+        self.open();
+    }
+
+    pub fn render_rect(&self, frame: &mut Frame, x: i32, y: i32, w: i32, h: i32, color: Color) {
+        let position = self.screen_to_opengl_coordinates(x, y);
+        let size = self.screen_to_relative_coordinates(w, h);
         let transformation: Matrix4<f32> = Matrix4::identity()
             .append_nonuniform_scaling(&Vector3::new(size[0], size[1], 1.0))
             .append_translation(&Vector3::new(position[0], position[1], 0.0));
         let compiled_matrix = TryInto::<[[f32; 4]; 4]>::try_into(transformation.data.0).unwrap();
         let uniforms = uniform! {
             transform: compiled_matrix,
-            in_color: color.as_opengl_color()
+            in_color: color.as_opengl_color(),
+            opacity: self.opacity.get()
         };
         frame
             .draw(
@@ -432,6 +1829,8 @@ impl Window {
                 self.solid_color_program.as_ref().unwrap(),
                 &uniforms,
                 &DrawParameters {
+                    blend: Blend::alpha_blending(),
+                    scissor: self.scissor.get(),
                     ..Default::default()
                 },
             )
@@ -439,7 +1838,7 @@ impl Window {
     }
 
     pub fn render_toolbar(&mut self, frame: &mut Frame) {
-        let screen_size = self.window.as_ref().unwrap().inner_size();
+        let screen_size = self.screen_size();
         let screen_width = screen_size.width as i32;
 
         // Draw background
@@ -470,140 +1869,1398 @@ impl Window {
         // Draw text
         self.render_string(
             frame,
+            self.fonts().default_font(),
             &self.toolbar.url,
             Position {
                 x: x_offset,
                 y: y_offset / 2,
             },
             text_area_height as f32,
-            None,
             Color::black(),
         );
 
+        self.render_tab_strip(frame, screen_width);
+        self.render_suggestions(frame, x_offset, width);
+
         // Draw home button
         self.render_string(
             frame,
+            self.fonts().default_font(),
             "H",
             Position { x: 0, y: 0 },
             height as f32,
-            None,
             Color::white(),
         );
     }
 
-    pub fn render_string(
-        &self,
-        frame: &mut Frame,
-        string: &str,
-        position: Position,
-        font_size: f32,
-        background_color: Option<Color>,
-        text_color: Color,
-    ) {
-        let x = position.x;
-        let y = position.y;
+    /// Draws the tabs below the url bar. The active tab is highlighted.
+    pub fn render_tab_strip(&self, frame: &mut Frame, screen_width: i32) {
+        let y = self.toolbar.height;
+        let height = self.toolbar.tab_strip_height;
+        self.render_rect(
+            frame,
+            screen_width / 2,
+            y + height / 2,
+            screen_width,
+            height,
+            Color::black(),
+        );
 
-        // Culling
-        let top_y = self.screen_to_opengl_coordinates(0, y)[1];
-        let bottom_y = self.screen_to_opengl_coordinates(
-            0,
-            y + self.font.as_ref().unwrap().get_glyph_height(font_size),
-        )[1];
-        if bottom_y > 1.0 || top_y < -1.0 {
+        let font_size = height as f32 * 0.8;
+        let mut x = 0;
+        for (index, tab) in self.tabs.iter().enumerate() {
+            let width = if tab.pinned {
+                PINNED_TAB_WIDTH
+            } else {
+                TAB_WIDTH
+            };
+            let color = if index == self.tabs.active_index() {
+                Color::white()
+            } else {
+                Color::gray()
+            };
+            // Leave a gap between the tabs
+            self.render_rect(
+                frame,
+                x + width / 2,
+                y + height / 2,
+                width - 2,
+                height - 2,
+                color,
+            );
+
+            // The spinner is shown while the page and its favicon load, and the favicon once
+            // they are done
+            let icon_size = height - 10;
+            let icon_rect = Rect::new(Position::new(x + 5, y + 5), Size::new(icon_size, icon_size));
+            let favicon = tab
+                .favicon_url
+                .as_ref()
+                .and_then(|x| self.favicon_textures.get(x));
+            let has_icon = if let Some(started) = tab.loading_since() {
+                self.render_spinner(frame, icon_rect, started);
+                true
+            } else if let Some(texture) = favicon {
+                self.draw_texture(frame, texture, icon_rect, [1.0, 1.0], [0.0, 0.0]);
+                true
+            } else {
+                false
+            };
+            // Pinned tabs only have room for the icon
+            let label_x = if has_icon { x + icon_size + 10 } else { x + 5 };
+            let label: String = match (tab.pinned, has_icon) {
+                (true, true) => String::new(),
+                (true, false) => tab.label().chars().take(1).collect(),
+                (false, _) => tab.label().to_owned(),
+            };
+            let label = self.fit_tab_label(label, width - (label_x - x) - 5, font_size);
+            self.render_string(
+                frame,
+                self.fonts().default_font(),
+                &label,
+                Position::new(label_x, y),
+                font_size,
+                Color::black(),
+            );
+            x += width;
+        }
+    }
+
+    /// Cuts characters off the end of the label of a tab until it fits in the width
+    fn fit_tab_label(&self, mut label: String, width: i32, font_size: f32) -> String {
+        let font = self.fonts().default_font();
+        while !label.is_empty() && font.get_word_width(&label, font_size) > width {
+            label.pop();
+        }
+        label
+    }
+
+    /// Draws a ring of dots where the darkest dot goes around the ring as time passes. With
+    /// reduced motion the dots stay still.
+    ///
+    /// * `started` - When the loading started, which the animation is timed from
+    pub fn render_spinner(&self, frame: &mut Frame, rect: Rect, started: Instant) {
+        let lead = if self.settings.reduce_motion {
+            None
+        } else {
+            let steps = started.elapsed().as_millis() / SPINNER_STEP.as_millis();
+            Some(steps as usize % SPINNER_DOTS)
+        };
+        let dot_size = (rect.size.width / 5).max(2);
+        let radius = (rect.size.width - dot_size) as f32 / 2.0;
+        let center_x = rect.position.x as f32 + rect.size.width as f32 / 2.0;
+        let center_y = rect.position.y as f32 + rect.size.height as f32 / 2.0;
+        for index in 0..SPINNER_DOTS {
+            let angle = index as f32 * std::f32::consts::TAU / SPINNER_DOTS as f32;
+            // The dots fade out behind the leading dot
+            let shade = match lead {
+                Some(lead) => {
+                    let behind = (lead + SPINNER_DOTS - index) % SPINNER_DOTS;
+                    0.1 + 0.7 * behind as f32 / SPINNER_DOTS as f32
+                }
+                None => 0.4,
+            };
+            let color = Color {
+                r: shade,
+                g: shade,
+                b: shade,
+                a: 1.0,
+            };
+            self.render_rect(
+                frame,
+                (center_x + angle.sin() * radius) as i32,
+                (center_y - angle.cos() * radius) as i32,
+                dot_size,
+                dot_size,
+                color,
+            );
+        }
+    }
+
+    /// Draws the scrollbars of the page over its right and bottom edges, see [Window::scrollbars]
+    pub fn render_scrollbars(&self, frame: &mut Frame) {
+        for scrollbar in self.scrollbars() {
+            for (rect, color) in [
+                (scrollbar.track, SCROLLBAR_TRACK_COLOR),
+                (scrollbar.thumb(), Color::gray()),
+            ] {
+                self.render_rect(
+                    frame,
+                    rect.position.x + rect.size.width / 2,
+                    rect.position.y + rect.size.height / 2,
+                    rect.size.width,
+                    rect.size.height,
+                    color,
+                );
+            }
+        }
+    }
+
+    /// Draws a panel with information about the page of the active tab in the top right corner of
+    /// the page
+    pub fn render_page_info(&self, frame: &mut Frame) {
+        if !self.show_page_info {
             return;
         }
+        let tab = self.tabs.active();
+        let mut lines = vec![
+            format!("Title: {}", tab.title.as_deref().unwrap_or("")),
+            format!("URL: {}", tab.label()),
+        ];
+        if let Some(url) = &tab.canonical_url {
+            lines.push(format!("Canonical URL: {}", url));
+        }
 
-        let rgba_image = self
-            .font
-            .as_ref()
-            .unwrap()
-            .render_string(string, font_size, text_color);
-        let texture = self.rgba_image_to_texture(&rgba_image);
+        let screen_width = self.screen_size().width as i32;
+        let line_height = 30;
+        let width = 600.min(screen_width);
+        let height = lines.len() as i32 * line_height;
+        let x = screen_width - width;
+        let y = self.toolbar.height + self.toolbar.tab_strip_height;
+        self.render_rect(
+            frame,
+            x + width / 2,
+            y + height / 2,
+            width,
+            height,
+            Color::white(),
+        );
+        for (index, line) in lines.iter().enumerate() {
+            self.render_string(
+                frame,
+                self.fonts().default_font(),
+                line,
+                Position::new(x + 5, y + index as i32 * line_height),
+                line_height as f32 * 0.8,
+                Color::black(),
+            );
+        }
+    }
 
-        let size = self.screen_to_relative_coordinates(
-            (rgba_image.width()) as i32,
-            (rgba_image.height()) as i32,
+    /// Draws the labels of the hint mode next to the links. The characters that have already been
+    /// typed are left out of the labels.
+    pub fn render_hints(&self, frame: &mut Frame) {
+        let Some(hint_mode) = self.hint_mode.as_ref() else {
+            return;
+        };
+        let font = self.fonts().default_font();
+        let font_size = 24.0;
+        let height = font.get_glyph_height(font_size);
+        for hint in hint_mode.visible_hints() {
+            let label = hint.label[hint_mode.typed.len()..].to_uppercase();
+            let width = font.get_word_width(&label, font_size) + 8;
+            let Position { x, y } = hint.position;
+            self.render_rect(
+                frame,
+                x + width / 2,
+                y + height / 2,
+                width,
+                height,
+                Color::from_css(CssColor::Hex(255, 221, 68)).unwrap(),
+            );
+            self.render_string(
+                frame,
+                font,
+                &label,
+                Position::new(x + 4, y),
+                font_size,
+                Color::black(),
+            );
+        }
+    }
+
+    /// Draws the list of the key bindings over the page
+    pub fn render_keymap_help(&self, frame: &mut Frame) {
+        if !self.show_keymap_help {
+            return;
+        }
+        let screen_size = self.screen_size();
+        let line_height = 30;
+        let key_column_width = 200;
+        let width = 700.min(screen_size.width as i32);
+        // One line for the title and an empty line after it
+        let height = (KEYMAP.len() as i32 + 2) * line_height;
+        let x = (screen_size.width as i32 - width) / 2;
+        let y = ((screen_size.height as i32 - height) / 2).max(0);
+        self.render_rect(
+            frame,
+            x + width / 2,
+            y + height / 2,
+            width,
+            height,
+            Color::white(),
         );
 
-        let gl_coordinates = self.screen_to_opengl_coordinates(
-            x + (rgba_image.width() as f32 / 2.0) as i32,
-            y + (rgba_image.height() as f32 / 2.0) as i32,
+        let font_size = line_height as f32 * 0.8;
+        self.render_string(
+            frame,
+            self.fonts().default_font(),
+            "Keyboard shortcuts",
+            Position::new(x + 10, y),
+            font_size,
+            Color::black(),
         );
-        let mat4 = Matrix4::identity()
-            .append_nonuniform_scaling(&Vector3::new(size[0], size[1], 1.0))
-            .append_translation(&Vector3::new(gl_coordinates[0], gl_coordinates[1], 0.0));
-        let compiled_matrix = TryInto::<[[f32; 4]; 4]>::try_into(mat4.data.0).unwrap();
+        for (index, binding) in KEYMAP.iter().enumerate() {
+            let line_y = y + (index as i32 + 2) * line_height;
+            self.render_string(
+                frame,
+                self.fonts().default_font(),
+                &binding.label(),
+                Position::new(x + 10, line_y),
+                font_size,
+                Color::gray(),
+            );
+            self.render_string(
+                frame,
+                self.fonts().default_font(),
+                binding.description,
+                Position::new(x + 10 + key_column_width, line_y),
+                font_size,
+                Color::black(),
+            );
+        }
+    }
+
+    /// Returns the rect of the toast at the index. The newest toast is at the bottom of the window,
+    /// and the toasts slide in from the right edge.
+    fn toast_rect(&self, index: usize, toast: &Toast, now: Instant) -> Rect {
+        let screen_size = self.screen_size();
+        let progress = toast.slide_progress(now, self.settings.reduce_motion);
+        let x = screen_size.width as i32 - ((TOAST_WIDTH + TOAST_MARGIN) as f32 * progress) as i32;
+        let position_from_bottom = (self.toasts.len() - index) as i32;
+        let y = screen_size.height as i32 - position_from_bottom * (TOAST_HEIGHT + TOAST_MARGIN);
+        Rect::new(Position::new(x, y), Size::new(TOAST_WIDTH, TOAST_HEIGHT))
+    }
+
+    /// Returns the index of the toast at the screen coordinates
+    fn toast_at(&self, x: i32, y: i32) -> Option<usize> {
+        let now = Instant::now();
+        self.toasts.iter().enumerate().position(|(index, toast)| {
+            let rect = self.toast_rect(index, toast, now);
+            x >= rect.position.x
+                && y >= rect.position.y
+                && x < rect.position.x + rect.size.width
+                && y < rect.position.y + rect.size.height
+        })
+    }
+
+    /// Draws the toast notifications over the bottom right corner of the page
+    pub fn render_toasts(&mut self, frame: &mut Frame) {
+        let now = Instant::now();
+        self.toasts.remove_expired(now);
+        let background = Color::from_css(CssColor::Hex(50, 50, 50)).unwrap();
+        for (index, toast) in self.toasts.iter().enumerate() {
+            let rect = self.toast_rect(index, toast, now);
+            let Rect { position, size } = rect;
+            self.render_rect(
+                frame,
+                position.x + size.width / 2,
+                position.y + size.height / 2,
+                size.width,
+                size.height,
+                background,
+            );
+            self.render_string(
+                frame,
+                self.fonts().default_font(),
+                &toast.message,
+                position + Position::new(10, 5),
+                TOAST_HEIGHT as f32 * 0.6,
+                Color::white(),
+            );
+        }
+    }
 
-        let bg_color = match background_color {
-            Some(color) => [color.r, color.g, color.b, 1.0],
-            None => [0.0, 0.0, 0.0, 0.0],
+    /// Draws the history suggestions as a list below the url bar
+    pub fn render_suggestions(&self, frame: &mut Frame, x: i32, width: i32) {
+        if !self.focused_on_toolbar {
+            return;
+        }
+        let top = self.toolbar.height;
+        self.render_dropdown(frame, &self.toolbar.suggestions, x, top, width);
+    }
+
+    /// Draws the items of the dropdown as a list that starts at the y coordinate
+    pub fn render_dropdown(
+        &self,
+        frame: &mut Frame,
+        dropdown: &Dropdown,
+        x: i32,
+        top: i32,
+        width: i32,
+    ) {
+        let height = dropdown.item_height;
+        for (index, item) in dropdown.items.iter().enumerate() {
+            let y = top + index as i32 * height;
+            let color = if dropdown.selected == Some(index) {
+                Color::gray()
+            } else {
+                Color::white()
+            };
+            self.render_rect(frame, x + width / 2, y + height / 2, width, height, color);
+            self.render_string(
+                frame,
+                self.fonts().default_font(),
+                item,
+                Position::new(x, y),
+                height as f32,
+                Color::black(),
+            );
+        }
+    }
+
+    /// Checks if anything between the two y coordinates would be visible on the screen
+    pub fn is_visible_vertically(&self, y: i32, height: i32) -> bool {
+        let top_y = self.screen_to_opengl_coordinates(0, y)[1];
+        let bottom_y = self.screen_to_opengl_coordinates(0, y + height)[1];
+        !(bottom_y > 1.0 || top_y < -1.0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_string(
+        &self,
+        frame: &mut Frame,
+        font: &Font,
+        string: &str,
+        position: Position,
+        font_size: f32,
+        text_color: Color,
+    ) {
+        // Culling
+        if !self.is_visible_vertically(position.y, font.get_glyph_height(font_size)) {
+            return;
+        }
+        let font = font.scaled(font_size * self.scale_factor());
+
+        let glyphs = font.shape(string).glyphs;
+        self.render_glyphs(frame, font, &glyphs, position, text_color);
+    }
+
+    /// Draws the glyphs in a batch of their own, see [Window::push_glyphs]
+    fn render_glyphs(
+        &self,
+        frame: &mut Frame,
+        font: ScaledFont,
+        glyphs: &[ShapedGlyph],
+        position: Position,
+        text_color: Color,
+    ) {
+        let mut batch = QuadBatch {
+            vertices: Vec::new(),
+            scissor: self.scissor.get(),
         };
+        self.push_glyphs(frame, &mut batch, font, glyphs, position, text_color);
+        self.draw_quads(frame, &mut batch);
+    }
 
-        let uniforms = uniform![
-            transform: compiled_matrix,
-            font_texture: texture,
-            background_color: bg_color
+    /// Adds the quads of the glyphs to the batch, with the left end of their baseline at the
+    /// position. The glyphs that aren't in [Window::atlas_texture] yet are
+    /// uploaded first. If the atlas had to be cleared for them, the batch is drawn before, since
+    /// its glyphs are overwritten.
+    #[allow(clippy::too_many_arguments)]
+    fn push_glyphs(
+        &self,
+        frame: &mut Frame,
+        batch: &mut QuadBatch,
+        font: ScaledFont,
+        glyphs: &[ShapedGlyph],
+        position: Position,
+        text_color: Color,
+    ) {
+        let Some(texture) = self.atlas_texture.as_ref() else {
+            return;
+        };
+        // The glyphs are rasterized for the physical pixels, so they are placed on them too
+        let [origin_x, origin_y] = self.screen_to_physical_coordinates(position.x, position.y);
+        let placed: Vec<(ShapedGlyph, u32)> = glyphs
+            .iter()
+            .map(|x| (*x, subpixel_position(origin_x + x.x).1))
+            .collect();
+        let atlas_glyphs = {
+            let mut atlas = self.glyph_atlas.borrow_mut();
+            let generation = atlas.generation();
+            let atlas_glyphs = atlas.glyphs(font, &placed);
+            if atlas.generation() != generation {
+                self.draw_quads(frame, batch);
+            }
+            for (glyph, pixels) in atlas.take_added() {
+                if glyph.width == 0 || glyph.height == 0 {
+                    continue;
+                }
+                let rect = glium::Rect {
+                    left: glyph.x,
+                    bottom: glyph.y,
+                    width: glyph.width,
+                    height: glyph.height,
+                };
+                texture.write(
+                    rect,
+                    RawImage2d::from_raw_rgba(pixels, (glyph.width, glyph.height)),
+                );
+            }
+            atlas_glyphs
+        };
+
+        let baseline = origin_y.round() as i32 + font.ascent();
+        let color = self.quad_color(text_color);
+        // The colored glyphs are drawn with their own colors, and only take the opacity of the text
+        let untinted = [1.0, 1.0, 1.0, color[3]];
+        for (shaped, glyph) in glyphs.iter().zip(atlas_glyphs) {
+            let Some(glyph) = glyph else {
+                continue;
+            };
+            let left = subpixel_position(origin_x + shaped.x).0 + glyph.offset_x;
+            let top = baseline + shaped.y + glyph.offset_y;
+            let corners = [
+                [left as f32, top as f32],
+                [
+                    (left + glyph.width as i32) as f32,
+                    (top + glyph.height as i32) as f32,
+                ],
+            ];
+            let color = match glyph.color {
+                true => untinted,
+                false => color,
+            };
+            self.push_quad(batch, corners, &glyph, color);
+        }
+    }
+
+    /// Adds a rect filled with the color to the batch
+    fn push_rect(&self, batch: &mut QuadBatch, rect: Rect, color: Color) {
+        let color = self.quad_color(color);
+        let Rect { position, size } = rect;
+        let corners = [
+            self.screen_to_physical_coordinates(position.x, position.y),
+            self.screen_to_physical_coordinates(position.x + size.width, position.y + size.height),
         ];
+        self.push_quad(batch, corners, &GlyphAtlas::WHITE, color);
+    }
 
+    /// Adds two triangles that cover the rect on the screen with the part of the atlas that the
+    /// glyph is in
+    ///
+    /// * `corners` - The top left and the bottom right corner of the rect in physical pixels
+    fn push_quad(
+        &self,
+        batch: &mut QuadBatch,
+        corners: [[f32; 2]; 2],
+        glyph: &AtlasGlyph,
+        color: [f32; 4],
+    ) {
+        let [x0, y0] = self.physical_to_opengl_coordinates(corners[0]);
+        let [x1, y1] = self.physical_to_opengl_coordinates(corners[1]);
+        // The rows of the texture are in the same order as the rows of the atlas, so the top of a
+        // glyph is at its smallest texture coordinate
+        let atlas_size = GLYPH_ATLAS_SIZE as f32;
+        let u0 = glyph.x as f32 / atlas_size;
+        let v0 = glyph.y as f32 / atlas_size;
+        let u1 = (glyph.x + glyph.width) as f32 / atlas_size;
+        let v1 = (glyph.y + glyph.height) as f32 / atlas_size;
+        let corner = |x: f32, y: f32, u: f32, v: f32| QuadVertex {
+            a_position: [x, y],
+            a_tex_coord: [u, v],
+            a_color: color,
+        };
+        batch.vertices.extend([
+            corner(x0, y0, u0, v0),
+            corner(x0, y1, u0, v1),
+            corner(x1, y0, u1, v0),
+            corner(x1, y1, u1, v1),
+            corner(x1, y0, u1, v0),
+            corner(x0, y1, u0, v1),
+        ]);
+    }
+
+    /// Returns the color of a quad with [Window::opacity] multiplied into its alpha
+    fn quad_color(&self, color: Color) -> [f32; 4] {
+        let [r, g, b, a] = color.as_opengl_color();
+        [r, g, b, a * self.opacity.get()]
+    }
+
+    /// Draws the quads of the batch with one draw call and empties it
+    fn draw_quads(&self, frame: &mut Frame, batch: &mut QuadBatch) {
+        if batch.vertices.is_empty() {
+            return;
+        }
+        let Some(texture) = self.atlas_texture.as_ref() else {
+            return;
+        };
+        let vertex_buffer =
+            VertexBuffer::new(self.display.as_ref().unwrap(), &batch.vertices).unwrap();
+        batch.vertices.clear();
+        let uniforms = uniform![
+            atlas: texture
+                .sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest)
+        ];
         frame
             .draw(
-                &self.rect.as_ref().unwrap().vao,
-                &self.rect.as_ref().unwrap().ebo,
-                self.program.as_ref().unwrap(),
+                &vertex_buffer,
+                NoIndices(PrimitiveType::TrianglesList),
+                self.quad_program.as_ref().unwrap(),
                 &uniforms,
                 &DrawParameters {
                     blend: Blend::alpha_blending(),
+                    scissor: batch.scissor,
                     ..Default::default()
                 },
             )
             .unwrap();
     }
 
-    pub fn render_current_page(&mut self, frame: &mut Frame) {
+    /// Lays out the page of the active tab if it has changed, and starts loading its background
+    /// images. Returns the urls of the background images by their index, or [None] if there is
+    /// no page to draw yet.
+    fn prepare_current_page(&mut self) -> Option<Vec<Option<String>>> {
         self.update_page_layout();
-        for paragraph in &self.layout.as_ref().unwrap().paragraphs {
-            for sentence in &paragraph.sentences {
-                let color = match sentence.text_color {
-                    Some(v) => v,
-                    None => Color::black(),
-                };
-                for word in &sentence.words {
-                    self.render_string(
-                        frame,
-                        &word.word,
-                        Position {
-                            x: word.position.x,
-                            y: word.position.y + self.scroll_y,
-                        },
-                        paragraph.font_size,
-                        paragraph.background_color,
-                        color,
-                    );
-                }
+
+        // The urls of the images are relative to the page
+        let base = self.tabs.active().url.clone();
+        // A tab that is still loading its first page has nothing to show
+        let layout = self.tabs.active().layout.as_ref()?;
+        let image_urls: Vec<Option<String>> = layout
+            .background_images
+            .iter()
+            .map(|x| match &x.image {
+                Image::Url(url) => Some(resolve_url(&base, url)),
+                Image::LinearGradient(_) => None,
+            })
+            .collect();
+        for url in image_urls.iter().flatten() {
+            self.load_image(url);
+        }
+        Some(image_urls)
+    }
+
+    /// Returns the pixels of the background images by their index, see [SoftwareBackend]
+    fn background_image_pixels(&self, image_urls: &[Option<String>]) -> Vec<Option<&RgbaImage>> {
+        image_urls
+            .iter()
+            .map(|url| {
+                url.as_ref()
+                    .and_then(|x| self.images.get(x))
+                    .and_then(|x| x.pixels.as_ref())
+            })
+            .collect()
+    }
+
+    pub fn render_current_page(&mut self, frame: &mut Frame) {
+        let Some(image_urls) = self.prepare_current_page() else {
+            return;
+        };
+        let scroll_y = self.tabs.active().scroll_y;
+        let tab = self.tabs.active();
+        let display_list = tab.display_list.as_ref().unwrap();
+        let layout = tab.layout.as_ref().unwrap();
+        if self.software_rendering {
+            self.render_page_in_software(frame, display_list, layout, &image_urls);
+        } else {
+            let mut backend = GlBackend {
+                window: self,
+                frame,
+                batch: QuadBatch::default(),
+                layout,
+                image_urls: &image_urls,
+            };
+            paint(display_list, scroll_y, &mut backend);
+        }
+        // The toolbar and the overlays are drawn over the whole window
+        self.scissor.set(None);
+        self.opacity.set(1.0);
+        self.offset_x.set(0);
+    }
+
+    /// Draws the page into an image on the CPU and shows it over the whole window, see
+    /// [SoftwareBackend]. The image is drawn in logical pixels, so it's scaled up on screens that
+    /// have more pixels than that.
+    fn render_page_in_software(
+        &self,
+        frame: &mut Frame,
+        display_list: &DisplayList,
+        layout: &Layout,
+        image_urls: &[Option<String>],
+    ) {
+        let size = self.screen_size();
+        let size = Size::new(size.width as i32, size.height as i32);
+        let transparent = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+            a: 0.0,
+        };
+        let tab = self.tabs.active();
+        let image_pixels = self.background_image_pixels(image_urls);
+        let mut backend = SoftwareBackend::new(size, transparent, self.fonts(), tab.scroll());
+        backend.set_area(self.page_area());
+        backend.set_background_images(&layout.background_images, &image_pixels);
+        paint(display_list, tab.scroll_y, &mut backend);
+
+        let texture = self.rgba_image_to_texture(&backend.image);
+        self.scissor.set(None);
+        self.opacity.set(1.0);
+        self.offset_x.set(0);
+        let rect = Rect::new(Position::new(0, 0), size);
+        self.draw_texture(frame, &texture, rect, [1.0, 1.0], [0.0, 0.0]);
+    }
+
+    /// Draws the whole window on the CPU and puts it on the screen without OpenGL, see
+    /// [Window::software_surface]. The page, the toolbar, the tabs and the scrollbars are drawn,
+    /// but the overlays over them are left out.
+    fn redraw_in_software(&mut self) {
+        self.step_scroll_animation(Instant::now());
+        let image = self.render_window_in_software();
+        let size = self.window.as_ref().unwrap().inner_size();
+        let scale_factor = self.scale_factor();
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        else {
+            return;
+        };
+        let surface = self.software_surface.as_mut().unwrap();
+        if let Err(error) = surface.resize(width, height) {
+            println!("Couldn't resize the surface of the window: {}", error);
+            return;
+        }
+        let mut buffer = match surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(error) => {
+                println!("Couldn't draw the window: {}", error);
+                return;
+            }
+        };
+        // The image is in logical pixels, so every physical pixel takes the color of the logical
+        // pixel that it's in
+        let logical =
+            |physical: u32, length: u32| ((physical as f32 / scale_factor) as u32).min(length - 1);
+        for y in 0..size.height {
+            let image_y = logical(y, image.height());
+            for x in 0..size.width {
+                let [r, g, b, _] = image.get_pixel(logical(x, image.width()), image_y).0;
+                buffer[(y * size.width + x) as usize] =
+                    (r as u32) << 16 | (g as u32) << 8 | b as u32;
+            }
+        }
+        if let Err(error) = buffer.present() {
+            println!("Couldn't draw the window: {}", error);
+        }
+    }
+
+    /// Draws the page of the active tab and the toolbar over the background of the window into an
+    /// image on the CPU, see [SoftwareBackend]
+    fn render_window_in_software(&mut self) -> RgbaImage {
+        let image_urls = self.prepare_current_page();
+        let size = self.screen_size();
+        let size = Size::new(size.width as i32, size.height as i32);
+        let tab = self.tabs.active();
+        let image_pixels;
+        let mut backend = SoftwareBackend::new(size, self.background(), self.fonts(), tab.scroll());
+        if let (Some(image_urls), Some(layout), Some(display_list)) =
+            (image_urls, tab.layout.as_ref(), tab.display_list.as_ref())
+        {
+            image_pixels = self.background_image_pixels(&image_urls);
+            backend.set_area(self.page_area());
+            backend.set_background_images(&layout.background_images, &image_pixels);
+            paint(display_list, tab.scroll_y, &mut backend);
+        }
+        backend.set_area(Rect::new(Position::new(0, 0), size));
+        self.render_toolbar_in_software(&mut backend);
+        backend.image
+    }
+
+    /// Draws the url bar, the tabs and the scrollbars like [Window::render_toolbar],
+    /// [Window::render_tab_strip] and [Window::render_scrollbars] do with OpenGL. The icons of
+    /// the tabs are left out.
+    fn render_toolbar_in_software(&self, backend: &mut impl RenderBackend) {
+        let text = |text: &str, font_size: f32, color: Color| TextRun {
+            text: text.to_string(),
+            font_family: DEFAULT_FONT_FAMILY.to_string(),
+            face: FontFace::REGULAR,
+            font_size,
+            color,
+            monospace: false,
+        };
+        let screen_width = self.screen_size().width as i32;
+        backend.set_clip(None, true);
+        backend.set_opacity(1.0);
+        for scrollbar in self.scrollbars() {
+            backend.fill_rect(scrollbar.track, SCROLLBAR_TRACK_COLOR);
+            backend.fill_rect(scrollbar.thumb(), Color::gray());
+        }
+
+        let height = self.toolbar.height;
+        let y_offset = 10;
+        let x_offset = self.toolbar.home_button_width;
+        let width = 600.min(screen_width - 50);
+        let text_area_height = height - y_offset;
+        backend.fill_rect(
+            Rect::new(Position::new(0, 0), Size::new(screen_width, height)),
+            Color::black(),
+        );
+        backend.fill_rect(
+            Rect::new(
+                Position::new(x_offset, y_offset / 2),
+                Size::new(width, text_area_height),
+            ),
+            Color::white(),
+        );
+        backend.draw_text(
+            &text(&self.toolbar.url, text_area_height as f32, Color::black()),
+            Position::new(x_offset, y_offset / 2),
+        );
+        backend.draw_text(
+            &text("H", height as f32, Color::white()),
+            Position::new(0, 0),
+        );
+
+        let y = self.toolbar.height;
+        let height = self.toolbar.tab_strip_height;
+        let font_size = height as f32 * 0.8;
+        backend.fill_rect(
+            Rect::new(Position::new(0, y), Size::new(screen_width, height)),
+            Color::black(),
+        );
+        let mut x = 0;
+        for (index, tab) in self.tabs.iter().enumerate() {
+            let width = if tab.pinned {
+                PINNED_TAB_WIDTH
+            } else {
+                TAB_WIDTH
+            };
+            let color = if index == self.tabs.active_index() {
+                Color::white()
+            } else {
+                Color::gray()
+            };
+            // Leave a gap between the tabs
+            backend.fill_rect(
+                Rect::new(
+                    Position::new(x + 1, y + 1),
+                    Size::new(width - 2, height - 2),
+                ),
+                color,
+            );
+            let label = match tab.pinned {
+                true => tab.label().chars().take(1).collect(),
+                false => tab.label().to_owned(),
+            };
+            let label = self.fit_tab_label(label, width - 10, font_size);
+            backend.draw_text(
+                &text(&label, font_size, Color::black()),
+                Position::new(x + 5, y),
+            );
+            x += width;
+        }
+    }
+
+    /// Returns where the ends of the page can be scrolled to. The page can't be scrolled further
+    /// than to show its ends inside of the padding, see [Tab::clamp_scroll].
+    fn visible_end(&self) -> Position {
+        let size = self.screen_size();
+        Position::new(
+            size.width as i32 - self.settings.page_padding,
+            size.height as i32 - self.settings.page_padding,
+        )
+    }
+
+    /// Returns the color of the window around the page
+    fn background(&self) -> Color {
+        if self.settings.forced_colors {
+            self.settings.forced_colors_palette.background
+        } else {
+            Color::from_css(CssColor::Hex(204, 204, 204)).unwrap()
+        }
+    }
+
+    /// Returns the y where the content of the page starts. The page is kept away from the toolbar
+    /// by the padding, like from the other edges of the window.
+    fn page_top(&self) -> i32 {
+        self.toolbar.bottom() + self.settings.page_padding
+    }
+
+    /// Returns the part of the window below the toolbar, which is the only part the page is drawn
+    /// to. Content that is scrolled up goes under the toolbar instead of over it.
+    fn page_area(&self) -> Rect {
+        let size = self.screen_size();
+        let top = self.toolbar.bottom();
+        Rect::new(
+            Position::new(0, top),
+            Size::new(size.width as i32, (size.height as i32 - top).max(0)),
+        )
+    }
+
+    /// Makes the following draws only touch the part of the page area inside of the rect. [None]
+    /// lets them draw anywhere in the page area, see [Window::page_area]. The draws are also moved
+    /// by the horizontal scroll of the page, unless they are fixed.
+    ///
+    /// * `fixed` - Set if the rect stays in place when the page is scrolled
+    pub fn set_clip(&self, clip: Option<Rect>, fixed: bool) {
+        let screen_height = self.screen_size().height as i32;
+        let scroll = match fixed {
+            true => Position::new(0, 0),
+            false => self.tabs.active().scroll(),
+        };
+        self.offset_x.set(scroll.x);
+        let page_area = self.page_area();
+        let visible = match clip {
+            Some(clip) => Rect::new(clip.position + scroll, clip.size)
+                .intersection(&page_area)
+                .unwrap_or(Rect::new(page_area.position, Size::new(0, 0))),
+            None => page_area,
+        };
+        // OpenGL counts the rows from the bottom of the window, in physical pixels
+        let scale_factor = self.scale_factor();
+        let physical = |x: i32| (x as f32 * scale_factor).round() as i32;
+        let bottom = visible.position.y + visible.size.height;
+        self.scissor.set(Some(glium::Rect {
+            left: physical(visible.position.x) as u32,
+            bottom: physical(screen_height - bottom).max(0) as u32,
+            width: physical(visible.size.width) as u32,
+            height: physical(visible.size.height) as u32,
+        }));
+    }
+
+    /// Scrolls the innermost scrollable box under the mouse. The page is scrolled if there is no
+    /// such box, or if the box can't be scrolled any further in that direction.
+    ///
+    /// * `amount` - How many pixels to scroll. Positive values scroll down.
+    pub fn scroll_at_mouse(&mut self, amount: i32) {
+        // The labels would be left behind by the links
+        self.hint_mode = None;
+        let Position { x, y } = self.mouse_position;
+        let tab = self.tabs.active_mut();
+        let scroll = tab.scroll();
+        // The boxes inside of other boxes come first, so the innermost box is found first
+        let scroll_box = tab.layout.as_ref().and_then(|layout| {
+            layout
+                .scroll_boxes
+                .iter()
+                .filter(|scroll_box| match scroll_box.fixed {
+                    true => scroll_box.rect.contains(x, y),
+                    false => scroll_box.rect.contains(x - scroll.x, y - scroll.y),
+                })
+                .find(|x| match amount {
+                    v if v > 0 => x.offset < x.max_offset,
+                    _ => x.offset > 0,
+                })
+                .map(|x| (x.id, x.offset, x.max_offset))
+        });
+        match scroll_box {
+            Some((id, offset, max_offset)) => {
+                tab.box_scroll
+                    .insert(id, (offset + amount).clamp(0, max_offset));
+                // The content of the box is moved in the layout
+                tab.invalidate_layout();
+            }
+            None => self.scroll_by(amount),
+        }
+    }
+
+    /// Scrolls the page of the active tab by the amount, counted from where the page is already
+    /// sliding to. Positive amounts scroll down.
+    pub fn scroll_by(&mut self, amount: i32) {
+        let target = match self.scroll_animation.as_ref() {
+            Some(animation) => animation.target(),
+            None => self.tabs.active().scroll_y,
+        };
+        self.scroll_to(target.saturating_sub(amount));
+    }
+
+    /// Slides the page of the active tab to the vertical scroll, see [ScrollAnimation]. The page
+    /// jumps there at once with reduced motion.
+    pub fn scroll_to(&mut self, scroll_y: i32) {
+        let visible_end = self.visible_end();
+        let tab = self.tabs.active_mut();
+        let scroll_y = match tab.max_scroll(visible_end) {
+            Some(max_scroll) => scroll_y.clamp(-max_scroll.y, 0),
+            None => scroll_y,
+        };
+        if self.settings.reduce_motion || scroll_y == tab.scroll_y {
+            self.scroll_animation = None;
+            tab.scroll_y = scroll_y;
+            return;
+        }
+        self.scroll_animation = Some(ScrollAnimation::new(tab.scroll_y, scroll_y, Instant::now()));
+    }
+
+    /// Moves the page of the active tab on to where its scroll animation is at the moment
+    fn step_scroll_animation(&mut self, now: Instant) {
+        let Some(animation) = self.scroll_animation.as_mut() else {
+            return;
+        };
+        let tab = self.tabs.active_mut();
+        // Something else has moved the page, such as the scrollbar or another tab
+        if tab.scroll_y != animation.shown() {
+            self.scroll_animation = None;
+            return;
+        }
+        tab.scroll_y = animation.step(now);
+        if animation.is_finished(now) {
+            self.scroll_animation = None;
+        }
+    }
+
+    /// Scrolls the page sideways. The page is clamped to its content when it's laid out again.
+    ///
+    /// * `amount` - How many pixels to scroll. Positive values scroll right.
+    pub fn scroll_sideways(&mut self, amount: i32) {
+        if amount == 0 {
+            return;
+        }
+        // The labels would be left behind by the links
+        self.hint_mode = None;
+        self.tabs.active_mut().scroll_x -= amount;
+    }
+
+    /// Fetches the image into [Window::images], unless it's there already. This is called every
+    /// time the image is drawn, so it also keeps the image from being released.
+    pub fn load_image(&mut self, url: &str) {
+        if let Some(image) = self.images.get(url) {
+            let texture = match (&image.texture, &image.pixels) {
+                (None, Some(pixels)) => Some(self.rgba_image_to_texture(pixels)),
+                _ => None,
+            };
+            let image = self.images.get_mut(url).unwrap();
+            image.last_drawn = Instant::now();
+            if texture.is_some() {
+                image.texture = texture;
+            }
+            return;
+        }
+        println!("Getting image {:?}", url);
+        let referrer = self
+            .referrer_policy()
+            .referrer(&self.tabs.active().url, url);
+        // The image stays in the cache while it's fetched, so it's only fetched once
+        self.images.insert(
+            url.to_owned(),
+            CachedImage {
+                pixels: None,
+                loading: Some(fetch_image_in_background(url, referrer.as_deref())),
+                texture: None,
+                last_drawn: Instant::now(),
+            },
+        );
+    }
+
+    /// Takes the pixels of the images that have been fetched in the background, so the page is
+    /// drawn again with them. The `<img>` elements get their size on the next update, see
+    /// [Window::load_page_images].
+    pub fn poll_images(&mut self) {
+        for (url, image) in self.images.iter_mut() {
+            let Some(receiver) = image.loading.as_ref() else {
+                continue;
+            };
+            let pixels = match receiver.try_recv() {
+                Ok(v) => v,
+                Err(TryRecvError::Empty) => continue,
+                Err(TryRecvError::Disconnected) => None,
+            };
+            if pixels.is_none() {
+                println!("Couldn't load the image {}", url);
+            }
+            image.pixels = pixels;
+            image.loading = None;
+            self.dirty = true;
+        }
+    }
+
+    /// Loads the pictures of the `<img>` elements of the page, so the images without a size of
+    /// their own get the size of their picture. The page is laid out again once the pictures
+    /// arrive.
+    fn load_page_images(&mut self) {
+        let tab = self.tabs.active();
+        let Some(document) = tab.document.as_ref() else {
+            return;
+        };
+        let images: Vec<(usize, String)> = document
+            .pending_images()
+            .into_iter()
+            .map(|(id, src)| (id, resolve_url(&tab.url, &src)))
+            .collect();
+        for (id, url) in images {
+            self.load_image(&url);
+            // The image keeps waiting for its size while the picture is being fetched
+            let Some(image) = self.images.get(&url).filter(|x| x.loading.is_none()) else {
+                continue;
+            };
+            let size = image
+                .pixels
+                .as_ref()
+                .map(|x| x.dimensions())
+                .unwrap_or((0, 0));
+            if let Some(document) = self.tabs.active_mut().document.as_mut() {
+                document.set_natural_size(id, size);
+            }
+        }
+    }
+
+    /// Releases the memory of the things that haven't been used recently, once the user has
+    /// stopped doing anything for a while
+    pub fn clean_up_when_idle(&mut self) {
+        let now = Instant::now();
+        if now - self.last_input < IDLE_DELAY || now - self.last_cleanup < IDLE_DELAY {
+            return;
+        }
+        self.last_cleanup = now;
+        let images = self.release_unused_images(now);
+        self.update_favicon_textures();
+        let documents = self.discard_background_documents();
+        if images > 0 || documents > 0 {
+            println!(
+                "Released {} images and {} documents while idle",
+                images, documents
+            );
+        }
+    }
+
+    /// Releases the images that haven't been drawn recently, and then the least recently drawn
+    /// ones until the rest fit into [IMAGE_BUDGET]. The images on the screen are drawn every
+    /// frame, so they are always kept. Returns how many images were released.
+    fn release_unused_images(&mut self, now: Instant) -> usize {
+        let count = self.images.len();
+        self.images
+            .retain(|_, x| now - x.last_drawn < UNUSED_IMAGE_TIME);
+
+        let mut size: usize = self.images.values().map(|x| x.byte_size()).sum();
+        let mut oldest: Vec<(String, Instant, usize)> = self
+            .images
+            .iter()
+            .filter(|(_, x)| now - x.last_drawn >= IDLE_DELAY)
+            .map(|(url, x)| (url.clone(), x.last_drawn, x.byte_size()))
+            .collect();
+        oldest.sort_by_key(|x| x.1);
+        for (url, _, byte_size) in oldest {
+            if size <= IMAGE_BUDGET {
+                break;
             }
+            self.images.remove(&url);
+            size -= byte_size;
         }
+        count - self.images.len()
+    }
+
+    /// Discards the documents of the background tabs that were looked at the longest time ago,
+    /// so only [MAX_PARSED_BACKGROUND_TABS] stay parsed. Returns how many were discarded.
+    fn discard_background_documents(&mut self) -> usize {
+        let active = self.tabs.active_index();
+        let mut parsed: Vec<usize> = (0..self.tabs.len())
+            .filter(|x| *x != active && self.tabs.get(*x).document.is_some())
+            .collect();
+        parsed.sort_by_key(|x| std::cmp::Reverse(self.tabs.get(*x).last_active));
+        parsed
+            .into_iter()
+            .skip(MAX_PARSED_BACKGROUND_TABS)
+            .filter(|x| self.tabs.get_mut(*x).discard_document())
+            .count()
+    }
+
+    /// Draws the tiles of a background image. The whole painted area is drawn as one rectangle,
+    /// and the texture coordinates repeat the image inside of it.
+    ///
+    /// * `url` - The resolved url of the image. Gradients don't have one.
+    pub fn render_background_image(
+        &self,
+        frame: &mut Frame,
+        image: &BackgroundImage,
+        url: Option<&str>,
+    ) {
+        let texture = url
+            .and_then(|x| self.images.get(x))
+            .and_then(|x| x.texture.as_ref());
+        let natural_size = match (&image.image, texture) {
+            (Image::LinearGradient(_), _) => image.rect.size,
+            (Image::Url(_), Some(texture)) => {
+                Size::new(texture.width() as i32, texture.height() as i32)
+            }
+            (Image::Url(_), None) => return,
+        };
+        let tile = image.tile(natural_size);
+        if tile.size.width <= 0 || tile.size.height <= 0 {
+            return;
+        }
+        let Some(area) = image.painted_area(tile) else {
+            return;
+        };
+        let scroll_y = if image.fixed {
+            0
+        } else {
+            self.tabs.active().scroll_y
+        };
+        let y = area.position.y + scroll_y;
+        if !self.is_visible_vertically(y, area.size.height) {
+            return;
+        }
+
+        // The rows of the texture are upside down, so the coordinates start from the bottom
+        let tile_width = tile.size.width as f32;
+        let tile_height = tile.size.height as f32;
+        let left = (area.position.x - tile.position.x) as f32 / tile_width;
+        let bottom = (area.position.y + area.size.height - tile.position.y) as f32 / tile_height;
+        let tex_scale = [
+            area.size.width as f32 / tile_width,
+            area.size.height as f32 / tile_height,
+        ];
+        let tex_offset = [left, 1.0 - bottom];
+        let rect = Rect::new(Position::new(area.position.x, y), area.size);
+        match (&image.image, texture) {
+            (Image::LinearGradient(gradient), _) => {
+                let tile_size = [tile_width, tile_height];
+                self.draw_gradient(
+                    frame,
+                    gradient,
+                    image.font_size,
+                    tile_size,
+                    rect,
+                    tex_scale,
+                    tex_offset,
+                );
+            }
+            (Image::Url(_), Some(texture)) => {
+                self.draw_texture(frame, texture, rect, tex_scale, tex_offset)
+            }
+            (Image::Url(_), None) => (),
+        }
+    }
+
+    /// Returns the matrix that transforms the unit rectangle into the rect on the screen
+    pub fn rect_transform(&self, rect: Rect) -> [[f32; 4]; 4] {
+        let position = self.screen_to_opengl_coordinates(
+            rect.position.x + rect.size.width / 2,
+            rect.position.y + rect.size.height / 2,
+        );
+        let size = self.screen_to_relative_coordinates(rect.size.width, rect.size.height);
+        let transformation: Matrix4<f32> = Matrix4::identity()
+            .append_nonuniform_scaling(&Vector3::new(size[0], size[1], 1.0))
+            .append_translation(&Vector3::new(position[0], position[1], 0.0));
+        TryInto::<[[f32; 4]; 4]>::try_into(transformation.data.0).unwrap()
+    }
+
+    /// Draws the part of the texture given by `tex_scale` and `tex_offset` into the rect on the
+    /// screen. The texture repeats outside of the 0.0 - 1.0 range.
+    pub fn draw_texture(
+        &self,
+        frame: &mut Frame,
+        texture: &Texture2d,
+        rect: Rect,
+        tex_scale: [f32; 2],
+        tex_offset: [f32; 2],
+    ) {
+        let uniforms = uniform! {
+            transform: self.rect_transform(rect),
+            image: texture.sampled().wrap_function(SamplerWrapFunction::Repeat),
+            tex_scale: tex_scale,
+            tex_offset: tex_offset,
+            opacity: self.opacity.get()
+        };
+        frame
+            .draw(
+                &self.rect.as_ref().unwrap().vao,
+                &self.rect.as_ref().unwrap().ebo,
+                self.image_program.as_ref().unwrap(),
+                &uniforms,
+                &DrawParameters {
+                    blend: Blend::alpha_blending(),
+                    scissor: self.scissor.get(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+
+    /// Draws a linear gradient into the rect on the screen. The texture coordinates work like in
+    /// [Window::draw_texture], and every whole unit is one tile of the gradient.
+    ///
+    /// * `tile_size` - The size of a single tile in pixels
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_gradient(
+        &self,
+        frame: &mut Frame,
+        gradient: &LinearGradient,
+        font_size: f32,
+        tile_size: [f32; 2],
+        rect: Rect,
+        tex_scale: [f32; 2],
+        tex_offset: [f32; 2],
+    ) {
+        let [width, height] = tile_size;
+        let angle = gradient.direction.degrees(width, height);
+        let line_length = gradient::line_length(angle, width, height).max(1.0);
+        let ramp = self.rgba_image_to_texture(&gradient::ramp(gradient, line_length, font_size));
+        // The texture coordinates go up, like the angle of 0 degrees
+        let direction = [angle.to_radians().sin(), angle.to_radians().cos()];
+        let uniforms = uniform! {
+            transform: self.rect_transform(rect),
+            ramp: ramp.sampled().wrap_function(SamplerWrapFunction::Clamp),
+            ramp_width: gradient::RAMP_WIDTH as f32,
+            tile_size: tile_size,
+            direction: direction,
+            line_length: line_length,
+            tex_scale: tex_scale,
+            tex_offset: tex_offset,
+            opacity: self.opacity.get()
+        };
+        frame
+            .draw(
+                &self.rect.as_ref().unwrap().vao,
+                &self.rect.as_ref().unwrap().ebo,
+                self.gradient_program.as_ref().unwrap(),
+                &uniforms,
+                &DrawParameters {
+                    blend: Blend::alpha_blending(),
+                    scissor: self.scissor.get(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
     }
 
+    /// Lays out the page of the active tab if the layout it has is outdated. The page is only laid
+    /// out again when the window is resized, a new page is shown, the styles change or the text
+    /// is zoomed, so drawing a page that doesn't change stays cheap.
     pub fn update_page_layout(&mut self) {
+        let inner_size = self.screen_size();
+        let page_top = self.page_top();
+        let (page_left, page_width) = self.settings.content_column(inner_size.width as i32);
+        let page_height = inner_size.height as i32 - page_top - self.settings.page_padding;
+        let screen_height = inner_size.height as i32;
+        let visible_end = self.visible_end();
+        let page_zoom = self.page_zoom();
+        self.load_page_images();
+        let key = LayoutKey {
+            area: Rect::new(
+                Position::new(page_left, page_top),
+                Size::new(page_width, page_height),
+            ),
+            text_zoom: self.settings.text_zoom,
+            page_zoom,
+        };
+        let tab = self.tabs.active_mut();
+        if tab.text_document.is_some() && tab.layout_is_current(key) {
+            tab.clamp_scroll(visible_end);
+            return;
+        }
+        if let Some(text_document) = tab.text_document.as_mut() {
+            let mut layout = text_document.layout(
+                Size::new(page_width, page_height),
+                self.settings.text_zoom * page_zoom,
+                self.fonts.as_ref().unwrap().default_font(),
+            );
+            if text_document.scroll_to_highlight {
+                text_document.scroll_to_highlight = false;
+                // Some of the lines before the highlight are left visible
+                let y = text_document.highlight_position(&layout).unwrap_or(0);
+                tab.scroll_y = -(y - inner_size.height as i32 / 4).max(0);
+            }
+            layout.make_relative_to(Position::new(page_left, page_top));
+            let display_list = DisplayList::new(&layout, self.fonts.as_ref().unwrap());
+            tab.set_layout(layout, display_list, key);
+            tab.clamp_scroll(visible_end);
+            return;
+        }
+
+        let Some(document) = tab.document.as_mut() else {
+            tab.layout = None;
+            tab.display_list = None;
+            return;
+        };
+
+        // The styles are computed here, so media queries get evaluated again when the window is
+        // resized
+        let color_scheme = match self.window.as_ref().unwrap().theme() {
+            Some(Theme::Dark) => ColorScheme::Dark,
+            _ => ColorScheme::Light,
+        };
+        let restyled = document.update_styles(MediaEnvironment {
+            // The page sees the window as narrower when it's zoomed in, like in the other browsers
+            width: (inner_size.width as f32 / page_zoom).round() as i32,
+            color_scheme,
+            reduced_motion: self.settings.reduce_motion,
+            forced_colors: self
+                .settings
+                .forced_colors
+                .then_some(self.settings.forced_colors_palette),
+            text_zoom: self.settings.text_zoom,
+            page_zoom,
+        });
+        let animating = document.animate(Instant::now());
+        let screen = -tab.scroll_y..-tab.scroll_y + screen_height;
+        if !restyled && tab.layout_is_current(key) && !tab.virtualization.is_outside(screen) {
+            tab.scroll_to_fragment(page_top);
+            tab.clamp_scroll(visible_end);
+            return;
+        }
+
         let mut body = None;
-        for element in &self.document.as_ref().unwrap().elements[0].children {
+        for element in &tab.document.as_ref().unwrap().elements[0].children {
             if element.element_type == Tag::Body {
                 body = Some(element);
                 break;
             }
         }
         let body = body.unwrap();
-        let inner_size = self.window.as_ref().unwrap().inner_size();
+        // The paragraphs of a gigantic page are only laid out a screen above and below the
+        // visible part
+        let top = -tab.scroll_y - screen_height;
+        tab.virtualization.visible = top..top + screen_height * 3;
         let mut layout = Layout::from_body(
             body,
-            Size {
-                width: inner_size.width as i32 - 40,
-                height: inner_size.height as i32 - 40,
-            },
-            self.font.as_ref().unwrap(),
+            Size::new(page_width, page_height),
+            self.fonts.as_ref().unwrap(),
+            &tab.box_scroll,
+            &mut tab.virtualization,
         );
-        layout.make_relative_to(Position::new(40, 40));
-        self.layout = Some(layout);
+        layout.make_relative_to(Position::new(page_left, page_top));
+        let display_list = DisplayList::new(&layout, self.fonts.as_ref().unwrap());
+        tab.set_layout(layout, display_list, key);
+        // A running transition changes the colors on every frame, and the layout made after the
+        // last frame of it has the final colors
+        if animating {
+            tab.invalidate_layout();
+        }
+        tab.scroll_to_fragment(page_top);
+        tab.clamp_scroll(visible_end);
     }
 
     pub fn rgba_image_to_texture(&self, image: &RgbaImage) -> Texture2d {
@@ -612,18 +3269,338 @@ impl Window {
         Texture2d::new(self.display.as_ref().unwrap(), raw_image).unwrap()
     }
 
-    pub fn load_font(&mut self) {
-        self.font = Some(
-            Font::load(Path::new(
-                "./fonts/liberation-sans/LiberationSans-Regular.ttf",
-            ))
+    /// Opens the window of the browser, and picks the OpenGL config that it's drawn with. GLX and
+    /// WGL need the window to be made for the config, so they are created together. EGL is used
+    /// where it's available, and the native API of the platform otherwise, such as WGL on Windows
+    /// and CGL on macOS. Without any of them, the window is opened without a config and drawn on
+    /// the CPU, see [Window::redraw_in_software].
+    fn create_window(&mut self, event_loop: &ActiveEventLoop) {
+        let mut attributes = WinitWindow::default_attributes()
+            .with_window_icon(icon::to_window_icon(&icon::app_icon()));
+        // The X11 and Wayland versions set the same value, which is used as the WM class on X11
+        // and as the app id on Wayland
+        #[cfg(target_os = "linux")]
+        {
+            use winit::platform::x11::WindowAttributesExtX11;
+            attributes = attributes.with_name(APP_ID, APP_ID);
+        }
+        let built = DisplayBuilder::new()
+            .with_preference(ApiPreference::PreferEgl)
+            .with_window_attributes(Some(attributes.clone()))
+            .build(event_loop, ConfigTemplateBuilder::new(), |mut configs| {
+                configs.next().unwrap()
+            });
+        match built {
+            Ok((Some(window), config)) => {
+                self.window = Some(Rc::new(window));
+                self.gl_config = Some(config);
+            }
+            Ok((None, _)) => unreachable!("The window attributes were given"),
+            Err(error) => {
+                println!(
+                    "Couldn't find a way to draw with OpenGL, drawing on the CPU instead: {}",
+                    error
+                );
+                self.window = Some(Rc::new(event_loop.create_window(attributes).unwrap()));
+            }
+        }
+        self.update_window_icon();
+    }
+
+    /// Creates the OpenGL display and surface of the window and everything that is drawn with
+    /// them. If OpenGL can't be used, the surface that the window is drawn to on the CPU is
+    /// created instead. Nothing is done if they exist already.
+    fn create_graphics(&mut self) {
+        if self.display.is_some() || self.software_surface.is_some() {
+            return;
+        }
+        let display = match self.gl_config.as_ref().map(|x| self.create_display(x)) {
+            Some(Ok(display)) => display,
+            Some(Err(error)) => {
+                println!("{}, drawing on the CPU instead", error);
+                self.create_software_surface();
+                return;
+            }
+            // The window was opened without OpenGL
+            None => {
+                self.create_software_surface();
+                return;
+            }
+        };
+        self.display = Some(display);
+
+        self.rect = Some(Rectangle::create(self.display.as_ref().unwrap()));
+
+        // The new texture is empty, so the glyphs are added to the atlas again as they are drawn
+        self.atlas_texture = Some(
+            Texture2d::empty(
+                self.display.as_ref().unwrap(),
+                GLYPH_ATLAS_SIZE,
+                GLYPH_ATLAS_SIZE,
+            )
             .unwrap(),
         );
+        self.glyph_atlas.get_mut().clear();
+
+        // The quads are already placed in window coordinates. The glyphs of the text are white in
+        // the atlas with their coverage in the alpha, so they take the color of the quad.
+        self.quad_program = Some(
+            program!(self.display.as_ref().unwrap(),
+            330 => {
+                vertex: r#"
+                    #version 330 core
+                    layout (location=0) in vec2 a_position;
+                    layout (location=1) in vec2 a_tex_coord;
+                    layout (location=2) in vec4 a_color;
+                    out vec2 texCoord;
+                    out vec4 quadColor;
+                    void main() {
+                        gl_Position = vec4(a_position, 0.0, 1.0);
+                        texCoord = a_tex_coord;
+                        quadColor = a_color;
+                    }
+                "#,
+                fragment: r#"
+                    #version 330 core
+                    out vec4 color;
+                    in vec2 texCoord;
+                    in vec4 quadColor;
+                    uniform sampler2D atlas;
+                    // The colors are blended as they are stored, not in linear light, so the
+                    // coverage is corrected as if the text was blended in linear light over a
+                    // background of the opposite brightness. Otherwise dark text looks too bold
+                    // and light text looks thin and washed out.
+                    const float GAMMA = 1.8;
+                    void main() {
+                        vec4 texel = texture(atlas, texCoord);
+                        float luminance = dot(quadColor.rgb, vec3(0.2126, 0.7152, 0.0722));
+                        float onLight = 1.0 - pow(1.0 - texel.a, 1.0 / GAMMA);
+                        float onDark = pow(texel.a, 1.0 / GAMMA);
+                        float coverage = mix(onLight, onDark, luminance);
+                        color = vec4(texel.rgb * quadColor.rgb, coverage * quadColor.a);
+                    }
+                "#
+            })
+            .unwrap(),
+        );
+
+        self.solid_color_program = Some(
+            program!(self.display.as_ref().unwrap(),
+            330 => {
+                vertex: r#"
+                    #version 330 core
+                    layout (location=0) in vec3 a_position;
+                    uniform mat4 transform;
+                    void main() {
+                        gl_Position = transform * vec4(a_position, 1.0);
+                    }
+                "#,
+                fragment: r#"
+                    #version 330 core
+                    out vec4 color;
+                    uniform vec4 in_color;
+                    uniform float opacity;
+                    void main() {
+                        color = in_color;
+                        color.a *= opacity;
+                    }
+                "#
+            })
+            .unwrap(),
+        );
+
+        self.image_program = Some(
+            program!(self.display.as_ref().unwrap(),
+            330 => {
+                vertex: r#"
+                    #version 330 core
+                    layout (location=0) in vec3 a_position;
+                    layout (location=1) in vec2 a_tex_coord;
+                    uniform mat4 transform;
+                    uniform vec2 tex_scale;
+                    uniform vec2 tex_offset;
+                    out vec2 texCoord;
+                    void main() {
+                        gl_Position = transform * vec4(a_position, 1.0);
+                        texCoord = a_tex_coord * tex_scale + tex_offset;
+                    }
+                "#,
+                fragment: r#"
+                    #version 330 core
+                    out vec4 color;
+                    in vec2 texCoord;
+                    uniform sampler2D image;
+                    uniform float opacity;
+                    void main() {
+                        color = texture(image, texCoord);
+                        color.a *= opacity;
+                    }
+                "#
+            })
+            .unwrap(),
+        );
+
+        self.gradient_program = Some(
+            program!(self.display.as_ref().unwrap(),
+            330 => {
+                vertex: r#"
+                    #version 330 core
+                    layout (location=0) in vec3 a_position;
+                    layout (location=1) in vec2 a_tex_coord;
+                    uniform mat4 transform;
+                    uniform vec2 tex_scale;
+                    uniform vec2 tex_offset;
+                    out vec2 texCoord;
+                    void main() {
+                        gl_Position = transform * vec4(a_position, 1.0);
+                        texCoord = a_tex_coord * tex_scale + tex_offset;
+                    }
+                "#,
+                fragment: r#"
+                    #version 330 core
+                    out vec4 color;
+                    in vec2 texCoord;
+                    uniform sampler2D ramp;
+                    uniform float ramp_width;
+                    uniform vec2 tile_size;
+                    uniform vec2 direction;
+                    uniform float line_length;
+                    uniform float opacity;
+                    void main() {
+                        // The distance along the gradient line from the center of the tile
+                        vec2 point = (fract(texCoord) - 0.5) * tile_size;
+                        float t = clamp(dot(point, direction) / line_length + 0.5, 0.0, 1.0);
+                        // Hit the centers of the first and the last pixel at the ends
+                        float x = (t * (ramp_width - 1.0) + 0.5) / ramp_width;
+                        color = texture(ramp, vec2(x, 0.5));
+                        color.a *= opacity;
+                    }
+                "#
+            })
+            .unwrap(),
+        );
+
+        // The favicons were dropped together with the old display
+        self.update_favicon_textures();
+    }
+
+    /// Creates the OpenGL context and surface of the window with the config
+    fn create_display(&self, config: &Config) -> Result<Display<WindowSurface>, String> {
+        let window = self.window.as_ref().unwrap();
+        let display = config.display();
+        let raw_window_handle = window.window_handle().unwrap().as_raw();
+
+        let surface_attributes = window
+            .build_surface_attributes(SurfaceAttributesBuilder::<WindowSurface>::new())
+            .unwrap();
+        let surface = unsafe { display.create_window_surface(config, &surface_attributes) }
+            .map_err(|error| {
+                format!(
+                    "Couldn't create the OpenGL surface of the window: {}",
+                    error
+                )
+            })?;
+
+        // The shaders need OpenGL 3.3. macOS only gives it to core profile contexts, and the
+        // default context of the driver is tried if they aren't supported.
+        let core_attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+            .with_profile(GlProfile::Core)
+            .build(Some(raw_window_handle));
+        let default_attributes = ContextAttributesBuilder::new().build(Some(raw_window_handle));
+        let context = unsafe {
+            display
+                .create_context(config, &core_attributes)
+                .or_else(|_| display.create_context(config, &default_attributes))
+        }
+        .and_then(|x| x.make_current(&surface))
+        .map_err(|error| {
+            format!(
+                "Couldn't create the OpenGL context of the window: {}",
+                error
+            )
+        })?;
+
+        Display::new(context, surface)
+            .map_err(|error| format!("Couldn't draw with the OpenGL context: {}", error))
+    }
+
+    /// Creates the surface that the window is drawn to on the CPU. Nothing can be shown without
+    /// it, so the browser exits if it can't be created.
+    fn create_software_surface(&mut self) {
+        let window = self.window.as_ref().unwrap();
+        let surface = softbuffer::Context::new(window.clone())
+            .and_then(|context| softbuffer::Surface::new(&context, window.clone()));
+        match surface {
+            Ok(surface) => self.software_surface = Some(surface),
+            Err(error) => {
+                println!("Couldn't create a surface to draw the window to: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Drops everything that lives on the GPU, or the surface that the window is drawn to on the
+    /// CPU. The images keep their pixels, so their textures are uploaded again when they are
+    /// drawn the next time.
+    fn release_graphics(&mut self) {
+        for image in self.images.values_mut() {
+            image.texture = None;
+        }
+        self.favicon_textures.clear();
+        self.rect = None;
+        self.atlas_texture = None;
+        self.quad_program = None;
+        self.solid_color_program = None;
+        self.image_program = None;
+        self.gradient_program = None;
+        self.display = None;
+        self.software_surface = None;
+    }
+
+    /// Throws away the graphics after the driver has lost the context, for example because the
+    /// GPU was reset, and creates them again
+    fn recreate_graphics(&mut self) {
+        println!("The graphics context was lost, creating it again");
+        self.release_graphics();
+        self.create_graphics();
+    }
+
+    pub fn load_font(&mut self) {
+        let fonts = FontRegistry::load(&bundled_font_directory(), &SystemFonts::discover());
+        self.fonts = Some(fonts.unwrap());
+    }
+
+    /// Draws the page with [SoftwareBackend] instead of OpenGL. The rest of the window is still
+    /// drawn with OpenGL. This is slower, but doesn't depend on how the graphics driver draws.
+    /// Where OpenGL can't be used at all, the whole window is drawn on the CPU without this, see
+    /// [Window::redraw_in_software].
+    pub fn use_software_rendering(&mut self) {
+        self.software_rendering = true;
+    }
+
+    /// The fonts that pages can use. The default font is also used for the browser's own text.
+    pub fn fonts(&self) -> &FontRegistry {
+        self.fonts.as_ref().unwrap()
     }
 
     pub fn open(&mut self) {
         let event_loop = EventLoop::new().unwrap();
-        event_loop.set_control_flow(ControlFlow::Poll);
+        event_loop.set_control_flow(ControlFlow::Wait);
         event_loop.run_app(self).unwrap();
     }
 }
+
+/// Opens the url with the program the desktop uses for it, such as a video player
+fn open_externally(url: &str) {
+    println!("Opening {:?} in another program", url);
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd").args(["/C", "start", "", url]).spawn();
+    #[cfg(target_os = "macos")]
+    let result = Command::new("open").arg(url).spawn();
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let result = Command::new("xdg-open").arg(url).spawn();
+    if let Err(error) = result {
+        println!("Couldn't open {}: {}", url, error);
+    }
+}
@@ -0,0 +1,49 @@
+//! Tests for archiving pages with [web::archive::Archive]
+
+// Not every test binary uses all of the server
+#[allow(dead_code)]
+mod common;
+
+use common::{Route, TestServer};
+use web::archive::Archive;
+
+#[test]
+fn leaves_out_stylesheets_that_fail_their_checks() {
+    let css = "p { color: red; }";
+    let server = TestServer::start(vec![
+        Route::page(
+            "/",
+            "text/html",
+            "<link rel=\"stylesheet\" href=\"/good.css\" \
+               integrity=\"sha256-wrong sha384-MsKMkI+2EFI/0a6ferw45VYxQOmqB11JofDpskpP1fm+099keHdjJXFgurNmvwb2\">\
+             <link rel=\"stylesheet\" href=\"/changed.css\" \
+               integrity=\"sha256-pckGv9YvNcB5xy+Y4fbqhyo+ib850wyiuWeNbZvLi00=\">\
+             <link rel=\"stylesheet\" href=\"/script.css\">",
+        ),
+        Route::page("/good.css", "text/css", css),
+        Route::page("/changed.css", "text/css", "p { color: blue; }"),
+        Route::page("/script.css", "text/javascript", css)
+            .header("X-Content-Type-Options", "nosniff"),
+    ]);
+
+    let archive = Archive::fetch(&server.url("/"));
+    let urls: Vec<&str> = archive.resources.iter().map(|x| x.url.as_str()).collect();
+    assert_eq!(urls, [server.url("/good.css")]);
+}
+
+#[test]
+fn leaves_out_stylesheets_that_cant_be_fetched() {
+    let server = TestServer::start(vec![
+        Route::page(
+            "/",
+            "text/html",
+            "<link rel=\"stylesheet\" href=\"/missing.css\">\
+             <link rel=\"stylesheet\" href=\"/style.css\">",
+        ),
+        Route::page("/style.css", "text/css", "p { color: red; }"),
+    ]);
+
+    let archive = Archive::fetch(&server.url("/"));
+    let urls: Vec<&str> = archive.resources.iter().map(|x| x.url.as_str()).collect();
+    assert_eq!(urls, [server.url("/style.css")]);
+}
@@ -0,0 +1,90 @@
+//! Tests for driving the engine without a window through [web::browser::Browser]
+
+// Not every test binary uses all of the server
+#[allow(dead_code)]
+mod common;
+
+use std::{cell::RefCell, rc::Rc};
+
+use common::{Route, TestServer};
+use web::browser::Browser;
+use web::font::{bundled_font_directory, FontRegistry};
+use web::render_layout::Size;
+use web::system_fonts::SystemFonts;
+
+#[test]
+fn navigates_back_and_forward() {
+    let server = TestServer::start(vec![
+        Route::page(
+            "/first",
+            "text/html",
+            "<html><head><title>First</title><style>p { color: red; }</style></head>\
+             <body><p>Hello,\n   world</p><script>let a = 1;</script></body></html>",
+        ),
+        Route::page("/second", "text/plain", "Plain\ttext"),
+    ]);
+    let loads = Rc::new(RefCell::new(Vec::new()));
+    let mut browser = Browser::new();
+    let recorded = loads.clone();
+    browser.on_load(move |x| recorded.borrow_mut().push(x.url.clone()));
+    assert!(!browser.back());
+
+    browser.navigate(&server.url("/first"));
+    browser.wait().unwrap();
+    assert_eq!(browser.get_title().as_deref(), Some("First"));
+    assert_eq!(browser.get_text(), "Hello, world");
+
+    browser.navigate(&server.url("/second"));
+    browser.wait().unwrap();
+    assert_eq!(browser.get_title(), None);
+    assert_eq!(browser.get_text(), "Plain    text");
+    assert!(browser.can_go_back());
+
+    assert!(browser.back());
+    browser.wait().unwrap();
+    assert_eq!(browser.url(), server.url("/first"));
+    assert!(!browser.can_go_back());
+
+    assert!(browser.forward());
+    browser.wait().unwrap();
+    assert_eq!(browser.url(), server.url("/second"));
+    assert!(!browser.can_go_forward());
+
+    assert_eq!(loads.borrow().len(), 4);
+}
+
+#[test]
+fn a_failed_load_keeps_the_page_and_the_history() {
+    let server = TestServer::start(vec![
+        Route::page("/", "text/html", "<p>Kept</p>"),
+        Route::page("/missing", "text/html", "Gone").status(404),
+    ]);
+    let mut browser = Browser::new();
+    browser.navigate(&server.url("/"));
+    browser.wait().unwrap();
+
+    browser.navigate(&server.url("/missing"));
+    assert!(browser.wait().is_err());
+    assert_eq!(browser.url(), server.url("/"));
+    assert_eq!(browser.get_text(), "Kept");
+    assert!(!browser.can_go_back());
+}
+
+#[test]
+fn renders_the_page_into_an_image() {
+    let server = TestServer::start(vec![Route::page(
+        "/",
+        "text/html",
+        "<html><body><div style=\"background-color: #ff0000; height: 50px\">Red</div>\
+         <p>Below</p></body></html>",
+    )]);
+    let fonts = FontRegistry::load(&bundled_font_directory(), &SystemFonts::default()).unwrap();
+    let mut browser = Browser::new();
+    browser.navigate(&server.url("/"));
+    browser.wait().unwrap();
+    let image = browser.render_to_image(Size::new(200, 150), &fonts);
+
+    assert_eq!(image.dimensions(), (200, 150));
+    assert_eq!(image.get_pixel(150, 10).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(150, 140).0, [255, 255, 255, 255]);
+}
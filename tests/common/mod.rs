@@ -0,0 +1,228 @@
+//! A small HTTP server for the tests. It serves the pages it's given on a random local port, so
+//! the networking code can be tested without the internet. Every route can have its own status,
+//! headers, delay and compression.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+/// A request that the server has received
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// The header names are in lowercase
+    pub headers: HashMap<String, String>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|x| x.as_str())
+    }
+}
+
+/// What the server answers to a path
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub path: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// How long the server waits before answering
+    pub delay: Duration,
+    /// Sends the body compressed with gzip
+    pub gzip: bool,
+}
+
+impl Route {
+    /// A route that answers with a `200 OK` page
+    pub fn page(path: &str, mime_type: &str, body: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            status: 200,
+            headers: vec![("Content-Type".to_owned(), mime_type.to_owned())],
+            body: body.as_bytes().to_vec(),
+            delay: Duration::ZERO,
+            gzip: false,
+        }
+    }
+
+    /// A route that serves a file from the `tests` directory. The mime type is guessed from the
+    /// extension of the file.
+    pub fn fixture(path: &str, file_name: &str) -> Self {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(file_name);
+        let body = std::fs::read_to_string(&file)
+            .unwrap_or_else(|x| panic!("Couldn't read {}: {}", file.display(), x));
+        let mime_type = match file.extension().and_then(|x| x.to_str()) {
+            Some("css") => "text/css",
+            Some("txt") => "text/plain",
+            _ => "text/html",
+        };
+        Self::page(path, mime_type, &body)
+    }
+
+    /// A route that redirects to another url
+    ///
+    /// * `status` - The redirect status, such as 301 or 302
+    pub fn redirect(path: &str, status: u16, location: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            status,
+            headers: vec![("Location".to_owned(), location.to_owned())],
+            body: Vec::new(),
+            delay: Duration::ZERO,
+            gzip: false,
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn gzip(mut self) -> Self {
+        self.gzip = true;
+        self
+    }
+}
+
+/// The server runs until the test process exits
+pub struct TestServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let routes = Arc::new(routes);
+
+        let received = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = routes.clone();
+                let received = received.clone();
+                // Every connection gets its own thread, so a delayed route doesn't hold up the
+                // other requests
+                thread::spawn(move || handle_connection(stream, &routes, &received));
+            }
+        });
+        Self { port, requests }
+    }
+
+    /// Returns the full url of a path on the server
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    /// Returns the requests that the server has received so far
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn handle_connection(stream: TcpStream, routes: &[Route], received: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    // A connection can be kept alive for more than one request
+    while let Some(request) = read_request(&mut reader) {
+        received.lock().unwrap().push(request.clone());
+        // The query doesn't matter for picking the route
+        let path = request.path.split('?').next().unwrap_or("");
+        let response = match routes.iter().find(|x| x.path == path) {
+            Some(route) => {
+                thread::sleep(route.delay);
+                encode_response(route)
+            }
+            None => encode_response(&Route::page(path, "text/plain", "Not found").status(404)),
+        };
+        if (&stream).write_all(&response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads the request line and the headers. Returns [None] if the connection was closed.
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+        }
+    }
+    Some(Request {
+        method,
+        path,
+        headers,
+    })
+}
+
+fn encode_response(route: &Route) -> Vec<u8> {
+    let body = if route.gzip {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&route.body).unwrap();
+        encoder.finish().unwrap()
+    } else {
+        route.body.clone()
+    };
+
+    let mut head = format!("HTTP/1.1 {} {}\r\n", route.status, reason(route.status));
+    for (name, value) in &route.headers {
+        head += &format!("{}: {}\r\n", name, value);
+    }
+    if route.gzip {
+        head += "Content-Encoding: gzip\r\n";
+    }
+    head += &format!("Content-Length: {}\r\n\r\n", body.len());
+
+    let mut response = head.into_bytes();
+    response.extend(body);
+    response
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,79 @@
+//! Tests for drawing pages on the CPU through [web::render_backend::SoftwareBackend]
+
+use web::color::Color;
+use web::display_list::{DisplayItem, DisplayItemKind, DisplayList, TextRun};
+use web::font::{bundled_font_directory, FontFace, FontRegistry};
+use web::render_backend::{paint, SoftwareBackend};
+use web::render_layout::{Position, Rect, Size};
+use web::system_fonts::SystemFonts;
+
+fn item(kind: DisplayItemKind, rect: Rect, fixed: bool, clip: Option<Rect>) -> DisplayItem {
+    DisplayItem {
+        kind,
+        rect,
+        opacity: 1.0,
+        fixed,
+        clip,
+    }
+}
+
+#[test]
+fn draws_rects_and_text_with_the_scroll_and_the_clip() {
+    let fonts = FontRegistry::load(&bundled_font_directory(), &SystemFonts::default()).unwrap();
+    let red = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    let text = TextRun {
+        text: "Hello".to_string(),
+        font_family: "sans-serif".to_string(),
+        face: FontFace::default(),
+        font_size: 20.0,
+        color: Color::black(),
+        monospace: false,
+    };
+    let list = DisplayList {
+        items: vec![
+            // Scrolled up by 10 pixels and cut off by the clip at x = 20
+            item(
+                DisplayItemKind::Rect(red),
+                Rect::new(Position::new(0, 10), Size::new(40, 10)),
+                false,
+                Some(Rect::new(Position::new(0, 0), Size::new(20, 100))),
+            ),
+            // Fixed items stay in place
+            item(
+                DisplayItemKind::Rect(red),
+                Rect::new(Position::new(0, 80), Size::new(10, 10)),
+                true,
+                None,
+            ),
+            item(
+                DisplayItemKind::Text(text),
+                Rect::new(Position::new(50, 40), Size::new(50, 24)),
+                false,
+                None,
+            ),
+        ],
+    };
+    let mut backend = SoftwareBackend::new(
+        Size::new(100, 100),
+        Color::white(),
+        &fonts,
+        Position::new(0, -10),
+    );
+    paint(&list, -10, &mut backend);
+    let image = backend.image;
+
+    assert_eq!(image.get_pixel(5, 5).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(5, 15).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(30, 5).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(5, 85).0, [255, 0, 0, 255]);
+    let dark = (50..100)
+        .flat_map(|x| (30..54).map(move |y| (x, y)))
+        .filter(|(x, y)| image.get_pixel(*x, *y).0[0] < 128)
+        .count();
+    assert!(dark > 20, "Only {} pixels of the text were drawn", dark);
+}
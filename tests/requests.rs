@@ -0,0 +1,161 @@
+//! Tests for fetching pages over HTTP, using the local server in [common]
+
+mod common;
+
+use std::{sync::mpsc::TryRecvError, time::Duration};
+
+use common::{Route, TestServer};
+use web::requests::{get_site_in_background, resolve_url, try_get_site};
+
+#[test]
+fn fetches_a_fixture_page() {
+    let server = TestServer::start(vec![
+        Route::fixture("/", "index.html"),
+        Route::fixture("/style.css", "style.css"),
+    ]);
+
+    let page = try_get_site(&server.url("/"), None).unwrap();
+    assert_eq!(page.mime_type.as_deref(), Some("text/html"));
+    assert!(!page.download);
+    assert!(page.body.contains("Web Browser"));
+
+    let style = try_get_site(&server.url("/style.css"), None).unwrap();
+    assert_eq!(style.mime_type.as_deref(), Some("text/css"));
+    assert!(style.body.contains("width: 50px"));
+}
+
+#[test]
+fn sends_the_referrer() {
+    let server = TestServer::start(vec![Route::page("/", "text/html", "<p>Hello</p>")]);
+
+    try_get_site(&server.url("/"), Some("http://example.com/")).unwrap();
+    try_get_site(&server.url("/?second"), None).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].header("Referer"), Some("http://example.com/"));
+    assert_eq!(requests[1].path, "/?second");
+    assert_eq!(requests[1].header("Referer"), None);
+}
+
+#[test]
+fn follows_redirects() {
+    let server = TestServer::start(vec![
+        Route::redirect("/old", 301, "/middle"),
+        Route::redirect("/middle", 302, "/new"),
+        Route::page("/new", "text/html", "<p>Moved</p>"),
+    ]);
+
+    let page = try_get_site(&server.url("/old"), None).unwrap();
+    assert_eq!(page.body, "<p>Moved</p>");
+    let paths: Vec<String> = server.requests().into_iter().map(|x| x.path).collect();
+    assert_eq!(paths, ["/old", "/middle", "/new"]);
+}
+
+#[test]
+fn decompresses_gzip() {
+    let body = "<p>Compressed</p>".repeat(100);
+    let server = TestServer::start(vec![Route::page("/", "text/html", &body).gzip()]);
+
+    let page = try_get_site(&server.url("/"), None).unwrap();
+    assert_eq!(page.body, body);
+}
+
+#[test]
+fn errors_on_missing_pages() {
+    let server = TestServer::start(vec![Route::page("/error", "text/html", "Oops").status(500)]);
+
+    assert!(try_get_site(&server.url("/missing"), None).is_err());
+    assert!(try_get_site(&server.url("/error"), None).is_err());
+}
+
+#[test]
+fn detects_downloads() {
+    let server = TestServer::start(vec![
+        Route::page("/file.zip", "application/zip", "PK"),
+        Route::page("/report.txt", "text/plain", "Report")
+            .header("Content-Disposition", "attachment; filename=\"report.txt\""),
+        Route::page("/notes.txt", "text/plain", "Notes"),
+    ]);
+
+    for path in ["/file.zip", "/report.txt"] {
+        let response = try_get_site(&server.url(path), None).unwrap();
+        assert!(response.download, "{} should be downloaded", path);
+        assert!(response.body.is_empty());
+    }
+    let notes = try_get_site(&server.url("/notes.txt"), None).unwrap();
+    assert!(!notes.download);
+    assert!(notes.is_plain_text());
+}
+
+#[test]
+fn loads_slow_pages_in_the_background() {
+    let server = TestServer::start(vec![
+        Route::page("/", "text/html", "<p>Slow</p>").delay(Duration::from_millis(300))
+    ]);
+
+    let receiver = get_site_in_background(&server.url("/"), None);
+    assert!(matches!(receiver.try_recv(), Err(TryRecvError::Empty)));
+    let page = receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap()
+        .unwrap();
+    assert_eq!(page.body, "<p>Slow</p>");
+}
+
+#[test]
+fn resolves_links_against_the_server() {
+    let server = TestServer::start(Vec::new());
+    let base = server.url("/docs/page.html");
+
+    assert_eq!(
+        resolve_url(&base, "other.html"),
+        server.url("/docs/other.html")
+    );
+    assert_eq!(
+        resolve_url(&base, "../index.html"),
+        server.url("/index.html")
+    );
+    assert_eq!(resolve_url(&base, "/style.css"), server.url("/style.css"));
+}
+
+#[test]
+fn resolves_fragments_and_queries_against_the_page() {
+    assert_eq!(
+        resolve_url("http://h/a/b.html", "#x"),
+        "http://h/a/b.html#x"
+    );
+    assert_eq!(
+        resolve_url("http://h/a/b.html?q=1#y", "#x"),
+        "http://h/a/b.html?q=1#x"
+    );
+    assert_eq!(
+        resolve_url("http://h/a/b.html", "?q=1"),
+        "http://h/a/b.html?q=1"
+    );
+    assert_eq!(
+        resolve_url("http://h/a/b.html?q=1#y", "?q=2"),
+        "http://h/a/b.html?q=2"
+    );
+}
+
+#[test]
+fn nosniff_stops_guessing_the_type() {
+    let server = TestServer::start(vec![
+        Route::page("/guessed", "application/x-unknown-text", "<p>Guessed</p>"),
+        Route::page("/strict", "application/x-unknown-text", "<p>Strict</p>")
+            .header("X-Content-Type-Options", "nosniff"),
+        Route::page("/page", "text/html", "<p>Page</p>")
+            .header("X-Content-Type-Options", "nosniff"),
+    ]);
+
+    let guessed = try_get_site(&server.url("/guessed"), None).unwrap();
+    assert!(!guessed.nosniff);
+    assert!(!guessed.is_plain_text());
+    let strict = try_get_site(&server.url("/strict"), None).unwrap();
+    assert!(strict.nosniff);
+    assert!(strict.is_plain_text());
+    let page = try_get_site(&server.url("/page"), None).unwrap();
+    assert!(!page.is_plain_text());
+}